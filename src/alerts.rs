@@ -0,0 +1,113 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Mutex,
+};
+
+/// Soft limits on the size of the graph. A warning is logged the first time each of them is crossed,
+/// but the crawl keeps going.
+#[derive(Debug, Default)]
+pub struct BudgetAlerts {
+    warn_nodes: Option<u64>,
+    warn_edges: Option<u64>,
+    /// Number of edges found so far. Edges found again after being spilled to disk are counted again,
+    /// so this is an upper bound of the number of distinct edges
+    edges: AtomicU64,
+    nodes_fired: AtomicBool,
+    edges_fired: AtomicBool,
+}
+
+impl BudgetAlerts {
+    pub fn new(warn_nodes: Option<u64>, warn_edges: Option<u64>) -> BudgetAlerts {
+        BudgetAlerts {
+            warn_nodes,
+            warn_edges,
+            ..Default::default()
+        }
+    }
+
+    /// Checks the number of nodes of the graph against its threshold
+    pub fn check_nodes(&self, nodes: usize) {
+        if let Some(threshold) = self.warn_nodes {
+            if nodes as u64 >= threshold && !self.nodes_fired.swap(true, Ordering::Relaxed) {
+                warn("nodes", threshold);
+            }
+        }
+    }
+
+    /// Records that a new edge has been found and checks the number of edges against its threshold
+    pub fn add_edge(&self) {
+        if let Some(threshold) = self.warn_edges {
+            let edges = self.edges.fetch_add(1, Ordering::Relaxed) + 1;
+            if edges >= threshold && !self.edges_fired.swap(true, Ordering::Relaxed) {
+                warn("edges", threshold);
+            }
+        }
+    }
+}
+
+/// Stops a crawl that keeps finding pages without links. A few of them are expected, but many in a row
+/// usually mean that the content selector is wrong, or that the server answers with a consent wall or a bot block
+/// instead of the pages
+#[derive(Debug, Default)]
+pub struct EmptyPageGuard {
+    max_in_a_row: Option<u64>,
+    in_a_row: AtomicU64,
+}
+
+impl EmptyPageGuard {
+    pub fn new(max_in_a_row: Option<u64>) -> EmptyPageGuard {
+        EmptyPageGuard {
+            max_in_a_row,
+            ..Default::default()
+        }
+    }
+
+    /// Records whether a fetched page has links. Returns the number of pages without links in a row
+    /// if it is more than the maximum, in which case the crawl has to be stopped
+    pub fn record(&self, has_links: bool) -> Option<u64> {
+        let max_in_a_row = self.max_in_a_row?;
+        if has_links {
+            self.in_a_row.store(0, Ordering::Relaxed);
+            return None;
+        }
+        let in_a_row = self.in_a_row.fetch_add(1, Ordering::Relaxed) + 1;
+        (in_a_row > max_in_a_row).then_some(in_a_row)
+    }
+}
+
+/// Pages abandoned by the workers because parsing them took longer than the page timeout
+#[derive(Debug, Default)]
+pub struct AbandonedPages {
+    urls: Mutex<Vec<String>>,
+}
+
+impl AbandonedPages {
+    pub fn record(&self, url: &str) {
+        self.urls.lock().unwrap().push(url.to_owned());
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        self.urls.lock().unwrap().clone()
+    }
+}
+
+fn warn(what: &str, threshold: u64) {
+    let line = "=".repeat(72);
+    eprintln!("[WARN] {line}");
+    eprintln!("[WARN] The graph has reached {threshold} {what}. The crawl keeps going:");
+    eprintln!("[WARN] pause or stop it through the control socket or with SIGUSR1 if this is not expected");
+    eprintln!("[WARN] {line}");
+}
+
+/// Parses a count given on the command line, either as an integer or in scientific notation (e.g. `1e6`)
+pub fn parse_count(count: &str) -> Result<u64, String> {
+    if let Ok(count) = count.parse::<u64>() {
+        return Ok(count);
+    }
+    match count.parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 && value.fract() == 0.0 => Ok(value as u64),
+        _ => Err(format!(
+            "Expected a non negative integer, e.g. 1000000 or 1e6, found `{count}`"
+        )),
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::{header::HeaderMap, StatusCode};
+
+use crate::config::RetryConfig;
+use crate::worker;
+
+/// Kind of page a server answers with when it is blocking the crawler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockReason {
+    /// A 403 response explaining that the requests have been blocked
+    Forbidden,
+    /// A page asking to solve a CAPTCHA
+    Captcha,
+    /// A Cloudflare challenge, which needs a browser running its scripts to be passed
+    CloudflareChallenge,
+}
+
+impl BlockReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockReason::Forbidden => "403 block page",
+            BlockReason::Captcha => "CAPTCHA",
+            BlockReason::CloudflareChallenge => "Cloudflare challenge",
+        }
+    }
+}
+
+impl fmt::Display for BlockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Text of the 403 responses of servers that have blocked the requests, in lowercase
+const FORBIDDEN_MARKERS: &[&str] = &[
+    "please set a user-agent",
+    "please respect our robot policy",
+    "scripted requests from your ip have been blocked",
+    "you have been blocked",
+    "request blocked",
+    "access denied",
+];
+
+/// Parts of the pages with a CAPTCHA widget
+const CAPTCHA_MARKERS: &[&str] = &[
+    "class=\"g-recaptcha\"",
+    "class=\"h-captcha\"",
+    "class=\"cf-turnstile\"",
+    "www.google.com/recaptcha/api.js",
+    "hcaptcha.com/1/api.js",
+];
+
+/// Parts of the Cloudflare challenge pages
+const CLOUDFLARE_MARKERS: &[&str] = &["/cdn-cgi/challenge-platform/", "window._cf_chl_opt"];
+
+/// Titles of the pages asking the visitor to prove to be human, in lowercase
+const CHALLENGE_TITLES: &[&str] = &[
+    "just a moment...",
+    "attention required! | cloudflare",
+    "captcha",
+    "are you a robot",
+    "verify you are human",
+];
+
+/// Checks whether a response is a block page rather than the page that has been requested.
+///
+/// Pages with a CAPTCHA widget are only block pages when the status is an error or the title asks to prove
+/// to be human, since a site may have a form protected by a CAPTCHA in its regular pages
+pub fn detect(status: StatusCode, headers: &HeaderMap, body: &str) -> Option<BlockReason> {
+    let title = page_title(body).map(|title| title.trim().to_lowercase());
+    let challenge_title =
+        title.is_some_and(|title| CHALLENGE_TITLES.iter().any(|marker| title.contains(marker)));
+
+    if is_cloudflare_challenge(headers)
+        || (!status.is_success()
+            && CLOUDFLARE_MARKERS
+                .iter()
+                .any(|marker| body.contains(marker)))
+    {
+        return Some(BlockReason::CloudflareChallenge);
+    }
+    if (!status.is_success() || challenge_title)
+        && CAPTCHA_MARKERS.iter().any(|marker| body.contains(marker))
+    {
+        return Some(BlockReason::Captcha);
+    }
+    if status == StatusCode::FORBIDDEN {
+        let body = body.to_lowercase();
+        if FORBIDDEN_MARKERS.iter().any(|marker| body.contains(marker)) {
+            return Some(BlockReason::Forbidden);
+        }
+    }
+    None
+}
+
+/// Whether the headers of a response mark it as a Cloudflare challenge, which does not need its body to be read
+pub fn is_cloudflare_challenge(headers: &HeaderMap) -> bool {
+    headers
+        .get("cf-mitigated")
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"challenge"))
+}
+
+/// Content of the `<title>` element of an HTML page
+fn page_title(body: &str) -> Option<&str> {
+    let start = body.find("<title")?;
+    let start = start + body[start..].find('>')? + 1;
+    let end = start + body[start..].find("</title>")?;
+    Some(&body[start..end])
+}
+
+/// Blocks of a host during the crawl
+#[derive(Debug, Clone)]
+pub struct HostBlock {
+    /// Kind of the last block page received
+    pub reason: BlockReason,
+    /// Number of block pages received from the host
+    pub count: u64,
+    /// Number of block pages received in a row since the last regular response, which sets the backoff
+    in_a_row: u32,
+    /// Requests to the host wait until this instant
+    until: Instant,
+}
+
+/// Hosts that have answered with block pages. Requests to a blocked host are held back for a time that doubles
+/// at each block page received in a row, as the retries are
+#[derive(Debug)]
+pub struct BlockedHosts {
+    retry: RetryConfig,
+    hosts: Mutex<BTreeMap<String, HostBlock>>,
+}
+
+impl BlockedHosts {
+    pub fn new(retry: RetryConfig) -> BlockedHosts {
+        BlockedHosts {
+            retry,
+            hosts: Mutex::default(),
+        }
+    }
+
+    /// Records a block page received from the host of `url`, returning the host and the time to wait before sending it another request
+    pub fn record(&self, url: &str, reason: BlockReason) -> (String, Duration) {
+        let host = worker::get_host(url).unwrap_or_default();
+        let mut hosts = self.hosts.lock().unwrap();
+        let block = hosts.entry(host.clone()).or_insert_with(|| HostBlock {
+            reason,
+            count: 0,
+            in_a_row: 0,
+            until: Instant::now(),
+        });
+        block.reason = reason;
+        block.count += 1;
+        block.in_a_row += 1;
+        let backoff = worker::backoff_delay(&self.retry, block.in_a_row);
+        block.until = Instant::now() + backoff;
+
+        if block.count == 1 {
+            eprintln!("[WARN] {host} answered with a {reason}, marking it as blocked");
+        }
+        (host, backoff)
+    }
+
+    /// Records a regular response from the host of `url`, which resets its backoff
+    pub fn record_success(&self, url: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if hosts.is_empty() {
+            return;
+        }
+        if let Some(block) = hosts.get_mut(&worker::get_host(url).unwrap_or_default()) {
+            block.in_a_row = 0;
+        }
+    }
+
+    /// Waits until the host of `url` can be sent a request
+    pub async fn wait(&self, url: &str) {
+        let until = {
+            let hosts = self.hosts.lock().unwrap();
+            if hosts.is_empty() {
+                return;
+            }
+            match hosts.get(&worker::get_host(url).unwrap_or_default()) {
+                Some(block) => block.until,
+                None => return,
+            }
+        };
+        let now = Instant::now();
+        if until > now {
+            tokio::time::sleep(until - now).await;
+        }
+    }
+
+    /// Hosts that have answered with at least a block page
+    pub fn by_host(&self) -> BTreeMap<String, HostBlock> {
+        self.hosts.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    #[test]
+    fn block_pages() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            detect(
+                StatusCode::FORBIDDEN,
+                &headers,
+                "Scripted requests from your IP have been blocked, please see https://meta.wikimedia.org/wiki/User-Agent_policy"
+            ),
+            Some(BlockReason::Forbidden)
+        );
+        assert_eq!(
+            detect(
+                StatusCode::OK,
+                &headers,
+                "<html><head><title>Are you a robot?</title></head><body><div class=\"g-recaptcha\"></div></body></html>"
+            ),
+            Some(BlockReason::Captcha)
+        );
+        assert_eq!(
+            detect(
+                StatusCode::FORBIDDEN,
+                &headers,
+                "<script src=\"/cdn-cgi/challenge-platform/h/b/orchestrate/chl_page/v1\"></script>"
+            ),
+            Some(BlockReason::CloudflareChallenge)
+        );
+
+        let mut challenge = HeaderMap::new();
+        challenge.insert("cf-mitigated", HeaderValue::from_static("challenge"));
+        assert_eq!(
+            detect(StatusCode::FORBIDDEN, &challenge, ""),
+            Some(BlockReason::CloudflareChallenge)
+        );
+    }
+
+    #[test]
+    fn regular_pages() {
+        let headers = HeaderMap::new();
+        // A contact form with a CAPTCHA, and an article about CAPTCHAs
+        assert_eq!(
+            detect(
+                StatusCode::OK,
+                &headers,
+                "<html><head><title>Contact us</title></head><body><form><div class=\"g-recaptcha\"></div></form></body></html>"
+            ),
+            None
+        );
+        assert_eq!(
+            detect(
+                StatusCode::OK,
+                &headers,
+                "<html><head><title>CAPTCHA - Wikipedia</title></head><body>&lt;div class=&quot;g-recaptcha&quot;&gt;</body></html>"
+            ),
+            None
+        );
+        assert_eq!(
+            detect(StatusCode::FORBIDDEN, &headers, "Members only"),
+            None
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use crate::config::ScraperConfig;
+use crate::errors::ScraperError;
+use crate::graph::Graph;
+use crate::http::HttpClient;
+use crate::mediawiki;
+use crate::node_id::{IdWidth, NodeId};
+use crate::output::{
+    Attribute, AttributeKind, AttributeValue, Edge, Node, OutputFormat, OutputOptions,
+    OutputWriter, PageDetails, Priority, Schema, WriterThread,
+};
+use crate::scraper::WikipediaScraper;
+
+/// Graph of the neighborhood of an article crawled in two languages, whose nodes are the Wikidata items of the articles.
+///
+/// The nodes and the links are flagged with the languages they have been found in, the first language being `a`
+/// and the second one `b` in the names of the attributes, so that the coverage of the two language editions can be compared
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BridgeGraph {
+    pub languages: [String; 2],
+    /// Url of the article of each item in each language, if it has been found there
+    pub items: BTreeMap<String, [Option<String>; 2]>,
+    /// Links between the items, with whether they have been found in each language
+    pub links: BTreeMap<(String, String), [bool; 2]>,
+}
+
+impl BridgeGraph {
+    /// Merges the pages of `graph` found in each language, given the language and the item of each of them.
+    /// The pages without an item are left out, as well as the links between the articles of the same item
+    pub fn merge(
+        graph: &Graph,
+        languages: [String; 2],
+        items: &HashMap<String, (usize, String)>,
+    ) -> io::Result<BridgeGraph> {
+        let mut bridge = BridgeGraph {
+            languages,
+            ..Default::default()
+        };
+        // The pages merged into another one share its id, and are left out
+        let aliases = graph.aliases.lock().unwrap();
+        let urls: HashMap<_, _> = graph
+            .pages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(url, _)| !aliases.contains_key(*url))
+            .map(|(url, id)| (*id, url.clone()))
+            .collect();
+        for url in urls.values() {
+            if let Some((lang, item)) = items.get(url) {
+                bridge.items.entry(item.clone()).or_default()[*lang] = Some(url.clone());
+            }
+        }
+
+        let item_of = |id: &NodeId| urls.get(id).and_then(|url| items.get(url));
+        for edge in graph.links.lock().unwrap().sorted_edges()? {
+            let ((source, target), _) = edge?;
+            let (Some((lang, source)), Some((target_lang, target))) =
+                (item_of(&source), item_of(&target))
+            else {
+                continue;
+            };
+            if lang == target_lang && source != target {
+                bridge
+                    .links
+                    .entry((source.clone(), target.clone()))
+                    .or_default()[*lang] = true;
+            }
+        }
+        Ok(bridge)
+    }
+
+    /// Number of items found in both languages, only in the first one and only in the second one
+    pub fn coverage(&self) -> (usize, usize, usize) {
+        self.items
+            .values()
+            .fold((0, 0, 0), |(both, a, b), urls| match urls {
+                [Some(_), Some(_)] => (both + 1, a, b),
+                [Some(_), None] => (both, a + 1, b),
+                [None, Some(_)] => (both, a, b + 1),
+                [None, None] => (both, a, b),
+            })
+    }
+
+    /// Attributes of the nodes and of the edges of the exported graph
+    fn schema(&self) -> Schema {
+        Schema {
+            directed: true,
+            id_width: IdWidth::U64,
+            node_attributes: vec![
+                Attribute::new("url_a", AttributeKind::String),
+                Attribute::new("url_b", AttributeKind::String),
+                Attribute::new("in_a", AttributeKind::Boolean),
+                Attribute::new("in_b", AttributeKind::Boolean),
+            ],
+            edge_attributes: vec![
+                Attribute::new("in_a", AttributeKind::Boolean),
+                Attribute::new("in_b", AttributeKind::Boolean),
+            ],
+            metadata: vec![
+                ("lang_a", self.languages[0].clone()),
+                ("lang_b", self.languages[1].clone()),
+            ],
+        }
+    }
+
+    /// Writes the graph with every one of `formats`. The url of each node is the page of its item on Wikidata
+    pub fn save_to_file(
+        &self,
+        output_file: impl AsRef<str>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+    ) -> io::Result<()> {
+        let mut writer = WriterThread::default().files(
+            Priority::Foreground,
+            output_file.as_ref(),
+            formats,
+            options,
+        )?;
+        self.export(&mut writer)?;
+        writer.finish()
+    }
+
+    fn export(&self, writer: &mut dyn OutputWriter) -> io::Result<()> {
+        writer.begin(&self.schema())?;
+        let mut ids = HashMap::new();
+        for (idx, item) in self.items.keys().enumerate() {
+            let id = NodeId::allocate(idx, IdWidth::U64)
+                .expect("The items should not outnumber the 64-bit ids");
+            ids.insert(item, id);
+        }
+        for ((source, target), found) in &self.links {
+            writer.write_edge(&Edge {
+                source: ids[source],
+                target: ids[target],
+                attributes: found
+                    .iter()
+                    .map(|&in_lang| AttributeValue::Boolean(in_lang))
+                    .collect(),
+            })?;
+        }
+        let details = writer.needs_page_details().then(PageDetails::default);
+        for (item, urls) in &self.items {
+            let mut attributes: Vec<_> = urls
+                .iter()
+                .map(|url| AttributeValue::String(url.clone().unwrap_or_default()))
+                .collect();
+            attributes.extend(
+                urls.iter()
+                    .map(|url| AttributeValue::Boolean(url.is_some())),
+            );
+            writer.write_node(&Node {
+                id: ids[item],
+                url: format!("https://www.wikidata.org/wiki/{item}"),
+                attributes,
+                details: details.clone(),
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// Crawls the neighborhood of the article at `seed`, in the first of `languages`, and the neighborhood of the same article
+/// in the second language with the same depth, then merges the two graphs by the Wikidata items of their articles
+pub fn crawl(
+    seed: &str,
+    languages: [String; 2],
+    config: ScraperConfig,
+) -> Result<BridgeGraph, ScraperError> {
+    let seeds = [seed.to_owned()];
+    let mut scraper = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            all_languages_of_seed: true,
+            languages: Some(vec![languages[1].clone()]),
+            ..config.clone()
+        },
+    )?;
+    // Built once the scraper has validated the config
+    let client = HttpClient::new(&config);
+    let (wiki, retry) = (config.wiki.clone(), config.retry.clone());
+    scraper.scrape()?;
+    let graph = scraper.graph();
+
+    // The pages of each language are on the wiki of its seed
+    let crawled_seeds = graph.seeds.lock().unwrap().clone();
+    if crawled_seeds.len() < 2 {
+        eprintln!(
+            "[WARN] {seed} is not available in the language `{}`",
+            languages[1]
+        );
+    }
+    let origins: Vec<_> = crawled_seeds.iter().map(|seed| origin(seed)).collect();
+    let mut urls = [Vec::new(), Vec::new()];
+    for url in graph.pages.lock().unwrap().keys() {
+        let page_origin = origin(url);
+        if let Some(lang) = origins.iter().position(|seed| *seed == page_origin) {
+            urls[lang].push(url.clone());
+        }
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(ScraperError::RuntimeFailed)?;
+    let mut items = HashMap::new();
+    for (lang, urls) in urls.iter().enumerate() {
+        let found = runtime.block_on(mediawiki::wikidata_items(&client, urls, &wiki, &retry))?;
+        eprintln!(
+            "[INFO] Found the Wikidata item of {} of the {} pages in `{}`",
+            found.len(),
+            urls.len(),
+            languages[lang]
+        );
+        items.extend(found.into_iter().map(|(url, item)| (url, (lang, item))));
+    }
+    BridgeGraph::merge(&graph, languages, &items).map_err(ScraperError::EdgeSpillFailed)
+}
+
+/// Scheme, host and port of `url`, which tell the wikis apart
+fn origin(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .map(|url| url.origin().ascii_serialization())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn pages_are_merged_by_item() {
+        let graph = Graph::new(Arc::new(ScraperConfig::default()));
+        let pages = ["en/S", "en/A", "en/B", "it/S", "it/A", "it/C"];
+        {
+            let mut own_pages = graph.pages.lock().unwrap();
+            let mut own_links = graph.links.lock().unwrap();
+            for (idx, page) in pages.iter().enumerate() {
+                own_pages.insert(
+                    page.to_string(),
+                    NodeId::allocate(idx, IdWidth::U64).unwrap(),
+                );
+            }
+            let id = |page: &str| own_pages[page];
+            for (source, target) in [
+                ("en/S", "en/A"),
+                ("en/S", "en/B"),
+                ("it/S", "it/A"),
+                ("it/A", "it/C"),
+            ] {
+                own_links
+                    .insert_weighted((id(source), id(target)), 1)
+                    .unwrap();
+            }
+        }
+        // C has no item
+        let items = HashMap::from([
+            ("en/S".to_owned(), (0, "Q1".to_owned())),
+            ("en/A".to_owned(), (0, "Q2".to_owned())),
+            ("en/B".to_owned(), (0, "Q3".to_owned())),
+            ("it/S".to_owned(), (1, "Q1".to_owned())),
+            ("it/A".to_owned(), (1, "Q2".to_owned())),
+        ]);
+        let bridge =
+            BridgeGraph::merge(&graph, ["en".to_owned(), "it".to_owned()], &items).unwrap();
+
+        let url = |page: &str| Some(page.to_owned());
+        assert_eq!(
+            bridge.items,
+            BTreeMap::from([
+                ("Q1".to_owned(), [url("en/S"), url("it/S")]),
+                ("Q2".to_owned(), [url("en/A"), url("it/A")]),
+                ("Q3".to_owned(), [url("en/B"), None]),
+            ])
+        );
+        assert_eq!(
+            bridge.links,
+            BTreeMap::from([
+                (("Q1".to_owned(), "Q2".to_owned()), [true, true]),
+                (("Q1".to_owned(), "Q3".to_owned()), [true, false]),
+            ])
+        );
+        assert_eq!(bridge.coverage(), (2, 1, 0));
+    }
+}
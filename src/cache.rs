@@ -0,0 +1,117 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+/// Validators of a response, sent back to the server to check whether the cached response is still fresh
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// Value of the ETag header, sent back in the If-None-Match header
+    pub etag: Option<String>,
+    /// Value of the Last-Modified header, sent back in the If-Modified-Since header
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// A response read from the cache
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub body: String,
+    pub validators: Validators,
+    /// When the response has been stored in the cache
+    pub stored_at: SystemTime,
+}
+
+impl CachedResponse {
+    /// Whether the response has been stored more than `max_age` ago
+    pub fn is_older_than(&self, max_age: Duration) -> bool {
+        self.stored_at.elapsed().is_ok_and(|age| age > max_age)
+    }
+}
+
+/// On-disk cache of the bodies of the responses, by url.
+///
+/// The cache is content-addressed and safe to use from several processes at once,
+/// so the same directory can be shared by concurrent crawls and reused by the following ones.
+/// The validators of each response are stored in the metadata of its entry.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>) -> ResponseCache {
+        ResponseCache { dir: dir.into() }
+    }
+
+    /// Returns the cached response to `url`, if there is one.
+    /// A cache that cannot be read is treated as empty, so that the page is fetched again.
+    pub async fn get(&self, url: &str) -> Option<CachedResponse> {
+        match self.read(url).await {
+            Ok(response) => response,
+            Err(err) => {
+                eprintln!("[WARN] Could not read the cached response of {url}: {err}");
+                None
+            }
+        }
+    }
+
+    async fn read(&self, url: &str) -> Result<Option<CachedResponse>, cacache::Error> {
+        let Some(entry) = cacache::metadata(&self.dir, url).await? else {
+            return Ok(None);
+        };
+        let body = match cacache::read_hash(&self.dir, &entry.integrity).await {
+            Ok(body) => body,
+            // The index entry may outlive its content, e.g. if the content has been removed by hand
+            Err(cacache::Error::EntryNotFound(..)) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let Ok(body) = String::from_utf8(body) else {
+            eprintln!("[WARN] Ignoring the cached response of {url}, which is not valid UTF-8");
+            return Ok(None);
+        };
+        // Entries written without validators have null metadata
+        let validators = serde_json::from_value(entry.metadata).unwrap_or_default();
+        let stored_at = UNIX_EPOCH + Duration::from_millis(entry.time as u64);
+        Ok(Some(CachedResponse {
+            body,
+            validators,
+            stored_at,
+        }))
+    }
+
+    /// Stores the body of the response to `url` along with its validators.
+    /// Failures are only logged, since the page has already been fetched
+    pub async fn put(&self, url: &str, body: &str, validators: &Validators) {
+        if let Err(err) = self.write(url, body, validators).await {
+            eprintln!("[WARN] Could not cache the response of {url}: {err}");
+        }
+    }
+
+    async fn write(
+        &self,
+        url: &str,
+        body: &str,
+        validators: &Validators,
+    ) -> Result<(), cacache::Error> {
+        let metadata = serde_json::to_value(validators).expect("Validators are valid json");
+        let mut writer = cacache::WriteOpts::new()
+            .size(body.len())
+            .metadata(metadata)
+            .open(&self.dir, url)
+            .await?;
+        writer.write_all(body.as_bytes()).await.map_err(|err| {
+            cacache::Error::IoError(err, format!("Failed to write to the cache entry of {url}"))
+        })?;
+        writer.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+};
+
+use crate::edge_store::EdgeStore;
+use crate::node_id::NodeId;
+use crate::output::AttributeValue;
+
+/// Betweenness and closeness of the pages of a graph, to find the pages that bridge its parts
+#[derive(Debug, Default)]
+pub struct Centrality {
+    /// Betweenness and closeness of each page
+    values: HashMap<NodeId, (f64, f64)>,
+    /// Whether the values have been estimated from the searches from a sample of the pages
+    sampled: bool,
+}
+
+impl Centrality {
+    /// Computes the centrality of `nodes`, considering only the links between them, each distinct link counting once.
+    /// If `mutual_only`, as in the undirected graphs, only the pages linking to each other are linked.
+    ///
+    /// The betweenness is the fraction of the shortest paths between the other pages that go through a page,
+    /// normalized by the number of pairs of other pages. The closeness is the harmonic one: the mean of the inverse
+    /// of the distances from the other pages, the pages that cannot reach it counting as 0.
+    /// Both need a breadth first search from every page, so with more than `samples` pages they are estimated
+    /// from the searches from `samples` of them, picked evenly by id so that the same graph always gets the same values
+    pub fn compute(
+        links: &mut EdgeStore,
+        nodes: &[NodeId],
+        mutual_only: bool,
+        samples: usize,
+    ) -> io::Result<Centrality> {
+        let mut nodes = nodes.to_vec();
+        nodes.sort_unstable();
+        let index: HashMap<NodeId, usize> =
+            nodes.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut out_links = vec![Vec::new(); nodes.len()];
+
+        // Both directions of an edge are next to each other in the sorted edges
+        let mut previous = None;
+        for edge in links.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            let reverse_found = previous == Some((dest, source));
+            previous = Some((source, dest));
+            let (Some(&source), Some(&dest)) = (index.get(&source), index.get(&dest)) else {
+                continue;
+            };
+            if source == dest {
+                continue;
+            }
+            if !mutual_only {
+                out_links[source].push(dest);
+            } else if reverse_found {
+                out_links[source].push(dest);
+                out_links[dest].push(source);
+            }
+        }
+
+        let n = nodes.len();
+        let sampled = n > samples;
+        let sources: Vec<usize> = if sampled {
+            (0..samples).map(|i| i * n / samples).collect()
+        } else {
+            (0..n).collect()
+        };
+        let mut betweenness = vec![0.0; n];
+        let mut closeness = vec![0.0; n];
+        let mut is_source = vec![false; n];
+        for &source in &sources {
+            is_source[source] = true;
+            search(&out_links, source, &mut betweenness, &mut closeness);
+        }
+
+        // Each search counts the paths from one page, so the sampled ones stand for n / samples pages each
+        let pairs = n.saturating_sub(1) as f64 * n.saturating_sub(2) as f64;
+        let scale = n as f64 / sources.len().max(1) as f64;
+        let values = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let betweenness = if pairs > 0.0 {
+                    betweenness[i] * scale / pairs
+                } else {
+                    0.0
+                };
+                let others = sources.len() - usize::from(is_source[i]);
+                let closeness = if others > 0 {
+                    closeness[i] / others as f64
+                } else {
+                    0.0
+                };
+                (*id, (betweenness, closeness))
+            })
+            .collect();
+        Ok(Centrality { values, sampled })
+    }
+
+    /// Betweenness of the page, if it is one of the pages the centrality has been computed on
+    pub fn betweenness(&self, id: &NodeId) -> Option<f64> {
+        self.values.get(id).map(|(betweenness, _)| *betweenness)
+    }
+
+    /// Closeness of the page, if it is one of the pages the centrality has been computed on
+    pub fn closeness(&self, id: &NodeId) -> Option<f64> {
+        self.values.get(id).map(|(_, closeness)| *closeness)
+    }
+
+    /// Whether the values have been estimated from a sample of the pages instead of being exact
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// The `count` pages with the highest betweenness, from the highest one, with their betweenness
+    pub fn highest_betweenness(&self, count: usize) -> Vec<(NodeId, f64)> {
+        let mut pages: Vec<_> = self
+            .values
+            .iter()
+            .map(|(id, (betweenness, _))| (*id, *betweenness))
+            .collect();
+        pages.sort_by(|(id, betweenness), (other_id, other)| {
+            other.total_cmp(betweenness).then(id.cmp(other_id))
+        });
+        pages.truncate(count);
+        pages
+    }
+
+    /// Values of the `betweenness` and `closeness` attributes of a page
+    pub fn attributes(&self, id: &NodeId) -> [AttributeValue; 2] {
+        let (betweenness, closeness) = self.values.get(id).copied().unwrap_or_default();
+        [
+            AttributeValue::Float(betweenness),
+            AttributeValue::Float(closeness),
+        ]
+    }
+}
+
+/// Breadth first search from the page at index `source`, as in Brandes' algorithm: adds to `betweenness` the dependency
+/// of `source` on each other page, and to `closeness` the inverse of the distance of each page reached from `source`
+fn search(out_links: &[Vec<usize>], source: usize, betweenness: &mut [f64], closeness: &mut [f64]) {
+    let n = out_links.len();
+    let mut distance = vec![usize::MAX; n];
+    // Number of shortest paths from the source to each page, and the pages right before it on them
+    let mut paths = vec![0.0; n];
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut order = Vec::new();
+    let mut queue = VecDeque::from([source]);
+    distance[source] = 0;
+    paths[source] = 1.0;
+    while let Some(page) = queue.pop_front() {
+        order.push(page);
+        for &next in &out_links[page] {
+            if distance[next] == usize::MAX {
+                distance[next] = distance[page] + 1;
+                queue.push_back(next);
+            }
+            if distance[next] == distance[page] + 1 {
+                paths[next] += paths[page];
+                predecessors[next].push(page);
+            }
+        }
+    }
+
+    let mut dependency = vec![0.0; n];
+    for &page in order.iter().rev() {
+        for &before in &predecessors[page] {
+            dependency[before] += paths[before] / paths[page] * (1.0 + dependency[page]);
+        }
+        if page != source {
+            betweenness[page] += dependency[page];
+            closeness[page] += 1.0 / distance[page] as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: u64) -> NodeId {
+        NodeId::new(id)
+    }
+
+    #[test]
+    fn pages_between_the_others_are_central() {
+        // 0 ⇄ 1 ⇄ 2, with 3 linking to 1 only
+        let mut links = EdgeStore::default();
+        for edge in [(0, 1), (1, 0), (1, 2), (2, 1), (3, 1)] {
+            links.insert((id(edge.0), id(edge.1))).unwrap();
+        }
+        let nodes: Vec<_> = (0..4).map(id).collect();
+
+        let centrality = Centrality::compute(&mut links, &nodes, false, 100).unwrap();
+        assert!(!centrality.is_sampled());
+        // 1 is on the paths 0 → 2, 2 → 0, 3 → 0 and 3 → 2, out of the 6 pairs of other pages
+        assert!((centrality.betweenness(&id(1)).unwrap() - 4.0 / 6.0).abs() < 1e-9);
+        assert_eq!(centrality.betweenness(&id(0)), Some(0.0));
+        // 0 is at distance 1 from 1 and 2 from 2 and 3
+        assert!((centrality.closeness(&id(0)).unwrap() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(centrality.closeness(&id(3)), Some(0.0));
+        assert_eq!(centrality.highest_betweenness(1), [(id(1), 4.0 / 6.0)]);
+
+        // Without the link from 3, which has no reverse, 1 is only between 0 and 2
+        let centrality = Centrality::compute(&mut links, &nodes, true, 100).unwrap();
+        assert!((centrality.betweenness(&id(1)).unwrap() - 2.0 / 6.0).abs() < 1e-9);
+
+        // With 2 of the 4 pages as sources, 0 and 2, the paths through 1 from them are counted twice
+        let centrality = Centrality::compute(&mut links, &nodes, false, 2).unwrap();
+        assert!(centrality.is_sampled());
+        assert!((centrality.betweenness(&id(1)).unwrap() - 4.0 / 6.0).abs() < 1e-9);
+        assert_eq!(
+            centrality.attributes(&id(7)),
+            [AttributeValue::Float(0.0), AttributeValue::Float(0.0)]
+        );
+    }
+}
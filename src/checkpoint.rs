@@ -0,0 +1,147 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::compression::Compression;
+use crate::edge_store::{EdgeStore, Weight};
+use crate::graph::{EdgeType, FailedPage, Graph, Provenance};
+use crate::node_id::NodeId;
+use crate::worker::QueueItem;
+
+/// Version of the checkpoint format, checked when a checkpoint is loaded
+const CHECKPOINT_VERSION: u32 = 6;
+
+/// State of an interrupted crawl, from which it can be resumed
+#[derive(Debug, Default)]
+pub struct Checkpoint {
+    /// Urls the crawl was started from
+    pub seeds: Vec<String>,
+    /// Hosts whose articles are followed when found in a page
+    pub hosts: HashSet<String>,
+    /// Pages waiting to be scraped, including the ones that were being scraped when the checkpoint was written
+    pub queue: Vec<QueueItem>,
+    pub pages: HashMap<String, NodeId>,
+    pub edges: Vec<((NodeId, NodeId), Weight)>,
+    pub ghosts: HashSet<NodeId>,
+    pub provenance: HashMap<NodeId, Provenance>,
+    pub link_positions: HashMap<(NodeId, NodeId), u64>,
+    pub edge_types: HashMap<(NodeId, NodeId), EdgeType>,
+    pub interlanguage: HashMap<(NodeId, NodeId), String>,
+    pub keyword_matches: HashMap<NodeId, Vec<String>>,
+    /// Pages that could not be fetched, which are queued again when resuming until they have failed too many times
+    pub failed: HashMap<String, FailedPage>,
+}
+
+/// Everything written to a checkpoint, borrowed from the running crawl
+pub struct CheckpointState<'a> {
+    pub seeds: &'a [String],
+    pub hosts: &'a HashSet<String>,
+    pub queue: &'a [QueueItem],
+    pub pages: &'a HashMap<String, NodeId>,
+    pub links: &'a mut EdgeStore,
+    pub graph: &'a Graph,
+}
+
+fn write_value(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    bincode::serialize_into(out, value).map_err(io::Error::other)
+}
+
+fn read_value<T: DeserializeOwned>(input: &mut impl Read) -> io::Result<T> {
+    bincode::deserialize_from(input).map_err(io::Error::other)
+}
+
+impl Checkpoint {
+    /// Writes a checkpoint to `path`. The file is replaced only once the new checkpoint has been written completely,
+    /// so that a crawl interrupted while writing it can still be resumed from the previous one.
+    pub fn write(path: impl AsRef<Path>, state: &mut CheckpointState) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        Checkpoint::write_to(&mut out, state)?;
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        std::fs::rename(tmp_path, path)
+    }
+
+    /// Writes a checkpoint to `out`, which is not flushed
+    pub fn write_to(out: &mut impl Write, state: &mut CheckpointState) -> io::Result<()> {
+        let mut out = out;
+        write_value(&mut out, &CHECKPOINT_VERSION)?;
+        write_value(&mut out, &state.seeds)?;
+        write_value(&mut out, state.hosts)?;
+        write_value(&mut out, &state.queue)?;
+        write_value(&mut out, state.pages)?;
+        write_value(&mut out, &*state.graph.ghosts.lock().unwrap())?;
+        write_value(&mut out, &*state.graph.provenance.lock().unwrap())?;
+        write_value(&mut out, &*state.graph.link_positions.lock().unwrap())?;
+        write_value(&mut out, &*state.graph.edge_types.lock().unwrap())?;
+        write_value(&mut out, &*state.graph.interlanguage.lock().unwrap())?;
+        write_value(&mut out, &*state.graph.keyword_matches.lock().unwrap())?;
+        write_value(&mut out, &*state.graph.failed.lock().unwrap())?;
+        // The edges may not fit in memory, so they are written one at a time and followed by None
+        for edge in state.links.sorted_edges()? {
+            write_value(&mut out, &Some(edge?))?;
+        }
+        write_value(&mut out, &None::<((NodeId, NodeId), Weight)>)
+    }
+
+    /// Loads the checkpoint at `path`, which can be compressed as told by its extension
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Checkpoint> {
+        let path = path.as_ref();
+        let compression = Compression::from_path(&path.to_string_lossy());
+        let mut input = BufReader::new(compression.open(path)?);
+        let version: u32 = read_value(&mut input)?;
+        if version != CHECKPOINT_VERSION {
+            return Err(io::Error::other(format!(
+                "Unsupported checkpoint version {version}, expected {CHECKPOINT_VERSION}"
+            )));
+        }
+
+        let mut checkpoint = Checkpoint {
+            seeds: read_value(&mut input)?,
+            hosts: read_value(&mut input)?,
+            queue: read_value(&mut input)?,
+            pages: read_value(&mut input)?,
+            ghosts: read_value(&mut input)?,
+            provenance: read_value(&mut input)?,
+            link_positions: read_value(&mut input)?,
+            edge_types: read_value(&mut input)?,
+            interlanguage: read_value(&mut input)?,
+            keyword_matches: read_value(&mut input)?,
+            failed: read_value(&mut input)?,
+            edges: Vec::new(),
+        };
+        while let Some(edge) = read_value(&mut input)? {
+            checkpoint.edges.push(edge);
+        }
+        Ok(checkpoint)
+    }
+
+    /// Moves the pages and the links of the checkpoint to `graph`, which is expected to be empty
+    pub fn restore_graph(&mut self, graph: &Graph) -> io::Result<()> {
+        *graph.seeds.lock().unwrap() = self.seeds.clone();
+        *graph.pages.lock().unwrap() = std::mem::take(&mut self.pages);
+        *graph.ghosts.lock().unwrap() = std::mem::take(&mut self.ghosts);
+        *graph.provenance.lock().unwrap() = std::mem::take(&mut self.provenance);
+        *graph.link_positions.lock().unwrap() = std::mem::take(&mut self.link_positions);
+        *graph.edge_types.lock().unwrap() = std::mem::take(&mut self.edge_types);
+        *graph.interlanguage.lock().unwrap() = std::mem::take(&mut self.interlanguage);
+        *graph.keyword_matches.lock().unwrap() = std::mem::take(&mut self.keyword_matches);
+        *graph.failed.lock().unwrap() = std::mem::take(&mut self.failed);
+
+        let mut own_links = graph.links.lock().unwrap();
+        for (edge, weight) in std::mem::take(&mut self.edges) {
+            own_links.insert_weighted(edge, weight)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::HashMap, io};
+
+use crate::edge_store::EdgeStore;
+use crate::node_id::NodeId;
+use crate::output::AttributeValue;
+
+/// Local clustering coefficient of the pages of a graph, to measure how close the neighborhoods of the pages are to cliques
+#[derive(Debug, Default)]
+pub struct Clustering {
+    /// Clustering coefficient of each page
+    coefficients: HashMap<NodeId, f64>,
+}
+
+impl Clustering {
+    /// Computes the clustering coefficient of `nodes`, considering only the links between them regardless of their direction,
+    /// as NetworkX does for undirected graphs. If `mutual_only`, as in the undirected graphs, only the pages linking
+    /// to each other are neighbors.
+    ///
+    /// The coefficient of a page is the fraction of the pairs of its neighbors that are linked to each other,
+    /// and 0 for the pages with fewer than two neighbors
+    pub fn compute(
+        links: &mut EdgeStore,
+        nodes: &[NodeId],
+        mutual_only: bool,
+    ) -> io::Result<Clustering> {
+        let index: HashMap<NodeId, usize> =
+            nodes.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut neighbors = vec![Vec::new(); nodes.len()];
+
+        // Both directions of an edge are next to each other in the sorted edges
+        let mut previous = None;
+        for edge in links.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            let reverse_found = previous == Some((dest, source));
+            previous = Some((source, dest));
+            let (Some(&source), Some(&dest)) = (index.get(&source), index.get(&dest)) else {
+                continue;
+            };
+            if source == dest || (mutual_only && !reverse_found) {
+                continue;
+            }
+            neighbors[source].push(dest);
+            neighbors[dest].push(source);
+        }
+        // The pages linking to each other are neighbors once
+        for page_neighbors in &mut neighbors {
+            page_neighbors.sort_unstable();
+            page_neighbors.dedup();
+        }
+
+        // Each link between two neighbors of a page is found once from each of them
+        let mut is_neighbor = vec![false; nodes.len()];
+        let coefficients = nodes
+            .iter()
+            .enumerate()
+            .map(|(page, id)| {
+                let degree = neighbors[page].len();
+                if degree < 2 {
+                    return (*id, 0.0);
+                }
+                for &neighbor in &neighbors[page] {
+                    is_neighbor[neighbor] = true;
+                }
+                let mut found = 0;
+                for &neighbor in &neighbors[page] {
+                    found += neighbors[neighbor]
+                        .iter()
+                        .filter(|other| is_neighbor[**other])
+                        .count();
+                }
+                for &neighbor in &neighbors[page] {
+                    is_neighbor[neighbor] = false;
+                }
+                (*id, found as f64 / (degree * (degree - 1)) as f64)
+            })
+            .collect();
+        Ok(Clustering { coefficients })
+    }
+
+    /// Clustering coefficient of the page, if it is one of the pages the coefficients have been computed on
+    pub fn coefficient(&self, id: &NodeId) -> Option<f64> {
+        self.coefficients.get(id).copied()
+    }
+
+    /// Mean of the clustering coefficients of the pages, 0 if there are none
+    pub fn average(&self) -> f64 {
+        if self.coefficients.is_empty() {
+            return 0.0;
+        }
+        self.coefficients.values().sum::<f64>() / self.coefficients.len() as f64
+    }
+
+    /// Value of the `clustering` attribute of a page
+    pub fn attributes(&self, id: &NodeId) -> [AttributeValue; 1] {
+        [AttributeValue::Float(
+            self.coefficient(id).unwrap_or_default(),
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: u64) -> NodeId {
+        NodeId::new(id)
+    }
+
+    #[test]
+    fn coefficients_count_the_links_between_the_neighbors() {
+        // 0 ⇄ 1, 1 → 2, 2 → 0 and 0 → 3: the neighbors of 0 are 1, 2 and 3, of which only 1 and 2 are linked
+        let mut links = EdgeStore::default();
+        for edge in [(0, 1), (1, 0), (1, 2), (2, 0), (0, 3)] {
+            links.insert((id(edge.0), id(edge.1))).unwrap();
+        }
+        let nodes: Vec<_> = (0..4).map(id).collect();
+
+        let clustering = Clustering::compute(&mut links, &nodes, false).unwrap();
+        assert!((clustering.coefficient(&id(0)).unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(clustering.coefficient(&id(1)), Some(1.0));
+        assert_eq!(clustering.coefficient(&id(3)), Some(0.0));
+        assert!((clustering.average() - (1.0 / 3.0 + 2.0) / 4.0).abs() < 1e-9);
+
+        // Only 0 and 1 link to each other, so no page has two neighbors
+        let clustering = Clustering::compute(&mut links, &nodes, true).unwrap();
+        assert_eq!(clustering.average(), 0.0);
+        assert_eq!(clustering.attributes(&id(7)), [AttributeValue::Float(0.0)]);
+    }
+}
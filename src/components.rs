@@ -0,0 +1,149 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+};
+
+use crate::edge_store::EdgeStore;
+use crate::node_id::NodeId;
+use crate::output::AttributeValue;
+
+/// Connected components of the pages of a graph, numbered from 0 by decreasing size
+#[derive(Debug)]
+pub struct Components {
+    /// Component of each page
+    ids: HashMap<NodeId, usize>,
+    /// Number of pages of each component, by component
+    sizes: Vec<usize>,
+}
+
+impl Components {
+    /// Computes the components of `nodes`, considering only the links between them regardless of their direction,
+    /// which are the weakly connected components of the graph. If `mutual_only`, as in the undirected graphs,
+    /// only the pages linking to each other are connected. Components of the same size are numbered by their lowest id
+    pub fn compute(
+        links: &mut EdgeStore,
+        nodes: &[NodeId],
+        mutual_only: bool,
+    ) -> io::Result<Components> {
+        let index: HashMap<NodeId, usize> =
+            nodes.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut parents: Vec<usize> = (0..nodes.len()).collect();
+
+        // Both directions of an edge are next to each other in the sorted edges
+        let mut previous = None;
+        for edge in links.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            let reverse_found = previous == Some((dest, source));
+            previous = Some((source, dest));
+            if mutual_only && !reverse_found {
+                continue;
+            }
+            if let (Some(&source), Some(&dest)) = (index.get(&source), index.get(&dest)) {
+                let (source, dest) = (root(&mut parents, source), root(&mut parents, dest));
+                // The root with the lowest index is kept, so that each component is rooted at its first page
+                parents[source.max(dest)] = source.min(dest);
+            }
+        }
+
+        let mut members: HashMap<usize, Vec<NodeId>> = HashMap::new();
+        for (i, id) in nodes.iter().enumerate() {
+            members.entry(root(&mut parents, i)).or_default().push(*id);
+        }
+        let mut members: Vec<_> = members.into_values().collect();
+        for component in &mut members {
+            component.sort();
+        }
+        members.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+
+        Ok(Components {
+            ids: members
+                .iter()
+                .enumerate()
+                .flat_map(|(component, ids)| ids.iter().map(move |id| (*id, component)))
+                .collect(),
+            sizes: members.iter().map(Vec::len).collect(),
+        })
+    }
+
+    /// Component of the page, if it is one of the pages the components have been computed on
+    pub fn component(&self, id: &NodeId) -> Option<usize> {
+        self.ids.get(id).copied()
+    }
+
+    /// Whether the page is part of the largest component
+    pub fn is_giant(&self, id: &NodeId) -> bool {
+        self.component(id) == Some(0)
+    }
+
+    /// Number of pages of each component, from the largest one
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+
+    /// Number of components of each size
+    pub fn size_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut distribution = BTreeMap::new();
+        for size in &self.sizes {
+            *distribution.entry(*size).or_default() += 1;
+        }
+        distribution
+    }
+
+    /// Value of the `component_id` attribute of a page
+    pub fn attributes(&self, id: &NodeId) -> [AttributeValue; 1] {
+        [AttributeValue::Integer(
+            self.component(id).map_or(-1, |component| component as i64),
+        )]
+    }
+}
+
+/// Root of the tree of the page at index `i`, halving the path to it along the way
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: u64) -> NodeId {
+        NodeId::new(id)
+    }
+
+    #[test]
+    fn pages_are_grouped_by_component() {
+        // 0 → 1 ⇄ 2 and 3 ⇄ 4, with 5 alone
+        let mut links = EdgeStore::default();
+        for edge in [(0, 1), (1, 2), (2, 1), (3, 4), (4, 3)] {
+            links.insert((id(edge.0), id(edge.1))).unwrap();
+        }
+        let nodes: Vec<_> = (0..6).map(id).collect();
+
+        let components = Components::compute(&mut links, &nodes, false).unwrap();
+        assert_eq!(components.sizes(), [3, 2, 1]);
+        assert_eq!(
+            nodes
+                .iter()
+                .map(|node| components.component(node).unwrap())
+                .collect::<Vec<_>>(),
+            [0, 0, 0, 1, 1, 2]
+        );
+        assert_eq!(
+            components.size_distribution(),
+            BTreeMap::from([(1, 1), (2, 1), (3, 1)])
+        );
+        assert!(components.is_giant(&id(2)));
+
+        // Without the link from 0 to 1, which has no reverse, both components have 2 pages and 1 comes first
+        let components = Components::compute(&mut links, &nodes, true).unwrap();
+        assert_eq!(components.sizes(), [2, 2, 1, 1]);
+        assert_eq!(components.component(&id(1)), Some(0));
+        assert_eq!(components.component(&id(3)), Some(1));
+        assert_eq!(components.component(&id(0)), Some(2));
+        assert_eq!(components.attributes(&id(7)), [AttributeValue::Integer(-1)]);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+
+#[cfg(not(feature = "zstd"))]
+use crate::errors::missing_feature;
+
+/// Compression applied to the output files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    /// Requires the `zstd` feature
+    Zstd,
+}
+
+impl Compression {
+    /// Extension appended to the name of the output files, including the leading dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    /// Infers the compression of a file from its extension, e.g. `edges.csv.gz`
+    pub fn from_path(path: &str) -> Compression {
+        [Compression::Gzip, Compression::Zstd]
+            .into_iter()
+            .find(|compression| path.ends_with(compression.extension()))
+            .unwrap_or(Compression::None)
+    }
+
+    /// Creates the file at `path` and returns a writer that compresses everything written to it
+    pub fn create(&self, path: impl AsRef<Path>) -> io::Result<OutputFile> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match self {
+            Compression::None => OutputFile::Plain(file),
+            Compression::Gzip => {
+                OutputFile::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => OutputFile::Zstd(zstd::Encoder::new(file, 0)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(missing_feature("zstd")),
+        })
+    }
+
+    /// Opens the file at `path` and returns a reader that decompresses its content
+    pub fn open(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn Read>> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(match self {
+            Compression::None => Box::new(file),
+            Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Box::new(zstd::Decoder::with_buffer(file)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(missing_feature("zstd")),
+        })
+    }
+}
+
+/// An output file, possibly streaming its content through an encoder.
+/// `finish` must be called once everything has been written, so that the encoder can write its trailer.
+pub enum OutputFile {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Finished,
+}
+
+impl OutputFile {
+    pub fn finish(&mut self) -> io::Result<()> {
+        match std::mem::replace(self, OutputFile::Finished) {
+            OutputFile::Plain(mut file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.finish()?.flush(),
+            #[cfg(feature = "zstd")]
+            OutputFile::Zstd(encoder) => encoder.finish()?.flush(),
+            OutputFile::Finished => Ok(()),
+        }
+    }
+}
+
+fn finished_error() -> io::Error {
+    io::Error::other("The file has already been finished")
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "zstd")]
+            OutputFile::Zstd(encoder) => encoder.write(buf),
+            OutputFile::Finished => Err(finished_error()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            OutputFile::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "zstd")]
+            OutputFile::Zstd(encoder) => encoder.flush(),
+            OutputFile::Finished => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,903 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use regex::Regex;
+use serde::{de, Deserialize, Deserializer};
+
+use crate::errors::{InvalidConfig, ScraperError};
+use crate::filter::Filter;
+use crate::http::{self, DEFAULT_USER_AGENT};
+use crate::keywords::KeywordFilter;
+use crate::node_id::IdWidth;
+use crate::rate_limit::AdaptiveRateConfig;
+
+/// What to do with the pages rejected by the keywords, which are linked to by other pages but not scraped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GhostNodes {
+    /// Keep them as regular nodes, along with the links to them
+    #[default]
+    Keep,
+    /// Keep them, and add a `ghost` column to the nodes telling whether each page has been rejected
+    Mark,
+    /// Remove them from the graph, along with the links to them
+    Exclude,
+}
+
+/// Algorithm used to compute the coordinates of the pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayoutAlgorithm {
+    /// Spring model where every page repels the others and linked pages attract each other
+    FruchtermanReingold,
+    /// Gephi's default layout, where pages with many links repel the others more, so that hubs are spread apart
+    ForceAtlas2,
+}
+
+/// Order in which the pages in the queue are scraped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CrawlStrategy {
+    /// Breadth first, in the order the pages are found
+    #[default]
+    Bfs,
+    /// Depth first, scraping the page found last
+    Dfs,
+    /// Scrape first the pages with the most links from the pages scraped so far
+    Priority,
+}
+
+/// How the keywords are matched against the content of a page. Every mode ignores case
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeywordMode {
+    /// The page contains at least one of the keywords
+    #[default]
+    Any,
+    /// The page contains every keyword
+    All,
+    /// The keywords are regular expressions, and the page matches at least one of them
+    Regex,
+}
+
+/// Which links of a page are kept when it has more than the maximum number of links per page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkSampling {
+    /// The links found first in the page
+    #[default]
+    First,
+    /// A random sample of the links of the page
+    Random,
+}
+
+/// Part of the content of a page whose links are part of the graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LinkScope {
+    /// Every link in the content
+    #[default]
+    All,
+    /// The links in the paragraphs before the first section heading, which summarize the article
+    Lead,
+    /// The links in the paragraphs, leaving out the navigation boxes, the tables, the lists and the references
+    Prose,
+}
+
+/// Kind of site being scraped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SiteMode {
+    /// A MediaWiki site, such as wikipedia, whose articles are followed
+    #[default]
+    Wiki,
+    /// Any website, such as a documentation site or a blog, whose pages are all followed
+    Generic,
+}
+
+/// Credentials sent with every request to the wiki, but not when checking the external links
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Auth {
+    /// Http basic authentication
+    Basic { user: String, password: String },
+    /// Token sent in the Authorization header as `Bearer <token>`
+    Bearer(String),
+}
+
+/// Account used to log in to a MediaWiki site with its Action API before the crawl starts.
+/// Bot passwords created in Special:BotPasswords are accepted, as `<user>@<bot name>`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotLogin {
+    pub user: String,
+    pub password: String,
+}
+
+/// Where the articles and their links are found in a MediaWiki site. The defaults are the ones of wikipedia
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WikiConfig {
+    /// Prefix of the path of the articles, e.g. `/wiki/` for `https://en.wikipedia.org/wiki/Crocodile`
+    pub article_path: String,
+
+    /// Path of the Action API, used to fetch the links when `use_api` is set
+    pub api_path: String,
+
+    /// CSS selector of the element of the pages containing the links that are part of the graph
+    pub content_selector: String,
+
+    /// Namespaces whose pages are part of the graph along with the articles, e.g. `Category`
+    pub namespaces: Vec<String>,
+}
+
+impl Default for WikiConfig {
+    fn default() -> Self {
+        WikiConfig {
+            article_path: "/wiki/".to_owned(),
+            api_path: "/w/api.php".to_owned(),
+            content_selector: "#bodyContent".to_owned(),
+            namespaces: Vec::new(),
+        }
+    }
+}
+
+/// How failed requests are retried
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Number of times a request is sent before giving up, including the first one
+    pub max_attempts: u32,
+
+    /// Delay before the first retry. It is doubled at each following retry
+    #[serde(deserialize_with = "deserialize_secs")]
+    pub base_delay: Duration,
+
+    /// The delay between two retries never goes above this value, unless the server asks for a longer one
+    #[serde(deserialize_with = "deserialize_secs")]
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Category whose members are the seeds of the crawl, which only builds the graph of the links among them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryConfig {
+    /// Url of the page of the category, e.g. `https://en.wikipedia.org/wiki/Category:Graph_theory`
+    pub url: String,
+
+    /// Levels of subcategories whose members are seeds as well. 0 only uses the pages directly in the category
+    pub depth: u64,
+}
+
+/// Random articles of a wiki used as seeds, to sample its overall structure rather than the neighborhood of a page
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RandomSeeds {
+    /// Url of any page of the wiki, e.g. `https://en.wikipedia.org/wiki/Special:Random`
+    pub url: String,
+
+    /// Number of articles to pick
+    pub count: u64,
+}
+
+/// Curated list of articles of the English Wikipedia, used as seeds to build standard reference graphs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeedPreset {
+    /// The 1000 vital articles of level 3, linked by `Wikipedia:Vital_articles`
+    VitalLevel3,
+    /// The featured articles, members of `Category:Featured_articles`
+    Featured,
+}
+
+impl SeedPreset {
+    /// Title of the page listing the articles of the preset
+    pub fn page_title(self) -> &'static str {
+        match self {
+            SeedPreset::VitalLevel3 => "Wikipedia:Vital_articles",
+            SeedPreset::Featured => "Category:Featured_articles",
+        }
+    }
+}
+
+/// Articles of a preset used as seeds
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresetSeeds {
+    pub preset: SeedPreset,
+
+    /// Url of the page listing the articles, e.g. `https://en.wikipedia.org/wiki/Wikipedia:Vital_articles`
+    pub url: String,
+}
+
+/// When a page that has already been scraped can be scraped again, adding its links to the graph once more.
+/// By default every page is scraped at most once
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RevisitConfig {
+    /// Time after which a page can be scraped again. `None` never revisits a page because of the time passed
+    #[serde(deserialize_with = "deserialize_optional_secs")]
+    pub after: Option<Duration>,
+
+    /// Number of hops from the seed after which a page found again can be scraped again.
+    /// `None` never revisits a page because of the hops passed
+    pub after_hops: Option<u64>,
+}
+
+impl RevisitConfig {
+    /// Whether pages are scraped at most once
+    pub fn is_disabled(&self) -> bool {
+        self.after.is_none() && self.after_hops.is_none()
+    }
+}
+
+/// What is done with the links pointing outside of the crawled wikis or sites, when they are kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExternalLinks {
+    /// Add the external pages to the graph, without fetching them
+    #[default]
+    Nodes,
+    /// Add the external pages to the graph and fetch them once, without following their links,
+    /// to record their title and the status and url of the final response
+    Fetch,
+    /// Write the links to the external pages to a separate file, <output-file>_external_edges.csv, instead of the graph
+    Separate,
+}
+
+/// Regular expressions restricting the links that are recorded and followed, searched for in their whole url.
+/// The links that are filtered out are dropped before being added to the graph
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UrlFilter {
+    /// A link has to match at least one of these, if there are any
+    #[serde(deserialize_with = "deserialize_regexes")]
+    pub allow: Vec<Regex>,
+    /// A link matching any of these is dropped
+    #[serde(deserialize_with = "deserialize_regexes")]
+    pub deny: Vec<Regex>,
+}
+
+impl UrlFilter {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether the link to `url` is kept
+    pub fn allows(&self, url: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|regex| regex.is_match(url)))
+            && !self.deny.iter().any(|regex| regex.is_match(url))
+    }
+}
+
+/// Fixed limit on the rate of the requests, shared by all of the workers
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub requests_per_second: f64,
+    /// Number of requests that can be sent at once after a pause
+    pub burst: u32,
+}
+
+impl RateLimitConfig {
+    /// Allows one request every `delay`
+    pub fn with_delay(delay: Duration) -> RateLimitConfig {
+        RateLimitConfig {
+            requests_per_second: 1.0 / delay.as_secs_f64(),
+            burst: 1,
+        }
+    }
+}
+
+/// Depth of the continuous crawls, which go on until their budget runs out instead of stopping at a depth
+pub const UNLIMITED_DEPTH: u64 = u64::MAX;
+
+/// Options controlling a scrape, shared between the scraper and all of its workers
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScraperConfig {
+    /// Depth of the scrape
+    pub depth: u64,
+
+    /// Order in which the pages in the queue are scraped
+    pub strategy: CrawlStrategy,
+
+    /// Maximum number of pages to scrape. Once reached, the pages left in the queue are not scraped. `None` means no limit
+    pub max_pages: Option<usize>,
+
+    /// Time after which the pages left in the queue are not scraped. `None` means no limit
+    #[serde(deserialize_with = "deserialize_optional_secs")]
+    pub max_duration: Option<Duration>,
+
+    /// Normalized url of a page whose discovery stops the crawl, when looking for a path to it
+    pub stop_at: Option<String>,
+
+    /// Number of threads of the async runtime, which fetch and parse the pages
+    pub num_threads: usize,
+
+    /// Maximum number of pages being scraped at the same time
+    pub concurrency: usize,
+
+    /// Kind of site being scraped
+    pub site_mode: SiteMode,
+
+    /// Whether to only follow the links to the hosts of the seeds when scraping a generic site.
+    /// Otherwise every link to a web page is followed
+    pub same_domain_only: bool,
+
+    /// Where the articles and their links are found in the pages of the wiki
+    pub wiki: WikiConfig,
+
+    /// Part of the content of the pages whose links are part of the graph. Only used when scraping the HTML of the pages
+    pub link_scope: LinkScope,
+
+    /// Whether to fetch the links of the pages from the MediaWiki Action API of their wiki, instead of scraping their HTML
+    pub use_api: bool,
+
+    /// Category whose members are the seeds, along with the given ones. When set, only the links among the seeds are part of the graph
+    pub category: Option<CategoryConfig>,
+
+    /// Random articles used as seeds, along with the given ones
+    pub random_seeds: Option<RandomSeeds>,
+
+    /// Articles of a curated list used as seeds, listed with the Action API of the wiki
+    pub preset: Option<PresetSeeds>,
+
+    /// Keywords that a page has to contain in order to be scraped
+    pub keywords: Option<Vec<String>>,
+
+    /// How the keywords are matched against the content of the pages
+    pub keyword_mode: KeywordMode,
+
+    /// Keywords rejecting the pages that contain any of them, which are not expanded.
+    /// They are regular expressions in the regex keyword mode
+    pub exclude_keywords: Option<Vec<String>>,
+
+    /// Number of hops from the seeds from which the pages have to contain the keywords. The pages closer to the seeds,
+    /// which are usually relevant even without the keywords, only have to not contain the excluded ones. 0 checks every page
+    pub keywords_from_hop: u64,
+
+    /// Links that are recorded and followed, by their url
+    pub url_filter: UrlFilter,
+
+    /// Whether to only keep the edges that exist in both directions
+    pub undirected: bool,
+
+    /// Whether to count how many times a page links to another one and save it as the weight of the edge
+    pub weighted: bool,
+
+    /// What to do with the pages rejected by the keywords
+    pub ghost_nodes: GhostNodes,
+
+    /// Whether to save a subgraph for each keyword, with the pages containing it
+    pub split_by_keyword: bool,
+
+    /// Prefix of the files the graph is saved to, next to which the subgraphs of the keywords and the separate
+    /// external links are written. `None` if the graph is not saved to files
+    pub output_file: Option<String>,
+
+    /// Whether to record the keywords contained in each scraped page, for `split_by_keyword` and the pages-jsonl format
+    pub record_keyword_matches: bool,
+
+    /// Whether to save the in-degree and the out-degree of the pages as attributes of the nodes
+    pub degrees: bool,
+
+    /// Whether to compute the connected components of the graph, saving the component of each page as an attribute of its node
+    pub components: bool,
+
+    /// Whether to only export the largest connected component of the graph, along with the links between its pages
+    pub giant_component_only: bool,
+
+    /// Whether to compute the PageRank of the pages, saving it along with its percentile as attributes of the nodes
+    pub pagerank: bool,
+
+    /// Fraction of the pages with the highest PageRank that are exported, along with the links between them.
+    /// `None` exports every page. Only used if the PageRank is computed
+    pub top_fraction: Option<f64>,
+
+    /// Whether to compute the betweenness and the closeness of the pages, saving them as attributes of the nodes
+    pub centrality: bool,
+
+    /// Number of pages the centrality is computed exactly up to. Above it, it is estimated from this many pages
+    pub centrality_samples: usize,
+
+    /// Whether to compute the clustering coefficient of the pages, saving it as an attribute of their nodes
+    pub clustering: bool,
+
+    /// Algorithm used to compute the `x` and `y` coordinates of the pages when exporting the graph. `None` does not lay out the graph
+    pub layout: Option<LayoutAlgorithm>,
+
+    /// Number of iterations of the layout algorithm
+    pub layout_iterations: usize,
+
+    /// Expression selecting the exported nodes by their attributes, along with the links between them. `None` exports every node
+    pub node_filter: Option<Filter>,
+
+    /// Expression selecting the exported edges by their attributes. `None` exports every edge between the exported nodes
+    pub edge_filter: Option<Filter>,
+
+    /// Whether to record when and by which worker each page has been scraped, and save it as attributes of its edges
+    pub provenance: bool,
+
+    /// Whether to record the position of each link among the links of its source page, and save it as an attribute of its edge
+    pub link_positions: bool,
+
+    /// Whether to record the part of its source page each link is found in, such as an infobox, and save it as the type of its edge
+    pub edge_types: bool,
+
+    /// Url of a Gephi workspace to stream the graph to while it is being built
+    pub gephi_stream: Option<String>,
+
+    /// Number of pages above which a warning is logged
+    pub warn_nodes: Option<u64>,
+
+    /// Number of links above which a warning is logged
+    pub warn_edges: Option<u64>,
+
+    /// Number of pages without links in a row above which the crawl is stopped with an error
+    pub max_empty_pages: Option<u64>,
+
+    /// Time after which a worker abandons a fetched page that it is still parsing, so that a pathological page
+    /// cannot hold it for the rest of the crawl. `None` waits for every page
+    #[serde(deserialize_with = "deserialize_optional_secs")]
+    pub page_timeout: Option<Duration>,
+
+    /// When the pages that have already been scraped can be scraped again
+    pub revisit: RevisitConfig,
+
+    /// Time after which a page waiting in the queue is checked again before being scraped, and dropped if it
+    /// has been scraped or rejected since it was queued. `None` scrapes every page taken from the queue
+    #[serde(deserialize_with = "deserialize_optional_secs")]
+    pub frontier_ttl: Option<Duration>,
+
+    /// What to do with the links pointing outside of wikipedia. `None` drops them
+    pub keep_external_links: Option<ExternalLinks>,
+
+    /// Whether to send a request to every external link, recording the status and the url of the final response
+    pub check_external: bool,
+
+    /// Whether to identify the pages by the url they are fetched from, even if they have a different canonical url
+    pub ignore_canonical: bool,
+
+    /// Whether to leave the disambiguation pages out of the graph, without following their links
+    pub skip_disambiguation: bool,
+
+    /// Width of the ids of the pages. Once every id has been used, the crawl is stopped
+    pub id_width: IdWidth,
+
+    /// Number of outgoing links of a page after which the rest of its links are skipped. `None` keeps every link
+    pub stop_expanding_at_degree: Option<usize>,
+
+    /// Number of distinct links kept from every page, before they are added to the graph. `None` keeps every link
+    pub max_links_per_page: Option<usize>,
+
+    /// Which links are kept from the pages with more than `max_links_per_page` links
+    pub link_sampling: LinkSampling,
+
+    /// Whether to also scrape the seed article in the other languages it is available in
+    pub all_languages_of_seed: bool,
+
+    /// Restricts the languages used by `all_languages_of_seed`. `None` means every available language
+    pub languages: Option<Vec<String>>,
+
+    /// Whether to add the links from every scraped article to the same article in the other languages,
+    /// recording the language of their target
+    pub interlanguage_links: bool,
+
+    /// Depth with which the articles in other languages are followed, from the article linking to them.
+    /// They are followed at most with the depth left to the article. `None` does not follow them
+    pub interlanguage_depth: Option<u64>,
+
+    /// Maximum number of edges kept in memory before spilling them to disk. `None` keeps every edge in memory
+    pub max_edges_in_memory: Option<usize>,
+
+    /// Directory where the spilled edges are written. Defaults to the system temporary directory
+    pub spill_dir: Option<PathBuf>,
+
+    /// Value of the User-Agent header of every request
+    pub user_agent: String,
+
+    /// Headers added to every request to the wiki, as name and value, e.g. cookies to crawl a private wiki.
+    /// They are not sent when checking the external links
+    pub headers: Vec<(String, String)>,
+
+    /// Credentials sent with every request to the wiki. `None` sends no credentials
+    pub auth: Option<Auth>,
+
+    /// Account to log in to the wiki with before the crawl starts. The cookies of its session are sent with
+    /// every request to the host of the first seed
+    pub bot_login: Option<BotLogin>,
+
+    /// Url of the proxy every request is sent through. `None` uses the proxy set in the environment, if any
+    pub proxy: Option<String>,
+
+    /// Directory of the cache of the responses, shared across runs. `None` always fetches the pages
+    pub cache_dir: Option<PathBuf>,
+
+    /// Whether to check with the server that the cached responses have not changed before using them
+    pub revalidate_cache: bool,
+
+    /// Age after which a cached response is stale: it is still used right away, and fetched again in the background
+    /// to refresh the cache for the following crawls. `None` never refreshes the cached responses in the background
+    #[serde(deserialize_with = "deserialize_optional_secs")]
+    pub stale_while_revalidate: Option<Duration>,
+
+    /// File the state of the crawl is periodically written to, so that it can be resumed if it is interrupted
+    pub checkpoint: Option<PathBuf>,
+
+    /// Time between two checkpoints
+    #[serde(deserialize_with = "deserialize_secs")]
+    pub checkpoint_interval: Duration,
+
+    /// Number of times the crawl can give up on fetching a page. The pages that could not be fetched are queued again
+    /// when the crawl is resumed from a checkpoint, until they have failed this many times
+    pub max_page_attempts: u32,
+
+    /// File the decisions taken about each page are appended to, one json object per line
+    pub event_log: Option<PathBuf>,
+
+    /// Page whose decisions are printed as they are taken
+    pub explain: Option<String>,
+
+    /// How failed requests are retried
+    pub retry: RetryConfig,
+
+    /// Fixed limit on the rate of the requests. `None` sends requests as fast as possible
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// Limit on the rate of the requests to each external host, sent to check or fetch the external links.
+    /// The other sites are often far less tolerant than the wiki. `None` sends them as fast as possible
+    pub external_rate_limit: Option<RateLimitConfig>,
+
+    /// Settings of the adaptive rate limiter. `None` sends requests as fast as possible
+    pub adaptive_rate_limit: Option<AdaptiveRateConfig>,
+
+    /// Maximum number of bytes downloaded per second, across all of the workers. `None` does not limit the bandwidth
+    pub max_bandwidth: Option<u64>,
+}
+
+impl ScraperConfig {
+    /// Reads the config from a json file and validates it like [`ScraperConfig::validate`]. The keys of the file are
+    /// the names of the fields, and the ones left out keep their default value, e.g. `{"depth": 2, "pagerank": true}`.
+    /// The durations are given in seconds, and the enums by the values of the matching command line options
+    pub fn from_file(path: &Path) -> Result<ScraperConfig, ScraperError> {
+        let file = fs::read_to_string(path).map_err(ScraperError::ConfigFileFailed)?;
+        let config: ScraperConfig =
+            serde_json::from_str(&file).map_err(|err| InvalidConfig(vec![err.to_string()]))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Whether the external links found in the pages are sent a request, to record the response
+    pub fn checks_external(&self) -> bool {
+        self.check_external || self.keep_external_links == Some(ExternalLinks::Fetch)
+    }
+
+    /// Checks that the options can be used together, so that none of them is silently ignored, and that the proxy,
+    /// headers, content selector and keywords are valid. Every problem found is reported at once, instead of only the first one
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        self.keyword_filter().map(|_| ())
+    }
+
+    /// Validates the config like [`ScraperConfig::validate`], returning the filter built from the keywords
+    /// along the way, so that their regular expressions are not compiled twice
+    pub(crate) fn keyword_filter(&self) -> Result<KeywordFilter, InvalidConfig> {
+        let mut problems = Vec::new();
+        let mut requires = |set: bool, option: &str, required: &str, present: bool| {
+            if set && !present {
+                problems.push(format!("{option} requires {required}"));
+            }
+        };
+        requires(
+            self.depth == UNLIMITED_DEPTH,
+            "an unlimited depth",
+            "max_pages or max_duration",
+            self.max_pages.is_some() || self.max_duration.is_some(),
+        );
+        requires(
+            self.keywords_from_hop > 0,
+            "keywords_from_hop",
+            "keywords",
+            self.keywords.is_some(),
+        );
+        requires(
+            self.split_by_keyword,
+            "split_by_keyword",
+            "keywords",
+            self.keywords.is_some(),
+        );
+        requires(
+            self.split_by_keyword,
+            "split_by_keyword",
+            "output_file",
+            self.output_file.is_some(),
+        );
+        requires(
+            self.keep_external_links == Some(ExternalLinks::Separate),
+            "keep_external_links = separate",
+            "output_file",
+            self.output_file.is_some(),
+        );
+        requires(
+            self.top_fraction.is_some(),
+            "top_fraction",
+            "pagerank",
+            self.pagerank,
+        );
+        requires(
+            self.check_external,
+            "check_external",
+            "keep_external_links",
+            self.keep_external_links.is_some(),
+        );
+        requires(
+            self.external_rate_limit.is_some(),
+            "external_rate_limit",
+            "check_external or keep_external_links = fetch",
+            self.checks_external(),
+        );
+        requires(
+            self.languages.is_some(),
+            "languages",
+            "all_languages_of_seed",
+            self.all_languages_of_seed,
+        );
+        requires(
+            self.interlanguage_depth.is_some(),
+            "interlanguage_depth",
+            "interlanguage_links",
+            self.interlanguage_links,
+        );
+        requires(
+            self.spill_dir.is_some(),
+            "spill_dir",
+            "max_edges_in_memory",
+            self.max_edges_in_memory.is_some(),
+        );
+        requires(
+            self.keyword_mode != KeywordMode::Any,
+            "keyword_mode",
+            "keywords or exclude_keywords",
+            self.keywords.is_some() || self.exclude_keywords.is_some(),
+        );
+        requires(
+            self.ghost_nodes != GhostNodes::Keep,
+            "ghost_nodes",
+            "keywords or exclude_keywords",
+            self.keywords.is_some() || self.exclude_keywords.is_some(),
+        );
+        requires(
+            self.link_sampling != LinkSampling::First,
+            "link_sampling",
+            "max_links_per_page",
+            self.max_links_per_page.is_some(),
+        );
+        requires(
+            self.revalidate_cache,
+            "revalidate_cache",
+            "cache_dir",
+            self.cache_dir.is_some(),
+        );
+        requires(
+            self.stale_while_revalidate.is_some(),
+            "stale_while_revalidate",
+            "cache_dir",
+            self.cache_dir.is_some(),
+        );
+
+        let conflicts = [
+            (
+                "revalidate_cache",
+                self.revalidate_cache,
+                vec![(
+                    "stale_while_revalidate",
+                    self.stale_while_revalidate.is_some(),
+                )],
+            ),
+            // The api only returns the titles of the linked pages, not their content nor where the links are
+            (
+                "use_api",
+                self.use_api,
+                vec![
+                    ("keywords", self.keywords.is_some()),
+                    ("exclude_keywords", self.exclude_keywords.is_some()),
+                    ("keep_external_links", self.keep_external_links.is_some()),
+                    (
+                        "a link_scope other than all",
+                        self.link_scope != LinkScope::All,
+                    ),
+                    ("edge_types", self.edge_types),
+                    ("interlanguage_links", self.interlanguage_links),
+                ],
+            ),
+            // Every other option relies on the api of a wiki
+            (
+                "the generic site mode",
+                self.site_mode == SiteMode::Generic,
+                vec![
+                    ("use_api", self.use_api),
+                    ("category", self.category.is_some()),
+                    ("random_seeds", self.random_seeds.is_some()),
+                    ("preset", self.preset.is_some()),
+                    ("bot_login", self.bot_login.is_some()),
+                    ("all_languages_of_seed", self.all_languages_of_seed),
+                    ("interlanguage_links", self.interlanguage_links),
+                ],
+            ),
+        ];
+        for (option, set, others) in conflicts {
+            for (other, other_set) in others {
+                if set && other_set {
+                    problems.push(format!("{option} cannot be used with {other}"));
+                }
+            }
+        }
+        if let Some(top_fraction) = self.top_fraction {
+            if !(top_fraction > 0.0 && top_fraction <= 1.0) {
+                problems.push(format!(
+                    "top_fraction has to be between 0 and 1, found {top_fraction}"
+                ));
+            }
+        }
+        for (option, rate_limit) in [
+            ("rate_limit", &self.rate_limit),
+            ("external_rate_limit", &self.external_rate_limit),
+        ] {
+            if let Some(rate_limit) = rate_limit {
+                let rate = rate_limit.requests_per_second;
+                if !(rate.is_finite() && rate > 0.0) {
+                    problems.push(format!(
+                        "{option} has to allow a positive number of requests per second, found {rate}"
+                    ));
+                }
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            problems.extend(http::parse_proxy(proxy).err());
+        }
+        for (name, value) in &self.headers {
+            problems.extend(http::check_header(name, value).err());
+        }
+        if let Err(err) = scraper::Selector::parse(&self.wiki.content_selector) {
+            problems.push(format!(
+                "Invalid CSS selector `{}`: {err:?}",
+                self.wiki.content_selector
+            ));
+        }
+        let keywords = KeywordFilter::new(
+            self.keywords.clone(),
+            self.exclude_keywords.clone(),
+            self.keyword_mode,
+            self.keywords_from_hop,
+        )
+        .map_err(|problem| problems.push(problem));
+
+        match keywords {
+            Ok(keywords) if problems.is_empty() => Ok(keywords),
+            _ => Err(InvalidConfig(problems)),
+        }
+    }
+}
+
+impl Default for ScraperConfig {
+    fn default() -> Self {
+        ScraperConfig {
+            depth: 5,
+            strategy: CrawlStrategy::Bfs,
+            max_pages: None,
+            max_duration: None,
+            stop_at: None,
+            num_threads: 4,
+            concurrency: 16,
+            site_mode: SiteMode::Wiki,
+            same_domain_only: false,
+            wiki: WikiConfig::default(),
+            link_scope: LinkScope::default(),
+            use_api: false,
+            category: None,
+            random_seeds: None,
+            preset: None,
+            keywords: None,
+            keyword_mode: KeywordMode::Any,
+            exclude_keywords: None,
+            keywords_from_hop: 0,
+            url_filter: UrlFilter::default(),
+            undirected: false,
+            weighted: false,
+            ghost_nodes: GhostNodes::Keep,
+            split_by_keyword: false,
+            output_file: None,
+            record_keyword_matches: false,
+            degrees: false,
+            components: false,
+            giant_component_only: false,
+            pagerank: false,
+            top_fraction: None,
+            centrality: false,
+            centrality_samples: 1000,
+            clustering: false,
+            layout: None,
+            layout_iterations: 300,
+            node_filter: None,
+            edge_filter: None,
+            provenance: false,
+            link_positions: false,
+            edge_types: false,
+            gephi_stream: None,
+            warn_nodes: None,
+            warn_edges: None,
+            max_empty_pages: Some(20),
+            page_timeout: Some(Duration::from_secs(60)),
+            revisit: RevisitConfig::default(),
+            frontier_ttl: None,
+            keep_external_links: None,
+            check_external: false,
+            ignore_canonical: false,
+            skip_disambiguation: false,
+            id_width: IdWidth::U64,
+            stop_expanding_at_degree: None,
+            max_links_per_page: None,
+            link_sampling: LinkSampling::default(),
+            all_languages_of_seed: false,
+            languages: None,
+            interlanguage_links: false,
+            interlanguage_depth: None,
+            max_edges_in_memory: None,
+            spill_dir: None,
+            user_agent: DEFAULT_USER_AGENT.to_owned(),
+            headers: Vec::new(),
+            auth: None,
+            bot_login: None,
+            proxy: None,
+            cache_dir: None,
+            revalidate_cache: false,
+            stale_while_revalidate: None,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+            max_page_attempts: 3,
+            event_log: None,
+            explain: None,
+            retry: RetryConfig::default(),
+            rate_limit: None,
+            external_rate_limit: None,
+            adaptive_rate_limit: None,
+            max_bandwidth: None,
+        }
+    }
+}
+
+/// Reads a duration given in seconds in the config files, e.g. `1.5`
+pub(crate) fn deserialize_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    Duration::try_from_secs_f64(f64::deserialize(deserializer)?).map_err(de::Error::custom)
+}
+
+fn deserialize_optional_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(|secs| Duration::try_from_secs_f64(secs).map_err(de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_regexes<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Regex>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pattern| Regex::new(pattern).map_err(de::Error::custom))
+        .collect()
+}
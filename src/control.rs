@@ -0,0 +1,292 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+use signal_hook::{
+    consts::{SIGUSR1, SIGUSR2},
+    iterator::Signals,
+};
+
+use serde_json::json;
+
+use crate::{
+    graph::Graph,
+    http::BandwidthStats,
+    keywords::KeywordFilter,
+    output::{OutputFormat, OutputOptions},
+};
+
+/// Shared state used to control a running crawl from outside of the workers
+#[derive(Debug, Default)]
+pub struct CrawlControl {
+    state: Mutex<ControlState>,
+    changed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: bool,
+    stopped: bool,
+}
+
+impl CrawlControl {
+    /// Stops the workers from taking new urls from the queue. Requests already in flight are completed.
+    pub fn pause(&self) {
+        self.state.lock().unwrap().paused = true;
+    }
+
+    /// Lets the workers take new urls from the queue again
+    pub fn resume(&self) {
+        self.state.lock().unwrap().paused = false;
+        self.changed.notify_all();
+    }
+
+    /// Makes the workers exit once their requests in flight are completed, discarding the rest of the queue.
+    /// The graph built so far is then saved as if the crawl had finished.
+    pub fn stop(&self) {
+        self.state.lock().unwrap().stopped = true;
+        self.changed.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.lock().unwrap().stopped
+    }
+
+    /// Blocks the calling thread until the crawl is resumed or stopped. Returns immediately if the crawl is not paused.
+    pub fn wait_while_paused(&self) {
+        let state = self.state.lock().unwrap();
+        drop(
+            self.changed
+                .wait_while(state, |state| state.paused && !state.stopped)
+                .unwrap(),
+        );
+    }
+}
+
+/// Output file prefix, formats and options used to flush the graph while the crawl is running
+pub type Output = (String, Vec<OutputFormat>, OutputOptions);
+
+/// Everything the control commands act on
+#[derive(Clone)]
+pub struct CrawlHandle {
+    pub control: Arc<CrawlControl>,
+    pub graph: Graph,
+    pub keywords: Arc<KeywordFilter>,
+    pub bandwidth: Arc<BandwidthStats>,
+    pub output: Option<Output>,
+}
+
+impl CrawlHandle {
+    /// Executes a command sent to the crawl, returning the response as a single line of json
+    pub fn execute(&self, command: ControlCommand) -> String {
+        match command {
+            ControlCommand::Pause => {
+                self.control.pause();
+                ok_response()
+            }
+            ControlCommand::Resume => {
+                self.control.resume();
+                ok_response()
+            }
+            ControlCommand::Stop => {
+                self.control.stop();
+                ok_response()
+            }
+            ControlCommand::Stats => match self.graph.num_links() {
+                Ok(num_links) => {
+                    let keywords = self
+                        .keywords
+                        .counts()
+                        .into_iter()
+                        .map(|(depth, counts)| {
+                            let counts = json!({
+                                "accepted": counts.accepted,
+                                "rejected": counts.rejected,
+                                "acceptance_ratio": counts.acceptance_ratio(),
+                            });
+                            (depth.to_string(), counts)
+                        })
+                        .collect::<serde_json::Map<_, _>>();
+
+                    let link_kinds = self
+                        .graph
+                        .link_kinds
+                        .counts()
+                        .into_iter()
+                        .map(|(kind, count)| (kind.as_str().to_owned(), json!(count)))
+                        .collect::<serde_json::Map<_, _>>();
+
+                    json!({
+                        "pages": self.graph.num_pages(),
+                        "links": num_links,
+                        "paused": self.control.is_paused(),
+                        "stopped": self.control.is_stopped(),
+                        "keywords": self
+                            .keywords
+                            .keywords()
+                            .as_ref()
+                            .map(|keywords| keywords.words())
+                            .filter(|words| !words.is_empty()),
+                        "keywords_by_depth": keywords,
+                        "links_by_kind": link_kinds,
+                        "bytes_downloaded": self.bandwidth.total(),
+                        "bytes_by_host": self.bandwidth.by_host(),
+                    })
+                    .to_string()
+                }
+                Err(err) => error_response(err),
+            },
+            ControlCommand::Flush => {
+                let Some((prefix, formats, options)) = &self.output else {
+                    return error_response("No output file was given");
+                };
+                match self.graph.save_to_file(prefix, formats, options) {
+                    Ok(()) => ok_response(),
+                    Err(err) => error_response(err),
+                }
+            }
+            ControlCommand::SetKeywords(keywords) => match self.keywords.set_keywords(keywords) {
+                Ok(()) => ok_response(),
+                Err(err) => error_response(err),
+            },
+        }
+    }
+}
+
+/// A command accepted by the control socket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// Pause the crawl, finishing the requests in flight
+    Pause,
+    /// Resume a paused crawl
+    Resume,
+    /// Report the size of the graph, the state of the crawl and the acceptance ratio of the keywords at each depth
+    Stats,
+    /// Save the graph built so far to the output files, without stopping the crawl
+    Flush,
+    /// Stop the crawl and save the graph built so far
+    Stop,
+    /// Replace the keywords used to filter the pages. `None` accepts every page that does not contain an excluded keyword
+    SetKeywords(Option<Vec<String>>),
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    fn from_str(command: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = command
+            .trim()
+            .split_once(char::is_whitespace)
+            .unwrap_or((command.trim(), ""));
+
+        match name.to_lowercase().as_str() {
+            "pause" => Ok(ControlCommand::Pause),
+            "resume" => Ok(ControlCommand::Resume),
+            "stats" => Ok(ControlCommand::Stats),
+            "flush" => Ok(ControlCommand::Flush),
+            "stop" | "stop-and-save" => Ok(ControlCommand::Stop),
+            "keywords" => {
+                let keywords = argument
+                    .split(',')
+                    .map(|keyword| keyword.trim().to_owned())
+                    .filter(|keyword| !keyword.is_empty())
+                    .collect::<Vec<_>>();
+                Ok(ControlCommand::SetKeywords(
+                    (!keywords.is_empty()).then_some(keywords),
+                ))
+            }
+            other => Err(format!("Unknown command `{other}`")),
+        }
+    }
+}
+
+fn ok_response() -> String {
+    json!({ "ok": true }).to_string()
+}
+
+fn error_response(err: impl std::fmt::Display) -> String {
+    json!({ "ok": false, "error": err.to_string() }).to_string()
+}
+
+/// Pauses the crawl on SIGUSR1 and resumes it on SIGUSR2
+pub fn install_signal_handlers(handle: CrawlHandle) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGUSR1, SIGUSR2])?;
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let command = match signal {
+                SIGUSR1 => ControlCommand::Pause,
+                SIGUSR2 => ControlCommand::Resume,
+                _ => unreachable!("Only SIGUSR1 and SIGUSR2 are registered"),
+            };
+            eprintln!("[INFO] Received signal {signal}. Executing {command:?}");
+            handle.execute(command);
+        }
+    });
+    Ok(())
+}
+
+/// Listens for commands on a unix socket at `path`.
+///
+/// The protocol is line based: each line sent to the socket is a command (`pause`, `resume`, `stats`, `flush`, `stop`
+/// or `keywords <comma separated keywords>`) and is answered by a single line of json.
+pub fn serve_socket(path: impl AsRef<Path>, handle: CrawlHandle) -> std::io::Result<ControlSocket> {
+    let path = path.as_ref().to_path_buf();
+    let listener = UnixListener::bind(&path)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| handle_connection(stream, &handle));
+            if let Err(err) = result {
+                eprintln!("[WARN] Control socket connection failed: {err}");
+            }
+        }
+    });
+
+    Ok(ControlSocket { path })
+}
+
+fn handle_connection(stream: UnixStream, handle: &CrawlHandle) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match line.parse::<ControlCommand>() {
+            Ok(command) => {
+                eprintln!("[INFO] Received {command:?} from the control socket");
+                handle.execute(command)
+            }
+            Err(err) => error_response(err),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+/// Removes the socket file when dropped
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            eprintln!(
+                "[WARN] Could not remove control socket {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+}
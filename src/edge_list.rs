@@ -0,0 +1,170 @@
+use std::io;
+
+use crate::compression::Compression;
+use crate::edge_store::Weight;
+use crate::graph::Graph;
+use crate::node_id::{IdWidth, NodeId};
+
+/// Layout of an edge list written by another tool, with a line for each edge.
+///
+/// The columns are given by their name in the header, or by their index starting from 0 if the file has no header.
+/// The values of the source and target columns identify the nodes, and take the place of the urls of the pages
+#[derive(Debug, Clone)]
+pub struct EdgeListFormat {
+    pub source_column: String,
+    pub target_column: String,
+    /// Column with the weight of each edge. Without it, every line counts as one link
+    pub weight_column: Option<String>,
+    pub has_header: bool,
+    pub delimiter: u8,
+}
+
+impl Default for EdgeListFormat {
+    fn default() -> Self {
+        EdgeListFormat {
+            source_column: "source".to_owned(),
+            target_column: "target".to_owned(),
+            weight_column: None,
+            has_header: true,
+            delimiter: b',',
+        }
+    }
+}
+
+/// Adds the nodes and the edges of the edge list at `path` to `graph`, with new ids allocated in the order the nodes are found.
+/// The edges found on several lines are merged, summing their weights. The file can be compressed, as told by its extension.
+/// Returns the number of lines read
+pub fn load(
+    path: &str,
+    format: &EdgeListFormat,
+    graph: &Graph,
+    id_width: IdWidth,
+) -> io::Result<usize> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(format.delimiter)
+        .has_headers(format.has_header)
+        .from_reader(Compression::from_path(path).open(path)?);
+    let headers = if format.has_header {
+        Some(reader.headers()?.clone())
+    } else {
+        None
+    };
+    let column = |name: &str| column_index(headers.as_ref(), name);
+    let source = column(&format.source_column)?;
+    let target = column(&format.target_column)?;
+    let weight = format.weight_column.as_deref().map(column).transpose()?;
+
+    let mut own_pages = graph.pages.lock().unwrap();
+    let mut own_links = graph.links.lock().unwrap();
+    let mut id_of = |node: &str| -> io::Result<NodeId> {
+        if let Some(id) = own_pages.get(node) {
+            return Ok(*id);
+        }
+        let id = NodeId::allocate(own_pages.len(), id_width).ok_or_else(|| {
+            io::Error::other("The edge list has more nodes than the ids can count")
+        })?;
+        own_pages.insert(node.to_owned(), id);
+        Ok(id)
+    };
+
+    let mut lines = 0;
+    for record in reader.records() {
+        let record = record?;
+        let field = |index: usize| {
+            record.get(index).ok_or_else(|| {
+                io::Error::other(format!("Missing column {index} in the record {record:?}"))
+            })
+        };
+        let edge = (id_of(field(source)?)?, id_of(field(target)?)?);
+        let weight = match weight {
+            Some(index) => field(index)?.trim().parse::<Weight>().map_err(|err| {
+                io::Error::other(format!("Invalid weight in the record {record:?}: {err}"))
+            })?,
+            None => 1,
+        };
+        // Edges with no weight are left out, as the links that have never been found
+        if weight > 0 {
+            own_links.insert_weighted(edge, weight)?;
+        }
+        lines += 1;
+    }
+    Ok(lines)
+}
+
+/// Index of the column called `name` in the header or, without a header, the index given by `name`
+fn column_index(headers: Option<&csv::StringRecord>, name: &str) -> io::Result<usize> {
+    match headers {
+        Some(headers) => headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| io::Error::other(format!("No column {name} in the header {headers:?}"))),
+        None => name.parse().map_err(|_| {
+            io::Error::other(format!(
+                "Expected the index of a column in an edge list without a header, found `{name}`"
+            ))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::Arc,
+    };
+
+    use super::*;
+    use crate::config::ScraperConfig;
+    use crate::pipeline::StageDir;
+
+    #[test]
+    fn edge_lists_become_a_crawl_stage() {
+        let dir = std::env::temp_dir().join(format!("graphgen-edge-list-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("edges.tsv");
+        std::fs::write(&path, "w\tto\tfrom\n2\tb\ta\n1\tc\ta\n3\tb\ta\n0\ta\tc\n").unwrap();
+        let format = EdgeListFormat {
+            source_column: "from".to_owned(),
+            target_column: "to".to_owned(),
+            weight_column: Some("w".to_owned()),
+            delimiter: b'\t',
+            ..Default::default()
+        };
+        let config = Arc::new(ScraperConfig::default());
+        let graph = Graph::new(config.clone());
+        let lines = load(path.to_str().unwrap(), &format, &graph, IdWidth::U64).unwrap();
+        let stage_dir = StageDir::new(dir.join("stage"));
+        stage_dir.create().unwrap();
+        stage_dir.write_graph(&graph).unwrap();
+        let loaded = stage_dir.load_graph(config).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(lines, 4);
+        let own_pages = loaded.pages.lock().unwrap();
+        let names: HashMap<_, _> = own_pages
+            .iter()
+            .map(|(name, id)| (*id, name.as_str()))
+            .collect();
+        let edges: BTreeMap<_, _> = loaded
+            .links
+            .lock()
+            .unwrap()
+            .sorted_edges()
+            .unwrap()
+            .map(|edge| {
+                let ((source, target), weight) = edge.unwrap();
+                ((names[&source], names[&target]), weight)
+            })
+            .collect();
+        assert_eq!(edges, BTreeMap::from([(("a", "b"), 5), (("a", "c"), 1)]));
+    }
+
+    #[test]
+    fn columns_are_found_by_name_or_index() {
+        let headers = csv::StringRecord::from(vec!["from", "to", "w"]);
+        assert_eq!(column_index(Some(&headers), "to").unwrap(), 1);
+        assert!(column_index(Some(&headers), "1").is_err());
+        assert_eq!(column_index(None, "2").unwrap(), 2);
+        assert!(column_index(None, "to").is_err());
+    }
+}
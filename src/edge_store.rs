@@ -0,0 +1,454 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::node_id::NodeId;
+
+/// Number of times a page links to another one
+pub type Weight = u64;
+
+/// Size in bytes of an edge written to a run file: source, destination and weight
+const EDGE_SIZE: usize = 2 * std::mem::size_of::<u64>() + std::mem::size_of::<Weight>();
+
+/// Key used to sort the edges. Both directions of an edge end up next to each other,
+/// so that the undirected export can find them in a single pass over the sorted edges.
+fn sort_key(&(source, dest): &(NodeId, NodeId)) -> (NodeId, NodeId, bool) {
+    (source.min(dest), source.max(dest), source > dest)
+}
+
+/// Edges of the graph, along with the number of times each of them has been found.
+///
+/// By default every edge is kept in memory. If a spill directory is configured, once more than
+/// `max_in_memory` edges are stored they are sorted and appended to a new run file on disk.
+/// The runs are merged back together, summing the weights of duplicate edges, when the edges are read at export time.
+#[derive(Debug, Default)]
+pub struct EdgeStore {
+    edges: HashMap<(NodeId, NodeId), Weight>,
+    spill: Option<Spill>,
+    /// Pages merged into other ones, such as redirects, with the page they have been merged into.
+    /// Their edges are moved to the other page when the edges are read
+    merged: HashMap<NodeId, NodeId>,
+}
+
+#[derive(Debug)]
+struct Spill {
+    dir: PathBuf,
+    max_in_memory: usize,
+    runs: Vec<PathBuf>,
+    /// Whether some of the runs have been written before the last merge, and still have edges of merged pages
+    stale: bool,
+}
+
+impl EdgeStore {
+    /// Creates a store that writes sorted runs of at most `max_in_memory` edges to a new directory inside of `dir`.
+    /// Every store gets its own directory, since the directory is removed with everything in it when the store is dropped
+    pub fn with_spill(dir: impl Into<PathBuf>, max_in_memory: usize) -> io::Result<EdgeStore> {
+        static STORES: AtomicUsize = AtomicUsize::new(0);
+        let dir = dir.into().join(format!(
+            "graphgen-edges-{}-{}",
+            std::process::id(),
+            STORES.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(EdgeStore {
+            edges: Default::default(),
+            spill: Some(Spill {
+                dir,
+                max_in_memory: max_in_memory.max(1),
+                runs: Vec::new(),
+                stale: false,
+            }),
+            merged: Default::default(),
+        })
+    }
+
+    /// Adds an edge to the store, or increases its weight if it is already there.
+    /// Returns whether the edge is new in the current run, i.e. was not in memory: once some edges have been spilled
+    /// to disk, an edge new in the run may already be in one of the runs, which are not checked
+    pub fn insert(&mut self, edge: (NodeId, NodeId)) -> io::Result<bool> {
+        self.insert_weighted(edge, 1)
+    }
+
+    /// Adds an edge that has been found `weight` times to the store, like [`EdgeStore::insert`]
+    pub fn insert_weighted(&mut self, edge: (NodeId, NodeId), weight: Weight) -> io::Result<bool> {
+        let total = self.edges.entry(edge).or_insert(0);
+        let inserted = *total == 0;
+        *total += weight;
+
+        if let Some(spill) = &self.spill {
+            if self.edges.len() >= spill.max_in_memory {
+                self.spill_run()?;
+            }
+        }
+        Ok(inserted)
+    }
+
+    /// Whether some of the edges have been written to disk
+    pub fn has_spilled(&self) -> bool {
+        self.spill
+            .as_ref()
+            .is_some_and(|spill| !spill.runs.is_empty())
+    }
+
+    /// Merges the page `from` into the page `into`: every edge of `from` becomes an edge of `into`,
+    /// whose weight is summed with the one of the same edge of `into` if it has it
+    pub fn merge_node(&mut self, from: NodeId, into: NodeId) {
+        if from == into {
+            return;
+        }
+        for target in self.merged.values_mut() {
+            if *target == from {
+                *target = into;
+            }
+        }
+        self.merged.insert(from, into);
+        if let Some(spill) = &mut self.spill {
+            spill.stale |= !spill.runs.is_empty();
+        }
+    }
+
+    /// Rewrites the runs on disk that still have edges of merged pages, one at a time,
+    /// so that their edges are sorted and summed with the other edges of the pages they have been merged into
+    pub fn apply_merges(&mut self) -> io::Result<()> {
+        let Some(spill) = &mut self.spill else {
+            return Ok(());
+        };
+        if !spill.stale {
+            return Ok(());
+        }
+        for run in &spill.runs {
+            let mut source = EdgeSource::Run(BufReader::new(File::open(run)?));
+            let mut edges: HashMap<(NodeId, NodeId), Weight> = HashMap::new();
+            while let Some((edge, weight)) = source.next_edge()? {
+                *edges.entry(resolve_edge(&self.merged, edge)).or_default() += weight;
+            }
+            write_run(run, edges)?;
+        }
+        spill.stale = false;
+        Ok(())
+    }
+
+    /// Number of distinct edges in the store. This requires merging the runs if any edge has been spilled to disk.
+    pub fn len(&mut self) -> io::Result<usize> {
+        if !self.has_spilled() && self.merged.is_empty() {
+            return Ok(self.edges.len());
+        }
+        let mut count = 0;
+        for edge in self.sorted_edges()? {
+            edge?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Whether the store has no edges, either in memory or on disk
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty() && !self.has_spilled()
+    }
+
+    /// Returns every distinct edge with its total weight, sorted so that both directions of an edge are next to each other.
+    /// The runs that still have edges of merged pages are rewritten first, see [`EdgeStore::apply_merges`]
+    pub fn sorted_edges(&mut self) -> io::Result<SortedEdges> {
+        self.apply_merges()?;
+        let in_memory = if self.merged.is_empty() {
+            let mut in_memory = self
+                .edges
+                .iter()
+                .map(|(edge, weight)| (*edge, *weight))
+                .collect::<Vec<_>>();
+            in_memory.sort_unstable_by_key(|(edge, _)| sort_key(edge));
+            in_memory
+        } else {
+            sorted(self.resolved_edges())
+        };
+
+        let mut sources = vec![EdgeSource::Memory(in_memory.into_iter())];
+        if let Some(spill) = &self.spill {
+            for run in &spill.runs {
+                sources.push(EdgeSource::Run(BufReader::new(File::open(run)?)));
+            }
+        }
+
+        let mut heap = BinaryHeap::new();
+        for (idx, source) in sources.iter_mut().enumerate() {
+            if let Some((edge, weight)) = source.next_edge()? {
+                heap.push(Reverse((sort_key(&edge), edge, weight, idx)));
+            }
+        }
+
+        Ok(SortedEdges { sources, heap })
+    }
+
+    /// In-degree and out-degree of every page with at least one edge, counting each distinct edge once
+    pub fn degrees(&mut self) -> io::Result<HashMap<NodeId, (u64, u64)>> {
+        let mut degrees: HashMap<NodeId, (u64, u64)> = HashMap::new();
+        for edge in self.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            degrees.entry(source).or_default().1 += 1;
+            degrees.entry(dest).or_default().0 += 1;
+        }
+        Ok(degrees)
+    }
+
+    /// Edges in memory, with the edges of the merged pages moved to the pages they have been merged into
+    fn resolved_edges(&self) -> HashMap<(NodeId, NodeId), Weight> {
+        let mut edges = HashMap::with_capacity(self.edges.len());
+        for (edge, weight) in &self.edges {
+            *edges.entry(resolve_edge(&self.merged, *edge)).or_default() += weight;
+        }
+        edges
+    }
+
+    fn spill_run(&mut self) -> io::Result<()> {
+        if self.spill.is_none() {
+            return Ok(());
+        }
+
+        let edges = if self.merged.is_empty() {
+            std::mem::take(&mut self.edges)
+        } else {
+            let edges = self.resolved_edges();
+            self.edges.clear();
+            edges
+        };
+        let Some(spill) = &mut self.spill else {
+            return Ok(());
+        };
+
+        let path = spill.dir.join(format!("run_{}.bin", spill.runs.len()));
+        write_run(&path, edges)?;
+
+        eprintln!("[INFO] Spilled edges to {}", path.display());
+        spill.runs.push(path);
+        Ok(())
+    }
+}
+
+/// Returns the edge with the merged pages replaced by the pages they have been merged into
+fn resolve_edge(
+    merged: &HashMap<NodeId, NodeId>,
+    (source, dest): (NodeId, NodeId),
+) -> (NodeId, NodeId) {
+    let resolve = |id| merged.get(&id).copied().unwrap_or(id);
+    (resolve(source), resolve(dest))
+}
+
+fn sorted(edges: HashMap<(NodeId, NodeId), Weight>) -> Vec<((NodeId, NodeId), Weight)> {
+    let mut edges = edges.into_iter().collect::<Vec<_>>();
+    edges.sort_unstable_by_key(|(edge, _)| sort_key(edge));
+    edges
+}
+
+/// Writes the edges, sorted, to the run file at `path`
+fn write_run(path: &Path, edges: HashMap<(NodeId, NodeId), Weight>) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for ((source, dest), weight) in sorted(edges) {
+        file.write_all(&source.get().to_le_bytes())?;
+        file.write_all(&dest.get().to_le_bytes())?;
+        file.write_all(&weight.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+impl Drop for EdgeStore {
+    fn drop(&mut self) {
+        if let Some(spill) = &self.spill {
+            if let Err(err) = std::fs::remove_dir_all(&spill.dir) {
+                eprintln!(
+                    "[WARN] Could not remove directory {}: {err}",
+                    spill.dir.display()
+                );
+            }
+        }
+    }
+}
+
+enum EdgeSource {
+    Memory(std::vec::IntoIter<((NodeId, NodeId), Weight)>),
+    Run(BufReader<File>),
+}
+
+impl EdgeSource {
+    fn next_edge(&mut self) -> io::Result<Option<((NodeId, NodeId), Weight)>> {
+        match self {
+            EdgeSource::Memory(edges) => Ok(edges.next()),
+            EdgeSource::Run(file) => {
+                let mut buf = [0u8; EDGE_SIZE];
+                match file.read_exact(&mut buf) {
+                    Ok(()) => {
+                        let id_size = std::mem::size_of::<u64>();
+                        let (source, rest) = buf.split_at(id_size);
+                        let (dest, weight) = rest.split_at(id_size);
+                        Ok(Some((
+                            (
+                                NodeId::new(u64::from_le_bytes(source.try_into().unwrap())),
+                                NodeId::new(u64::from_le_bytes(dest.try_into().unwrap())),
+                            ),
+                            Weight::from_le_bytes(weight.try_into().unwrap()),
+                        )))
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+                    Err(err) => Err(err),
+                }
+            }
+        }
+    }
+}
+
+type HeapEntry = Reverse<((NodeId, NodeId, bool), (NodeId, NodeId), Weight, usize)>;
+
+/// K-way merge of the in-memory edges and of the runs on disk, summing the weights of duplicate edges
+pub struct SortedEdges {
+    sources: Vec<EdgeSource>,
+    heap: BinaryHeap<HeapEntry>,
+}
+
+impl SortedEdges {
+    /// Pops the smallest edge from the heap, replacing it with the next edge of the same source
+    fn pop(&mut self) -> Option<io::Result<((NodeId, NodeId), Weight)>> {
+        let Reverse((_, edge, weight, idx)) = self.heap.pop()?;
+        match self.sources[idx].next_edge() {
+            Ok(Some((next, next_weight))) => {
+                self.heap
+                    .push(Reverse((sort_key(&next), next, next_weight, idx)))
+            }
+            Ok(None) => {}
+            Err(err) => return Some(Err(err)),
+        }
+        Some(Ok((edge, weight)))
+    }
+}
+
+impl Iterator for SortedEdges {
+    type Item = io::Result<((NodeId, NodeId), Weight)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (edge, mut total_weight) = match self.pop()? {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(err)),
+        };
+
+        while self
+            .heap
+            .peek()
+            .is_some_and(|Reverse((_, next, _, _))| *next == edge)
+        {
+            match self.pop()? {
+                Ok((_, weight)) => total_weight += weight,
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        Some(Ok((edge, total_weight)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: u64) -> NodeId {
+        NodeId::new(id)
+    }
+
+    fn edge(source: u64, dest: u64) -> (NodeId, NodeId) {
+        (id(source), id(dest))
+    }
+
+    fn spilling(max_in_memory: usize) -> EdgeStore {
+        EdgeStore::with_spill(std::env::temp_dir(), max_in_memory).unwrap()
+    }
+
+    fn sorted_edges(store: &mut EdgeStore) -> Vec<((NodeId, NodeId), Weight)> {
+        store
+            .sorted_edges()
+            .unwrap()
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn edges_are_spilled_once_more_than_the_maximum_are_in_memory() {
+        let mut store = spilling(1);
+        let mut other = spilling(1);
+        for (source, dest) in [(2, 3), (0, 1), (1, 2)] {
+            assert!(store.insert(edge(source, dest)).unwrap());
+            other.insert(edge(source, dest)).unwrap();
+        }
+        assert!(store.has_spilled());
+        assert!(store.edges.is_empty());
+
+        // Each store has its own runs, which are left alone when the other one is dropped
+        drop(other);
+        assert_eq!(
+            sorted_edges(&mut store),
+            [(edge(0, 1), 1), (edge(1, 2), 1), (edge(2, 3), 1)]
+        );
+        assert_eq!(store.len().unwrap(), 3);
+    }
+
+    #[test]
+    fn weights_of_an_edge_are_summed_across_runs() {
+        let mut store = spilling(2);
+        store.insert_weighted(edge(0, 1), 2).unwrap();
+        store.insert(edge(1, 2)).unwrap();
+        // The edge is new in the run, even though it has already been spilled
+        assert!(store.insert_weighted(edge(0, 1), 3).unwrap());
+        store.insert(edge(2, 0)).unwrap();
+        store.insert(edge(0, 1)).unwrap();
+
+        assert_eq!(
+            sorted_edges(&mut store),
+            [(edge(0, 1), 6), (edge(2, 0), 1), (edge(1, 2), 1)]
+        );
+        assert_eq!(store.len().unwrap(), 3);
+        assert_eq!(
+            store.degrees().unwrap(),
+            HashMap::from([(id(0), (1, 1)), (id(1), (1, 1)), (id(2), (1, 1))])
+        );
+    }
+
+    #[test]
+    fn merged_pages_are_summed_with_the_edges_of_the_pages_they_are_merged_into() {
+        let mut store = spilling(2);
+        store.insert(edge(0, 3)).unwrap();
+        store.insert(edge(4, 3)).unwrap();
+        store.insert(edge(2, 1)).unwrap();
+        store.insert(edge(4, 2)).unwrap();
+        store.insert(edge(1, 4)).unwrap();
+        // 4 is merged into 0, which comes before the other edges of the runs once resolved
+        store.merge_node(id(4), id(0));
+
+        assert_eq!(
+            sorted_edges(&mut store),
+            [
+                (edge(1, 0), 1),
+                (edge(0, 2), 1),
+                (edge(0, 3), 2),
+                (edge(2, 1), 1)
+            ]
+        );
+        assert_eq!(store.len().unwrap(), 4);
+    }
+
+    #[test]
+    fn both_directions_of_an_edge_are_next_to_each_other_across_runs() {
+        let mut store = spilling(1);
+        for (source, dest) in [(3, 2), (1, 0), (2, 3), (0, 2), (0, 1)] {
+            store.insert(edge(source, dest)).unwrap();
+        }
+
+        assert_eq!(
+            sorted_edges(&mut store)
+                .into_iter()
+                .map(|(edge, _)| edge)
+                .collect::<Vec<_>>(),
+            [edge(0, 1), edge(1, 0), edge(0, 2), edge(2, 3), edge(3, 2)]
+        );
+    }
+}
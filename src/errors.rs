@@ -1,16 +1,108 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
 use thiserror::Error;
 
+use crate::blocks::BlockReason;
+
 #[derive(Debug, Error)]
 pub enum ScraperError {
     #[error("Could not fetch data: {0}")]
     RequestFailed(#[from] reqwest::Error),
 
+    #[error("Request to {url} failed with status {status}")]
+    HttpStatus {
+        url: String,
+        status: StatusCode,
+        /// Time to wait before retrying, as requested by the server with the Retry-After header
+        retry_after: Option<Duration>,
+    },
+
+    #[error("{url} answered with a {reason}: {host} is blocking the crawler")]
+    Blocked {
+        url: String,
+        host: String,
+        reason: BlockReason,
+        /// Time to wait before sending another request to the host
+        retry_after: Duration,
+    },
+
+    #[error("Invalid url {0}")]
+    InvalidUrl(String),
+
+    #[error("Invalid response from the MediaWiki API at {url}: {reason}")]
+    InvalidApiResponse { url: String, reason: String },
+
+    #[error("Could not log in to the wiki as {user}: {reason}")]
+    LoginFailed { user: String, reason: String },
+
     #[error("Could not find any content in the page with url {0}")]
     NoContentFound(String),
 
-    #[error("Could not send data to internal channel")]
-    ChannelError(#[from] crossbeam_channel::SendError<(String, u64)>),
+    #[error("{count} pages in a row had no links under the content selector `{selector}`, the last one being {url}. \
+        The selector may be wrong, or the server may be answering with a consent wall or a bot block")]
+    NoLinksFound {
+        count: u64,
+        selector: String,
+        url: String,
+    },
 
     #[error("Could not read response: {0}")]
     ReadError(#[from] std::io::Error),
+
+    #[error("Could not write edges to disk: {0}")]
+    EdgeSpillFailed(std::io::Error),
+
+    #[error("Could not open the event log: {0}")]
+    EventLogFailed(std::io::Error),
+
+    #[error("Could not import the graph: {0}")]
+    ImportFailed(std::io::Error),
+
+    #[error("Could not start the async runtime: {0}")]
+    RuntimeFailed(std::io::Error),
+
+    #[error("Could not read the config file: {0}")]
+    ConfigFileFailed(std::io::Error),
+
+    #[error(transparent)]
+    InvalidConfig(#[from] InvalidConfig),
+}
+
+/// Options of a [`ScraperConfig`](crate::config::ScraperConfig) that cannot be used together, with every problem found
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("Invalid configuration: {}", .0.join("; "))]
+pub struct InvalidConfig(pub Vec<String>);
+
+impl ScraperError {
+    /// Whether the request that caused the error may succeed if it is sent again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ScraperError::RequestFailed(err) => {
+                err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+            }
+            ScraperError::HttpStatus { status, .. } => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            ScraperError::ReadError(_) | ScraperError::Blocked { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Time to wait before retrying, if the server asked for it
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ScraperError::HttpStatus { retry_after, .. } => *retry_after,
+            ScraperError::Blocked { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+/// Error returned when using something that has been left out of the build, e.g. the arrow format without the `arrow` feature
+pub fn missing_feature(feature: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("graphgen has been built without the `{feature}` feature"),
+    )
 }
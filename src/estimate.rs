@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use crate::config::ScraperConfig;
+use crate::errors::ScraperError;
+use crate::scraper::WikipediaScraper;
+
+/// Measures of a short crawl, from which the size and the cost of a full crawl with the same settings are projected
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sample {
+    /// Number of seeds of the crawl
+    pub seeds: usize,
+    /// Pages whose links have been added to the graph
+    pub expanded: usize,
+    /// Pages found, including the ones that have not been fetched
+    pub pages: usize,
+    pub links: usize,
+    /// Bytes downloaded, including the pages without links
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Whether the crawl has ended before reaching the sample size, in which case it is the full crawl
+    pub complete: bool,
+}
+
+impl Sample {
+    /// Crawls up to `size` pages from `seeds` with `config`, whose depth and concurrency are the ones of the crawl to estimate
+    pub fn take(
+        seeds: &[String],
+        config: ScraperConfig,
+        size: usize,
+    ) -> Result<Sample, ScraperError> {
+        let mut scraper = WikipediaScraper::new(
+            seeds,
+            ScraperConfig {
+                max_pages: Some(size),
+                ..config
+            },
+        )?;
+        let handle = scraper.handle(None);
+        let start = Instant::now();
+        scraper.scrape()?;
+
+        let expanded = handle.graph.expanded.lock().unwrap().len();
+        Ok(Sample {
+            seeds: seeds.len(),
+            expanded,
+            pages: scraper.num_pages(),
+            links: scraper.num_links().map_err(ScraperError::EdgeSpillFailed)?,
+            bytes: handle.bandwidth.total(),
+            elapsed: start.elapsed(),
+            complete: expanded < size,
+        })
+    }
+
+    /// Average number of links of the expanded pages
+    pub fn links_per_page(&self) -> f64 {
+        self.links as f64 / self.expanded.max(1) as f64
+    }
+
+    /// Fraction of the links that lead to a page that had not been found yet
+    pub fn new_page_ratio(&self) -> f64 {
+        let new_pages = self.pages.saturating_sub(self.seeds);
+        (new_pages as f64 / self.links.max(1) as f64).min(1.0)
+    }
+
+    /// Projects a crawl of `depth` stopped after `max_pages` pages, if given.
+    ///
+    /// Every page at a depth is expected to lead to as many new pages as the sampled ones did, and the pages at the last
+    /// depth are found but not fetched, as in the crawl. The links among the pages found grow with the crawl, so the
+    /// projection is rather an upper bound for the deeper crawls
+    pub fn project(&self, depth: u64, max_pages: Option<usize>) -> Projection {
+        if self.complete {
+            return Projection {
+                fetched: self.expanded as f64,
+                pages: self.pages as f64,
+                links: self.links as f64,
+                bytes: self.bytes as f64,
+                runtime: self.elapsed,
+            };
+        }
+
+        let branching = self.links_per_page() * self.new_page_ratio();
+        let mut at_depth = self.seeds as f64;
+        let mut fetched = 0.0;
+        for _ in 0..depth {
+            fetched += at_depth;
+            at_depth *= branching;
+        }
+        if let Some(max_pages) = max_pages {
+            fetched = fetched.min(max_pages as f64);
+        }
+
+        let per_page = |value: f64| value / self.expanded.max(1) as f64;
+        Projection {
+            fetched,
+            pages: self.seeds as f64 + fetched * branching,
+            links: fetched * self.links_per_page(),
+            bytes: fetched * per_page(self.bytes as f64),
+            runtime: self.elapsed.mul_f64(per_page(fetched)),
+        }
+    }
+}
+
+/// Expected size and cost of a crawl
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Projection {
+    /// Pages fetched
+    pub fetched: f64,
+    /// Pages of the graph, including the ones found but not fetched
+    pub pages: f64,
+    pub links: f64,
+    pub bytes: f64,
+    pub runtime: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Sample {
+        // 10 pages with 20 links each, half of them to new pages
+        Sample {
+            seeds: 1,
+            expanded: 10,
+            pages: 101,
+            links: 200,
+            bytes: 10 * 100_000,
+            elapsed: Duration::from_secs(5),
+            complete: false,
+        }
+    }
+
+    #[test]
+    fn projection() {
+        let sample = sample();
+        assert_eq!(sample.links_per_page(), 20.0);
+        assert_eq!(sample.new_page_ratio(), 0.5);
+
+        // 1 + 10 + 100 pages fetched, finding 1000 pages at the last depth
+        let projection = sample.project(3, None);
+        assert_eq!(projection.fetched, 111.0);
+        assert_eq!(projection.pages, 1111.0);
+        assert_eq!(projection.links, 2220.0);
+        assert_eq!(projection.bytes, 11_100_000.0);
+        assert_eq!(projection.runtime, Duration::from_millis(55_500));
+
+        assert_eq!(sample.project(3, Some(50)).fetched, 50.0);
+    }
+
+    #[test]
+    fn complete_samples_are_the_crawl() {
+        let sample = Sample {
+            complete: true,
+            ..sample()
+        };
+        let projection = sample.project(3, None);
+        assert_eq!(projection.fetched, 10.0);
+        assert_eq!(projection.pages, 101.0);
+        assert_eq!(projection.runtime, Duration::from_secs(5));
+    }
+}
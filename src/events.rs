@@ -0,0 +1,193 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Decision taken by the crawl about a page, recorded in the event log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Decision {
+    /// The page has been added to the queue, by the page `from` or as a seed
+    Queued { depth: u64, from: Option<String> },
+    /// A link to the page has been found in the page `from` and added to the graph
+    Linked { from: String },
+    /// A link to the page found in the page `from` has been left out of the graph
+    Rejected { from: String, reason: String },
+    /// The page has been fetched and its links added to the graph
+    Scraped { depth: u64, links: usize },
+    /// The page has been expanded with its links in the imported graph instead of being fetched
+    Imported { depth: u64 },
+    /// The page has been taken from the queue, or fetched, but its links have not been added to the graph
+    Skipped { reason: String },
+    /// The page could not be fetched even after retrying it
+    Failed { error: String },
+    /// The page has the canonical url `to`, which identifies it in the graph
+    Redirected { to: String },
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Queued { depth, from: None } => {
+                write!(f, "queued as a seed with depth {depth}")
+            }
+            Decision::Queued {
+                depth,
+                from: Some(from),
+            } => write!(f, "queued with depth {depth}, found in {from}"),
+            Decision::Linked { from } => write!(f, "linked from {from}"),
+            Decision::Rejected { from, reason } => {
+                write!(f, "link from {from} left out: {reason}")
+            }
+            Decision::Scraped { depth, links } => {
+                write!(f, "scraped with depth {depth}, {links} links found")
+            }
+            Decision::Imported { depth } => {
+                write!(f, "expanded from the imported graph with depth {depth}")
+            }
+            Decision::Skipped { reason } => write!(f, "skipped: {reason}"),
+            Decision::Failed { error } => write!(f, "failed: {error}"),
+            Decision::Redirected { to } => write!(f, "identified by its canonical url {to}"),
+        }
+    }
+}
+
+/// Line of the event log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    pub url: String,
+    /// Unix time of the decision, in milliseconds
+    pub at: u64,
+    #[serde(flatten)]
+    pub decision: Decision,
+}
+
+/// Log of the decisions taken by the crawl about each page, one json object per line, to find out why a page
+/// is or is not in the graph. Each line is written as soon as the decision is taken, so the log can be read
+/// while the crawl is running. The decisions about the explained page, if any, are printed as well
+#[derive(Debug, Default)]
+pub struct EventLog {
+    out: Option<Mutex<LineWriter<File>>>,
+    /// Explained page and the canonical urls it has been found to have
+    explained: Option<Mutex<HashSet<String>>>,
+    /// Whether writing to the log has failed, so that the failure is reported only once
+    failed: AtomicBool,
+}
+
+impl EventLog {
+    /// Appends the decisions to the log at `path`, if any, so that a resumed crawl keeps the decisions taken before.
+    /// The decisions about the page at `explain` are printed as they are taken
+    pub fn create(path: Option<&Path>, explain: Option<String>) -> io::Result<EventLog> {
+        let out = match path {
+            Some(path) => Some(Mutex::new(LineWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ))),
+            None => None,
+        };
+        Ok(EventLog {
+            out,
+            explained: explain.map(|url| Mutex::new(HashSet::from([url]))),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    /// Whether the decisions about the page at `url` are recorded
+    fn records(&self, url: &str) -> bool {
+        self.out.is_some()
+            || self
+                .explained
+                .as_ref()
+                .is_some_and(|explained| explained.lock().unwrap().contains(url))
+    }
+
+    /// Records a decision about the page at `url`. The decision is only built if it is recorded,
+    /// since most of the decisions are about links, taken for every link of every page
+    pub fn record(&self, url: &str, decision: impl FnOnce() -> Decision) {
+        if !self.records(url) {
+            return;
+        }
+        let event = Event {
+            url: url.to_owned(),
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            decision: decision(),
+        };
+
+        if let Some(explained) = &self.explained {
+            let mut explained = explained.lock().unwrap();
+            if explained.contains(url) {
+                eprintln!("[EXPLAIN] {url}: {}", event.decision);
+                if let Decision::Redirected { to } = &event.decision {
+                    explained.insert(to.clone());
+                }
+            }
+        }
+        if let Some(out) = &self.out {
+            let line = serde_json::to_string(&event).expect("Events are valid json");
+            if let Err(err) = writeln!(out.lock().unwrap(), "{line}") {
+                if !self.failed.swap(true, Ordering::Relaxed) {
+                    eprintln!("[WARN] Could not write to the event log: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Reads the decisions about the page at `url` from the event log at `path`, in the order they have been taken.
+/// The decisions about the other urls of the page are included: the canonical urls it has been found to have,
+/// and the urls that have it as their canonical url
+pub fn trail(path: &Path, url: &str) -> io::Result<Vec<Event>> {
+    let mut redirects = Vec::new();
+    for event in read_events(path)? {
+        let event = event?;
+        if let Decision::Redirected { to } = event.decision {
+            redirects.push((event.url, to));
+        }
+    }
+    let mut urls = HashSet::from([url.to_owned()]);
+    loop {
+        let found = urls.len();
+        for (from, to) in &redirects {
+            if urls.contains(from) || urls.contains(to) {
+                urls.insert(from.clone());
+                urls.insert(to.clone());
+            }
+        }
+        if urls.len() == found {
+            break;
+        }
+    }
+
+    read_events(path)?
+        .filter(|event| {
+            event
+                .as_ref()
+                .map_or(true, |event| urls.contains(&event.url))
+        })
+        .collect()
+}
+
+/// Events of the log at `path`, failing on the lines that are not events
+fn read_events(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Event>>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(reader.lines().enumerate().map(|(idx, line)| {
+        serde_json::from_str(&line?).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid event on line {}: {err}", idx + 1),
+            )
+        })
+    }))
+}
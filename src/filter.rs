@@ -0,0 +1,400 @@
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer};
+
+use crate::output::{AttributeKind, AttributeValue};
+
+/// Expression selecting the nodes or the edges to export by their attributes,
+/// e.g. `degree > 2 && depth <= 3 && node_type == 'article'`.
+///
+/// An expression compares attributes with literals, numbers, strings between single or double quotes, `true` or `false`,
+/// with `==`, `!=`, `<`, `<=`, `>` and `>=`. The comparisons are combined with `&&`, `||`, `!` and parentheses,
+/// and a boolean attribute can be used on its own, e.g. `!ghost`. A comparison with an attribute that has no value is false
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// The expression as it has been given
+    source: String,
+    expr: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(String, Comparison, Literal),
+    /// A boolean attribute on its own
+    Flag(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(Literal),
+    Comparison(Comparison),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl Filter {
+    /// Whether the attributes given by `value_of` satisfy the expression
+    pub fn matches(&self, value_of: &impl Fn(&str) -> Option<AttributeValue>) -> bool {
+        self.expr.eval(value_of)
+    }
+
+    /// Fails if the expression uses an attribute that is unknown to `kind_of`,
+    /// or compares an attribute with a literal of another type
+    pub fn check(&self, kind_of: &impl Fn(&str) -> Option<AttributeKind>) -> Result<(), String> {
+        self.expr.check(kind_of)
+    }
+
+    /// Whether the expression uses the attribute called `name`
+    pub fn uses(&self, name: &str) -> bool {
+        self.expr.uses(name)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.next) {
+            return Err(format!("Unexpected {token:?} in the filter `{source}`"));
+        }
+        Ok(Filter {
+            source: source.to_owned(),
+            expr,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Filter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Expr {
+    fn eval(&self, value_of: &impl Fn(&str) -> Option<AttributeValue>) -> bool {
+        match self {
+            Expr::Or(left, right) => left.eval(value_of) || right.eval(value_of),
+            Expr::And(left, right) => left.eval(value_of) && right.eval(value_of),
+            Expr::Not(expr) => !expr.eval(value_of),
+            Expr::Compare(name, comparison, literal) => value_of(name)
+                .and_then(|value| literal.compared_to(&value))
+                .is_some_and(|ordering| comparison.holds(ordering)),
+            Expr::Flag(name) => value_of(name) == Some(AttributeValue::Boolean(true)),
+        }
+    }
+
+    fn check(&self, kind_of: &impl Fn(&str) -> Option<AttributeKind>) -> Result<(), String> {
+        match self {
+            Expr::Or(left, right) | Expr::And(left, right) => {
+                left.check(kind_of)?;
+                right.check(kind_of)
+            }
+            Expr::Not(expr) => expr.check(kind_of),
+            Expr::Compare(name, _, literal) => {
+                let kind = kind_of(name).ok_or_else(|| format!("Unknown attribute `{name}`"))?;
+                let compatible = matches!(
+                    (kind, literal),
+                    (
+                        AttributeKind::Integer | AttributeKind::Float,
+                        Literal::Number(_)
+                    ) | (AttributeKind::String, Literal::String(_))
+                        | (AttributeKind::Boolean, Literal::Boolean(_))
+                );
+                if compatible {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "The attribute `{name}` is a {kind:?} and cannot be compared with {literal:?}"
+                    ))
+                }
+            }
+            Expr::Flag(name) => match kind_of(name) {
+                Some(AttributeKind::Boolean) => Ok(()),
+                Some(kind) => Err(format!(
+                    "The attribute `{name}` is a {kind:?} and has to be compared with a value"
+                )),
+                None => Err(format!("Unknown attribute `{name}`")),
+            },
+        }
+    }
+
+    fn uses(&self, name: &str) -> bool {
+        match self {
+            Expr::Or(left, right) | Expr::And(left, right) => left.uses(name) || right.uses(name),
+            Expr::Not(expr) => expr.uses(name),
+            Expr::Compare(attribute, _, _) | Expr::Flag(attribute) => attribute == name,
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equal => ordering.is_eq(),
+            Comparison::NotEqual => ordering.is_ne(),
+            Comparison::Less => ordering.is_lt(),
+            Comparison::LessOrEqual => ordering.is_le(),
+            Comparison::Greater => ordering.is_gt(),
+            Comparison::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+impl Literal {
+    /// Ordering of `value` with respect to the literal, if they have the same type
+    fn compared_to(&self, value: &AttributeValue) -> Option<Ordering> {
+        match (value, self) {
+            (AttributeValue::Integer(value), Literal::Number(number)) => {
+                (*value as f64).partial_cmp(number)
+            }
+            (AttributeValue::Float(value), Literal::Number(number)) => value.partial_cmp(number),
+            (AttributeValue::String(value), Literal::String(string)) => {
+                Some(value.as_str().cmp(string))
+            }
+            (AttributeValue::Boolean(value), Literal::Boolean(boolean)) => Some(value.cmp(boolean)),
+            _ => None,
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut followed_by = |next: char| chars.next_if(|(_, c)| *c == next).is_some();
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '&' if followed_by('&') => Token::And,
+            '|' if followed_by('|') => Token::Or,
+            '=' if followed_by('=') => Token::Comparison(Comparison::Equal),
+            '!' if followed_by('=') => Token::Comparison(Comparison::NotEqual),
+            '!' => Token::Not,
+            '<' if followed_by('=') => Token::Comparison(Comparison::LessOrEqual),
+            '<' => Token::Comparison(Comparison::Less),
+            '>' if followed_by('=') => Token::Comparison(Comparison::GreaterOrEqual),
+            '>' => Token::Comparison(Comparison::Greater),
+            '\'' | '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, next)) if next == c => break,
+                        Some((_, next)) => string.push(next),
+                        None => {
+                            return Err(format!("Unterminated string in the filter `{source}`"))
+                        }
+                    }
+                }
+                Token::Literal(Literal::String(string))
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut end = start + c.len_utf8();
+                while let Some((idx, next)) =
+                    chars.next_if(|(_, next)| next.is_ascii_digit() || *next == '.')
+                {
+                    end = idx + next.len_utf8();
+                }
+                let number = source[start..end].parse().map_err(|_| {
+                    format!(
+                        "Invalid number `{}` in the filter `{source}`",
+                        &source[start..end]
+                    )
+                })?;
+                Token::Literal(Literal::Number(number))
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((idx, next)) =
+                    chars.next_if(|(_, next)| next.is_alphanumeric() || *next == '_')
+                {
+                    end = idx + next.len_utf8();
+                }
+                match &source[start..end] {
+                    "true" => Token::Literal(Literal::Boolean(true)),
+                    "false" => Token::Literal(Literal::Boolean(false)),
+                    ident => Token::Ident(ident.to_owned()),
+                }
+            }
+            c => return Err(format!("Unexpected `{c}` in the filter `{source}`")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser of the expressions, where `&&` binds tighter than `||`
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn skip(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.next) == Some(token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.and()?;
+        while self.skip(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while self.skip(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.advance() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                if !self.skip(&Token::Close) {
+                    return Err("Missing `)` in the filter".to_owned());
+                }
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => {
+                let Some(Token::Comparison(comparison)) = self.tokens.get(self.next).cloned()
+                else {
+                    return Ok(Expr::Flag(name));
+                };
+                self.next += 1;
+                match self.advance() {
+                    Some(Token::Literal(literal)) => Ok(Expr::Compare(name, comparison, literal)),
+                    token => Err(format!(
+                        "Expected a value to compare `{name}` with, found {token:?}"
+                    )),
+                }
+            }
+            token => Err(format!("Expected an attribute, found {token:?}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn filters_are_evaluated_on_the_attributes() {
+        let filter: Filter = "degree > 2 && depth <= 3 && node_type == 'article'"
+            .parse()
+            .unwrap();
+        let node = |degree: i64, depth: Option<i64>, node_type: &str| {
+            let mut attributes = HashMap::from([
+                ("degree", AttributeValue::Integer(degree)),
+                ("node_type", AttributeValue::String(node_type.to_owned())),
+            ]);
+            if let Some(depth) = depth {
+                attributes.insert("depth", AttributeValue::Integer(depth));
+            }
+            move |name: &str| attributes.get(name).cloned()
+        };
+        assert!(filter.matches(&node(3, Some(3), "article")));
+        assert!(!filter.matches(&node(2, Some(3), "article")));
+        assert!(!filter.matches(&node(3, Some(4), "article")));
+        assert!(!filter.matches(&node(3, Some(1), "category")));
+        assert!(!filter.matches(&node(3, None, "article")));
+        assert!(filter.uses("depth"));
+        assert!(!filter.uses("title"));
+
+        let filter: Filter = "!(ghost || pagerank < 0.5) || url == \"a\""
+            .parse()
+            .unwrap();
+        let page = |ghost: bool, pagerank: f64, url: &str| {
+            let attributes = HashMap::from([
+                ("ghost", AttributeValue::Boolean(ghost)),
+                ("pagerank", AttributeValue::Float(pagerank)),
+                ("url", AttributeValue::String(url.to_owned())),
+            ]);
+            move |name: &str| attributes.get(name).cloned()
+        };
+        assert!(filter.matches(&page(false, 0.7, "b")));
+        assert!(!filter.matches(&page(true, 0.7, "b")));
+        assert!(!filter.matches(&page(false, 0.2, "b")));
+        assert!(filter.matches(&page(true, 0.2, "a")));
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        for source in [
+            "",
+            "degree >",
+            "degree > 2 &&",
+            "(degree > 2",
+            "degree > 2)",
+            "title == 'a",
+            "degree = 2",
+            "2 < degree",
+            "degree > 2 depth < 3",
+        ] {
+            assert!(source.parse::<Filter>().is_err(), "{source}");
+        }
+
+        let kind_of = |name: &str| match name {
+            "degree" => Some(AttributeKind::Integer),
+            "ghost" => Some(AttributeKind::Boolean),
+            _ => None,
+        };
+        let check = |source: &str| source.parse::<Filter>().unwrap().check(&kind_of);
+        assert!(check("degree >= 1 && !ghost").is_ok());
+        assert!(check("title == 'a'").is_err());
+        assert!(check("degree == 'a'").is_err());
+        assert!(check("ghost == 1").is_err());
+        assert!(check("degree").is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::{thread, time::Duration};
+
+use crossbeam_channel::{Receiver, Sender};
+use serde_json::json;
+
+use crate::node_id::NodeId;
+
+/// Maximum number of events sent to Gephi in a single request
+const MAX_BATCH_SIZE: usize = 500;
+
+/// A change to the graph, as sent to the Gephi Streaming plugin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphEvent {
+    AddNode { id: NodeId, url: String },
+    AddEdge { source: NodeId, target: NodeId },
+}
+
+/// Pushes the nodes and edges of the graph to a running Gephi instance through the HTTP API
+/// of its Streaming plugin, as soon as they are found.
+///
+/// Events are sent in batches from a background thread, so that a slow or unreachable Gephi
+/// does not slow down the scrape. Cloning the stream only clones the sending side.
+#[derive(Debug, Clone)]
+pub struct GephiStream {
+    tx: Sender<GraphEvent>,
+}
+
+/// The background thread sending events to Gephi. `finish` waits until every event has been sent.
+pub struct GephiStreamThread {
+    handle: thread::JoinHandle<()>,
+}
+
+impl GephiStream {
+    /// Starts streaming to the workspace at `url`, e.g. `http://localhost:8080/workspace1`
+    pub fn start(url: impl Into<String>, directed: bool) -> (GephiStream, GephiStreamThread) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let url = url.into();
+        let handle = thread::spawn(move || send_events(&url, directed, rx));
+        (GephiStream { tx }, GephiStreamThread { handle })
+    }
+
+    pub fn add_node(&self, id: NodeId, url: impl Into<String>) {
+        // The thread only stops once every stream has been dropped, so this cannot fail
+        let _ = self.tx.send(GraphEvent::AddNode {
+            id,
+            url: url.into(),
+        });
+    }
+
+    pub fn add_edge(&self, source: NodeId, target: NodeId) {
+        let _ = self.tx.send(GraphEvent::AddEdge { source, target });
+    }
+}
+
+impl GephiStreamThread {
+    /// Waits until every event has been sent. All of the streams must have been dropped before calling this.
+    pub fn finish(self) {
+        if self.handle.join().is_err() {
+            eprintln!("[WARN] The Gephi stream stopped unexpectedly");
+        }
+    }
+}
+
+/// Formats an event as a line of the Gephi streaming format
+fn format_event(event: &GraphEvent, directed: bool) -> String {
+    let value = match event {
+        GraphEvent::AddNode { id, url } => json!({ "an": { id.to_string(): { "label": url } } }),
+        GraphEvent::AddEdge { source, target } => {
+            // Both directions of an undirected edge get the same id, so that Gephi only keeps one of them
+            let (first, second) = if directed {
+                (source, target)
+            } else {
+                (source.min(target), source.max(target))
+            };
+            json!({ "ae": { format!("{first}-{second}"): {
+                "source": source.to_string(),
+                "target": target.to_string(),
+                "directed": directed,
+            } } })
+        }
+    };
+    value.to_string()
+}
+
+fn send_events(url: &str, directed: bool, rx: Receiver<GraphEvent>) {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("The Gephi client should be buildable");
+    let endpoint = format!("{url}?operation=updateGraph");
+    let mut warned = false;
+
+    while let Ok(event) = rx.recv() {
+        let mut body = format_event(&event, directed);
+        for event in rx.try_iter().take(MAX_BATCH_SIZE - 1) {
+            body.push_str("\r\n");
+            body.push_str(&format_event(&event, directed));
+        }
+
+        let result = client
+            .post(&endpoint)
+            .body(body)
+            .send()
+            .and_then(|resp| resp.error_for_status());
+        // Only warn once, so that an unreachable Gephi does not flood the logs
+        if let Err(err) = result {
+            if !warned {
+                eprintln!("[WARN] Could not stream the graph to Gephi at {url}: {err}");
+                warned = true;
+            }
+        }
+    }
+}
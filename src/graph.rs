@@ -0,0 +1,1351 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+
+use crate::centrality::Centrality;
+use crate::clustering::Clustering;
+use crate::components::Components;
+use crate::config::{ExternalLinks, GhostNodes, ScraperConfig, SiteMode, UNLIMITED_DEPTH};
+use crate::edge_store::{EdgeStore, Weight};
+use crate::filter::Filter;
+use crate::http::LinkCheck;
+use crate::layout::Layout;
+use crate::links::{self, LinkStats};
+use crate::mediawiki;
+use crate::node_id::NodeId;
+use crate::output::{
+    Attribute, AttributeKind, AttributeValue, Edge, ExternalEdgesWriter, Node, OutputFormat,
+    OutputOptions, OutputWriter, PageDetails, Priority, Schema, WriterThread,
+};
+use crate::pagerank::Ranking;
+use crate::pipeline::StageResults;
+use crate::worker;
+
+/// The graph built by a scrape.
+/// Cloning it only clones the handle, so that the same graph can be read from other threads while the scrape is running.
+#[derive(Clone)]
+pub struct Graph {
+    /// Urls the crawl has been started from
+    pub seeds: Arc<Mutex<Vec<String>>>,
+    pub links: Arc<Mutex<EdgeStore>>,
+    pub pages: Arc<Mutex<HashMap<String, NodeId>>>,
+    /// Provenance of the scraped pages. Only filled if provenance is enabled in the config
+    pub provenance: Arc<Mutex<HashMap<NodeId, Provenance>>>,
+    /// Position of each link among the distinct links of its source page, when it has first been found.
+    /// Only filled if the positions of the links are enabled in the config
+    pub link_positions: Arc<Mutex<HashMap<(NodeId, NodeId), u64>>>,
+    /// Type of each link, given by the part of its source page it has first been found in.
+    /// Only filled if the types of the edges are enabled in the config
+    pub edge_types: Arc<Mutex<HashMap<(NodeId, NodeId), EdgeType>>>,
+    /// Language of the target of each link to the same article in another language.
+    /// Only filled if the interlanguage links are enabled in the config
+    pub interlanguage: Arc<Mutex<HashMap<(NodeId, NodeId), String>>>,
+    /// Number of links found in the scraped pages, by kind
+    pub link_kinds: Arc<LinkStats>,
+    /// Pages rejected by the keywords. Only filled if ghost nodes are marked or excluded in the config
+    pub ghosts: Arc<Mutex<HashSet<NodeId>>>,
+    /// Keywords contained in each scraped page. Only filled if splitting by keyword is enabled in the config
+    pub keyword_matches: Arc<Mutex<HashMap<NodeId, Vec<String>>>>,
+    /// Pages whose links have been added to the graph, along with when they have last been scraped
+    pub expanded: Arc<Mutex<HashMap<NodeId, Visit>>>,
+    /// Outcome of the check of the external pages. Only filled if checking external links is enabled in the config
+    pub link_checks: Arc<Mutex<HashMap<NodeId, LinkCheck>>>,
+    /// Links from the scraped pages to the external ones, which are not part of the graph.
+    /// Only filled if the external links are kept in a separate file
+    pub external_links: Arc<Mutex<BTreeSet<(NodeId, String)>>>,
+    /// Canonical url of the fetched pages whose canonical url is different, by the url they have been fetched from
+    pub aliases: Arc<Mutex<HashMap<String, String>>>,
+    /// Pages that could not be fetched even after retrying them, by url. They are queued again when the crawl is resumed
+    pub failed: Arc<Mutex<HashMap<String, FailedPage>>>,
+    /// Disambiguation pages, which are left out of the exported graph. Only filled if skipping them is enabled in the config
+    pub disambiguation: Arc<Mutex<HashSet<NodeId>>>,
+    /// Links of the pages expanded by an imported crawl. Only filled if a graph has been imported
+    pub imported: Arc<Mutex<ImportedLinks>>,
+    /// Results of the later stages of a staged crawl, exported along with the graph
+    pub stages: Arc<Mutex<StageResults>>,
+    /// Thread writing the output files of the graph
+    pub writer: WriterThread,
+    config: Arc<ScraperConfig>,
+}
+
+/// Links of the pages expanded by an imported crawl, which are expanded again from the graph instead of being fetched
+#[derive(Debug, Default)]
+pub struct ImportedLinks {
+    /// Urls of the pages linked by each page, until it is expanded by this crawl
+    pub links: HashMap<NodeId, Vec<String>>,
+    /// Pages queued while expanding the imported pages, along with the expanded ones, so that each of them is only queued once
+    pub queued: HashSet<String>,
+}
+
+/// When and by which worker a page has been scraped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Provenance {
+    /// Position of the page in the order the pages have been scraped, starting from 0
+    pub sequence: u64,
+    pub worker_id: usize,
+    pub fetched_at: SystemTime,
+}
+
+/// When a page has last been scraped
+#[derive(Debug, Clone, Copy)]
+pub struct Visit {
+    pub at: Instant,
+    /// Number of hops from the seed at which the page has been found
+    pub hop: u64,
+}
+
+/// Lowest, highest and mean degree of the pages of a graph
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DegreeStats {
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+}
+
+/// Page that could not be fetched, with the depth it was to be scraped with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FailedPage {
+    pub depth: u64,
+    /// Number of times the crawl has given up on fetching the page, across the runs resuming it
+    pub attempts: u32,
+}
+
+/// Part of a page a link has been found in, written in the `type` attribute of the edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum EdgeType {
+    /// The text of the page, or any part of it that is not one of the others
+    Body,
+    /// The list of related articles in the "See also" section
+    SeeAlso,
+    /// The box summarizing the subject of the article, usually next to the lead
+    Infobox,
+    /// The navigation boxes and sidebars linking to the other articles of a topic
+    Navbox,
+}
+
+impl EdgeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeType::Body => "body",
+            EdgeType::SeeAlso => "see_also",
+            EdgeType::Infobox => "infobox",
+            EdgeType::Navbox => "navbox",
+        }
+    }
+}
+
+/// Type of a node, written in the `node_type` attribute of every output format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeType {
+    /// An article of a wiki, in its main namespace
+    Article,
+    /// A category page of a wiki
+    Category,
+    /// The description page of a file of a wiki, which are mostly images
+    Image,
+    /// A page of a wiki in another namespace, or a page of a generic site
+    Page,
+    /// A page outside of the crawled wikis or sites, only found when the external links are kept
+    External,
+}
+
+impl NodeType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeType::Article => "article",
+            NodeType::Category => "category",
+            NodeType::Image => "image",
+            NodeType::Page => "page",
+            NodeType::External => "external",
+        }
+    }
+}
+
+impl Provenance {
+    fn attributes(&self) -> [AttributeValue; 3] {
+        let fetched_at = self
+            .fetched_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        [
+            AttributeValue::Integer(self.sequence as i64),
+            AttributeValue::Integer(self.worker_id as i64),
+            AttributeValue::Integer(fetched_at as i64),
+        ]
+    }
+}
+
+impl Graph {
+    pub fn new(config: Arc<ScraperConfig>) -> Graph {
+        Graph {
+            seeds: Default::default(),
+            links: Default::default(),
+            pages: Default::default(),
+            provenance: Default::default(),
+            link_positions: Default::default(),
+            edge_types: Default::default(),
+            interlanguage: Default::default(),
+            keyword_matches: Default::default(),
+            ghosts: Default::default(),
+            link_kinds: Default::default(),
+            expanded: Default::default(),
+            link_checks: Default::default(),
+            external_links: Default::default(),
+            aliases: Default::default(),
+            failed: Default::default(),
+            disambiguation: Default::default(),
+            imported: Default::default(),
+            stages: Default::default(),
+            writer: Default::default(),
+            config,
+        }
+    }
+
+    pub fn num_links(&self) -> Result<usize, std::io::Error> {
+        self.links.lock().unwrap().len()
+    }
+
+    pub fn num_pages(&self) -> usize {
+        self.pages.lock().unwrap().len()
+    }
+
+    /// Writes the graph to the output files for the prefix `output_file`, in each of the given formats
+    pub fn save_to_file(
+        &self,
+        output_file: impl AsRef<str>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        let mut writer =
+            self.writer
+                .files(Priority::Foreground, output_file.as_ref(), formats, options)?;
+        self.export_subgraph(&mut writer, None)?;
+        writer.finish()?;
+        if self.config.keep_external_links == Some(ExternalLinks::Separate) {
+            self.save_external_edges(output_file.as_ref(), options)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the links to the external pages, which are kept apart from the graph, with the columns `source,url`.
+    /// As the graph, they are written by the writer thread
+    fn save_external_edges(
+        &self,
+        output_file: &str,
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        let (path, options) = (external_edges_path(output_file, options), *options);
+        let mut writer = self.writer.writer(Priority::Foreground, move || {
+            let out = options.compression.create(&path)?;
+            Ok(vec![Box::new(ExternalEdgesWriter::new(
+                out,
+                options.delimiter,
+            ))])
+        })?;
+        writer.begin(&Schema::default())?;
+        for (id, url) in self.external_links.lock().unwrap().iter() {
+            writer.write_node(&Node {
+                id: *id,
+                url: url.clone(),
+                attributes: Vec::new(),
+                details: None,
+            })?;
+        }
+        writer.finish()
+    }
+
+    /// Attributes of the nodes and of the edges of the exported graph
+    pub fn schema(&self) -> Schema {
+        let mut schema = Schema {
+            directed: !self.config.undirected,
+            id_width: self.config.id_width,
+            node_attributes: vec![Attribute::new("node_type", AttributeKind::String)],
+            ..Default::default()
+        };
+        // The title of the articles is more readable than their url, which may be percent encoded
+        if self.with_title() {
+            schema
+                .node_attributes
+                .push(Attribute::new("title", AttributeKind::String));
+        }
+        if self.with_lang() {
+            schema
+                .node_attributes
+                .push(Attribute::new("lang", AttributeKind::String));
+        }
+        if self.config.ghost_nodes == GhostNodes::Mark {
+            schema
+                .node_attributes
+                .push(Attribute::new("ghost", AttributeKind::Boolean));
+        }
+        if self.config.checks_external() {
+            schema.node_attributes.extend([
+                Attribute::new("http_status", AttributeKind::Integer),
+                Attribute::new("final_url", AttributeKind::String),
+            ]);
+        }
+        if self.config.degrees {
+            schema.node_attributes.extend([
+                Attribute::new("in_degree", AttributeKind::Integer),
+                Attribute::new("out_degree", AttributeKind::Integer),
+            ]);
+        }
+        if self.config.components {
+            schema
+                .node_attributes
+                .push(Attribute::new("component_id", AttributeKind::Integer));
+        }
+        if self.config.pagerank {
+            schema.node_attributes.extend([
+                Attribute::new("pagerank", AttributeKind::Float),
+                Attribute::new("rank_percentile", AttributeKind::Integer),
+            ]);
+        }
+        if self.config.centrality {
+            schema.node_attributes.extend([
+                Attribute::new("betweenness", AttributeKind::Float),
+                Attribute::new("closeness", AttributeKind::Float),
+            ]);
+        }
+        if self.config.clustering {
+            schema
+                .node_attributes
+                .push(Attribute::new("clustering", AttributeKind::Float));
+        }
+        if self.config.layout.is_some() {
+            schema.node_attributes.extend([
+                Attribute::new("x", AttributeKind::Float),
+                Attribute::new("y", AttributeKind::Float),
+            ]);
+        }
+        let own_stages = self.stages.lock().unwrap();
+        if own_stages.pageviews.is_some() {
+            schema
+                .node_attributes
+                .push(Attribute::new("pageviews", AttributeKind::Integer));
+        }
+        if own_stages.analysis.is_some() {
+            schema.node_attributes.extend([
+                Attribute::new("pagerank", AttributeKind::Float),
+                Attribute::new("in_degree", AttributeKind::Integer),
+                Attribute::new("out_degree", AttributeKind::Integer),
+            ]);
+        }
+        if self.config.weighted {
+            schema
+                .edge_attributes
+                .push(Attribute::new("weight", AttributeKind::Integer));
+        }
+        schema.metadata = self.metadata();
+        if self.config.provenance {
+            schema.edge_attributes.extend([
+                Attribute::new("crawl_seq", AttributeKind::Integer),
+                Attribute::new("worker_id", AttributeKind::Integer),
+                Attribute::new("fetched_at", AttributeKind::Integer),
+            ]);
+        }
+        if self.config.link_positions {
+            schema
+                .edge_attributes
+                .push(Attribute::new("position", AttributeKind::Integer));
+        }
+        if self.config.edge_types {
+            schema
+                .edge_attributes
+                .push(Attribute::new("type", AttributeKind::String));
+        }
+        if self.config.interlanguage_links {
+            schema
+                .edge_attributes
+                .push(Attribute::new("interlanguage", AttributeKind::String));
+        }
+        schema
+    }
+
+    /// Parameters of the crawl that built the graph
+    fn metadata(&self) -> Vec<(&'static str, String)> {
+        let list = |values: &Option<Vec<String>>| {
+            values
+                .as_ref()
+                .map_or_else(|| "none".to_owned(), |values| values.join(","))
+        };
+        let patterns = |regexes: &[regex::Regex]| {
+            if regexes.is_empty() {
+                return "none".to_owned();
+            }
+            regexes
+                .iter()
+                .map(regex::Regex::as_str)
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        // Graphs that have not been built by a crawl, such as simulated ones, start from their first page
+        let seeds = self.seeds.lock().unwrap().join(",");
+        let seed = if seeds.is_empty() {
+            self.pages
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(_, id)| id.get() == 0)
+                .map(|(url, _)| url.clone())
+                .unwrap_or_default()
+        } else {
+            seeds
+        };
+
+        vec![
+            ("seed", seed),
+            (
+                "depth",
+                if self.config.depth == UNLIMITED_DEPTH {
+                    "unlimited".to_owned()
+                } else {
+                    self.config.depth.to_string()
+                },
+            ),
+            (
+                "strategy",
+                self.config
+                    .strategy
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_owned()),
+            ),
+            (
+                "max_pages",
+                self.config
+                    .max_pages
+                    .map_or_else(|| "none".to_owned(), |max_pages| max_pages.to_string()),
+            ),
+            (
+                "max_duration_secs",
+                self.config.max_duration.map_or_else(
+                    || "none".to_owned(),
+                    |max_duration| max_duration.as_secs().to_string(),
+                ),
+            ),
+            ("keywords", list(&self.config.keywords)),
+            ("exclude_keywords", list(&self.config.exclude_keywords)),
+            (
+                "keywords_from_hop",
+                self.config.keywords_from_hop.to_string(),
+            ),
+            (
+                "link_scope",
+                self.config
+                    .link_scope
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_owned()),
+            ),
+            ("url_allow", patterns(&self.config.url_filter.allow)),
+            ("url_deny", patterns(&self.config.url_filter.deny)),
+            (
+                "keyword_mode",
+                self.config
+                    .keyword_mode
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_owned()),
+            ),
+            ("weighted", self.config.weighted.to_string()),
+            (
+                "keep_external_links",
+                self.config
+                    .keep_external_links
+                    .and_then(|policy| policy.to_possible_value())
+                    .map_or_else(|| "none".to_owned(), |value| value.get_name().to_owned()),
+            ),
+            (
+                "skip_disambiguation",
+                self.config.skip_disambiguation.to_string(),
+            ),
+            (
+                "all_languages_of_seed",
+                self.config.all_languages_of_seed.to_string(),
+            ),
+            ("languages", list(&self.config.languages)),
+            (
+                "interlanguage_links",
+                self.config.interlanguage_links.to_string(),
+            ),
+            (
+                "interlanguage_depth",
+                self.config
+                    .interlanguage_depth
+                    .map_or_else(|| "none".to_owned(), |depth| depth.to_string()),
+            ),
+            (
+                "revisit_after_secs",
+                self.config
+                    .revisit
+                    .after
+                    .map_or_else(|| "none".to_owned(), |after| after.as_secs().to_string()),
+            ),
+            (
+                "revisit_after_hops",
+                self.config
+                    .revisit
+                    .after_hops
+                    .map_or_else(|| "none".to_owned(), |hops| hops.to_string()),
+            ),
+            (
+                "frontier_ttl_secs",
+                self.config
+                    .frontier_ttl
+                    .map_or_else(|| "none".to_owned(), |ttl| ttl.as_secs().to_string()),
+            ),
+            (
+                "stop_expanding_at_degree",
+                self.config
+                    .stop_expanding_at_degree
+                    .map_or_else(|| "none".to_owned(), |degree| degree.to_string()),
+            ),
+            (
+                "max_links_per_page",
+                self.config
+                    .max_links_per_page
+                    .map_or_else(|| "none".to_owned(), |max| max.to_string()),
+            ),
+            (
+                "link_sampling",
+                self.config
+                    .link_sampling
+                    .to_possible_value()
+                    .map_or_else(String::new, |value| value.get_name().to_owned()),
+            ),
+            (
+                "centrality_samples",
+                if self.config.centrality {
+                    self.config.centrality_samples.to_string()
+                } else {
+                    "none".to_owned()
+                },
+            ),
+            (
+                "node_filter",
+                self.config
+                    .node_filter
+                    .as_ref()
+                    .map_or_else(|| "none".to_owned(), Filter::to_string),
+            ),
+            (
+                "edge_filter",
+                self.config
+                    .edge_filter
+                    .as_ref()
+                    .map_or_else(|| "none".to_owned(), Filter::to_string),
+            ),
+        ]
+    }
+
+    /// Writes the graph with the given writer, without finishing it.
+    /// Fails if the external links are kept separate, since they are written next to an output file, see [`Graph::save_to_file`]
+    pub fn export(&self, writer: &mut dyn OutputWriter) -> Result<(), std::io::Error> {
+        if self.config.keep_external_links == Some(ExternalLinks::Separate) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "The external links are kept separate, which requires saving the graph to a file",
+            ));
+        }
+        self.export_subgraph(writer, None)
+    }
+
+    /// Writes, for each keyword, the subgraph induced by the pages containing it to the output files
+    /// for the prefix `<output_file>_<keyword>`. Only the pages that have been scraped are checked for the keywords.
+    pub fn save_keyword_subgraphs(
+        &self,
+        output_file: impl AsRef<str>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        let mut pages_by_keyword: BTreeMap<String, HashSet<NodeId>> = BTreeMap::new();
+        for (id, keywords) in self.keyword_matches.lock().unwrap().iter() {
+            for keyword in keywords {
+                pages_by_keyword
+                    .entry(keyword.clone())
+                    .or_default()
+                    .insert(*id);
+            }
+        }
+
+        for (keyword, pages) in pages_by_keyword {
+            let prefix = keyword_prefix(output_file.as_ref(), &keyword);
+            let mut writer = self
+                .writer
+                .files(Priority::Foreground, prefix, formats, options)?;
+            self.export_subgraph(&mut writer, Some(&pages))?;
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the subgraph induced by the `nodes` pages found last, with the links between them, to the output files
+    /// for the prefix `output_file`. Returns the number of pages written, which is lower than `nodes` if the graph has fewer.
+    /// The ids are allocated in the order the pages are found, so the pages found last are the ones with the highest ids
+    pub fn save_window(
+        &self,
+        output_file: impl AsRef<str>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+        nodes: usize,
+    ) -> Result<usize, std::io::Error> {
+        let mut ids: Vec<NodeId> = self.pages.lock().unwrap().values().copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let window: HashSet<NodeId> = ids.into_iter().rev().take(nodes).collect();
+        let mut writer =
+            self.writer
+                .files(Priority::Background, output_file.as_ref(), formats, options)?;
+        self.export_subgraph(&mut writer, Some(&window))?;
+        writer.finish()?;
+        Ok(window.len())
+    }
+
+    /// Writes the subgraph induced by the pages in `only` with the given writer, or the whole graph if it is `None`
+    fn export_subgraph(
+        &self,
+        writer: &mut dyn OutputWriter,
+        only: Option<&HashSet<NodeId>>,
+    ) -> Result<(), std::io::Error> {
+        let merged = self.merge_aliases();
+        if merged > 0 {
+            eprintln!("[INFO] Merged {merged} pages into the pages they redirect to");
+        }
+        let own_ghosts = self.ghosts.lock().unwrap();
+        let own_disambiguation = self.disambiguation.lock().unwrap();
+        let exclude_ghosts = self.config.ghost_nodes == GhostNodes::Exclude;
+        let kept = |id: &NodeId| {
+            only.is_none_or(|only| only.contains(id))
+                && !(exclude_ghosts && own_ghosts.contains(id))
+                && !own_disambiguation.contains(id)
+        };
+        let schema = self.schema();
+        self.check_filters(&schema).map_err(std::io::Error::other)?;
+        writer.begin(&schema)?;
+        let with_details = writer.needs_page_details();
+
+        let weighted = self.config.weighted;
+        let with_title = self.with_title();
+        let with_lang = self.with_lang();
+        let seed_hosts = self.seed_hosts();
+        let own_provenance = self
+            .config
+            .provenance
+            .then(|| self.provenance.lock().unwrap());
+        let own_link_positions = self
+            .config
+            .link_positions
+            .then(|| self.link_positions.lock().unwrap());
+        let own_edge_types = self
+            .config
+            .edge_types
+            .then(|| self.edge_types.lock().unwrap());
+        let own_interlanguage = self
+            .config
+            .interlanguage_links
+            .then(|| self.interlanguage.lock().unwrap());
+
+        let edge_filter = self.config.edge_filter.as_ref();
+
+        // The provenance of an edge is the one of its source page. In undirected graphs, both pages
+        // are the source of an edge, and the provenance of the one scraped last is used.
+        // Returns whether the edge has been written, which it is not if it is rejected by the edge filter
+        let mut write_edge = |source: NodeId, target: NodeId, weight: Weight| {
+            let mut attributes = Vec::new();
+            if weighted {
+                attributes.push(AttributeValue::Integer(weight as i64));
+            }
+            if let Some(own_provenance) = &own_provenance {
+                let provenance = if self.config.undirected {
+                    [own_provenance.get(&source), own_provenance.get(&target)]
+                        .into_iter()
+                        .flatten()
+                        .max_by_key(|provenance| provenance.sequence)
+                } else {
+                    own_provenance.get(&source)
+                };
+                let provenance =
+                    provenance.expect("The source of an edge should have been scraped");
+                attributes.extend(provenance.attributes());
+            }
+            // In undirected graphs, the position of the edge is the lowest of its two directions.
+            // The links whose position is not known, such as the ones of an imported graph, have position -1
+            if let Some(own_link_positions) = &own_link_positions {
+                let position = if self.config.undirected {
+                    [
+                        own_link_positions.get(&(source, target)),
+                        own_link_positions.get(&(target, source)),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .min()
+                } else {
+                    own_link_positions.get(&(source, target))
+                };
+                let position = position.map_or(-1, |position| *position as i64);
+                attributes.push(AttributeValue::Integer(position));
+            }
+            // The type of an undirected edge is the one of its direction from the source, if it has been found.
+            // The links whose type is not known, such as the ones of an imported graph, have an empty type
+            if let Some(own_edge_types) = &own_edge_types {
+                let edge_type = own_edge_types.get(&(source, target)).or_else(|| {
+                    self.config
+                        .undirected
+                        .then(|| own_edge_types.get(&(target, source)))
+                        .flatten()
+                });
+                attributes.push(AttributeValue::String(
+                    edge_type.map_or("", EdgeType::as_str).to_owned(),
+                ));
+            }
+            // The language of the target of an interlanguage link, and an empty one for the other links
+            if let Some(own_interlanguage) = &own_interlanguage {
+                let lang = own_interlanguage.get(&(source, target)).or_else(|| {
+                    self.config
+                        .undirected
+                        .then(|| own_interlanguage.get(&(target, source)))
+                        .flatten()
+                });
+                attributes.push(AttributeValue::String(lang.cloned().unwrap_or_default()));
+            }
+            if let Some(filter) = edge_filter {
+                let value_of = |name: &str| match attribute_index(&schema.edge_attributes, name) {
+                    Some(idx) => Some(attributes[idx].clone()),
+                    None => (name == "weight").then_some(AttributeValue::Integer(weight as i64)),
+                };
+                if !filter.matches(&value_of) {
+                    return Ok(false);
+                }
+            }
+            writer
+                .write_edge(&Edge {
+                    source,
+                    target,
+                    attributes,
+                })
+                .map(|()| true)
+        };
+
+        let mut own_links = self.links.lock().unwrap();
+        let own_pages = self.pages.lock().unwrap();
+        let distinct_pages = self.distinct_pages(&own_pages);
+        let own_link_checks = self
+            .config
+            .checks_external()
+            .then(|| self.link_checks.lock().unwrap());
+
+        // Computed on the pages that would be exported without the ranking, before writing any edge,
+        // since only the edges between the top pages are written
+        let ranking = if self.config.pagerank {
+            let nodes: Vec<_> = distinct_pages
+                .iter()
+                .map(|(_, id)| *id)
+                .filter(|id| kept(id))
+                .collect();
+            Some(Ranking::compute(
+                &mut own_links,
+                &nodes,
+                self.config.top_fraction,
+            )?)
+        } else {
+            None
+        };
+        let kept =
+            |id: &NodeId| kept(id) && ranking.as_ref().is_none_or(|ranking| ranking.is_kept(id));
+        // Computed on the pages that are exported, so that the components are the ones of the exported graph
+        let components = if self.config.components || self.config.giant_component_only {
+            let nodes: Vec<_> = distinct_pages
+                .iter()
+                .map(|(_, id)| *id)
+                .filter(|id| kept(id))
+                .collect();
+            Some(Components::compute(
+                &mut own_links,
+                &nodes,
+                self.config.undirected,
+            )?)
+        } else {
+            None
+        };
+        let kept = |id: &NodeId| {
+            kept(id)
+                && (!self.config.giant_component_only
+                    || components
+                        .as_ref()
+                        .is_some_and(|components| components.is_giant(id)))
+        };
+        let centrality = if self.config.centrality {
+            let nodes: Vec<_> = distinct_pages
+                .iter()
+                .map(|(_, id)| *id)
+                .filter(|id| kept(id))
+                .collect();
+            Some(Centrality::compute(
+                &mut own_links,
+                &nodes,
+                self.config.undirected,
+                self.config.centrality_samples,
+            )?)
+        } else {
+            None
+        };
+        let clustering = if self.config.clustering {
+            let nodes: Vec<_> = distinct_pages
+                .iter()
+                .map(|(_, id)| *id)
+                .filter(|id| kept(id))
+                .collect();
+            Some(Clustering::compute(
+                &mut own_links,
+                &nodes,
+                self.config.undirected,
+            )?)
+        } else {
+            None
+        };
+        let layout = match self.config.layout {
+            Some(algorithm) => {
+                let nodes: Vec<_> = distinct_pages
+                    .iter()
+                    .map(|(_, id)| *id)
+                    .filter(|id| kept(id))
+                    .collect();
+                Some(Layout::compute(
+                    algorithm,
+                    &mut own_links,
+                    &nodes,
+                    self.config.layout_iterations,
+                )?)
+            }
+            None => None,
+        };
+
+        let node_filter = self.config.node_filter.as_ref();
+        let depths = if with_details || node_filter.is_some_and(|filter| filter.uses("depth")) {
+            Some(self.depths(&mut own_links)?)
+        } else {
+            None
+        };
+        // Pages with no edges have degree 0
+        let degrees = if self.config.degrees
+            || node_filter.is_some_and(|filter| {
+                ["degree", "in_degree", "out_degree"]
+                    .iter()
+                    .any(|name| filter.uses(name))
+            }) {
+            own_links.degrees()?
+        } else {
+            HashMap::new()
+        };
+        let degree_of = |id: &NodeId| degrees.get(id).copied().unwrap_or_default();
+        let own_keyword_matches = with_details.then(|| self.keyword_matches.lock().unwrap());
+        let own_stages = self.stages.lock().unwrap();
+
+        let node_attributes = |url: &String, id: &NodeId| {
+            let mut attributes = vec![AttributeValue::String(
+                self.node_type(url, &seed_hosts).as_str().to_owned(),
+            )];
+            // Pages that are not articles, such as the external ones, have an empty title unless they have been fetched
+            if with_title {
+                let title = self.title(url).or_else(|| {
+                    own_link_checks
+                        .as_ref()
+                        .and_then(|checks| checks.get(id)?.title.clone())
+                });
+                attributes.push(AttributeValue::String(title.unwrap_or_default()));
+            }
+            if with_lang {
+                let lang = worker::get_wikipedia_language(url).unwrap_or_default();
+                attributes.push(AttributeValue::String(lang));
+            }
+            if self.config.ghost_nodes == GhostNodes::Mark {
+                attributes.push(AttributeValue::Boolean(own_ghosts.contains(id)));
+            }
+            if let Some(own_link_checks) = &own_link_checks {
+                // Pages that have not been checked, or that have not answered, have status 0 and no final url
+                let check = own_link_checks.get(id).cloned().unwrap_or_default();
+                attributes.extend([
+                    AttributeValue::Integer(check.status.unwrap_or_default().into()),
+                    AttributeValue::String(check.final_url.unwrap_or_default()),
+                ]);
+            }
+            if self.config.degrees {
+                let (in_degree, out_degree) = degree_of(id);
+                attributes.extend([
+                    AttributeValue::Integer(in_degree as i64),
+                    AttributeValue::Integer(out_degree as i64),
+                ]);
+            }
+            if let Some(components) = components.as_ref().filter(|_| self.config.components) {
+                attributes.extend(components.attributes(id));
+            }
+            if let Some(ranking) = &ranking {
+                attributes.extend(ranking.attributes(id));
+            }
+            if let Some(centrality) = &centrality {
+                attributes.extend(centrality.attributes(id));
+            }
+            if let Some(clustering) = &clustering {
+                attributes.extend(clustering.attributes(id));
+            }
+            if let Some(layout) = &layout {
+                attributes.extend(layout.attributes(id));
+            }
+            // Pages missing from the results of a stage, e.g. the ones that are not articles, have 0
+            if let Some(pageviews) = &own_stages.pageviews {
+                let pageviews = pageviews.get(id).copied().unwrap_or_default();
+                attributes.push(AttributeValue::Integer(pageviews as i64));
+            }
+            if let Some(analysis) = &own_stages.analysis {
+                let analysis = analysis.get(id).copied().unwrap_or_default();
+                attributes.extend([
+                    AttributeValue::Float(analysis.pagerank),
+                    AttributeValue::Integer(analysis.in_degree as i64),
+                    AttributeValue::Integer(analysis.out_degree as i64),
+                ]);
+            }
+            attributes
+        };
+
+        // The node filter is checked last, on the attributes of the pages that would be exported without it,
+        // so that it can select them by their rank. Only the edges between the selected pages are kept
+        let matching = match node_filter {
+            Some(filter) => {
+                let mut matching = HashSet::new();
+                for (url, id) in &distinct_pages {
+                    if !kept(id) {
+                        continue;
+                    }
+                    let attributes = node_attributes(url, id);
+                    // Pages that have not been reached have no depth
+                    let (in_degree, out_degree) = degree_of(id);
+                    let value_of = |name: &str| {
+                        if let Some(idx) = attribute_index(&schema.node_attributes, name) {
+                            return Some(attributes[idx].clone());
+                        }
+                        match name {
+                            "url" => Some(AttributeValue::String((*url).clone())),
+                            "depth" => depths
+                                .as_ref()?
+                                .get(id)
+                                .map(|depth| AttributeValue::Integer(*depth as i64)),
+                            "degree" => {
+                                Some(AttributeValue::Integer((in_degree + out_degree) as i64))
+                            }
+                            "in_degree" => Some(AttributeValue::Integer(in_degree as i64)),
+                            "out_degree" => Some(AttributeValue::Integer(out_degree as i64)),
+                            _ => None,
+                        }
+                    };
+                    if filter.matches(&value_of) {
+                        matching.insert(*id);
+                    }
+                }
+                Some(matching)
+            }
+            None => None,
+        };
+        let kept = |id: &NodeId| {
+            kept(id)
+                && matching
+                    .as_ref()
+                    .is_none_or(|matching| matching.contains(id))
+        };
+
+        // Only the nodes with at least one edge are kept in undirected graphs
+        let mut kept_pages = None;
+
+        if !self.config.undirected {
+            for edge in own_links.sorted_edges()? {
+                let ((source, dest), weight) = edge?;
+                if kept(&source) && kept(&dest) {
+                    write_edge(source, dest, weight)?;
+                }
+            }
+        } else {
+            let mut visited_pages_set = HashSet::new();
+
+            // Both directions of an edge are next to each other in the sorted edges,
+            // so an edge is kept if the previous one is its reverse.
+            // Self loops are always kept, since they are their own reverse.
+            // The weight of an undirected edge is the sum of the weights of both directions.
+            let mut previous: Option<((NodeId, NodeId), Weight)> = None;
+            for edge in own_links.sorted_edges()? {
+                let ((source, dest), weight) = edge?;
+                // Both directions of a skipped edge are skipped, so they never count as the reverse of a kept one
+                if !kept(&source) || !kept(&dest) {
+                    continue;
+                }
+                let reverse_weight = previous
+                    .filter(|(previous_edge, _)| *previous_edge == (dest, source))
+                    .map(|(_, previous_weight)| previous_weight);
+                previous = Some(((source, dest), weight));
+
+                if source == dest {
+                    if write_edge(source, dest, weight)? {
+                        visited_pages_set.insert(source);
+                    }
+                } else if let Some(reverse_weight) = reverse_weight {
+                    if write_edge(dest, source, weight + reverse_weight)? {
+                        visited_pages_set.insert(source);
+                        visited_pages_set.insert(dest);
+                    }
+                }
+            }
+            kept_pages = Some(visited_pages_set);
+        }
+
+        let mut pages = distinct_pages;
+        // The nodes are written by decreasing rank
+        if let Some(ranking) = &ranking {
+            pages.sort_by_key(|(_, id)| ranking.position(id));
+        }
+        for (url, id) in &pages {
+            if !kept(id) {
+                continue;
+            }
+            if kept_pages
+                .as_ref()
+                .is_some_and(|kept_pages| !kept_pages.contains(id))
+            {
+                continue;
+            }
+
+            let attributes = node_attributes(url, id);
+            let details = with_details.then(|| PageDetails {
+                title: self.title(url),
+                depth: depths.as_ref().and_then(|depths| depths.get(id).copied()),
+                keywords: own_keyword_matches
+                    .as_ref()
+                    .filter(|_| self.config.record_keyword_matches)
+                    .map(|matches| matches.get(id).cloned().unwrap_or_default()),
+            });
+            writer.write_node(&Node {
+                id: *id,
+                url: (*url).clone(),
+                attributes,
+                details,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Fails if the node or the edge filter use a value the exported nodes or edges do not have,
+    /// or compare it with a value of another type
+    pub fn check_filters(&self, schema: &Schema) -> Result<(), String> {
+        if let Some(filter) = &self.config.node_filter {
+            filter
+                .check(&|name| filter_kind(&schema.node_attributes, NODE_FILTER_EXTRAS, name))
+                .map_err(|err| format!("Invalid node filter `{filter}`: {err}"))?;
+        }
+        if let Some(filter) = &self.config.edge_filter {
+            filter
+                .check(&|name| filter_kind(&schema.edge_attributes, EDGE_FILTER_EXTRAS, name))
+                .map_err(|err| format!("Invalid edge filter `{filter}`: {err}"))?;
+        }
+        Ok(())
+    }
+
+    /// Merges the page `from` into the page `into`: its links, with their weights summed with the ones of the same links of `into`,
+    /// and the values recorded for them, such as their positions
+    pub fn merge_edges(&self, links: &mut EdgeStore, from: NodeId, into: NodeId) {
+        links.merge_node(from, into);
+        if self.config.link_positions {
+            merge_edge_values(&mut self.link_positions.lock().unwrap(), from, into);
+        }
+        if self.config.edge_types {
+            merge_edge_values(&mut self.edge_types.lock().unwrap(), from, into);
+        }
+        if self.config.interlanguage_links {
+            merge_edge_values(&mut self.interlanguage.lock().unwrap(), from, into);
+        }
+    }
+
+    /// Merges the pages still apart from the page their url redirects to, following the chains of redirects,
+    /// so that the links to the different urls of a page end up in a single edge. This happens when a page has been
+    /// found with an url whose target has then been found to redirect again. Returns the number of pages merged
+    pub fn merge_aliases(&self) -> usize {
+        let mut own_pages = self.pages.lock().unwrap();
+        let mut own_links = self.links.lock().unwrap();
+        let own_aliases = self.aliases.lock().unwrap();
+        let mut merged = 0;
+        for alias in own_aliases.keys() {
+            let canonical = resolve_alias(&own_aliases, alias);
+            let (Some(&from), Some(&into)) = (own_pages.get(alias), own_pages.get(canonical))
+            else {
+                continue;
+            };
+            if from == into {
+                continue;
+            }
+            // Every url of the merged page takes the new id, so that it does not remain as a node without links
+            for id in own_pages.values_mut().filter(|id| **id == from) {
+                *id = into;
+            }
+            self.merge_edges(&mut own_links, from, into);
+            merged += 1;
+        }
+        merged
+    }
+
+    /// Lowest, highest and mean in-degree and out-degree of the pages of the graph, counting the distinct links
+    pub fn degree_stats(&self) -> Result<(DegreeStats, DegreeStats), std::io::Error> {
+        let mut own_links = self.links.lock().unwrap();
+        let degrees = own_links.degrees()?;
+        let own_pages = self.pages.lock().unwrap();
+        let ids: Vec<_> = self
+            .distinct_pages(&own_pages)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        let stats = |degree: fn(&(u64, u64)) -> u64| {
+            let degrees: Vec<_> = ids
+                .iter()
+                .map(|id| degree(&degrees.get(id).copied().unwrap_or_default()))
+                .collect();
+            DegreeStats {
+                min: degrees.iter().copied().min().unwrap_or_default(),
+                max: degrees.iter().copied().max().unwrap_or_default(),
+                mean: degrees.iter().sum::<u64>() as f64 / degrees.len().max(1) as f64,
+            }
+        };
+        Ok((
+            stats(|(in_degree, _)| *in_degree),
+            stats(|(_, out_degree)| *out_degree),
+        ))
+    }
+
+    /// Connected components of the graph, ignoring the direction of the links, or only connecting the pages
+    /// linking to each other in undirected graphs
+    pub fn components(&self) -> Result<Components, std::io::Error> {
+        let mut own_links = self.links.lock().unwrap();
+        let own_pages = self.pages.lock().unwrap();
+        let nodes: Vec<_> = self
+            .distinct_pages(&own_pages)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        Components::compute(&mut own_links, &nodes, self.config.undirected)
+    }
+
+    /// Clustering coefficient of the pages of the graph, ignoring the direction of the links, or only considering
+    /// the pages linking to each other as neighbors in undirected graphs
+    pub fn clustering(&self) -> Result<Clustering, std::io::Error> {
+        let mut own_links = self.links.lock().unwrap();
+        let own_pages = self.pages.lock().unwrap();
+        let nodes: Vec<_> = self
+            .distinct_pages(&own_pages)
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect();
+        Clustering::compute(&mut own_links, &nodes, self.config.undirected)
+    }
+
+    /// The `count` pages with the highest betweenness, the ones the most shortest paths between the other pages go through,
+    /// with their url and betweenness, and whether it has been estimated from a sample of the pages
+    pub fn bridge_pages(&self, count: usize) -> Result<(Vec<(String, f64)>, bool), std::io::Error> {
+        let mut own_links = self.links.lock().unwrap();
+        let own_pages = self.pages.lock().unwrap();
+        let distinct_pages = self.distinct_pages(&own_pages);
+        let nodes: Vec<_> = distinct_pages.iter().map(|(_, id)| *id).collect();
+        let centrality = Centrality::compute(
+            &mut own_links,
+            &nodes,
+            self.config.undirected,
+            self.config.centrality_samples,
+        )?;
+        let urls: HashMap<_, _> = distinct_pages
+            .into_iter()
+            .map(|(url, id)| (id, url))
+            .collect();
+        let pages = centrality
+            .highest_betweenness(count)
+            .into_iter()
+            .map(|(id, betweenness)| (urls[&id].clone(), betweenness))
+            .collect();
+        Ok((pages, centrality.is_sampled()))
+    }
+
+    /// Pages of the graph once per node, with their id. The pages merged into another one, such as redirects,
+    /// share its id, and only the canonical url of the node is kept
+    fn distinct_pages<'a>(&self, pages: &'a HashMap<String, NodeId>) -> Vec<(&'a String, NodeId)> {
+        let own_aliases = self.aliases.lock().unwrap();
+        let mut urls: Vec<_> = pages.iter().collect();
+        urls.sort_by_key(|(url, _)| own_aliases.contains_key(*url));
+        let mut seen = HashSet::new();
+        urls.into_iter()
+            .filter(|(_, id)| seen.insert(**id))
+            .map(|(url, id)| (url, *id))
+            .collect()
+    }
+
+    /// Depth of each page: the number of hops from the seed it was last scraped at,
+    /// or one more than the one of the scraped pages linking to it if it has not been scraped
+    fn depths(&self, links: &mut EdgeStore) -> Result<HashMap<NodeId, u64>, std::io::Error> {
+        let own_expanded = self.expanded.lock().unwrap();
+        let mut depths: HashMap<_, _> = own_expanded
+            .iter()
+            .map(|(id, visit)| (*id, visit.hop))
+            .collect();
+        for edge in links.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            if own_expanded.contains_key(&dest) {
+                continue;
+            }
+            if let Some(visit) = own_expanded.get(&source) {
+                let depth = depths.entry(dest).or_insert(u64::MAX);
+                *depth = (*depth).min(visit.hop + 1);
+            }
+        }
+        Ok(depths)
+    }
+
+    /// Type of the page at `url`. The pages of the sites of the seeds are told apart from the external ones by their host
+    /// in the generic site mode, and by their url being the one of an article in the wiki mode
+    pub fn node_type(&self, url: &str, seed_hosts: &HashSet<String>) -> NodeType {
+        if self.config.site_mode == SiteMode::Generic {
+            return match worker::get_host(url) {
+                Some(host) if seed_hosts.contains(&host) => NodeType::Page,
+                _ => NodeType::External,
+            };
+        }
+        let Some(title) = self.title(url) else {
+            return NodeType::External;
+        };
+        match links::namespace(&title) {
+            None => NodeType::Article,
+            Some("Category") => NodeType::Category,
+            Some("File" | "Media") => NodeType::Image,
+            Some(_) => NodeType::Page,
+        }
+    }
+
+    /// Hosts of the seeds of the crawl
+    fn seed_hosts(&self) -> HashSet<String> {
+        self.seeds
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|seed| worker::get_host(seed))
+            .collect()
+    }
+
+    /// Whether the nodes have the language of their wiki: the crawls spanning several languages
+    fn with_lang(&self) -> bool {
+        self.config.all_languages_of_seed || self.config.interlanguage_links
+    }
+
+    /// Whether the nodes have a title: the articles of a wiki, and the external pages if they are fetched
+    fn with_title(&self) -> bool {
+        self.config.site_mode == SiteMode::Wiki
+            || self.config.keep_external_links == Some(ExternalLinks::Fetch)
+    }
+
+    /// Title of the page at `url`, if it is an article of the wiki
+    fn title(&self, url: &str) -> Option<String> {
+        if self.config.site_mode != SiteMode::Wiki {
+            return None;
+        }
+        let url = reqwest::Url::parse(url).ok()?;
+        mediawiki::article_title(&url, &self.config.wiki.article_path)
+    }
+}
+
+/// Values that can be used in the node filter along with the attributes of the nodes
+const NODE_FILTER_EXTRAS: &[Attribute] = &[
+    Attribute::new("url", AttributeKind::String),
+    Attribute::new("depth", AttributeKind::Integer),
+    Attribute::new("degree", AttributeKind::Integer),
+    Attribute::new("in_degree", AttributeKind::Integer),
+    Attribute::new("out_degree", AttributeKind::Integer),
+];
+
+/// Values that can be used in the edge filter along with the attributes of the edges,
+/// so that the edges can be selected by their weight even if it is not exported
+const EDGE_FILTER_EXTRAS: &[Attribute] = &[Attribute::new("weight", AttributeKind::Integer)];
+
+/// Index of the attribute called `name` among `attributes`
+fn attribute_index(attributes: &[Attribute], name: &str) -> Option<usize> {
+    attributes
+        .iter()
+        .position(|attribute| attribute.name == name)
+}
+
+/// Kind of the value called `name` in a filter: an attribute, or one of the `extras` computed for the filter
+fn filter_kind(
+    attributes: &[Attribute],
+    extras: &[Attribute],
+    name: &str,
+) -> Option<AttributeKind> {
+    attributes
+        .iter()
+        .chain(extras)
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.kind)
+}
+
+/// Url that `url` redirects to, following the chain of redirects, or `url` itself if it is not a redirect
+pub fn resolve_alias<'a>(aliases: &'a HashMap<String, String>, url: &'a str) -> &'a str {
+    let mut resolved = url;
+    // A cycle of redirects stops after going around it once
+    for _ in 0..aliases.len() {
+        match aliases.get(resolved) {
+            Some(target) if target != url => resolved = target,
+            _ => break,
+        }
+    }
+    resolved
+}
+
+/// Moves the values recorded for the links of the page `from`, such as their positions, to the page `into`,
+/// which it is merged into. The values that `into` already has for the same links are kept
+fn merge_edge_values<T>(values: &mut HashMap<(NodeId, NodeId), T>, from: NodeId, into: NodeId) {
+    let moved: Vec<_> = values
+        .keys()
+        .filter(|(source, dest)| *source == from || *dest == from)
+        .copied()
+        .collect();
+    for edge in moved {
+        let value = values.remove(&edge).expect("The edge was just found");
+        let rename = |id| if id == from { into } else { id };
+        values
+            .entry((rename(edge.0), rename(edge.1)))
+            .or_insert(value);
+    }
+}
+
+/// Path of the file the links to the external pages are written to when they are kept apart from the graph
+pub fn external_edges_path(output_file: &str, options: &OutputOptions) -> String {
+    let extension = if options.delimiter == b'\t' {
+        "tsv"
+    } else {
+        "csv"
+    };
+    format!(
+        "{output_file}_external_edges.{extension}{}",
+        options.compression.extension()
+    )
+}
+
+/// Output prefix of the `index`-th window of the graph, counting from 1
+pub fn window_prefix(output_file: &str, index: u64) -> String {
+    format!("{output_file}_window_{index}")
+}
+
+/// Output prefix of the subgraph of a keyword. Characters that are not allowed in file names are replaced by `_`
+pub fn keyword_prefix(output_file: &str, keyword: &str) -> String {
+    let keyword = keyword
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("{output_file}_{keyword}")
+}
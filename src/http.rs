@@ -0,0 +1,663 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, ETAG, IF_MODIFIED_SINCE,
+        IF_NONE_MATCH, LAST_MODIFIED, PROXY_AUTHORIZATION, RETRY_AFTER, SET_COOKIE,
+    },
+    RequestBuilder, Response, StatusCode,
+};
+
+use crate::{
+    blocks::{self, BlockedHosts},
+    cache::{CachedResponse, ResponseCache, Validators},
+    config::{Auth, ScraperConfig},
+    errors::ScraperError,
+    rate_limit::{AdaptiveRateLimiter, BandwidthLimiter, HostTokenBuckets, TokenBucket},
+    worker,
+};
+
+/// User-Agent sent when none is configured
+pub const DEFAULT_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " (https://github.com/fedfontana/graphgen)"
+);
+
+/// Http client shared by all of the workers
+#[derive(Debug)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    /// Client used to check the external links, without the configured headers, so that cookies and credentials
+    /// for the wiki are not sent to other sites
+    link_checker: reqwest::Client,
+    auth: Option<Auth>,
+    session: Mutex<Session>,
+    token_bucket: Option<TokenBucket>,
+    /// Limits the requests to each external host, to check or fetch the external links
+    external_token_buckets: Option<HostTokenBuckets>,
+    rate_limiter: Option<AdaptiveRateLimiter>,
+    bandwidth_limiter: Option<BandwidthLimiter>,
+    bandwidth: Arc<BandwidthStats>,
+    requests: Arc<RequestStats>,
+    blocked: Arc<BlockedHosts>,
+    cache: Option<ResponseCache>,
+    /// Whether cached responses are checked with the server before being used
+    revalidate: bool,
+    /// Age after which the cached responses that are used are refreshed in the background
+    stale_after: Option<Duration>,
+    /// Stale cached responses that have been used, by url, waiting to be refreshed
+    stale: Mutex<Vec<(String, CachedResponse)>>,
+}
+
+/// Reads the body of `response` chunk by chunk, each chunk waiting for the ones downloaded before it to fit in the bandwidth,
+/// so that a large page is not downloaded at full speed. The body is read as UTF-8, as the pages of the wikis are
+async fn read_throttled(
+    mut response: reqwest::Response,
+    bandwidth_limiter: &BandwidthLimiter,
+) -> Result<String, reqwest::Error> {
+    let mut body = Vec::new();
+    loop {
+        bandwidth_limiter.acquire().await;
+        let Some(chunk) = response.chunk().await? else {
+            break;
+        };
+        bandwidth_limiter.record(chunk.len());
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Bytes of the bodies of the responses downloaded from each host, once decompressed. Cached responses are not counted
+#[derive(Debug, Default)]
+pub struct BandwidthStats {
+    bytes: Mutex<BTreeMap<String, u64>>,
+}
+
+impl BandwidthStats {
+    fn add(&self, url: &str, bytes: usize) {
+        let host = worker::get_host(url).unwrap_or_default();
+        *self.bytes.lock().unwrap().entry(host).or_default() += bytes as u64;
+    }
+
+    /// Bytes downloaded from each host
+    pub fn by_host(&self) -> BTreeMap<String, u64> {
+        self.bytes.lock().unwrap().clone()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.bytes.lock().unwrap().values().sum()
+    }
+}
+
+/// Requests sent to a host, to check that the crawl has been polite with it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HostRequests {
+    pub requests: u64,
+    /// Requests that got no response, or a response with a 4xx or 5xx status
+    pub errors: u64,
+    /// Time spent waiting for the rate limits and the blocks of the host before sending the requests
+    pub delay: Duration,
+}
+
+impl HostRequests {
+    /// Fraction of the requests that failed
+    pub fn error_rate(&self) -> f64 {
+        if self.requests == 0 {
+            return 0.0;
+        }
+        self.errors as f64 / self.requests as f64
+    }
+
+    /// Average time waited before sending a request
+    pub fn mean_delay(&self) -> Duration {
+        if self.requests == 0 {
+            return Duration::ZERO;
+        }
+        self.delay / self.requests as u32
+    }
+}
+
+/// Requests sent to each host, including the ones to check the external links. Cached responses are not counted
+#[derive(Debug, Default)]
+pub struct RequestStats {
+    hosts: Mutex<BTreeMap<String, HostRequests>>,
+}
+
+impl RequestStats {
+    fn add(&self, url: &str, delay: Duration, status: Option<StatusCode>) {
+        let host = worker::get_host(url).unwrap_or_default();
+        let mut hosts = self.hosts.lock().unwrap();
+        let requests = hosts.entry(host).or_default();
+        requests.requests += 1;
+        if !status.is_some_and(|status| status.is_success() || status.is_redirection()) {
+            requests.errors += 1;
+        }
+        requests.delay += delay;
+    }
+
+    /// Requests sent to each host
+    pub fn by_host(&self) -> BTreeMap<String, HostRequests> {
+        self.hosts.lock().unwrap().clone()
+    }
+}
+
+/// Cookies set by the wiki while logging in, which are sent back with every request to its host
+#[derive(Debug, Default)]
+struct Session {
+    host: Option<String>,
+    cookies: Vec<(String, String)>,
+}
+
+impl Session {
+    /// Value of the Cookie header of a request to `url`, if it is sent to the host of the session
+    fn cookie_header(&self, url: &str) -> Option<HeaderValue> {
+        if self.cookies.is_empty() || self.host.is_none() || worker::get_host(url) != self.host {
+            return None;
+        }
+        let cookies = self
+            .cookies
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let mut value = HeaderValue::from_str(&cookies).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+
+    /// Keeps the cookies set by a response, replacing the ones with the same name.
+    /// Their attributes are ignored, since the session only lasts for the crawl
+    fn store(&mut self, response: &Response) {
+        let cookies = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| value.split(';').next()?.split_once('='));
+        for (name, value) in cookies {
+            let (name, value) = (name.trim().to_owned(), value.trim().to_owned());
+            match self.cookies.iter_mut().find(|(other, _)| *other == name) {
+                Some(cookie) => cookie.1 = value,
+                None => self.cookies.push((name, value)),
+            }
+        }
+    }
+}
+
+/// Outcome of checking an external link
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkCheck {
+    /// Status of the final response, after following the redirects. `None` if no response has been received
+    pub status: Option<u16>,
+    /// Url of the final response, after following the redirects
+    pub final_url: Option<String>,
+    /// Title of the page, if it has been fetched and is an HTML page
+    pub title: Option<String>,
+}
+
+/// Time after which the check of an external link is given up
+const LINK_CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Outcome of a request
+enum Fetched {
+    Body(String, Validators),
+    /// The cached response is still fresh
+    NotModified,
+}
+
+impl HttpClient {
+    pub fn new(config: &ScraperConfig) -> HttpClient {
+        let builder = || {
+            // Without an explicit proxy, reqwest uses the one in the HTTP_PROXY, HTTPS_PROXY and ALL_PROXY environment variables
+            // The responses compressed with gzip or brotli are decompressed as they are read
+            let mut builder = reqwest::Client::builder()
+                .user_agent(&config.user_agent)
+                .gzip(true)
+                .brotli(true);
+            if let Some(proxy) = &config.proxy {
+                builder = builder.proxy(
+                    reqwest::Proxy::all(proxy).expect("The proxy url should have been validated"),
+                );
+            }
+            builder
+        };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .expect("The headers should have been validated");
+            let mut value =
+                HeaderValue::from_str(value).expect("The headers should have been validated");
+            // Keeps credentials out of the debug output of the requests
+            value.set_sensitive(matches!(name, COOKIE | AUTHORIZATION | PROXY_AUTHORIZATION));
+            headers.append(name, value);
+        }
+
+        // Like reqwest::Client::new, this only fails if the TLS backend cannot be initialized
+        let client = builder()
+            .default_headers(headers)
+            .build()
+            .expect("The http client should be buildable");
+        let link_checker = builder()
+            .build()
+            .expect("The http client should be buildable");
+        HttpClient {
+            client,
+            link_checker,
+            auth: config.auth.clone(),
+            session: Mutex::default(),
+            token_bucket: config.rate_limit.as_ref().map(|rate_limit| {
+                TokenBucket::new(rate_limit.requests_per_second, rate_limit.burst)
+            }),
+            external_token_buckets: config.external_rate_limit.as_ref().map(|rate_limit| {
+                HostTokenBuckets::new(rate_limit.requests_per_second, rate_limit.burst)
+            }),
+            rate_limiter: config
+                .adaptive_rate_limit
+                .clone()
+                .map(AdaptiveRateLimiter::new),
+            bandwidth_limiter: config.max_bandwidth.map(BandwidthLimiter::new),
+            bandwidth: Default::default(),
+            requests: Default::default(),
+            blocked: Arc::new(BlockedHosts::new(config.retry.clone())),
+            cache: config.cache_dir.clone().map(ResponseCache::new),
+            revalidate: config.revalidate_cache,
+            stale_after: config.stale_while_revalidate,
+            stale: Mutex::default(),
+        }
+    }
+
+    /// Fetches `url` and returns the body of the response, reading it from the cache if it is there.
+    /// When revalidating, a cached response is only used once the server confirms that it has not changed.
+    /// A stale cached response is used right away, and kept to be refreshed later with [`HttpClient::refresh`].
+    pub async fn get_text(&self, url: &str) -> Result<String, ScraperError> {
+        let Some(cache) = &self.cache else {
+            return match self.fetch_text(url, None).await? {
+                Fetched::Body(body, _) => Ok(body),
+                Fetched::NotModified => unreachable!("Only conditional requests get 304 responses"),
+            };
+        };
+
+        let cached = cache.get(url).await;
+        if let Some(cached) = &cached {
+            if let Some(stale_after) = self.stale_after {
+                if cached.is_older_than(stale_after) {
+                    self.stale
+                        .lock()
+                        .unwrap()
+                        .push((url.to_owned(), cached.clone()));
+                }
+                return Ok(cached.body.clone());
+            }
+            if !self.revalidate {
+                return Ok(cached.body.clone());
+            }
+        }
+        let validators = cached
+            .as_ref()
+            .map(|cached| &cached.validators)
+            .filter(|validators| !validators.is_empty());
+        match self.fetch_text(url, validators).await? {
+            Fetched::Body(body, validators) => {
+                cache.put(url, &body, &validators).await;
+                Ok(body)
+            }
+            Fetched::NotModified => Ok(cached
+                .expect("Only conditional requests get 304 responses")
+                .body),
+        }
+    }
+
+    /// Takes the stale cached responses that have been used since the last call, by url
+    pub fn take_stale(&self) -> Vec<(String, CachedResponse)> {
+        std::mem::take(&mut *self.stale.lock().unwrap())
+    }
+
+    /// Fetches `url` again to refresh its stale cached response, with a conditional request if the response has validators.
+    /// Failures are only logged, since the cached response has already been used
+    pub async fn refresh(&self, url: &str, cached: CachedResponse) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        let validators = Some(&cached.validators).filter(|validators| !validators.is_empty());
+        match self.fetch_text(url, validators).await {
+            Ok(Fetched::Body(body, validators)) => cache.put(url, &body, &validators).await,
+            // Storing the same response again makes it fresh
+            Ok(Fetched::NotModified) => cache.put(url, &cached.body, &cached.validators).await,
+            Err(err) => eprintln!("[WARN] Could not refresh the cached response of {url}: {err}"),
+        }
+    }
+
+    /// Bytes downloaded so far
+    pub fn bandwidth(&self) -> Arc<BandwidthStats> {
+        self.bandwidth.clone()
+    }
+
+    /// Requests sent so far
+    pub fn requests(&self) -> Arc<RequestStats> {
+        self.requests.clone()
+    }
+
+    /// Hosts that have answered with block pages so far
+    pub fn blocked(&self) -> Arc<BlockedHosts> {
+        self.blocked.clone()
+    }
+
+    /// Starts a new session with the host of `url`, whose cookies are sent with the requests to that host only
+    pub fn start_session(&self, url: &str) {
+        *self.session.lock().unwrap() = Session {
+            host: worker::get_host(url),
+            cookies: Vec::new(),
+        };
+    }
+
+    /// Sends a request of a login to `url`, along with the cookies of the session, and keeps the cookies set by the response.
+    /// The request is a POST of `form` if it is given, and a GET otherwise. It is neither rate limited nor cached
+    pub async fn session_request(
+        &self,
+        url: &str,
+        form: Option<&[(&str, &str)]>,
+    ) -> Result<String, ScraperError> {
+        let request = match form {
+            Some(form) => self.client.post(url).form(form),
+            None => self.client.get(url),
+        };
+        let result = self.authenticate(request, url).send().await;
+        self.requests.add(url, Duration::ZERO, status_of(&result));
+        let response = result?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(ScraperError::HttpStatus {
+                url: url.to_owned(),
+                status,
+                retry_after: retry_after(&response),
+            });
+        }
+        self.session.lock().unwrap().store(&response);
+        let body = response.text().await?;
+        self.bandwidth.add(url, body.len());
+        Ok(body)
+    }
+
+    /// Adds the credentials and the cookies of the session to a request to the wiki
+    fn authenticate(&self, mut request: RequestBuilder, url: &str) -> RequestBuilder {
+        request = match &self.auth {
+            Some(Auth::Basic { user, password }) => request.basic_auth(user, Some(password)),
+            Some(Auth::Bearer(token)) => request.bearer_auth(token),
+            None => request,
+        };
+        match self.session.lock().unwrap().cookie_header(url) {
+            Some(cookie) => request.header(COOKIE, cookie),
+            None => request,
+        }
+    }
+
+    /// Checks whether an external link works by sending it a HEAD request, following its redirects.
+    /// Servers that do not support HEAD requests are sent a GET request instead, whose body is not downloaded.
+    /// External links are on other hosts, so the requests are only subject to the rate limit of the external hosts.
+    pub async fn check_link(&self, url: &str) -> LinkCheck {
+        let delay = self.wait_for_external(url).await;
+        let mut result = self
+            .link_checker
+            .head(url)
+            .timeout(LINK_CHECK_TIMEOUT)
+            .send()
+            .await;
+        self.requests.add(url, delay, status_of(&result));
+        let unsupported = |status| {
+            matches!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+            )
+        };
+        if result
+            .as_ref()
+            .is_ok_and(|response| unsupported(response.status()))
+        {
+            let delay = self.wait_for_external(url).await;
+            result = self
+                .link_checker
+                .get(url)
+                .timeout(LINK_CHECK_TIMEOUT)
+                .send()
+                .await;
+            self.requests.add(url, delay, status_of(&result));
+        }
+
+        match result {
+            // The challenge only tells that the site does not want crawlers, not whether the link works
+            Ok(response) if blocks::is_cloudflare_challenge(response.headers()) => {
+                self.blocked
+                    .record(url, blocks::BlockReason::CloudflareChallenge);
+                LinkCheck::default()
+            }
+            Ok(response) => LinkCheck {
+                status: Some(response.status().as_u16()),
+                final_url: Some(response.url().to_string()),
+                title: None,
+            },
+            Err(err) => {
+                eprintln!("[WARN] Could not check the link {url}: {err}");
+                LinkCheck::default()
+            }
+        }
+    }
+
+    /// Fetches an external page, following its redirects, to record the final response along with the title of the page.
+    /// Only the HTML pages are downloaded. As for the checks, the requests are only subject to the rate limit of the external hosts
+    pub async fn fetch_link(&self, url: &str) -> LinkCheck {
+        let delay = self.wait_for_external(url).await;
+        let result = self
+            .link_checker
+            .get(url)
+            .timeout(LINK_CHECK_TIMEOUT)
+            .send()
+            .await;
+        self.requests.add(url, delay, status_of(&result));
+        let response = match result {
+            Ok(response) if blocks::is_cloudflare_challenge(response.headers()) => {
+                self.blocked
+                    .record(url, blocks::BlockReason::CloudflareChallenge);
+                return LinkCheck::default();
+            }
+            Ok(response) => response,
+            Err(err) => {
+                eprintln!("[WARN] Could not fetch the link {url}: {err}");
+                return LinkCheck::default();
+            }
+        };
+
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("text/html"));
+        let mut check = LinkCheck {
+            status: Some(response.status().as_u16()),
+            final_url: Some(response.url().to_string()),
+            title: None,
+        };
+        if is_html {
+            match response.text().await {
+                Ok(body) => {
+                    self.bandwidth.add(url, body.len());
+                    check.title = worker::get_page_title(&body);
+                }
+                Err(err) => eprintln!("[WARN] Could not read the page {url}: {err}"),
+            }
+        }
+        check
+    }
+
+    /// Waits until a request can be sent to the external page at `url`, returning the time waited
+    async fn wait_for_external(&self, url: &str) -> Duration {
+        let waiting = Instant::now();
+        if let Some(buckets) = &self.external_token_buckets {
+            buckets
+                .acquire(&worker::get_host(url).unwrap_or_default())
+                .await;
+        }
+        waiting.elapsed()
+    }
+
+    /// Sends a request for `url`, which is conditional if `validators` are given, and returns the body of the response
+    async fn fetch_text(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+    ) -> Result<Fetched, ScraperError> {
+        let waiting = Instant::now();
+        self.blocked.wait(url).await;
+        if let Some(token_bucket) = &self.token_bucket {
+            token_bucket.acquire().await;
+        }
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        if let Some(bandwidth_limiter) = &self.bandwidth_limiter {
+            bandwidth_limiter.acquire().await;
+        }
+
+        let mut request = self.authenticate(self.client.get(url), url);
+        if let Some(validators) = validators {
+            if let Some(etag) = &validators.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &validators.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let start = Instant::now();
+        let delay = start - waiting;
+        let result = request.send().await;
+        self.requests.add(url, delay, status_of(&result));
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.record(start.elapsed(), status_of(&result));
+        }
+
+        let response = result?;
+        let status = response.status();
+        let throttled = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if throttled && !blocks::is_cloudflare_challenge(response.headers()) {
+            return Err(ScraperError::HttpStatus {
+                url: url.to_owned(),
+                status,
+                retry_after: retry_after(&response),
+            });
+        }
+
+        if validators.is_some() && status == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+
+        let validators = response_validators(&response);
+        let headers = response.headers().clone();
+        let body = match &self.bandwidth_limiter {
+            Some(bandwidth_limiter) => read_throttled(response, bandwidth_limiter).await?,
+            None => response.text().await?,
+        };
+        self.bandwidth.add(url, body.len());
+
+        // A block page is not the page that has been requested, so it is neither cached nor scraped
+        if let Some(reason) = blocks::detect(status, &headers, &body) {
+            let (host, retry_after) = self.blocked.record(url, reason);
+            return Err(ScraperError::Blocked {
+                url: url.to_owned(),
+                host,
+                reason,
+                retry_after,
+            });
+        }
+        self.blocked.record_success(url);
+        Ok(Fetched::Body(body, validators))
+    }
+}
+
+/// Status of the response to a request, if one has been received
+fn status_of(result: &Result<Response, reqwest::Error>) -> Option<StatusCode> {
+    result.as_ref().ok().map(Response::status)
+}
+
+/// Reads the validators of a response from its ETag and Last-Modified headers
+fn response_validators(response: &Response) -> Validators {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    }
+}
+
+/// Checks a proxy url given on the command line, e.g. `http://proxy.example.com:8080` or `socks5://127.0.0.1:1080`
+pub fn parse_proxy(proxy: &str) -> Result<String, String> {
+    if cfg!(not(feature = "socks")) && proxy.starts_with("socks") {
+        return Err(format!(
+            "Invalid proxy url `{proxy}`: socks proxies require the `socks` feature"
+        ));
+    }
+    reqwest::Proxy::all(proxy)
+        .map(|_| proxy.to_owned())
+        .map_err(|err| format!("Invalid proxy url `{proxy}`: {err}"))
+}
+
+/// Parses a header given on the command line as `Name: value`, e.g. `Accept-Language: de`
+pub fn parse_header(header: &str) -> Result<(String, String), String> {
+    let Some((name, value)) = header.split_once(':') else {
+        return Err(format!(
+            "Expected a header in the form `Name: value`, found `{header}`"
+        ));
+    };
+    let (name, value) = (name.trim(), value.trim());
+    check_header(name, value)?;
+    Ok((name.to_owned(), value.to_owned()))
+}
+
+/// Checks that a header can be sent, i.e. that its name and value only contain valid characters
+pub fn check_header(name: &str, value: &str) -> Result<(), String> {
+    HeaderName::from_bytes(name.as_bytes())
+        .map_err(|err| format!("Invalid header name `{name}`: {err}"))?;
+    HeaderValue::from_str(value)
+        .map_err(|err| format!("Invalid value of the header `{name}`: {err}"))?;
+    Ok(())
+}
+
+/// Formats a number of bytes with a binary unit, e.g. `1.5 MiB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Parses the Retry-After header of a response, which is either a number of seconds or an http date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
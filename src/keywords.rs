@@ -0,0 +1,313 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, RwLock},
+    thread,
+    time::Duration,
+};
+
+use regex::{Regex, RegexBuilder};
+
+use crate::config::KeywordMode;
+
+/// Keywords a page has to contain in order to be scraped.
+///
+/// The keywords can be replaced while the crawl is running, and the number of pages accepted and rejected
+/// at each depth is tracked so that the effect of the keywords can be monitored.
+#[derive(Debug, Default)]
+pub struct KeywordFilter {
+    mode: KeywordMode,
+    /// Keywords that reject the pages containing them, which are kept when the keywords are replaced
+    excluded: Vec<String>,
+    /// Number of hops from the seeds from which the pages have to contain the keywords
+    from_hop: u64,
+    /// Only the excluded keywords, checked against the pages closer to the seeds than `from_hop`
+    relaxed: Option<Arc<Keywords>>,
+    keywords: RwLock<Option<Arc<Keywords>>>,
+    counts: Mutex<BTreeMap<u64, KeywordCounts>>,
+}
+
+/// Keywords checked against the content of the pages with a matching mode,
+/// along with the excluded keywords rejecting the pages that contain any of them
+#[derive(Debug)]
+pub struct Keywords {
+    /// Keywords a page has to contain as requested by the mode. No keywords accept every page that is not excluded
+    words: Vec<String>,
+    mode: KeywordMode,
+    /// The keywords compiled as regular expressions, in the same order. Only used in the regex mode
+    regexes: Vec<Regex>,
+    excluded: Vec<String>,
+    /// The excluded keywords compiled as regular expressions. Only used in the regex mode
+    excluded_regexes: Vec<Regex>,
+}
+
+impl Keywords {
+    /// Fails if the mode is regex and one of the keywords is not a valid regular expression
+    pub fn new(words: Vec<String>, mode: KeywordMode) -> Result<Keywords, String> {
+        Ok(Keywords {
+            regexes: compile_all(&words, mode)?,
+            words,
+            mode,
+            excluded: Vec::new(),
+            excluded_regexes: Vec::new(),
+        })
+    }
+
+    /// Rejects the pages containing any of the `excluded` keywords, which are regular expressions in the regex mode.
+    /// Fails if the mode is regex and one of them is not a valid regular expression
+    pub fn excluding(self, excluded: Vec<String>) -> Result<Keywords, String> {
+        Ok(Keywords {
+            excluded_regexes: compile_all(&excluded, self.mode)?,
+            excluded,
+            ..self
+        })
+    }
+
+    /// Keywords as they have been given
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Keywords rejecting the pages that contain any of them
+    pub fn excluded(&self) -> &[String] {
+        &self.excluded
+    }
+
+    /// Whether `content` is accepted by the keywords, and does not contain any of the excluded ones
+    pub fn matches(&self, content: &str) -> bool {
+        (self.words.is_empty() || self.contains_words(content)) && !self.is_excluded(content)
+    }
+
+    fn contains_words(&self, content: &str) -> bool {
+        match self.mode {
+            KeywordMode::Any => {
+                let lower_content = content.to_lowercase();
+                self.words
+                    .iter()
+                    .any(|word| lower_content.contains(word.to_lowercase().as_str()))
+            }
+            KeywordMode::All => {
+                let lower_content = content.to_lowercase();
+                self.words
+                    .iter()
+                    .all(|word| lower_content.contains(word.to_lowercase().as_str()))
+            }
+            KeywordMode::Regex => self.regexes.iter().any(|regex| regex.is_match(content)),
+        }
+    }
+
+    fn is_excluded(&self, content: &str) -> bool {
+        if self.excluded.is_empty() {
+            return false;
+        }
+        match self.mode {
+            KeywordMode::Any | KeywordMode::All => {
+                let lower_content = content.to_lowercase();
+                self.excluded
+                    .iter()
+                    .any(|word| lower_content.contains(word.to_lowercase().as_str()))
+            }
+            KeywordMode::Regex => self
+                .excluded_regexes
+                .iter()
+                .any(|regex| regex.is_match(content)),
+        }
+    }
+
+    /// Keywords found in `content`
+    pub fn matching(&self, content: &str) -> Vec<String> {
+        match self.mode {
+            KeywordMode::Any | KeywordMode::All => {
+                let lower_content = content.to_lowercase();
+                self.words
+                    .iter()
+                    .filter(|word| lower_content.contains(word.to_lowercase().as_str()))
+                    .cloned()
+                    .collect()
+            }
+            KeywordMode::Regex => self
+                .words
+                .iter()
+                .zip(&self.regexes)
+                .filter(|(_, regex)| regex.is_match(content))
+                .map(|(word, _)| word.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Compiles the keywords if the mode is regex
+fn compile_all(words: &[String], mode: KeywordMode) -> Result<Vec<Regex>, String> {
+    match mode {
+        KeywordMode::Regex => words.iter().map(|word| compile(word)).collect(),
+        KeywordMode::Any | KeywordMode::All => Ok(Vec::new()),
+    }
+}
+
+/// Compiles a keyword of the regex mode, ignoring case as the other modes do
+fn compile(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| format!("Invalid regular expression `{pattern}`: {err}"))
+}
+
+/// Number of pages accepted and rejected by the keyword filter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeywordCounts {
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+impl KeywordCounts {
+    /// Fraction of the pages that have been accepted
+    pub fn acceptance_ratio(&self) -> f64 {
+        let total = self.accepted + self.rejected;
+        if total == 0 {
+            return 0.0;
+        }
+        self.accepted as f64 / total as f64
+    }
+}
+
+impl KeywordFilter {
+    /// Fails if the mode is regex and one of the keywords or of the excluded ones is not a valid regular expression
+    /// The pages found less than `from_hop` hops away from the seeds are accepted whether or not they contain the keywords,
+    /// as long as they do not contain any of the excluded ones
+    pub fn new(
+        keywords: Option<Vec<String>>,
+        excluded: Option<Vec<String>>,
+        mode: KeywordMode,
+        from_hop: u64,
+    ) -> Result<KeywordFilter, String> {
+        let excluded = excluded.unwrap_or_default();
+        let relaxed = if excluded.is_empty() {
+            None
+        } else {
+            let keywords = Keywords::new(Vec::new(), mode)?.excluding(excluded.clone())?;
+            Some(Arc::new(keywords))
+        };
+        let filter = KeywordFilter {
+            mode,
+            excluded,
+            from_hop,
+            relaxed,
+            ..Default::default()
+        };
+        filter.set_keywords(keywords)?;
+        Ok(filter)
+    }
+
+    /// Keywords currently in use. `None` means that every page is accepted, since there are neither keywords nor excluded ones
+    pub fn keywords(&self) -> Option<Arc<Keywords>> {
+        self.keywords.read().unwrap().clone()
+    }
+
+    /// Keywords checked against the pages found `hop` hops away from the seeds
+    pub fn keywords_at(&self, hop: u64) -> Option<Arc<Keywords>> {
+        if hop < self.from_hop {
+            return self.relaxed.clone();
+        }
+        self.keywords()
+    }
+
+    /// Replaces the keywords, keeping the matching mode and the excluded keywords. Pages already scraped are not checked again.
+    /// The keywords are left unchanged if they are not valid in the matching mode
+    pub fn set_keywords(&self, keywords: Option<Vec<String>>) -> Result<(), String> {
+        let keywords = if keywords.is_none() && self.excluded.is_empty() {
+            None
+        } else {
+            let keywords = Keywords::new(keywords.unwrap_or_default(), self.mode)?;
+            Some(Arc::new(keywords.excluding(self.excluded.clone())?))
+        };
+        *self.keywords.write().unwrap() = keywords;
+        Ok(())
+    }
+
+    /// Records whether a page found with the given remaining depth has been accepted
+    pub fn record(&self, depth: u64, accepted: bool) {
+        let mut counts = self.counts.lock().unwrap();
+        let counts = counts.entry(depth).or_default();
+        if accepted {
+            counts.accepted += 1;
+        } else {
+            counts.rejected += 1;
+        }
+    }
+
+    /// Number of pages accepted and rejected so far, by remaining depth
+    pub fn counts(&self) -> BTreeMap<u64, KeywordCounts> {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+/// Logs the acceptance ratio of the keyword filter at each depth every `interval`
+pub fn report_periodically(filter: Arc<KeywordFilter>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        for (depth, counts) in filter.counts() {
+            eprintln!(
+                "[INFO] Keywords at depth {depth}: {} accepted, {} rejected ({:.1}% accepted)",
+                counts.accepted,
+                counts.rejected,
+                counts.acceptance_ratio() * 100.0
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn keyword_modes() {
+        let content = "The Rust programming language was designed by Graydon Hoare";
+
+        let any = Keywords::new(words(&["python", "RUST"]), KeywordMode::Any).unwrap();
+        assert!(any.matches(content));
+        assert_eq!(any.matching(content), words(&["RUST"]));
+
+        let all = Keywords::new(words(&["python", "rust"]), KeywordMode::All).unwrap();
+        assert!(!all.matches(content));
+        let all = Keywords::new(words(&["hoare", "rust"]), KeywordMode::All).unwrap();
+        assert!(all.matches(content));
+
+        let regex =
+            Keywords::new(words(&[r"\bgraydon\b", r"^python"]), KeywordMode::Regex).unwrap();
+        assert!(regex.matches(content));
+        assert_eq!(regex.matching(content), words(&[r"\bgraydon\b"]));
+
+        assert!(Keywords::new(words(&["(unclosed"]), KeywordMode::Regex).is_err());
+        assert!(Keywords::new(words(&["(unclosed"]), KeywordMode::Any).is_ok());
+    }
+
+    #[test]
+    fn excluded_keywords() {
+        let content = "The Rust programming language was designed by Graydon Hoare";
+
+        let keywords = Keywords::new(words(&["rust"]), KeywordMode::Any)
+            .unwrap()
+            .excluding(words(&["football", "HOARE"]))
+            .unwrap();
+        assert!(!keywords.matches(content));
+        assert!(keywords.matches("Rust is a programming language"));
+
+        let excluded_only = Keywords::new(Vec::new(), KeywordMode::Any)
+            .unwrap()
+            .excluding(words(&["football"]))
+            .unwrap();
+        assert!(excluded_only.matches(content));
+        assert!(!excluded_only.matches("A football club"));
+
+        let regex = Keywords::new(Vec::new(), KeywordMode::Regex)
+            .unwrap()
+            .excluding(words(&[r"\bfoot\w+"]))
+            .unwrap();
+        assert!(regex.matches(content));
+        assert!(!regex.matches("The footballers of the club"));
+    }
+}
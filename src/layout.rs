@@ -0,0 +1,224 @@
+use std::{collections::HashMap, io};
+
+use crate::config::LayoutAlgorithm;
+use crate::edge_store::EdgeStore;
+use crate::node_id::NodeId;
+use crate::output::AttributeValue;
+
+/// Above this many pages the layout is not computed, since every iteration compares each pair of pages
+pub const MAX_LAYOUT_NODES: usize = 20_000;
+
+/// Ideal distance between two linked pages in the Fruchterman-Reingold layout
+const SPRING_LENGTH: f64 = 100.0;
+
+/// Strength of the repulsion and of the gravity in the ForceAtlas2 layout
+const REPULSION: f64 = 10.0;
+const GRAVITY: f64 = 1.0;
+
+/// Coordinates of the pages of a graph, to open it in Gephi or Cosmograph already laid out
+#[derive(Debug, Default)]
+pub struct Layout {
+    positions: HashMap<NodeId, (f64, f64)>,
+}
+
+impl Layout {
+    /// Lays out `nodes` with `algorithm`, considering only the links between them.
+    /// The starting positions only depend on the ids, so the same graph always gets the same layout.
+    /// Graphs with more than [`MAX_LAYOUT_NODES`] pages are not laid out, and every page is left at the origin
+    pub fn compute(
+        algorithm: LayoutAlgorithm,
+        links: &mut EdgeStore,
+        nodes: &[NodeId],
+        iterations: usize,
+    ) -> io::Result<Layout> {
+        if nodes.len() > MAX_LAYOUT_NODES {
+            eprintln!(
+                "[WARN] The graph has {} pages, more than the {MAX_LAYOUT_NODES} that can be laid out. Skipping the layout",
+                nodes.len()
+            );
+            return Ok(Layout::default());
+        }
+
+        let index: HashMap<NodeId, usize> =
+            nodes.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut edges = Vec::new();
+        for edge in links.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            if let (Some(&source), Some(&dest)) = (index.get(&source), index.get(&dest)) {
+                if source != dest {
+                    edges.push((source, dest));
+                }
+            }
+        }
+
+        let side = (nodes.len() as f64).sqrt() * SPRING_LENGTH;
+        let mut positions: Vec<_> = nodes
+            .iter()
+            .map(|id| starting_position(*id, side))
+            .collect();
+        match algorithm {
+            LayoutAlgorithm::FruchtermanReingold => {
+                fruchterman_reingold(&mut positions, &edges, side, iterations)
+            }
+            LayoutAlgorithm::ForceAtlas2 => force_atlas2(&mut positions, &edges, side, iterations),
+        }
+
+        Ok(Layout {
+            positions: nodes.iter().copied().zip(positions).collect(),
+        })
+    }
+
+    /// Values of the `x` and `y` attributes of a page
+    pub fn attributes(&self, id: &NodeId) -> [AttributeValue; 2] {
+        let (x, y) = self.positions.get(id).copied().unwrap_or_default();
+        [AttributeValue::Float(x), AttributeValue::Float(y)]
+    }
+}
+
+/// Pseudo-random position of a page in a square of side `side` centered on the origin, computed from its id
+fn starting_position(id: NodeId, side: f64) -> (f64, f64) {
+    // splitmix64
+    let mut z = id.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    let unit = |bits: u64| (bits as f64 / u32::MAX as f64) - 0.5;
+    (unit(z >> 32) * side, unit(z & 0xFFFF_FFFF) * side)
+}
+
+/// Vector from `b` to `a` and its length, which is never 0 so that overlapping pages are pushed apart
+fn delta(a: (f64, f64), b: (f64, f64)) -> (f64, f64, f64) {
+    let (dx, dy) = (a.0 - b.0, a.1 - b.1);
+    let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+    (dx, dy, distance)
+}
+
+/// Moves every page by its displacement, limited to `max_step`
+fn apply(positions: &mut [(f64, f64)], displacements: &[(f64, f64)], max_step: f64) {
+    for (position, (dx, dy)) in positions.iter_mut().zip(displacements) {
+        let length = (dx * dx + dy * dy).sqrt();
+        if length > 0.0 {
+            let step = length.min(max_step);
+            position.0 += dx / length * step;
+            position.1 += dy / length * step;
+        }
+    }
+}
+
+/// Fruchterman-Reingold: every pair of pages repel each other, linked pages attract each other,
+/// and the pages move less and less as the temperature cools down
+fn fruchterman_reingold(
+    positions: &mut [(f64, f64)],
+    edges: &[(usize, usize)],
+    side: f64,
+    iterations: usize,
+) {
+    let k = SPRING_LENGTH;
+    for iteration in 0..iterations {
+        let temperature = side / 10.0 * (1.0 - iteration as f64 / iterations as f64);
+        let mut displacements = vec![(0.0, 0.0); positions.len()];
+
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                let (dx, dy, distance) = delta(positions[i], positions[j]);
+                let force = k * k / distance;
+                displacements[i].0 += dx / distance * force;
+                displacements[i].1 += dy / distance * force;
+                displacements[j].0 -= dx / distance * force;
+                displacements[j].1 -= dy / distance * force;
+            }
+        }
+        for &(source, dest) in edges {
+            let (dx, dy, distance) = delta(positions[source], positions[dest]);
+            let force = distance * distance / k;
+            displacements[source].0 -= dx / distance * force;
+            displacements[source].1 -= dy / distance * force;
+            displacements[dest].0 += dx / distance * force;
+            displacements[dest].1 += dy / distance * force;
+        }
+
+        apply(positions, &displacements, temperature);
+    }
+}
+
+/// ForceAtlas2, without the Barnes-Hut approximation: pages repel each other proportionally to their degrees,
+/// linked pages attract each other linearly with their distance, and gravity keeps the disconnected pages close to the center
+fn force_atlas2(
+    positions: &mut [(f64, f64)],
+    edges: &[(usize, usize)],
+    side: f64,
+    iterations: usize,
+) {
+    let mut degrees = vec![0.0; positions.len()];
+    for &(source, dest) in edges {
+        degrees[source] += 1.0;
+        degrees[dest] += 1.0;
+    }
+
+    for iteration in 0..iterations {
+        let max_step = side / 10.0 * (1.0 - iteration as f64 / iterations as f64);
+        let mut displacements = vec![(0.0, 0.0); positions.len()];
+
+        for i in 0..positions.len() {
+            for j in i + 1..positions.len() {
+                let (dx, dy, distance) = delta(positions[i], positions[j]);
+                let force = REPULSION * (degrees[i] + 1.0) * (degrees[j] + 1.0) / distance;
+                displacements[i].0 += dx / distance * force;
+                displacements[i].1 += dy / distance * force;
+                displacements[j].0 -= dx / distance * force;
+                displacements[j].1 -= dy / distance * force;
+            }
+        }
+        for &(source, dest) in edges {
+            let (dx, dy, _) = delta(positions[source], positions[dest]);
+            displacements[source].0 -= dx;
+            displacements[source].1 -= dy;
+            displacements[dest].0 += dx;
+            displacements[dest].1 += dy;
+        }
+        for (i, position) in positions.iter().enumerate() {
+            let (x, y, distance) = delta(*position, (0.0, 0.0));
+            let force = GRAVITY * (degrees[i] + 1.0);
+            displacements[i].0 -= x / distance * force;
+            displacements[i].1 -= y / distance * force;
+        }
+
+        apply(positions, &displacements, max_step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(layout: &Layout, a: u64, b: u64) -> f64 {
+        let (a, b) = (
+            layout.positions[&NodeId::new(a)],
+            layout.positions[&NodeId::new(b)],
+        );
+        delta(a, b).2
+    }
+
+    #[test]
+    fn linked_pages_end_up_closer() {
+        // Two triangles linked by a single edge
+        let mut links = EdgeStore::default();
+        for (source, dest) in [(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3), (2, 3)] {
+            links
+                .insert((NodeId::new(source), NodeId::new(dest)))
+                .unwrap();
+        }
+        let nodes: Vec<_> = (0..6).map(NodeId::new).collect();
+
+        for algorithm in [
+            LayoutAlgorithm::FruchtermanReingold,
+            LayoutAlgorithm::ForceAtlas2,
+        ] {
+            let layout = Layout::compute(algorithm, &mut links, &nodes, 300).unwrap();
+            assert!(distance(&layout, 0, 1) < distance(&layout, 0, 4));
+            assert!(distance(&layout, 3, 4) < distance(&layout, 1, 5));
+            let again = Layout::compute(algorithm, &mut links, &nodes, 300).unwrap();
+            assert_eq!(layout.positions, again.positions);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Generate graph files by scraping the links between wikipedia pages.
+//!
+//! The graph is built by a [`scraper::WikipediaScraper`] and written by any [`output::OutputWriter`],
+//! so that new output formats can be plugged in without changing the scraper.
+
+pub mod alerts;
+pub mod blocks;
+pub mod bridge;
+pub mod cache;
+pub mod centrality;
+pub mod checkpoint;
+pub mod clustering;
+pub mod components;
+pub mod compression;
+pub mod config;
+pub mod control;
+pub mod edge_list;
+pub mod edge_store;
+pub mod errors;
+pub mod estimate;
+pub mod events;
+pub mod filter;
+pub mod gephi;
+pub mod graph;
+pub mod http;
+pub mod keywords;
+pub mod layout;
+pub mod links;
+pub mod mediawiki;
+pub mod node_id;
+pub mod output;
+pub mod pagerank;
+pub mod pipeline;
+pub mod rate_limit;
+pub mod scraper;
+pub mod simulate;
+pub mod snapshot;
+pub mod window;
+pub mod worker;
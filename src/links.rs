@@ -0,0 +1,474 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, CONTROLS};
+use reqwest::Url;
+
+/// Names of the namespaces of MediaWiki, with their ids. A title starting with one of them followed by `:`
+/// is a page of that namespace, e.g. `Category:Crocodiles`, while any other title with a colon is an article, e.g. `Star Wars: Andor`
+const NAMESPACES: &[(&str, i32)] = &[
+    ("Media", -2),
+    ("Special", -1),
+    ("Talk", 1),
+    ("User", 2),
+    ("User talk", 3),
+    ("Wikipedia", 4),
+    ("Wikipedia talk", 5),
+    ("File", 6),
+    ("File talk", 7),
+    ("MediaWiki", 8),
+    ("MediaWiki talk", 9),
+    ("Template", 10),
+    ("Template talk", 11),
+    ("Help", 12),
+    ("Help talk", 13),
+    ("Category", 14),
+    ("Category talk", 15),
+    ("Portal", 100),
+    ("Portal talk", 101),
+    ("Draft", 118),
+    ("Draft talk", 119),
+    ("TimedText", 710),
+    ("TimedText talk", 711),
+    ("Module", 828),
+    ("Module talk", 829),
+];
+
+/// Other names of the namespaces, with the ones they stand for
+const NAMESPACE_ALIASES: &[(&str, &str)] = &[
+    ("Image", "File"),
+    ("Image talk", "File talk"),
+    ("Project", "Wikipedia"),
+    ("Project talk", "Wikipedia talk"),
+    ("WP", "Wikipedia"),
+    ("WT", "Wikipedia talk"),
+];
+
+/// Characters escaped in the path of a normalized url, in addition to the non-ascii ones
+const PATH_ESCAPES: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Category of the target of a link, as found in the `href` attribute of an anchor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LinkKind {
+    /// An article of the same wiki, e.g. `/wiki/Crocodile`
+    Article,
+    /// A page of a namespace other than the articles one, e.g. `/wiki/Special:Random` or `/wiki/File:Crocodile.jpg`
+    Namespaced,
+    /// Any other path of the same wiki, e.g. `/w/index.php?title=Crocodile&action=edit`
+    NonArticle,
+    /// A section of the same page, e.g. `#History`
+    Fragment,
+    /// An absolute url, e.g. `https://example.com/`
+    External,
+    /// An url without scheme, e.g. `//upload.wikimedia.org/wikipedia/commons/crocodile.jpg`
+    ProtocolRelative,
+    /// An email address, e.g. `mailto:info@example.com`
+    Mailto,
+    /// A phone number, e.g. `tel:+39000000`
+    Tel,
+    /// A script run by the browser, e.g. `javascript:void(0)`
+    Javascript,
+    /// A path relative to the current page, or a scheme without authority other than the ones above, e.g. `news:comp.lang.rust`
+    Other,
+}
+
+impl LinkKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkKind::Article => "article",
+            LinkKind::Namespaced => "namespaced",
+            LinkKind::NonArticle => "non_article",
+            LinkKind::Fragment => "fragment",
+            LinkKind::External => "external",
+            LinkKind::ProtocolRelative => "protocol_relative",
+            LinkKind::Mailto => "mailto",
+            LinkKind::Tel => "tel",
+            LinkKind::Javascript => "javascript",
+            LinkKind::Other => "other",
+        }
+    }
+}
+
+/// Returns the scheme of `href` in lowercase, if it has one
+fn scheme(href: &str) -> Option<String> {
+    let (scheme, _) = href.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Returns the canonical name of the namespace called `name`, ignoring its case and using either spaces or underscores
+fn namespace_named(name: &str) -> Option<&'static str> {
+    let name = name.replace('_', " ");
+    let name = name.trim();
+    NAMESPACES
+        .iter()
+        .map(|(namespace, _)| (*namespace, *namespace))
+        .chain(NAMESPACE_ALIASES.iter().copied())
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map(|(_, namespace)| namespace)
+}
+
+/// Returns the namespace of the page with the title `title`, as written in its url, or `None` if it is an article
+pub fn namespace(title: &str) -> Option<&'static str> {
+    let title = percent_decode_str(title).decode_utf8_lossy();
+    let (prefix, _) = title.split_once(':')?;
+    namespace_named(prefix)
+}
+
+/// Id of a namespace in the MediaWiki API
+pub fn namespace_id(namespace: &str) -> Option<i32> {
+    NAMESPACES
+        .iter()
+        .find(|(name, _)| *name == namespace)
+        .map(|(_, id)| *id)
+}
+
+/// Parses the name of a namespace given on the command line, e.g. `Category`, returning its canonical name
+pub fn parse_namespace(name: &str) -> Result<String, String> {
+    namespace_named(name)
+        .map(str::to_owned)
+        .ok_or_else(|| format!("Unknown namespace `{name}`"))
+}
+
+/// Classifies the target of a link, given the value of its `href` attribute
+/// and the prefix of the path of the articles of the wiki, e.g. `/wiki/`
+pub fn classify_link(href: &str, article_path: &str) -> LinkKind {
+    let href = href.trim();
+
+    if href.starts_with("//") {
+        return LinkKind::ProtocolRelative;
+    }
+    if href.starts_with('/') {
+        // The query and fragment may contain colons, e.g. /wiki/Crocodile#Note:_1, which do not make the page namespaced
+        let path = href.split(['?', '#']).next().unwrap_or_default();
+        let Some(title) = path.strip_prefix(article_path) else {
+            return LinkKind::NonArticle;
+        };
+        if title.is_empty() {
+            return LinkKind::NonArticle;
+        }
+        return if namespace(title).is_some() {
+            LinkKind::Namespaced
+        } else {
+            LinkKind::Article
+        };
+    }
+    if href.starts_with('#') {
+        return LinkKind::Fragment;
+    }
+
+    match scheme(href).as_deref() {
+        Some("mailto") => LinkKind::Mailto,
+        Some("tel") => LinkKind::Tel,
+        Some("javascript") => LinkKind::Javascript,
+        Some(_) if href.contains("://") => LinkKind::External,
+        _ => LinkKind::Other,
+    }
+}
+
+/// Parameters of the query that only track where a visitor comes from, which do not change the page
+const TRACKING_PARAMETERS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "igshid",
+];
+
+/// Normalizes `url`, so that the urls of the same page written in different ways are the same:
+/// the fragment, the tracking parameters of the query and the trailing slash of the path are removed,
+/// and the path is escaped consistently, e.g. `HTTP://example.com/Caf%c3%a9/?utm_source=feed#History` becomes
+/// `http://example.com/Caf%C3%A9`. The scheme and host are already lowercase once parsed.
+///
+/// If the path starts with `article_path`, the rest of it is the title of an article, which identifies the page:
+/// the whole query is removed, and the spaces of the title become underscores, as MediaWiki does,
+/// so `/wiki/Nile crocodile?action=view` and `/wiki/Nile__crocodile_` are both `/wiki/Nile_crocodile`.
+/// The query of the other pages is kept, since it may identify them, as in `/index.php?title=Nile` or `/?p=123`
+pub fn normalize_url(url: &mut Url, article_path: Option<&str>) {
+    url.set_fragment(None);
+    if url.cannot_be_a_base() {
+        return;
+    }
+
+    let mut decoded = percent_decode_str(url.path()).collect::<Vec<_>>();
+    while decoded.len() > 1 && decoded.ends_with(b"/") {
+        decoded.pop();
+    }
+    let article = article_path.and_then(|article_path| {
+        let title = decoded.strip_prefix(article_path.as_bytes())?;
+        Some((article_path.len(), normalize_title(title)))
+    });
+    match article {
+        Some((prefix, title)) => {
+            decoded.truncate(prefix);
+            decoded.extend(title);
+            url.set_query(None);
+        }
+        None => remove_tracking_parameters(url),
+    }
+    url.set_path(&percent_encode(&decoded, PATH_ESCAPES).to_string());
+}
+
+/// Removes the parameters of the query of `url` that only track the visitors, such as `utm_source`,
+/// and the query itself if it is left empty
+fn remove_tracking_parameters(url: &mut Url) {
+    let Some(query) = url.query() else {
+        return;
+    };
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|parameter| {
+            let name = parameter.split('=').next().unwrap_or_default();
+            !name.is_empty() && !name.starts_with("utm_") && !TRACKING_PARAMETERS.contains(&name)
+        })
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        let kept = kept.join("&");
+        url.set_query(Some(&kept));
+    }
+}
+
+/// Title of an article as written in its url: the words are separated by single underscores,
+/// with none at the start and end
+fn normalize_title(title: &[u8]) -> Vec<u8> {
+    title
+        .split(|byte| matches!(byte, b' ' | b'_'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(&b'_')
+}
+
+/// Returns `url` normalized as by [`normalize_url`], or as it is if it is not a valid url
+pub fn normalize(url: &str, article_path: Option<&str>) -> String {
+    match Url::parse(url) {
+        Ok(mut parsed) => {
+            normalize_url(&mut parsed, article_path);
+            parsed.to_string()
+        }
+        Err(_) => url.to_owned(),
+    }
+}
+
+/// Number of links found in the scraped pages, by kind
+#[derive(Debug, Default)]
+pub struct LinkStats {
+    counts: Mutex<BTreeMap<LinkKind, u64>>,
+}
+
+impl LinkStats {
+    /// Adds the links found in a page, counted by kind
+    pub fn add(&self, page_counts: &BTreeMap<LinkKind, u64>) {
+        let mut counts = self.counts.lock().unwrap();
+        for (kind, count) in page_counts {
+            *counts.entry(*kind).or_default() += count;
+        }
+    }
+
+    pub fn counts(&self) -> BTreeMap<LinkKind, u64> {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(href: &str) -> LinkKind {
+        classify_link(href, "/wiki/")
+    }
+
+    #[test]
+    fn articles() {
+        assert_eq!(classify("/wiki/Crocodile"), LinkKind::Article);
+        assert_eq!(classify("/wiki/Crocodile#History"), LinkKind::Article);
+        assert_eq!(classify("/wiki/Crocodile#Note:_1"), LinkKind::Article);
+        assert_eq!(classify(" /wiki/Crocodile "), LinkKind::Article);
+    }
+
+    #[test]
+    fn namespaced_pages() {
+        assert_eq!(classify("/wiki/Special:Random"), LinkKind::Namespaced);
+        assert_eq!(classify("/wiki/File:Crocodile.jpg"), LinkKind::Namespaced);
+        assert_eq!(classify("/wiki/Talk:Crocodile"), LinkKind::Namespaced);
+        assert_eq!(
+            classify("/wiki/category_talk:Crocodiles"),
+            LinkKind::Namespaced
+        );
+        assert_eq!(classify("/wiki/Image:Crocodile.jpg"), LinkKind::Namespaced);
+    }
+
+    #[test]
+    fn titles_with_colons() {
+        assert_eq!(classify("/wiki/Star_Wars:_Andor"), LinkKind::Article);
+        assert_eq!(classify("/wiki/Re:Zero"), LinkKind::Article);
+        assert_eq!(
+            namespace("User_talk:Example"),
+            Some("User talk"),
+            "Underscores stand for spaces"
+        );
+        assert_eq!(namespace("Category%3ACrocodiles"), Some("Category"));
+        assert_eq!(namespace("Crocodile"), None);
+    }
+
+    #[test]
+    fn non_article_paths() {
+        assert_eq!(
+            classify("/w/index.php?title=Crocodile&action=edit"),
+            LinkKind::NonArticle
+        );
+        assert_eq!(classify("/wiki/"), LinkKind::NonArticle);
+        assert_eq!(classify("/wikipedia/Crocodile"), LinkKind::NonArticle);
+        assert_eq!(classify("/"), LinkKind::NonArticle);
+    }
+
+    #[test]
+    fn custom_article_path() {
+        assert_eq!(
+            classify_link("/index.php/Main_Page", "/index.php/"),
+            LinkKind::Article
+        );
+        assert_eq!(
+            classify_link("/wiki/Crocodile", "/index.php/"),
+            LinkKind::NonArticle
+        );
+    }
+
+    #[test]
+    fn protocol_relative() {
+        assert_eq!(
+            classify("//upload.wikimedia.org/wikipedia/commons/crocodile.jpg"),
+            LinkKind::ProtocolRelative
+        );
+        assert_eq!(
+            classify("//en.wikipedia.org/wiki/Crocodile"),
+            LinkKind::ProtocolRelative
+        );
+    }
+
+    #[test]
+    fn fragments() {
+        assert_eq!(classify("#History"), LinkKind::Fragment);
+        assert_eq!(classify("#"), LinkKind::Fragment);
+    }
+
+    #[test]
+    fn external() {
+        assert_eq!(classify("https://example.com/"), LinkKind::External);
+        assert_eq!(classify("HTTP://example.com/a:b"), LinkKind::External);
+        assert_eq!(classify("ftp://example.com/file"), LinkKind::External);
+    }
+
+    #[test]
+    fn pseudo_links() {
+        assert_eq!(classify("mailto:info@example.com"), LinkKind::Mailto);
+        assert_eq!(classify("MAILTO:info@example.com"), LinkKind::Mailto);
+        assert_eq!(classify("tel:+39000000"), LinkKind::Tel);
+        assert_eq!(classify("javascript:void(0)"), LinkKind::Javascript);
+        assert_eq!(classify("JavaScript:alert(1)"), LinkKind::Javascript);
+    }
+
+    #[test]
+    fn other() {
+        assert_eq!(classify("news:comp.lang.rust"), LinkKind::Other);
+        assert_eq!(classify("Crocodile"), LinkKind::Other);
+        assert_eq!(classify("./Crocodile"), LinkKind::Other);
+        assert_eq!(classify("a b:c"), LinkKind::Other);
+        assert_eq!(classify(""), LinkKind::Other);
+    }
+
+    #[test]
+    fn normalization() {
+        for url in [
+            "https://en.wikipedia.org/wiki/Caf%C3%A9",
+            "https://en.wikipedia.org/wiki/Café",
+            "https://en.wikipedia.org/wiki/Caf%c3%a9",
+            "HTTPS://EN.wikipedia.org/wiki/Caf%C3%A9/",
+            "https://en.wikipedia.org/wiki/Caf%C3%A9?utm_source=feed#History",
+        ] {
+            assert_eq!(
+                normalize(url, None),
+                "https://en.wikipedia.org/wiki/Caf%C3%A9",
+                "{url}"
+            );
+        }
+        assert_eq!(
+            normalize("https://en.wikipedia.org/wiki/%28Rust%29", None),
+            "https://en.wikipedia.org/wiki/(Rust)"
+        );
+        assert_eq!(
+            normalize("https://en.wikipedia.org/wiki/100%25_Rust", None),
+            "https://en.wikipedia.org/wiki/100%25_Rust"
+        );
+        assert_eq!(
+            normalize("https://example.com/", None),
+            "https://example.com/"
+        );
+        assert_eq!(
+            normalize("mailto:info@example.com", None),
+            "mailto:info@example.com"
+        );
+    }
+
+    #[test]
+    fn queries_are_kept_unless_they_only_track_the_visitors() {
+        assert_eq!(
+            normalize("https://example.com/?p=123", None),
+            "https://example.com/?p=123"
+        );
+        assert_eq!(
+            normalize(
+                "https://example.com/blog/?utm_source=feed&page=2&fbclid=abc",
+                None
+            ),
+            "https://example.com/blog?page=2"
+        );
+        assert_eq!(
+            normalize("https://example.com/w/index.php?title=Nile", Some("/wiki/")),
+            "https://example.com/w/index.php?title=Nile"
+        );
+    }
+
+    #[test]
+    fn article_titles() {
+        for url in [
+            "https://en.wikipedia.org/wiki/Nile_crocodile",
+            "https://en.wikipedia.org/wiki/Nile crocodile",
+            "https://en.wikipedia.org/wiki/Nile%20crocodile",
+            "https://en.wikipedia.org/wiki/_Nile__crocodile_",
+            "https://en.wikipedia.org/wiki/Nile_crocodile?action=view",
+        ] {
+            assert_eq!(
+                normalize(url, Some("/wiki/")),
+                "https://en.wikipedia.org/wiki/Nile_crocodile",
+                "{url}"
+            );
+        }
+        assert_eq!(
+            normalize("https://example.com/docs/a b", Some("/wiki/")),
+            "https://example.com/docs/a%20b"
+        );
+    }
+
+    #[test]
+    fn stats() {
+        let stats = LinkStats::default();
+        stats.add(&BTreeMap::from([
+            (LinkKind::Article, 2),
+            (LinkKind::Mailto, 1),
+        ]));
+        stats.add(&BTreeMap::from([(LinkKind::Article, 1)]));
+        let counts = stats.counts();
+        assert_eq!(counts.get(&LinkKind::Article), Some(&3));
+        assert_eq!(counts.get(&LinkKind::Mailto), Some(&1));
+        assert_eq!(counts.get(&LinkKind::Tel), None);
+    }
+}
@@ -1,75 +1,1621 @@
-mod errors;
-mod scraper;
-mod worker;
+use clap::{Parser, Subcommand};
+use std::{error::Error, path, sync::Arc, time::Duration};
 
-use clap::Parser;
-use std::{error::Error, path};
-
-use crate::scraper::WikipediaScraper;
+use graphgen::alerts;
+use graphgen::bridge;
+use graphgen::checkpoint::Checkpoint;
+use graphgen::compression::Compression;
+use graphgen::config::{
+    Auth, BotLogin, CategoryConfig, CrawlStrategy, ExternalLinks, GhostNodes, KeywordMode,
+    LayoutAlgorithm, LinkSampling, LinkScope, PresetSeeds, RandomSeeds, RateLimitConfig,
+    RetryConfig, RevisitConfig, ScraperConfig, SeedPreset, SiteMode, UrlFilter, WikiConfig,
+    UNLIMITED_DEPTH,
+};
+use graphgen::control;
+use graphgen::edge_list::{self, EdgeListFormat};
+use graphgen::estimate::Sample;
+use graphgen::events;
+use graphgen::filter::Filter;
+use graphgen::graph::{self, Graph};
+use graphgen::http::{self, HttpClient};
+use graphgen::keywords;
+use graphgen::links;
+use graphgen::node_id::IdWidth;
+use graphgen::output::{self, OutputFormat, OutputOptions, OutputWriter, Priority, StdoutNodes};
+use graphgen::pipeline::{EnrichConfig, StageDir};
+use graphgen::rate_limit::{self, AdaptiveRateConfig};
+use graphgen::scraper::WikipediaScraper;
+use graphgen::simulate::{ExportedGraph, SimulationConfig, SimulationStrategy};
+use graphgen::snapshot;
+use graphgen::window;
+use graphgen::worker;
 
 /// Simple wikipedia scraper
+///
+/// A running scrape can be paused by sending SIGUSR1 to the process and resumed by sending SIGUSR2,
+/// or controlled through the socket given with --control-socket.
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true)]
+#[clap(group(clap::ArgGroup::new("keyword_filters").args(["keywords", "exclude_keywords"]).multiple(true)))]
+#[clap(group(clap::ArgGroup::new("budget").args(["max_pages", "max_duration_secs"]).multiple(true)))]
 struct Args {
-    /// Url to scrape
-    url: String,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Urls to scrape, or titles of wikipedia articles (e.g. "Nile crocodile"). Every seed is scraped
+    /// with the full depth, and the pages found from all of them end up in the same graph
+    #[clap(required_unless_present_any = ["resume", "seeds_file", "category", "random_seeds", "preset", "explain"], conflicts_with = "resume")]
+    urls: Vec<String>,
+
+    /// File with more seeds, one url or title per line. Blank lines and lines starting with `#` are ignored
+    #[clap(long, conflicts_with = "resume")]
+    seeds_file: Option<std::path::PathBuf>,
+
+    /// Language of the wikipedia to scrape, e.g. `it` for it.wikipedia.org. Replaces the language of a wikipedia url,
+    /// and is used to find the article when a title is given instead of an url. Titles default to the English wikipedia
+    #[clap(long, conflicts_with = "resume")]
+    lang: Option<String>,
+
+    /// Url of the MediaWiki site to scrape, e.g. https://wiki.example.com, used to find the article when a title
+    /// is given instead of an url
+    #[clap(long, conflicts_with_all = ["resume", "lang"], value_parser = parse_base_url)]
+    base_url: Option<reqwest::Url>,
+
+    /// Prefix of the path of the articles of the wiki, e.g. `/index.php/` for https://wiki.example.com/index.php/Main_Page
+    #[clap(long, default_value = "/wiki/", value_parser = parse_path)]
+    article_path: String,
+
+    /// Path of the MediaWiki Action API of the wiki, used by --use-api. Many wikis serve it at `/api.php`
+    #[clap(long, default_value = "/w/api.php", value_parser = parse_path)]
+    api_path: String,
+
+    /// CSS selector of the element of the pages containing the links that are part of the graph.
+    /// Defaults to `#bodyContent` for wikis and to `body` for generic sites
+    #[clap(long, value_parser = parse_selector)]
+    content_selector: Option<String>,
+
+    /// Only keep the links in this part of the content: `lead` keeps the paragraphs before the first section heading,
+    /// and `prose` keeps every paragraph, leaving out the navigation boxes, the infoboxes and the reference lists
+    #[clap(long, value_enum, default_value_t = LinkScope::All, conflicts_with = "use_api")]
+    link_scope: LinkScope,
+
+    /// Comma separated list of namespaces whose pages are part of the graph along with the articles, e.g. `Category,Portal`.
+    /// By default, only the articles of the main namespace are. Titles with a colon that is not after a namespace, e.g. `Star Wars: Andor`, are articles
+    #[clap(long, value_delimiter = ',', value_parser = links::parse_namespace)]
+    include_namespaces: Vec<String>,
+
+    /// Only record and follow the links whose url matches one of these regular expressions, e.g. `/wiki/List_of_`.
+    /// Can be given more than once. The urls are the ones of the graph, which are percent encoded
+    #[clap(long, value_parser = parse_regex)]
+    url_allow: Vec<regex::Regex>,
+
+    /// Drop the links whose url matches any of these regular expressions, e.g. `/wiki/1\d\d\d$` for the years.
+    /// Can be given more than once
+    #[clap(long, value_parser = parse_regex)]
+    url_deny: Vec<regex::Regex>,
+
+    /// Kind of site to scrape. Generic sites are crawled following the links to any page, instead of only the articles
+    #[clap(long, value_enum, default_value_t = SiteMode::Wiki, conflicts_with_all = ["use_api", "lang", "all_languages_of_seed", "interlanguage_links", "auth_bot_user", "category", "random_seeds", "preset"])]
+    site_mode: SiteMode,
+
+    /// Only follow the links to the hosts of the seeds when crawling a generic site, as is always done for wikis.
+    /// The links to other sites are then only kept with --keep-external-links
+    #[clap(long, default_value_t = false)]
+    same_domain_only: bool,
+
+    /// Fetch the links of the pages from the MediaWiki Action API of their wiki (`/w/api.php`) instead of scraping their HTML.
+    /// It only returns links to articles, with redirects resolved, and does not download the content of the pages,
+    /// so it cannot be used with the keywords or with external links
+    #[clap(long, default_value_t = false, conflicts_with_all = ["keyword_filters", "keep_external_links"])]
+    use_api: bool,
+
+    /// Category whose pages are used as seeds, as an url or a title, e.g. `Category:Graph_theory`. Its members are listed
+    /// with the Action API of the wiki, and only the links among them are part of the graph
+    #[clap(long)]
+    category: Option<String>,
+
+    /// Levels of subcategories whose pages are seeds as well, with --category
+    #[clap(long, default_value_t = 0, requires = "category")]
+    category_depth: u64,
+
+    /// Start from this many random articles, picked with the Action API as Special:Random does, to sample the overall
+    /// structure of the wiki rather than the neighborhood of a page. They are picked from the wiki of --lang or --base-url
+    #[clap(long, conflicts_with = "resume", value_parser = clap::value_parser!(u64).range(1..))]
+    random_seeds: Option<u64>,
+
+    /// Start from the articles of a curated list of the English Wikipedia, listed with the Action API:
+    /// `vital-level-3` for the 1000 vital articles, `featured` for the featured articles
+    #[clap(long, value_enum, conflicts_with_all = ["resume", "lang", "base_url"])]
+    preset: Option<SeedPreset>,
 
     /// Keywords to search for in the pages
     #[clap(short, long)]
     keywords: Option<Vec<String>>,
 
+    /// How the keywords are matched, ignoring case: a page is kept if it contains any of them, all of them,
+    /// or if any of them read as a regular expression matches it
+    #[clap(long, value_enum, default_value_t = KeywordMode::Any, requires = "keyword_filters")]
+    keyword_mode: KeywordMode,
+
+    /// Skip the pages containing any of these keywords, ignoring case, without expanding them.
+    /// They are regular expressions with --keyword-mode regex
+    #[clap(long)]
+    exclude_keywords: Option<Vec<String>>,
+
+    /// Only require the keywords from this many links away from the seeds, e.g. 2 keeps the pages the seeds link to
+    /// whether or not they contain the keywords, since the neighborhood of a seed is usually relevant. The excluded keywords
+    /// apply at every hop
+    #[clap(long, default_value_t = 0, requires = "keywords")]
+    keywords_from_hop: u64,
+
+    /// Every this many seconds, log how many pages have been accepted and rejected by the keywords at each depth
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    keyword_stats_interval: Option<u64>,
+
     /// Depth of the scrape
     #[clap(short, long, default_value_t = 5, value_parser=clap::value_parser!(u64).range(1..))]
     depth: u64,
 
-    /// The first part of the name of the output files. The edges will be saved to <output-file>_edges.csv and the nodes will be saved to <output-file>_nodes.csv
-    #[clap(short, long = "output-file")]
+    /// Crawl without a depth limit, following the links until --max-pages or --max-duration-secs is reached.
+    /// Pair it with --delay-ms or --requests-per-second to keep a long crawl polite, and with --window-nodes to follow it while it runs
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with = "depth",
+        requires = "budget"
+    )]
+    continuous: bool,
+
+    /// Every --window-interval-secs, write the subgraph of this many pages found last, with the links between them,
+    /// to the output files for the prefixes <output-file>_window_1, <output-file>_window_2 and so on
+    #[clap(long, requires = "output_file", value_parser = alerts::parse_count)]
+    window_nodes: Option<u64>,
+
+    /// Seconds between two windows of the graph
+    #[clap(long, default_value_t = 60, requires = "window_nodes", value_parser=clap::value_parser!(u64).range(1..))]
+    window_interval_secs: u64,
+
+    /// The first part of the name of the output files. With the csv format, the edges will be saved to <output-file>_edges.csv
+    /// and the nodes will be saved to <output-file>_nodes.csv (.tsv if the delimiter is a tab).
+    /// Without --format, a path ending in .json or .graphml (optionally followed by .gz or .zst) is written as a single file in that format
+    #[clap(short, long = "output-file", conflicts_with = "stdout")]
     output_file: Option<String>,
 
+    /// Write the edges to stdout instead of saving them to files, so that the output can be piped to other commands
+    #[clap(long, default_value_t = false)]
+    stdout: bool,
+
+    /// Where to write the nodes when using --stdout
+    #[clap(long, value_enum, default_value_t = StdoutNodes::None, requires = "stdout")]
+    stdout_nodes: StdoutNodes,
+
+    /// Maximum number of pages to scrape, across all of the workers. Once reached, the pages being scraped
+    /// are finished and the graph is saved as usual, without scraping the pages left in the queue
+    #[clap(long, value_parser = alerts::parse_count)]
+    max_pages: Option<u64>,
+
+    /// Maximum number of seconds to crawl for. Once reached, the pages being scraped are finished
+    /// and the graph is saved as usual, without scraping the pages left in the queue
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    max_duration_secs: Option<u64>,
+
+    /// Order in which the pages in the queue are scraped. It decides which pages end up in the graph when --max-pages is reached.
+    /// The priority strategy scrapes first the pages with the most links from the pages scraped so far
+    #[clap(long, value_enum, default_value_t = CrawlStrategy::Bfs)]
+    strategy: CrawlStrategy,
+
     /// Number of threads to use
     #[clap(short='t', long, default_value_t = 4, value_parser=clap::value_parser!(u64).range(1..))]
     num_threads: u64,
 
+    /// Maximum number of pages to scrape at the same time.
+    /// Most of the time of a page is spent waiting for the response, so this can be much higher than the number of threads
+    #[clap(short = 'c', long, default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: u64,
+
     /// Wheter to generate an undirected graph
     /// If this is set to true, the script will only save the edges where there is both a link from source to destination and viceversa.
     #[clap(long, default_value_t = false)]
     undirected: bool,
 
-    /// Wheter to remove external links
+    /// Whether to count how many times a page links to another one.
+    /// The count will be saved in the `weight` column of the edges file. In undirected graphs, the weight is the sum of both directions.
+    #[clap(long, default_value_t = false)]
+    weighted: bool,
+
+    /// What to do with the pages rejected by the keywords, which are linked to by the scraped pages
+    #[clap(long, value_enum, default_value_t = GhostNodes::Keep, requires = "keyword_filters")]
+    ghost_nodes: GhostNodes,
+
+    /// Also save, for each keyword, the subgraph of the pages containing it to the output files
+    /// with the prefix <output-file>_<keyword>
+    #[clap(long, default_value_t = false, requires_all = ["keywords", "output_file"])]
+    split_by_keyword: bool,
+
+    /// Whether to save when and by which worker the source page of each edge has been scraped.
+    /// Adds the columns `crawl_seq` (order in which the pages have been scraped), `worker_id`
+    /// and `fetched_at` (unix timestamp in milliseconds) to the edges file.
+    #[clap(long, default_value_t = false)]
+    provenance: bool,
+
+    /// Save the position of each link among the distinct links of its source page, starting from 0, in the `position` column
+    /// of the edges file, e.g. to tell the links in the lead of an article from the ones further down.
+    /// In undirected graphs, the position of an edge is the lowest of its two directions
+    #[clap(long, default_value_t = false)]
+    link_positions: bool,
+
+    /// Save the part of its source page each link has first been found in, in the `type` column of the edges file:
+    /// `infobox`, `navbox` (the navigation boxes and sidebars), `see_also` (the "See also" section) or `body` for the rest
+    #[clap(long, default_value_t = false, conflicts_with = "use_api")]
+    edge_types: bool,
+
+    /// Add the columns `in_degree` and `out_degree` to the nodes, counting the distinct links to and from each page
+    /// in the whole crawled graph, and report the lowest, highest and mean degree once the crawl is over
+    #[clap(long, default_value_t = false)]
+    degrees: bool,
+
+    /// Compute the connected components of the graph, ignoring the direction of the links, and add the column `component_id`
+    /// to the nodes, numbering the components from 0 by decreasing size. With --undirected, only the pages linking
+    /// to each other are connected, as in the exported graph. The number of components of each size is reported once the crawl is over
+    #[clap(long, default_value_t = false)]
+    components: bool,
+
+    /// Only export the largest connected component of the graph, along with the links between its pages,
+    /// dropping the pages cut off from it. The components are found as with --components
+    #[clap(long, default_value_t = false)]
+    giant_component_only: bool,
+
+    /// Compute the PageRank of the pages once the crawl is over. Adds the columns `pagerank` and `rank_percentile`
+    /// (from 100 for the top 1% of the pages to 1 for the bottom 1%) to the nodes, which are written by decreasing rank
+    #[clap(long, default_value_t = false)]
+    pagerank: bool,
+
+    /// Only export this fraction of the pages with the highest PageRank, along with the links between them,
+    /// e.g. 0.1 for the top 10% of the pages
+    #[clap(long, requires = "pagerank", value_parser = parse_fraction)]
+    top_fraction: Option<f64>,
+
+    /// Compute the betweenness and the closeness centrality of the pages and add them as the columns `betweenness` and `closeness`
+    /// of the nodes, to find the articles bridging the parts of the graph, which are reported once the crawl is over.
+    /// The closeness is the mean of the inverse of the distances from the other pages
+    #[clap(long, default_value_t = false)]
+    centrality: bool,
+
+    /// Number of pages up to which the centrality is exact. In larger graphs, it is estimated from the shortest paths
+    /// from this many pages, since the exact one takes a search from every page
+    #[clap(long, default_value_t = 1000, requires = "centrality", value_parser = clap::value_parser!(u64).range(1..))]
+    centrality_samples: u64,
+
+    /// Compute the local clustering coefficient of the pages, ignoring the direction of the links, and add it as the column
+    /// `clustering` of the nodes: the fraction of the pairs of neighbors of a page that are linked to each other.
+    /// With --undirected, only the pages linking to each other are neighbors. The average is reported once the crawl is over
+    #[clap(long, default_value_t = false)]
+    clustering: bool,
+
+    /// Compute the coordinates of the pages with this algorithm and add them as the `x` and `y` columns of the nodes,
+    /// so that the graph opens in Gephi or Cosmograph already laid out. Only meant for graphs with up to 20000 pages
+    #[clap(long, value_enum)]
+    layout: Option<LayoutAlgorithm>,
+
+    /// Number of iterations of the layout algorithm
+    #[clap(long, default_value_t = 300, requires = "layout", value_parser = clap::value_parser!(u64).range(1..))]
+    layout_iterations: u64,
+
+    #[clap(flatten)]
+    filters: FilterArgs,
+
+    /// Url of a Gephi workspace to push the nodes and edges to as they are found,
+    /// using the Streaming plugin, e.g. `http://localhost:8080/workspace1`
+    #[clap(long)]
+    gephi_stream: Option<String>,
+
+    /// Log a prominent warning once the graph has this many pages, e.g. `1e6`. The crawl keeps going
+    #[clap(long, value_parser = alerts::parse_count)]
+    warn_nodes: Option<u64>,
+
+    /// Log a prominent warning once the graph has this many links, e.g. `1e7`. The crawl keeps going
+    #[clap(long, value_parser = alerts::parse_count)]
+    warn_edges: Option<u64>,
+
+    /// Stop the crawl with an error once more than this many pages in a row have no links under the content selector,
+    /// which usually means that the selector is wrong or that the server is blocking the crawl. 0 never stops it
+    #[clap(long, default_value_t = 20)]
+    max_empty_pages: u64,
+
+    /// Abandon a page that is still being parsed after this many seconds, e.g. an enormous page, and log it
+    /// so that it does not hold a worker for the rest of the crawl. 0 waits for every page
+    #[clap(long, default_value_t = 60)]
+    page_timeout_secs: u64,
+
+    /// Scrape again a page that has already been scraped, when it is found again at least this many seconds later.
+    /// Its links are added to the graph once more, so the weights of its edges accumulate across the visits
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    revisit_after_secs: Option<u64>,
+
+    /// Scrape again a page that has already been scraped, when it is found again at least this many hops
+    /// further from the seed than where it was last scraped. Its links are added to the graph once more
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    revisit_after_hops: Option<u64>,
+
+    /// Check again the pages that have been waiting in the queue for more than this many seconds before scraping them,
+    /// dropping the ones that have been scraped or rejected by the keywords in the meantime. Useful in long crawls,
+    /// where the queue grows old. The pages of a resumed crawl are considered queued when it is resumed
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    frontier_ttl_secs: Option<u64>,
+
+    /// Keep the links pointing outside of the crawled wikis or sites, which are dropped by default.
+    /// Without a value, the external pages are added to the graph. With `--keep-external-links=fetch` they are also fetched once,
+    /// without following their links, to save their title and the status and url of the final response (see --check-external),
+    /// and with `--keep-external-links=separate` the links are written to <output-file>_external_edges.csv instead of the graph
+    #[clap(long, value_enum, num_args = 0..=1, require_equals = true, default_missing_value = "nodes")]
+    keep_external_links: Option<ExternalLinks>,
+
+    /// Send a HEAD request to every external link, following its redirects, and save the status and the url
+    /// of the final response in the `http_status` and `final_url` attributes of the nodes.
+    /// Pages that have not been checked have status 0
+    #[clap(long, default_value_t = false, requires = "keep_external_links")]
+    check_external: bool,
+
+    /// Skip the remaining links of a page once it has this many outgoing links in the graph.
+    /// Pages with a huge number of links stay in the graph, without flooding it with their neighbours
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    stop_expanding_at_degree: Option<u64>,
+
+    /// Keep at most this many distinct links of every page, dropping the others before they are added to the graph
+    /// or followed, so that hub pages with thousands of links do not blow up the queue on deep crawls
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_links_per_page: Option<u64>,
+
+    /// Which links are kept from the pages with more than --max-links-per-page links
+    #[clap(long, value_enum, default_value_t = LinkSampling::First, requires = "max_links_per_page")]
+    link_sampling: LinkSampling,
+
+    /// Identify the pages by the url they are fetched from. By default, a page with a `<link rel="canonical">` element
+    /// is identified by its canonical url, so that the urls of the same page (e.g. redirects or mobile versions) end up in the same node
+    #[clap(long, default_value_t = false)]
+    ignore_canonical: bool,
+
+    /// Leave the disambiguation pages out of the graph and do not follow their links.
+    /// They are detected by their disambiguation box or category, or by their page property with --use-api
+    #[clap(long, default_value_t = false)]
+    skip_disambiguation: bool,
+
+    /// Use 32-bit ids for the pages, which halves the size of the ids in memory-sensitive formats such as arrow.
+    /// The crawl is stopped if the graph grows past 4294967296 pages
+    #[clap(long, default_value_t = false)]
+    small_ids: bool,
+
+    /// Whether to also scrape the seed article in every other language it is available in.
+    /// The language of each page will be saved in the `lang` column of the nodes file.
+    #[clap(long, default_value_t = false)]
+    all_languages_of_seed: bool,
+
+    /// Comma separated list of language codes (e.g. `it,de,fr`) to use with --all-languages-of-seed.
+    /// If not set, every language the seed article is available in will be scraped.
+    #[clap(long, value_delimiter = ',', requires = "all_languages_of_seed")]
+    languages: Option<Vec<String>>,
+
+    /// Add the links from each article to the same article in the other languages it is available in,
+    /// saving the language of their target in the `interlanguage` column of the edges file
+    #[clap(long, default_value_t = false, conflicts_with = "use_api")]
+    interlanguage_links: bool,
+
+    /// Also scrape the articles in the other languages found with --interlanguage-links, with this depth at most.
+    /// Without it, the articles in the other languages are added to the graph but not scraped
+    #[clap(long, requires = "interlanguage_links", value_parser = clap::value_parser!(u64).range(1..))]
+    interlanguage_depth: Option<u64>,
+
+    /// Comma separated list of formats of the output files (e.g. `csv,graphml`), all written from the same graph.
+    /// Defaults to csv, unless it can be inferred from the extension of --output-file
+    #[clap(long, value_enum, value_delimiter = ',')]
+    format: Vec<OutputFormat>,
+
+    /// Compress the output files. The matching extension (.gz or .zst) will be appended to their names.
+    /// Defaults to none, unless it can be inferred from the extension of --output-file
+    #[clap(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// Field delimiter of the csv output files. Use `tab` or `\t` to write tsv files
+    #[clap(long, default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: u8,
+
+    /// Maximum number of edges to keep in memory. Once exceeded, the edges are sorted and written to disk,
+    /// and merged back together when saving the output. Useful for crawls whose edges do not fit in memory.
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    max_edges_in_memory: Option<u64>,
+
+    /// Directory where the edges are written when --max-edges-in-memory is exceeded. Defaults to the system temporary directory
+    #[clap(long, requires = "max_edges_in_memory")]
+    spill_dir: Option<std::path::PathBuf>,
+
+    /// Path of a unix socket accepting commands to control the running crawl.
+    /// Each line sent to the socket is one of `pause`, `resume`, `stats`, `flush`, `stop` or `keywords <comma separated keywords>`,
+    /// and is answered by a line of json.
+    #[clap(long)]
+    control_socket: Option<std::path::PathBuf>,
+
+    /// User-Agent header sent with every request. Wikimedia asks bots to identify themselves with a way to contact
+    /// their operator, e.g. `my-crawler/1.0 (me@example.com) graphgen/0.1.0`
+    #[clap(long, default_value = http::DEFAULT_USER_AGENT)]
+    user_agent: String,
+
+    /// Extra header sent with every request to the wiki, e.g. `Accept-Language: de` or `Cookie: session=...`
+    /// to crawl a wiki behind a login. Can be repeated. The headers are not sent when checking external links
+    #[clap(long = "header", value_parser = http::parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Log in to the wiki with http basic authentication, given as `user:password`
+    #[clap(long, value_parser = parse_basic_auth, conflicts_with = "auth_bearer")]
+    auth_basic: Option<Auth>,
+
+    /// Send this token in the Authorization header of every request to the wiki, as `Bearer <token>`
+    #[clap(long)]
+    auth_bearer: Option<String>,
+
+    /// Log in to the wiki of the first seed with this account through the Action API before crawling,
+    /// e.g. with a bot password created in Special:BotPasswords as `User@bot`
+    #[clap(long, requires = "auth_bot_pass")]
+    auth_bot_user: Option<String>,
+
+    /// Password of the account given with --auth-bot-user
+    #[clap(long, requires = "auth_bot_user")]
+    auth_bot_pass: Option<String>,
+
+    /// Send every request through this proxy. Supports http, https and, with the `socks` feature, socks5 proxies, e.g. `socks5://127.0.0.1:1080`.
+    /// If not set, the proxy in the HTTP_PROXY, HTTPS_PROXY or ALL_PROXY environment variables is used
+    #[clap(long, value_parser = http::parse_proxy)]
+    proxy: Option<String>,
+
+    /// Directory of a cache of the fetched pages. Cached pages are read from disk instead of being fetched again,
+    /// and the cache can be shared by several crawls running at the same time
+    #[clap(long)]
+    cache_dir: Option<std::path::PathBuf>,
+
+    /// Check with the server whether the cached pages have changed, with the ETag and Last-Modified headers of their responses,
+    /// instead of always using them. Pages that have not changed are not downloaded again
+    #[clap(long, default_value_t = false, requires = "cache_dir")]
+    revalidate_cache: bool,
+
+    /// Use the cached pages stored more than this many seconds ago right away, and fetch them again in the background
+    /// to refresh the cache for the next crawls, so that iterating on the same topic builds the graph from the cache.
+    /// The crawl waits for the refreshes before ending. 0 refreshes every cached page that is used
+    #[clap(long, requires = "cache_dir", conflicts_with = "revalidate_cache")]
+    stale_while_revalidate_secs: Option<u64>,
+
+    /// Periodically write the pages, the links and the queue of the crawl to this file, and once more when it ends,
+    /// so that an interrupted crawl can be continued with --resume
+    #[clap(long)]
+    checkpoint: Option<std::path::PathBuf>,
+
+    /// Seconds between two checkpoints
+    #[clap(long, default_value_t = 60, value_parser=clap::value_parser!(u64).range(1..))]
+    checkpoint_interval_secs: u64,
+
+    /// Continue the crawl saved in this checkpoint instead of starting a new one from an url.
+    /// The pages in the queue keep the depth they had, and new checkpoints are written to the same file unless --checkpoint is set
+    #[clap(long)]
+    resume: Option<std::path::PathBuf>,
+
+    /// Number of runs that can give up on fetching a page. When resuming a crawl, the pages that could not be fetched
+    /// are queued again with their depth, until they have failed this many times
+    #[clap(long, default_value_t = 3, value_parser=clap::value_parser!(u32).range(1..))]
+    max_page_attempts: u32,
+
+    /// Append the decisions taken about each page to this file, one json object per line: the pages it has been queued
+    /// and linked from, the links to it left out and why, and whether it has been scraped, skipped or could not be fetched
+    #[clap(long)]
+    event_log: Option<std::path::PathBuf>,
+
+    /// Print the decisions taken about this url or title, to find out why it is or is not in the graph. With seeds, they are
+    /// printed as the crawl takes them. Without seeds, they are read from --event-log, which can be the log of a running crawl
+    #[clap(long)]
+    explain: Option<String>,
+
+    /// Run the crawl as the first stage of a staged pipeline (`crawl → enrich → analyze → export`), writing its checkpoint
+    /// to this directory. The other stages are run on the directory with the `enrich`, `analyze` and `export` commands,
+    /// and each of them can be run again without redoing the previous ones
+    #[clap(long, conflicts_with = "checkpoint")]
+    stage_dir: Option<std::path::PathBuf>,
+
+    /// Start from the graph written for this output prefix by a previous crawl, in the csv format with the same delimiter.
+    /// Its pages and links are added to the graph, and the pages it has expanded are not fetched again.
+    /// A snapshot written with --snapshot, which loads much faster, can be given instead
+    #[clap(long, conflicts_with = "resume")]
+    import_graph: Option<String>,
+
+    /// Also write the whole graph to this file when the crawl ends, in a binary format that loads much faster than the csv files
+    /// in place of them in the `simulate` and `convert` commands and with --import-graph. Its name ends with `.snapshot`,
+    /// followed by `.gz` or `.zst` to compress it, e.g. `graph.snapshot.zst`
+    #[clap(long, value_parser = parse_snapshot_path)]
+    snapshot: Option<String>,
+
+    /// Number of times a request is sent before giving up on a page, including the first one.
+    /// Only network errors and responses with status 429 or 5xx are retried
+    #[clap(long, default_value_t = 3, value_parser=clap::value_parser!(u32).range(1..))]
+    max_attempts: u32,
+
+    /// Delay in milliseconds before the first retry of a request. It is doubled at each following retry,
+    /// with some random jitter, unless the server asks for a specific delay with the Retry-After header
+    #[clap(long, default_value_t = 500)]
+    retry_base_delay_ms: u64,
+
+    /// Wait at least this many milliseconds between two requests, across all of the threads
+    #[clap(long, conflicts_with = "requests_per_second", value_parser=clap::value_parser!(u64).range(1..))]
+    delay_ms: Option<u64>,
+
+    /// Maximum number of requests per second, across all of the threads
+    #[clap(long, value_parser = rate_limit::parse_rate)]
+    requests_per_second: Option<f64>,
+
+    /// Number of requests that can be sent at once after a pause, with --requests-per-second
+    #[clap(long, default_value_t = 1, requires = "requests_per_second")]
+    burst: u32,
+
+    /// Maximum number of requests per second to each external host, to check or fetch the external links.
+    /// Without it, the external links are sent requests as fast as possible
+    #[clap(long, value_parser = rate_limit::parse_rate)]
+    external_requests_per_second: Option<f64>,
+
+    /// Number of requests that can be sent at once to an external host after a pause, with --external-requests-per-second
+    #[clap(long, default_value_t = 1, requires = "external_requests_per_second")]
+    external_burst: u32,
+
+    /// Adapt the request rate to the server: the rate is slowly raised while responses are fast,
+    /// and halved whenever a response is slower than --target-latency-ms or is throttled (status 429 or 503)
     #[clap(long, default_value_t = false)]
-    keep_external_links: bool,
+    adaptive_rate_limit: bool,
+
+    /// Latency in milliseconds above which the adaptive rate limiter slows down
+    #[clap(long, default_value_t = 1000, requires = "adaptive_rate_limit")]
+    target_latency_ms: u64,
+
+    /// Maximum number of requests per second allowed by the adaptive rate limiter
+    #[clap(long, default_value_t = 50.0, requires = "adaptive_rate_limit")]
+    max_rate: f64,
+
+    /// Maximum bandwidth in bytes per second, across all of the threads, e.g. `512k` or `2MB/s` on a metered connection.
+    /// The responses are read in chunks, and each chunk waits for the average to go back under the limit.
+    /// The bodies are counted once decompressed, so the limit is conservative for the pages served compressed
+    #[clap(long, value_parser = rate_limit::parse_bandwidth)]
+    max_bandwidth: Option<u64>,
 }
-fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
 
-    if let Some(output_file_path) = &args.output_file {
-        let edges_file_path = format!("{}_edges.csv", output_file_path);
-        if path::Path::new(&edges_file_path).exists() {
-            return Err(format!("File {edges_file_path} already exists. Delete it and run the program again if you want to use that path.").into());
+#[derive(Subcommand)]
+enum Command {
+    /// Replay a crawl over a previously exported graph, without any request,
+    /// so that different strategies and budgets can be compared on the same data
+    Simulate(SimulateArgs),
+
+    /// Enrich stage of a staged crawl: fetch the pageviews of the articles from the Wikimedia REST API.
+    /// The articles enriched by the previous runs are skipped, so a failed run can be continued by running it again
+    Enrich(EnrichArgs),
+
+    /// Analyze stage of a staged crawl: compute the PageRank, the in-degree and the out-degree of the pages
+    Analyze(StageArgs),
+
+    /// Export stage of a staged crawl: write the graph of the crawl stage with the results of the stages run on it
+    Export(ExportArgs),
+
+    /// Convert an edge list written by another tool into the crawl stage of a staged crawl, so that the `analyze` and `export`
+    /// commands can compute its PageRank and write it in any output format, as for a crawled graph
+    Convert(ConvertArgs),
+
+    /// Crawl a handful of pages and project the number of pages, the bytes downloaded and the time
+    /// of a crawl with the given depth, to pick the depth and the budget before starting it
+    Estimate(EstimateArgs),
+
+    /// Crawl the neighborhood of an article in two languages and merge the two graphs by the Wikidata items of the articles,
+    /// flagging each node and link with the languages it has been found in, to compare the coverage of the two editions
+    Bridge(BridgeArgs),
+
+    /// Print the shortest path of links from an article to another one, crawling from the first article
+    /// until the second one is found, or looking for the path in a graph saved by a previous crawl
+    Path(PathArgs),
+}
+
+#[derive(clap::Args)]
+struct EstimateArgs {
+    /// Url of the page the crawl would start from
+    url: String,
+
+    /// Depth of the crawl to estimate
+    #[clap(short, long, default_value_t = 5, value_parser=clap::value_parser!(u64).range(1..))]
+    depth: u64,
+
+    /// Maximum number of pages of the crawl to estimate
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    max_pages: Option<u64>,
+
+    /// Number of pages to crawl for the estimate
+    #[clap(long, default_value_t = 20, value_parser=clap::value_parser!(u64).range(1..))]
+    sample: u64,
+
+    /// Maximum number of pages to scrape at the same time, as in the crawl to estimate
+    #[clap(short = 'c', long, default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: u64,
+}
+
+#[derive(clap::Args)]
+struct PathArgs {
+    /// Url or title of the article the path starts from, looked up in the English wikipedia if it is a title
+    from: String,
+
+    /// Url or title of the article the path leads to, like the first one
+    to: String,
+
+    /// Look for the path in the graph written for this output prefix by a previous crawl, in the csv format,
+    /// or in a snapshot written with --snapshot, instead of crawling
+    #[clap(long)]
+    graph: Option<String>,
+
+    /// Maximum number of links of the path, which is the depth of the crawl
+    #[clap(short, long, default_value_t = 3, value_parser=clap::value_parser!(u64).range(1..))]
+    depth: u64,
+
+    /// Maximum number of pages to scrape
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    max_pages: Option<u64>,
+
+    /// Maximum number of pages to scrape at the same time
+    #[clap(short = 'c', long, default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: u64,
+
+    /// Field delimiter of the csv files of --graph
+    #[clap(long, default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: u8,
+}
+
+#[derive(clap::Args)]
+struct BridgeArgs {
+    /// Url or title of the article to start from, in the first of --languages
+    seed: String,
+
+    /// The two language codes of the wikipedias to compare, e.g. `en,it`. The attributes of the first one end with `_a`
+    /// and the ones of the second with `_b`
+    #[clap(long, value_delimiter = ',', required = true)]
+    languages: Vec<String>,
+
+    /// Depth of the crawl in each language
+    #[clap(short, long, default_value_t = 2, value_parser=clap::value_parser!(u64).range(1..))]
+    depth: u64,
+
+    /// Maximum number of pages to scrape, across the two languages
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    max_pages: Option<u64>,
+
+    /// Maximum number of pages to scrape at the same time
+    #[clap(short = 'c', long, default_value_t = 16, value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: u64,
+
+    /// The first part of the name of the files to save the merged graph to
+    #[clap(short, long = "output-file")]
+    output_file: String,
+
+    /// Comma separated list of formats of the output files (e.g. `csv,graphml`), all written from the same graph.
+    /// Defaults to csv, unless it can be inferred from the extension of --output-file
+    #[clap(long, value_enum, value_delimiter = ',')]
+    format: Vec<OutputFormat>,
+
+    /// Compress the output files
+    #[clap(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// Field delimiter of the csv files
+    #[clap(long, default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: u8,
+}
+
+#[derive(clap::Args)]
+struct ConvertArgs {
+    /// Path of the edge list, a csv file with a line for each edge, optionally compressed with gzip or zstd.
+    /// A snapshot written with --snapshot can be given instead, and its whole graph becomes the crawl stage
+    edge_list: String,
+
+    /// Directory of the staged crawl to create. The edge list takes the place of the crawl stage
+    stage_dir: std::path::PathBuf,
+
+    /// Column with the source of each edge: its name in the header, or its index starting from 0 with --no-header.
+    /// The values of the source and target columns identify the nodes, and are written in place of their urls
+    #[clap(long, default_value = "source")]
+    source_column: String,
+
+    /// Column with the target of each edge, like --source-column
+    #[clap(long, default_value = "target")]
+    target_column: String,
+
+    /// Column with the weight of each edge, like --source-column. Without it, every line counts as one link
+    #[clap(long)]
+    weight_column: Option<String>,
+
+    /// The edge list has no header, and its columns are given by their index
+    #[clap(long, default_value_t = false)]
+    no_header: bool,
+
+    /// Field delimiter of the edge list
+    #[clap(long, default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: u8,
+}
+
+#[derive(clap::Args)]
+struct StageArgs {
+    /// Directory of the staged crawl, as given to --stage-dir
+    stage_dir: std::path::PathBuf,
+}
+
+#[derive(clap::Args)]
+struct EnrichArgs {
+    #[clap(flatten)]
+    stage: StageArgs,
+
+    /// Number of days, up to yesterday, whose views are summed
+    #[clap(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    days: u64,
+
+    /// Base url of the Wikimedia REST API
+    #[clap(long, default_value = "https://wikimedia.org/api/rest_v1")]
+    pageviews_api: String,
+
+    /// Path of the articles of the wiki, used to find their titles
+    #[clap(long, default_value = "/wiki/", value_parser = parse_path)]
+    article_path: String,
+
+    /// User-Agent header sent with every request. The Wikimedia APIs ask for one that identifies the tool and its operator
+    #[clap(long, default_value = http::DEFAULT_USER_AGENT)]
+    user_agent: String,
+}
+
+#[derive(clap::Args)]
+struct ExportArgs {
+    #[clap(flatten)]
+    stage: StageArgs,
+
+    /// The first part of the name of the files to save the graph to
+    #[clap(short, long = "output-file")]
+    output_file: String,
+
+    /// Comma separated list of formats of the output files (e.g. `csv,graphml`), all written from the same graph.
+    /// Defaults to csv, unless it can be inferred from the extension of --output-file
+    #[clap(long, value_enum, value_delimiter = ',')]
+    format: Vec<OutputFormat>,
+
+    /// Compress the output files
+    #[clap(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// Field delimiter of the csv files
+    #[clap(long, default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: u8,
+
+    #[clap(flatten)]
+    filters: FilterArgs,
+}
+
+#[derive(clap::Args)]
+struct FilterArgs {
+    /// Only export the pages matching this expression, along with the links between them,
+    /// e.g. `degree > 2 && depth <= 3 && node_type == 'article'`.
+    /// It compares the columns of the nodes, as well as `url`, `depth`, `degree`, `in_degree` and `out_degree`,
+    /// with numbers, strings between quotes, `true` or `false`, and combines the comparisons with `&&`, `||`, `!` and parentheses
+    #[clap(long = "where")]
+    node_filter: Option<Filter>,
+
+    /// Only export the links matching this expression, like --where, e.g. `weight > 1 && type == 'body'`.
+    /// It compares the columns of the edges, as well as `weight`
+    #[clap(long = "where-edges")]
+    edge_filter: Option<Filter>,
+}
+
+#[derive(clap::Args)]
+struct SimulateArgs {
+    /// The first part of the name of the csv files of the exported graph, as given to --output-file,
+    /// or the path of a snapshot written with --snapshot
+    prefix: String,
+
+    /// Url of the page to start from. Defaults to the seed of the exported crawl
+    #[clap(long)]
+    seed: Option<String>,
+
+    /// Depth of the simulated crawl
+    #[clap(short, long, default_value_t = 5, value_parser=clap::value_parser!(u64).range(1..))]
+    depth: u64,
+
+    /// Order in which the pages are expanded
+    #[clap(long, value_enum, default_value_t = SimulationStrategy::Bfs)]
+    strategy: SimulationStrategy,
+
+    /// Maximum number of pages to expand
+    #[clap(long, value_parser=clap::value_parser!(u64).range(1..))]
+    max_pages: Option<u64>,
+
+    /// Field delimiter of the csv files, both of the exported graph and of the output files
+    #[clap(long, default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: u8,
+
+    /// The first part of the name of the files to save the simulated graph to
+    #[clap(short, long = "output-file")]
+    output_file: Option<String>,
+
+    /// Comma separated list of formats of the output files (e.g. `csv,graphml`), all written from the same graph.
+    /// Defaults to csv, unless it can be inferred from the extension of --output-file
+    #[clap(long, value_enum, value_delimiter = ',')]
+    format: Vec<OutputFormat>,
+
+    /// Compress the output files
+    #[clap(long, value_enum)]
+    compress: Option<Compression>,
+
+    /// Wheter to generate an undirected graph
+    #[clap(long, default_value_t = false)]
+    undirected: bool,
+}
+
+/// Reads the seeds in a file, one per line, skipping blank lines and comments starting with `#`
+fn read_seeds_file(path: &path::Path) -> std::io::Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+/// Returns the url of a seed given on the command line, either as an url or as the title of an article
+fn resolve_seed(seed: &str, args: &Args) -> Result<String, String> {
+    if args.site_mode == SiteMode::Generic {
+        return match reqwest::Url::parse(seed) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url.to_string()),
+            _ => Err(format!("Invalid url {seed}")),
+        };
+    }
+    if let Some(base_url) = &args.base_url {
+        return Ok(worker::wiki_seed_url(seed, base_url, &args.article_path));
+    }
+    worker::wikipedia_seed_url(seed, args.lang.as_deref()).ok_or_else(|| match &args.lang {
+        Some(lang) => format!("Cannot scrape {seed} in the `{lang}` wikipedia: the url is not a wikipedia one, or the language is not valid"),
+        None => format!("Invalid url {seed}"),
+    })
+}
+
+/// Parses the credentials of the basic authentication given on the command line as `user:password`
+fn parse_basic_auth(credentials: &str) -> Result<Auth, String> {
+    match credentials.split_once(':') {
+        Some((user, password)) if !user.is_empty() => Ok(Auth::Basic {
+            user: user.to_owned(),
+            password: password.to_owned(),
+        }),
+        _ => Err("Expected the credentials in the form `user:password`".to_owned()),
+    }
+}
+
+/// Parses the url of a wiki given on the command line, which has to be an http or https one
+fn parse_base_url(url: &str) -> Result<reqwest::Url, String> {
+    match reqwest::Url::parse(url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url),
+        _ => Err(format!(
+            "Expected an http or https url, e.g. https://wiki.example.com, found `{url}`"
+        )),
+    }
+}
+
+/// Parses a path of a wiki given on the command line, which has to be absolute
+fn parse_path(path: &str) -> Result<String, String> {
+    if path.starts_with('/') {
+        Ok(path.to_owned())
+    } else {
+        Err(format!("Expected a path starting with `/`, found `{path}`"))
+    }
+}
+
+/// Checks that the path of a snapshot given on the command line ends with its extension, optionally followed by a compression
+fn parse_snapshot_path(path: &str) -> Result<String, String> {
+    if snapshot::is_snapshot(path) {
+        Ok(path.to_owned())
+    } else {
+        Err(format!(
+            "Expected a path ending with `{}`, optionally followed by `.gz` or `.zst`, found `{path}`",
+            snapshot::EXTENSION
+        ))
+    }
+}
+
+/// Parses a regular expression given on the command line
+fn parse_regex(pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(pattern)
+        .map_err(|err| format!("Invalid regular expression `{pattern}`: {err}"))
+}
+
+/// Checks that a CSS selector given on the command line is valid
+fn parse_selector(selector: &str) -> Result<String, String> {
+    ::scraper::Selector::parse(selector)
+        .map(|_| selector.to_owned())
+        .map_err(|err| format!("Invalid CSS selector `{selector}`: {err:?}"))
+}
+
+/// Parses a fraction given on the command line, greater than 0 and at most 1
+fn parse_fraction(fraction: &str) -> Result<f64, String> {
+    match fraction.parse::<f64>() {
+        Ok(value) if value > 0.0 && value <= 1.0 => Ok(value),
+        _ => Err(format!(
+            "Expected a number greater than 0 and at most 1, e.g. 0.1, found `{fraction}`"
+        )),
+    }
+}
+
+/// Returns the output prefix, formats and compression. Without an explicit format, they are inferred from the
+/// extension of the output file if possible, e.g. `graph.graphml.gz` is written as graphml compressed with gzip
+fn resolve_output(
+    output_file: Option<String>,
+    mut formats: Vec<OutputFormat>,
+    compress: Option<Compression>,
+) -> (Option<String>, Vec<OutputFormat>, Compression) {
+    if formats.is_empty() {
+        if let Some((prefix, format, compression)) =
+            output_file.as_deref().and_then(OutputFormat::infer)
+        {
+            // An explicit compression replaces the one of the extension
+            let compression = compress.unwrap_or(compression);
+            return (Some(prefix.to_owned()), vec![format], compression);
         }
+        formats.push(OutputFormat::default());
+    }
+    // Keep the first occurrence of each format, so that no file is written twice
+    let mut seen = Vec::new();
+    formats.retain(|format| {
+        let first = !seen.contains(format);
+        seen.push(*format);
+        first
+    });
+    (output_file, formats, compress.unwrap_or_default())
+}
 
-        let nodes_file_path = format!("{}_nodes.csv", output_file_path);
-        if path::Path::new(&nodes_file_path).exists() {
-            return Err(format!("File {nodes_file_path} already exists. Delete it and run the program again if you want to use that path.").into());
+/// Checks that the output files for the prefix `output_file` can be written and that none of them already exist
+fn check_output_files(
+    output_file: &str,
+    formats: &[OutputFormat],
+    options: &OutputOptions,
+) -> Result<(), Box<dyn Error>> {
+    for format in formats {
+        format
+            .check_supported(options)
+            .map_err(|err| err.to_string())?;
+        for file_path in format.file_paths(output_file, options) {
+            if path::Path::new(&file_path).exists() {
+                return Err(format!("File {file_path} already exists. Delete it and run the program again if you want to use that path.").into());
+            }
         }
     }
+    Ok(())
+}
+
+fn simulate(args: SimulateArgs) -> Result<(), Box<dyn Error>> {
+    let (output_file, formats, compression) =
+        resolve_output(args.output_file, args.format, args.compress);
+    let output_options = OutputOptions {
+        delimiter: args.delimiter,
+        compression,
+    };
+    if let Some(output_file_path) = &output_file {
+        check_output_files(output_file_path, &formats, &output_options)?;
+    }
+
+    let graph = ExportedGraph::open(&args.prefix, args.delimiter)?;
+    eprintln!("[INFO] Loaded {} pages", graph.num_pages());
+
+    let simulation = graph.simulate(SimulationConfig {
+        seed: args.seed,
+        depth: args.depth,
+        strategy: args.strategy,
+        max_pages: args.max_pages.map(|max| max as usize),
+        graph: ScraperConfig {
+            undirected: args.undirected,
+            ..Default::default()
+        },
+    })?;
+
+    if let Some(output_file_path) = &output_file {
+        simulation.save_to_file(output_file_path, &formats, &output_options)?;
+    }
+    println!(
+        "Expanded {expanded} pages, found {num_pages} pages and {num_links} links",
+        expanded = simulation.expanded,
+        num_pages = simulation.graph.num_pages(),
+        num_links = simulation.graph.num_links()?
+    );
+    Ok(())
+}
 
-    let mut scraper = WikipediaScraper::new(
-        &args.url,
+/// Config of the graph of a staged crawl, which is loaded from its stage directory rather than built by a crawl
+fn stage_config() -> Arc<ScraperConfig> {
+    Arc::new(ScraperConfig::default())
+}
+
+fn enrich(args: EnrichArgs) -> Result<(), Box<dyn Error>> {
+    let stage_dir = StageDir::new(args.stage.stage_dir);
+    let config = ScraperConfig {
+        user_agent: args.user_agent,
+        ..Default::default()
+    };
+    let client = HttpClient::new(&config);
+    let graph = stage_dir.load_graph(stage_config())?;
+    let (enriched, failed) = stage_dir.enrich(
+        &graph,
+        &client,
+        &EnrichConfig {
+            pageviews_api: args.pageviews_api,
+            days: args.days,
+            article_path: args.article_path,
+            retry: config.retry.clone(),
+        },
+    )?;
+    println!("Enriched {enriched} pages");
+    if failed > 0 {
+        return Err(format!(
+            "Could not fetch the pageviews of {failed} pages. Run the stage again to retry them"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn analyze(args: StageArgs) -> Result<(), Box<dyn Error>> {
+    let stage_dir = StageDir::new(args.stage_dir);
+    let graph = stage_dir.load_graph(stage_config())?;
+    let analyzed = stage_dir.analyze(&graph)?;
+    println!("Analyzed {analyzed} pages");
+    Ok(())
+}
+
+fn export(args: ExportArgs) -> Result<(), Box<dyn Error>> {
+    let (output_file, formats, compression) =
+        resolve_output(Some(args.output_file), args.format, args.compress);
+    let output_file = output_file.expect("The output file is required");
+    let output_options = OutputOptions {
+        delimiter: args.delimiter,
+        compression,
+    };
+    check_output_files(&output_file, &formats, &output_options)?;
+
+    let stage_dir = StageDir::new(args.stage.stage_dir);
+    let config = ScraperConfig {
+        node_filter: args.filters.node_filter,
+        edge_filter: args.filters.edge_filter,
+        ..Default::default()
+    };
+    let graph = stage_dir.load_graph(Arc::new(config))?;
+    stage_dir.load_results(&graph)?;
+    graph.check_filters(&graph.schema())?;
+    graph.save_to_file(&output_file, &formats, &output_options)?;
+    println!(
+        "Exported {num_pages} pages and {num_links} links",
+        num_pages = graph.num_pages(),
+        num_links = graph.num_links()?
+    );
+    Ok(())
+}
+
+fn convert(args: ConvertArgs) -> Result<(), Box<dyn Error>> {
+    let stage_dir = StageDir::new(args.stage_dir);
+    let format = EdgeListFormat {
+        source_column: args.source_column,
+        target_column: args.target_column,
+        weight_column: args.weight_column,
+        has_header: !args.no_header,
+        delimiter: args.delimiter,
+    };
+    if snapshot::is_snapshot(&args.edge_list) {
+        let graph = snapshot::load(&args.edge_list, stage_config())?;
+        stage_dir.create()?;
+        stage_dir.write_graph(&graph)?;
+        println!(
+            "Converted the snapshot into {num_pages} pages and {num_links} links",
+            num_pages = graph.num_pages(),
+            num_links = graph.num_links()?
+        );
+        return Ok(());
+    }
+    let graph = Graph::new(stage_config());
+    let lines = edge_list::load(&args.edge_list, &format, &graph, IdWidth::U64)?;
+    stage_dir.create()?;
+    stage_dir.write_graph(&graph)?;
+    println!(
+        "Converted {lines} lines into {num_pages} pages and {num_links} links",
+        num_pages = graph.num_pages(),
+        num_links = graph.num_links()?
+    );
+    Ok(())
+}
+
+fn estimate(args: EstimateArgs) -> Result<(), Box<dyn Error>> {
+    let seeds = vec![args.url];
+    let config = ScraperConfig {
+        depth: args.depth,
+        concurrency: args.concurrency as usize,
+        ..Default::default()
+    };
+    let sample = Sample::take(&seeds, config, args.sample as usize)?;
+    let projection = sample.project(args.depth, args.max_pages.map(|max| max as usize));
+
+    if sample.complete {
+        println!("The whole crawl has been sampled");
+    } else {
+        println!(
+            "Sampled {} pages: {:.1} links per page, {:.0}% of them to new pages, {} per page",
+            sample.expanded,
+            sample.links_per_page(),
+            sample.new_page_ratio() * 100.0,
+            http::format_bytes(sample.bytes / sample.expanded.max(1) as u64)
+        );
+    }
+    println!(
+        "Depth {}: about {:.0} pages fetched, {:.0} pages and {:.0} links in the graph, {} downloaded in {}",
         args.depth,
-        args.num_threads as usize,
-        args.keywords,
-        args.undirected,
-        args.keep_external_links,
+        projection.fetched,
+        projection.pages,
+        projection.links,
+        http::format_bytes(projection.bytes as u64),
+        format_duration(projection.runtime)
+    );
+    Ok(())
+}
+
+fn bridge(args: BridgeArgs) -> Result<(), Box<dyn Error>> {
+    let [first, second]: [String; 2] = args.languages.try_into().map_err(|languages: Vec<_>| {
+        format!(
+            "Expected two languages to compare, found {}",
+            languages.len()
+        )
+    })?;
+    if first == second {
+        return Err(format!("Expected two different languages, found `{first}` twice").into());
+    }
+    let seed = worker::wikipedia_seed_url(&args.seed, Some(&first))
+        .ok_or_else(|| format!("Cannot scrape {} in the `{first}` wikipedia", args.seed))?;
+    let (output_file, formats, compression) =
+        resolve_output(Some(args.output_file), args.format, args.compress);
+    let output_file = output_file.expect("The output file is required");
+    let output_options = OutputOptions {
+        delimiter: args.delimiter,
+        compression,
+    };
+    check_output_files(&output_file, &formats, &output_options)?;
+
+    let config = ScraperConfig {
+        depth: args.depth,
+        max_pages: args.max_pages.map(|max| max as usize),
+        concurrency: args.concurrency as usize,
+        ..Default::default()
+    };
+    let graph = bridge::crawl(&seed, [first.clone(), second.clone()], config)?;
+    graph.save_to_file(&output_file, &formats, &output_options)?;
+    let (both, only_first, only_second) = graph.coverage();
+    println!(
+        "Merged {} items and {} links: {both} articles in both languages, {only_first} only in `{first}`, {only_second} only in `{second}`",
+        graph.items.len(),
+        graph.links.len()
     );
-    scraper.scrape()?;
+    Ok(())
+}
+
+fn path(args: PathArgs) -> Result<(), Box<dyn Error>> {
+    let config = ScraperConfig {
+        depth: args.depth,
+        max_pages: args.max_pages.map(|max| max as usize),
+        concurrency: args.concurrency as usize,
+        ..Default::default()
+    };
+    let article_url = |article: &str| {
+        worker::wikipedia_seed_url(article, None)
+            .map(|url| links::normalize(&url, Some(&config.wiki.article_path)))
+            .ok_or_else(|| format!("Cannot find the article {article}"))
+    };
+    let (from, to) = (article_url(&args.from)?, article_url(&args.to)?);
+
+    let graph = match &args.graph {
+        Some(prefix) => ExportedGraph::open(prefix, args.delimiter)
+            .map_err(|err| format!("Could not load the graph {prefix}: {err}"))?,
+        None => {
+            let seeds = vec![from.clone()];
+            let mut scraper = WikipediaScraper::new(
+                &seeds,
+                ScraperConfig {
+                    stop_at: Some(to.clone()),
+                    ..config
+                },
+            )?;
+            scraper.scrape()?;
+            ExportedGraph::from_graph(&scraper.graph())?
+        }
+    };
+
+    let path = graph
+        .shortest_path(&from, &to)?
+        .ok_or_else(|| format!("No path of links from {from} to {to} has been found"))?;
+    for url in &path {
+        println!("{url}");
+    }
+    eprintln!("[INFO] {} links from {from} to {to}", path.len() - 1);
+    Ok(())
+}
+
+/// Prints the decisions about the page at `url` recorded in `event_log`, with the time since the first one
+fn explain_url(url: &str, event_log: &path::Path) -> Result<(), Box<dyn Error>> {
+    let trail = events::trail(event_log, url).map_err(|err| {
+        format!(
+            "Could not read the event log {}: {err}",
+            event_log.display()
+        )
+    })?;
+    let Some(first) = trail.first() else {
+        return Err(format!(
+            "No decision about {url} has been recorded in {}. The crawl has not found any link to it",
+            event_log.display()
+        )
+        .into());
+    };
+    let start = first.at;
+    for event in &trail {
+        println!(
+            "+{:.3}s {}: {}",
+            event.at.saturating_sub(start) as f64 / 1000.0,
+            event.url,
+            event.decision
+        );
+    }
+    Ok(())
+}
+
+/// Formats a duration with its two largest units, e.g. `2h 5m`
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match (
+        seconds / 86400,
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+    ) {
+        (0, 0, 0, s) => format!("{s}s"),
+        (0, 0, m, s) => format!("{m}m {s}s"),
+        (0, h, m, _) => format!("{h}h {m}m"),
+        (d, h, _, _) => format!("{d}d {h}h"),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    match args.command {
+        Some(Command::Simulate(simulate_args)) => return simulate(simulate_args),
+        Some(Command::Enrich(enrich_args)) => return enrich(enrich_args),
+        Some(Command::Analyze(analyze_args)) => return analyze(analyze_args),
+        Some(Command::Export(export_args)) => return export(export_args),
+        Some(Command::Convert(convert_args)) => return convert(convert_args),
+        Some(Command::Estimate(estimate_args)) => return estimate(estimate_args),
+        Some(Command::Bridge(bridge_args)) => return bridge(bridge_args),
+        Some(Command::Path(path_args)) => return path(path_args),
+        None => {}
+    }
+    let explain = match &args.explain {
+        Some(url) => {
+            let article_path =
+                (args.site_mode == SiteMode::Wiki).then_some(args.article_path.as_str());
+            Some(links::normalize(&resolve_seed(url, &args)?, article_path))
+        }
+        None => None,
+    };
+    let has_seeds = !args.urls.is_empty()
+        || args.resume.is_some()
+        || args.seeds_file.is_some()
+        || args.category.is_some()
+        || args.random_seeds.is_some()
+        || args.preset.is_some();
+    if let (Some(url), false) = (&explain, has_seeds) {
+        let event_log = args.event_log.as_deref().ok_or(
+            "--explain without seeds requires --event-log, to read the decisions of a crawl",
+        )?;
+        return explain_url(url, event_log);
+    }
+    let stage_dir = args.stage_dir.clone().map(StageDir::new);
+    if let Some(stage_dir) = &stage_dir {
+        stage_dir
+            .create()
+            .map_err(|err| format!("Could not create the stage directory: {err}"))?;
+    }
+    let checkpoint = match &args.resume {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path).map_err(|err| {
+                format!("Could not load the checkpoint {}: {err}", path.display())
+            })?;
+            eprintln!(
+                "[INFO] Resuming the crawl of {} with {} pages and {} pages in the queue",
+                checkpoint.seeds.join(", "),
+                checkpoint.pages.len(),
+                checkpoint.queue.len()
+            );
+            Some(checkpoint)
+        }
+        None => None,
+    };
+    let seeds = match &checkpoint {
+        Some(checkpoint) => checkpoint.seeds.clone(),
+        None => {
+            let mut seeds = args.urls.clone();
+            if let Some(path) = &args.seeds_file {
+                seeds.extend(read_seeds_file(path).map_err(|err| {
+                    format!("Could not read the seeds file {}: {err}", path.display())
+                })?);
+            }
+            let mut urls = Vec::new();
+            for seed in &seeds {
+                let url = resolve_seed(seed, &args)?;
+                if !urls.contains(&url) {
+                    urls.push(url);
+                }
+            }
+            urls
+        }
+    };
 
-    if let Some(output_file_path) = &args.output_file {
-        scraper.save_to_file(output_file_path)?;
+    let category = match &args.category {
+        Some(category) => Some(CategoryConfig {
+            url: resolve_seed(category, &args)?,
+            depth: args.category_depth,
+        }),
+        None => None,
+    };
+    let random_seeds = match args.random_seeds {
+        Some(count) => Some(RandomSeeds {
+            url: resolve_seed("Special:Random", &args)?,
+            count,
+        }),
+        None => None,
+    };
+    let preset = match args.preset {
+        Some(preset) => Some(PresetSeeds {
+            preset,
+            url: resolve_seed(preset.page_title(), &args)?,
+        }),
+        None => None,
+    };
+
+    let (output_file, formats, compression) =
+        resolve_output(args.output_file, args.format, args.compress);
+    let output_options = OutputOptions {
+        delimiter: args.delimiter,
+        compression,
+    };
+
+    if let Some(output_file_path) = &output_file {
+        check_output_files(output_file_path, &formats, &output_options)?;
+        if args.split_by_keyword {
+            for keyword in args.keywords.iter().flatten() {
+                let prefix = graph::keyword_prefix(output_file_path, keyword);
+                check_output_files(&prefix, &formats, &output_options)?;
+            }
+        }
+        // The windows are numbered from 1 in every crawl, so the ones of a previous crawl would be overwritten
+        if args.window_nodes.is_some() {
+            let prefix = graph::window_prefix(output_file_path, 1);
+            check_output_files(&prefix, &formats, &output_options)?;
+        }
+        if args.keep_external_links == Some(ExternalLinks::Separate) {
+            let file_path = graph::external_edges_path(output_file_path, &output_options);
+            if path::Path::new(&file_path).exists() {
+                return Err(format!("File {file_path} already exists. Delete it and run the program again if you want to use that path.").into());
+            }
+        }
+    }
+    if let Some(snapshot_path) = &args.snapshot {
+        if path::Path::new(snapshot_path).exists() {
+            return Err(format!("File {snapshot_path} already exists. Delete it and run the program again if you want to use that path.").into());
+        }
+    }
+
+    let rate_limited =
+        args.delay_ms.is_some() || args.requests_per_second.is_some() || args.adaptive_rate_limit;
+    if args.continuous && !rate_limited {
+        eprintln!("[WARN] The continuous crawl has no rate limit. Consider setting --delay-ms or --requests-per-second to keep it polite");
+    }
+    let config = ScraperConfig {
+        depth: if args.continuous {
+            UNLIMITED_DEPTH
+        } else {
+            args.depth
+        },
+        strategy: args.strategy,
+        max_pages: args.max_pages.map(|max| max as usize),
+        max_duration: args.max_duration_secs.map(Duration::from_secs),
+        stop_at: None,
+        num_threads: args.num_threads as usize,
+        concurrency: args.concurrency as usize,
+        site_mode: args.site_mode,
+        same_domain_only: args.same_domain_only,
+        wiki: WikiConfig {
+            article_path: args.article_path,
+            api_path: args.api_path,
+            content_selector: args
+                .content_selector
+                .unwrap_or_else(|| match args.site_mode {
+                    SiteMode::Wiki => WikiConfig::default().content_selector,
+                    SiteMode::Generic => "body".to_owned(),
+                }),
+            namespaces: args.include_namespaces,
+        },
+        link_scope: args.link_scope,
+        use_api: args.use_api,
+        category,
+        random_seeds,
+        preset,
+        keywords: args.keywords,
+        keyword_mode: args.keyword_mode,
+        exclude_keywords: args.exclude_keywords,
+        keywords_from_hop: args.keywords_from_hop,
+        url_filter: UrlFilter {
+            allow: args.url_allow,
+            deny: args.url_deny,
+        },
+        undirected: args.undirected,
+        weighted: args.weighted,
+        ghost_nodes: args.ghost_nodes,
+        split_by_keyword: args.split_by_keyword,
+        output_file: output_file.clone(),
+        record_keyword_matches: args.split_by_keyword
+            || formats.contains(&OutputFormat::PagesJsonl),
+        degrees: args.degrees,
+        components: args.components,
+        giant_component_only: args.giant_component_only,
+        pagerank: args.pagerank,
+        top_fraction: args.top_fraction,
+        centrality: args.centrality,
+        centrality_samples: args.centrality_samples as usize,
+        clustering: args.clustering,
+        layout: args.layout,
+        layout_iterations: args.layout_iterations as usize,
+        node_filter: args.filters.node_filter,
+        edge_filter: args.filters.edge_filter,
+        provenance: args.provenance,
+        link_positions: args.link_positions,
+        edge_types: args.edge_types,
+        gephi_stream: args.gephi_stream,
+        warn_nodes: args.warn_nodes,
+        warn_edges: args.warn_edges,
+        max_empty_pages: (args.max_empty_pages > 0).then_some(args.max_empty_pages),
+        page_timeout: (args.page_timeout_secs > 0)
+            .then(|| Duration::from_secs(args.page_timeout_secs)),
+        revisit: RevisitConfig {
+            after: args.revisit_after_secs.map(Duration::from_secs),
+            after_hops: args.revisit_after_hops,
+        },
+        frontier_ttl: args.frontier_ttl_secs.map(Duration::from_secs),
+        keep_external_links: args.keep_external_links,
+        check_external: args.check_external,
+        ignore_canonical: args.ignore_canonical,
+        skip_disambiguation: args.skip_disambiguation,
+        id_width: if args.small_ids {
+            IdWidth::U32
+        } else {
+            IdWidth::U64
+        },
+        stop_expanding_at_degree: args.stop_expanding_at_degree.map(|degree| degree as usize),
+        max_links_per_page: args.max_links_per_page.map(|max| max as usize),
+        link_sampling: args.link_sampling,
+        all_languages_of_seed: args.all_languages_of_seed,
+        languages: args.languages,
+        interlanguage_links: args.interlanguage_links,
+        interlanguage_depth: args.interlanguage_depth,
+        max_edges_in_memory: args.max_edges_in_memory.map(|max| max as usize),
+        spill_dir: args.spill_dir,
+        user_agent: args.user_agent,
+        headers: args.headers,
+        auth: args.auth_basic.or(args.auth_bearer.map(Auth::Bearer)),
+        bot_login: args
+            .auth_bot_user
+            .zip(args.auth_bot_pass)
+            .map(|(user, password)| BotLogin { user, password }),
+        proxy: args.proxy,
+        cache_dir: args.cache_dir,
+        revalidate_cache: args.revalidate_cache,
+        stale_while_revalidate: args.stale_while_revalidate_secs.map(Duration::from_secs),
+        checkpoint: stage_dir
+            .map(|stage_dir| stage_dir.checkpoint())
+            .or(args.checkpoint)
+            .or(args.resume),
+        checkpoint_interval: Duration::from_secs(args.checkpoint_interval_secs),
+        max_page_attempts: args.max_page_attempts,
+        event_log: args.event_log.clone(),
+        explain,
+        retry: RetryConfig {
+            max_attempts: args.max_attempts,
+            base_delay: Duration::from_millis(args.retry_base_delay_ms),
+            ..Default::default()
+        },
+        rate_limit: match (args.delay_ms, args.requests_per_second) {
+            (Some(delay_ms), _) => {
+                Some(RateLimitConfig::with_delay(Duration::from_millis(delay_ms)))
+            }
+            (None, Some(requests_per_second)) => Some(RateLimitConfig {
+                requests_per_second,
+                burst: args.burst,
+            }),
+            (None, None) => None,
+        },
+        external_rate_limit: args
+            .external_requests_per_second
+            .map(|requests_per_second| RateLimitConfig {
+                requests_per_second,
+                burst: args.external_burst,
+            }),
+        adaptive_rate_limit: args.adaptive_rate_limit.then(|| AdaptiveRateConfig {
+            target_latency: Duration::from_millis(args.target_latency_ms),
+            max_rate: args.max_rate,
+            ..Default::default()
+        }),
+        max_bandwidth: args.max_bandwidth,
+    };
+
+    let mut scraper = WikipediaScraper::new(&seeds, config)?;
+    let graph = scraper.graph();
+    graph.check_filters(&graph.schema())?;
+    if let Some(checkpoint) = checkpoint {
+        scraper.resume(checkpoint);
+    }
+    if let Some(prefix) = &args.import_graph {
+        let graph = ExportedGraph::open(prefix, args.delimiter)
+            .map_err(|err| format!("Could not load the graph {prefix}: {err}"))?;
+        scraper.import(graph);
+    }
+    let output = output_file
+        .clone()
+        .map(|output_file| (output_file, formats.clone(), output_options));
+    control::install_signal_handlers(scraper.handle(output.clone()))?;
+
+    let _control_socket = match &args.control_socket {
+        Some(socket_path) => Some(control::serve_socket(
+            socket_path,
+            scraper.handle(output.clone()),
+        )?),
+        None => None,
+    };
+    let windows = match (args.window_nodes, output) {
+        (Some(nodes), Some(output)) => Some(window::emit_periodically(
+            scraper.graph(),
+            output,
+            nodes as usize,
+            Duration::from_secs(args.window_interval_secs),
+        )),
+        _ => None,
+    };
+
+    if let Some(interval) = args.keyword_stats_interval {
+        keywords::report_periodically(scraper.keywords(), Duration::from_secs(interval));
+    }
+    let scraped = scraper.scrape();
+    if let Some(windows) = windows {
+        windows.stop();
+    }
+    scraped?;
+
+    if let Some(snapshot_path) = &args.snapshot {
+        snapshot::write(snapshot_path, &scraper.graph())?;
+    }
+    if let Some(output_file_path) = &output_file {
+        scraper.save_to_file(output_file_path, &formats, &output_options)?;
+        if args.split_by_keyword {
+            scraper
+                .graph()
+                .save_keyword_subgraphs(output_file_path, &formats, &output_options)?;
+        }
+    } else if args.stdout {
+        let (delimiter, nodes) = (args.delimiter, args.stdout_nodes);
+        let graph = scraper.graph();
+        let mut writer = graph.writer.writer(Priority::Foreground, move || {
+            Ok(vec![output::stdout_writer(delimiter, nodes)])
+        })?;
+        graph.export(&mut writer)?;
+        writer.finish()?;
     } else {
         println!(
             "Found {num_pages} pages and {num_links} links",
             num_pages = scraper.num_pages(),
-            num_links = scraper.num_links()
+            num_links = scraper.num_links()?
         );
     }
 
@@ -0,0 +1,534 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::config::{BotLogin, RetryConfig, WikiConfig};
+use crate::errors::ScraperError;
+use crate::http::HttpClient;
+use crate::links;
+use crate::worker::{get_text_with_retry, PageLinks};
+
+/// Maximum number of random articles returned by a request to the Action API
+const RANDOM_LIMIT: u64 = 500;
+
+/// Maximum number of titles in a request to the Action API
+const TITLES_LIMIT: usize = 50;
+
+/// Characters of a title that are escaped in the path of an article url
+const TITLE_ESCAPES: &AsciiSet = &CONTROLS.add(b'%').add(b'?').add(b'#').add(b'"');
+
+#[derive(Debug, Deserialize)]
+struct LinksResponse {
+    /// Parameters to send with the next request to get the rest of the links. Missing in the last response
+    #[serde(rename = "continue")]
+    continuation: Option<HashMap<String, String>>,
+    query: Option<LinksQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LinksQuery {
+    #[serde(default)]
+    pages: Vec<LinksPage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LinksPage {
+    /// Title of the page, after resolving the redirects
+    title: String,
+    #[serde(default)]
+    links: Vec<LinkTarget>,
+    /// Properties of the page that have been asked for, e.g. `disambiguation`
+    #[serde(default)]
+    pageprops: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LinkTarget {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CategoryMembersResponse {
+    /// Parameters to send with the next request to get the rest of the members. Missing in the last response
+    #[serde(rename = "continue")]
+    continuation: Option<HashMap<String, String>>,
+    query: Option<CategoryMembersQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CategoryMembersQuery {
+    #[serde(default)]
+    categorymembers: Vec<CategoryMember>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CategoryMember {
+    /// Id of the namespace of the page
+    ns: i32,
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RandomResponse {
+    query: Option<RandomQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RandomQuery {
+    #[serde(default)]
+    random: Vec<RandomPage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RandomPage {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemsResponse {
+    query: Option<ItemsQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemsQuery {
+    /// Titles that have been normalized by the API, e.g. with their first letter capitalized
+    #[serde(default)]
+    normalized: Vec<TitleChange>,
+    #[serde(default)]
+    redirects: Vec<TitleChange>,
+    #[serde(default)]
+    pages: Vec<LinksPage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TitleChange {
+    from: String,
+    to: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokensResponse {
+    query: TokensQuery,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokensQuery {
+    tokens: Tokens,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tokens {
+    logintoken: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginResponse {
+    login: LoginResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginResult {
+    /// `Success` if the login succeeded
+    result: String,
+    reason: Option<String>,
+}
+
+/// Returns the title of the article at `url`, whose path starts with `article_path`,
+/// e.g. `Nile crocodile` for `https://en.wikipedia.org/wiki/Nile_crocodile`
+pub fn article_title(url: &Url, article_path: &str) -> Option<String> {
+    let title = url.path().strip_prefix(article_path)?;
+    let title = percent_decode_str(title).decode_utf8().ok()?;
+    Some(title.replace('_', " "))
+}
+
+/// Returns the url of the article with `title` in the same wiki as `page_url`, whose articles have paths starting with `article_path`
+pub fn article_url(page_url: &Url, title: &str, article_path: &str) -> String {
+    let path = utf8_percent_encode(&title.replace(' ', "_"), TITLE_ESCAPES).to_string();
+    let mut url = page_url.clone();
+    url.set_path(&format!("{article_path}{path}"));
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
+
+/// Fetches the links to the articles of the page at `page_url` from the Action API of its wiki,
+/// following the continuations until every link has been returned.
+/// Redirects are resolved by the API, so a redirect page has the url of its target as its canonical url.
+pub async fn get_page_links(
+    client: &HttpClient,
+    page_url: &str,
+    wiki: &WikiConfig,
+    retry: &RetryConfig,
+) -> Result<PageLinks, ScraperError> {
+    let invalid_url = || ScraperError::InvalidUrl(page_url.to_owned());
+    let page_url = Url::parse(page_url).map_err(|_| invalid_url())?;
+    let title = article_title(&page_url, &wiki.article_path).ok_or_else(invalid_url)?;
+
+    let namespaces = namespace_ids(&wiki.namespaces, None);
+    let mut api_url = api_url(&page_url, wiki);
+    api_url
+        .query_pairs_mut()
+        .append_pair("action", "query")
+        .append_pair("format", "json")
+        .append_pair("formatversion", "2")
+        .append_pair("prop", "links|pageprops")
+        .append_pair("ppprop", "disambiguation")
+        .append_pair("plnamespace", &namespaces)
+        .append_pair("pllimit", "max")
+        .append_pair("redirects", "1")
+        .append_pair("titles", &title);
+
+    let mut canonical_url = None;
+    let mut disambiguation = false;
+    let mut anchors = Vec::new();
+    let mut continuation = HashMap::new();
+    loop {
+        let mut request_url = api_url.clone();
+        request_url.query_pairs_mut().extend_pairs(&continuation);
+        let body = get_text_with_retry(client, request_url.as_str(), retry).await?;
+        let response: LinksResponse = parse_response(request_url.as_str(), &body)?;
+
+        for page in response.query.into_iter().flat_map(|query| query.pages) {
+            canonical_url
+                .get_or_insert_with(|| article_url(&page_url, &page.title, &wiki.article_path));
+            disambiguation |= page.pageprops.contains_key("disambiguation");
+            anchors.extend(
+                page.links
+                    .iter()
+                    .map(|link| article_url(&page_url, &link.title, &wiki.article_path)),
+            );
+        }
+
+        match response.continuation {
+            Some(next) => continuation = next,
+            None => break,
+        }
+    }
+
+    Ok(PageLinks {
+        canonical_url,
+        anchors,
+        disambiguation,
+        edge_types: HashMap::new(),
+        interlanguage: Vec::new(),
+    })
+}
+
+/// Returns the Wikidata item of each of the articles at `urls`, all in the same wiki, e.g. `Q42` for `https://en.wikipedia.org/wiki/Douglas_Adams`.
+/// Redirects are resolved to the item of their target. The articles that are not found, or have no item, are left out
+pub async fn wikidata_items(
+    client: &HttpClient,
+    urls: &[String],
+    wiki: &WikiConfig,
+    retry: &RetryConfig,
+) -> Result<HashMap<String, String>, ScraperError> {
+    let mut items = HashMap::new();
+    for chunk in urls.chunks(TITLES_LIMIT) {
+        let mut titles: HashMap<String, &String> = HashMap::new();
+        let mut request_url = None;
+        for url in chunk {
+            let Ok(page_url) = Url::parse(url) else {
+                continue;
+            };
+            if let Some(title) = article_title(&page_url, &wiki.article_path) {
+                request_url.get_or_insert_with(|| api_url(&page_url, wiki));
+                titles.insert(title, url);
+            }
+        }
+        let Some(mut request_url) = request_url else {
+            continue;
+        };
+        request_url
+            .query_pairs_mut()
+            .append_pair("action", "query")
+            .append_pair("format", "json")
+            .append_pair("formatversion", "2")
+            .append_pair("prop", "pageprops")
+            .append_pair("ppprop", "wikibase_item")
+            .append_pair("redirects", "1")
+            .append_pair(
+                "titles",
+                &titles
+                    .keys()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            );
+        let body = get_text_with_retry(client, request_url.as_str(), retry).await?;
+        let response: ItemsResponse = parse_response(request_url.as_str(), &body)?;
+        let Some(query) = response.query else {
+            continue;
+        };
+
+        let changes = |changes: Vec<TitleChange>| -> HashMap<String, String> {
+            changes
+                .into_iter()
+                .map(|change| (change.from, change.to))
+                .collect()
+        };
+        let normalized = changes(query.normalized);
+        let redirects = changes(query.redirects);
+        let page_items: HashMap<String, String> = query
+            .pages
+            .into_iter()
+            .filter_map(|page| {
+                let item = page.pageprops.get("wikibase_item")?.as_str()?.to_owned();
+                Some((page.title, item))
+            })
+            .collect();
+        for (title, url) in titles {
+            let title = normalized.get(&title).unwrap_or(&title);
+            let title = redirects.get(title).unwrap_or(title);
+            if let Some(item) = page_items.get(title) {
+                items.insert(url.clone(), item.clone());
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Returns the urls of the pages in the category at `category_url`, and in its subcategories up to `depth` levels below it.
+/// The pages are the articles and the pages of the namespaces in `wiki`, so subcategories are only returned if the `Category` namespace is included.
+/// Every subcategory is listed once, since categories may contain each other
+pub async fn category_members(
+    client: &HttpClient,
+    category_url: &str,
+    depth: u64,
+    wiki: &WikiConfig,
+    retry: &RetryConfig,
+) -> Result<Vec<String>, ScraperError> {
+    let invalid_url = || ScraperError::InvalidUrl(category_url.to_owned());
+    let category_url = Url::parse(category_url).map_err(|_| invalid_url())?;
+    let title = article_title(&category_url, &wiki.article_path).ok_or_else(invalid_url)?;
+    let title = match links::namespace(&title) {
+        Some("Category") => title,
+        _ => format!("Category:{title}"),
+    };
+
+    let category_id = links::namespace_id("Category").expect("Category is a namespace");
+    let namespaces = namespace_ids(&wiki.namespaces, Some(category_id));
+    let api_url = api_url(&category_url, wiki);
+
+    let mut members = Vec::new();
+    let mut found = HashSet::new();
+    let mut listed = HashSet::from([title.clone()]);
+    let mut categories = VecDeque::from([(title, 0)]);
+    while let Some((category, level)) = categories.pop_front() {
+        let mut continuation = HashMap::new();
+        loop {
+            let mut request_url = api_url.clone();
+            request_url
+                .query_pairs_mut()
+                .append_pair("action", "query")
+                .append_pair("format", "json")
+                .append_pair("formatversion", "2")
+                .append_pair("list", "categorymembers")
+                .append_pair("cmtitle", &category)
+                .append_pair("cmnamespace", &namespaces)
+                .append_pair("cmlimit", "max")
+                .extend_pairs(&continuation);
+            let body = get_text_with_retry(client, request_url.as_str(), retry).await?;
+            let response: CategoryMembersResponse = parse_response(request_url.as_str(), &body)?;
+
+            for member in response
+                .query
+                .into_iter()
+                .flat_map(|query| query.categorymembers)
+            {
+                if member.ns == category_id {
+                    if level < depth && listed.insert(member.title.clone()) {
+                        categories.push_back((member.title.clone(), level + 1));
+                    }
+                    if !wiki
+                        .namespaces
+                        .iter()
+                        .any(|namespace| namespace == "Category")
+                    {
+                        continue;
+                    }
+                }
+                let url = article_url(&category_url, &member.title, &wiki.article_path);
+                if found.insert(url.clone()) {
+                    members.push(url);
+                }
+            }
+
+            match response.continuation {
+                Some(next) => continuation = next,
+                None => break,
+            }
+        }
+    }
+    Ok(members)
+}
+
+/// Returns the urls of `count` random articles of the wiki of `page_url`, picked by the Action API as Special:Random does.
+/// Fewer are returned if the wiki does not have enough of them
+pub async fn random_articles(
+    client: &HttpClient,
+    page_url: &str,
+    count: u64,
+    wiki: &WikiConfig,
+    retry: &RetryConfig,
+) -> Result<Vec<String>, ScraperError> {
+    let page_url =
+        Url::parse(page_url).map_err(|_| ScraperError::InvalidUrl(page_url.to_owned()))?;
+    let api_url = api_url(&page_url, wiki);
+
+    let mut articles = Vec::new();
+    let mut found = HashSet::new();
+    while (articles.len() as u64) < count {
+        let missing = count - articles.len() as u64;
+        let mut request_url = api_url.clone();
+        request_url
+            .query_pairs_mut()
+            .append_pair("action", "query")
+            .append_pair("format", "json")
+            .append_pair("formatversion", "2")
+            .append_pair("list", "random")
+            .append_pair("rnnamespace", "0")
+            .append_pair("rnfilterredir", "nonredirects")
+            .append_pair("rnlimit", &missing.min(RANDOM_LIMIT).to_string());
+        let body = get_text_with_retry(client, request_url.as_str(), retry).await?;
+        let response: RandomResponse = parse_response(request_url.as_str(), &body)?;
+
+        let before = articles.len();
+        for page in response.query.into_iter().flat_map(|query| query.random) {
+            let url = article_url(&page_url, &page.title, &wiki.article_path);
+            if found.insert(url.clone()) && (articles.len() as u64) < count {
+                articles.push(url);
+            }
+        }
+        // Only the articles already picked are left
+        if articles.len() == before {
+            break;
+        }
+    }
+    Ok(articles)
+}
+
+/// Logs in to the wiki of `page_url` with the Action API, keeping the cookies of the session in the client
+/// so that they are sent with the following requests to the wiki
+pub async fn login(
+    client: &HttpClient,
+    page_url: &str,
+    wiki: &WikiConfig,
+    login: &BotLogin,
+) -> Result<(), ScraperError> {
+    let page_url =
+        Url::parse(page_url).map_err(|_| ScraperError::InvalidUrl(page_url.to_owned()))?;
+    let api_url = api_url(&page_url, wiki);
+    client.start_session(api_url.as_str());
+
+    let mut token_url = api_url.clone();
+    token_url
+        .query_pairs_mut()
+        .append_pair("action", "query")
+        .append_pair("format", "json")
+        .append_pair("formatversion", "2")
+        .append_pair("meta", "tokens")
+        .append_pair("type", "login");
+    let body = client.session_request(token_url.as_str(), None).await?;
+    let tokens: TokensResponse = parse_response(token_url.as_str(), &body)?;
+
+    let form = [
+        ("action", "login"),
+        ("format", "json"),
+        ("formatversion", "2"),
+        ("lgname", login.user.as_str()),
+        ("lgpassword", login.password.as_str()),
+        ("lgtoken", tokens.query.tokens.logintoken.as_str()),
+    ];
+    let body = client
+        .session_request(api_url.as_str(), Some(&form))
+        .await?;
+    let response: LoginResponse = parse_response(api_url.as_str(), &body)?;
+    if response.login.result != "Success" {
+        return Err(ScraperError::LoginFailed {
+            user: login.user.clone(),
+            reason: response.login.reason.unwrap_or(response.login.result),
+        });
+    }
+    Ok(())
+}
+
+/// Ids of the main namespace, of the `namespaces` and of `extra`, separated by `|` as the Action API expects them
+fn namespace_ids(namespaces: &[String], extra: Option<i32>) -> String {
+    let mut ids = std::iter::once(0)
+        .chain(
+            namespaces
+                .iter()
+                .filter_map(|name| links::namespace_id(name)),
+        )
+        .collect::<Vec<_>>();
+    if let Some(extra) = extra.filter(|extra| !ids.contains(extra)) {
+        ids.push(extra);
+    }
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Url of the Action API of the wiki of `page_url`, without any parameter
+fn api_url(page_url: &Url, wiki: &WikiConfig) -> Url {
+    let mut api_url = page_url.clone();
+    api_url.set_path(&wiki.api_path);
+    api_url.set_query(None);
+    api_url.set_fragment(None);
+    api_url
+}
+
+fn parse_response<'a, T: Deserialize<'a>>(url: &str, body: &'a str) -> Result<T, ScraperError> {
+    serde_json::from_str(body).map_err(|err| ScraperError::InvalidApiResponse {
+        url: url.to_owned(),
+        reason: err.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_round_trip_through_urls() {
+        let page_url = Url::parse("https://en.wikipedia.org/wiki/Reptile").unwrap();
+        for (title, url) in [
+            (
+                "Nile crocodile",
+                "https://en.wikipedia.org/wiki/Nile_crocodile",
+            ),
+            ("Crocodylidae", "https://en.wikipedia.org/wiki/Crocodylidae"),
+            ("Caïman", "https://en.wikipedia.org/wiki/Ca%C3%AFman"),
+            ("What?", "https://en.wikipedia.org/wiki/What%3F"),
+            ("100% Rust", "https://en.wikipedia.org/wiki/100%25_Rust"),
+        ] {
+            assert_eq!(article_url(&page_url, title, "/wiki/"), url);
+            assert_eq!(
+                article_title(&Url::parse(url).unwrap(), "/wiki/").as_deref(),
+                Some(title)
+            );
+        }
+    }
+
+    #[test]
+    fn pages_outside_of_the_articles_have_no_title() {
+        let url = Url::parse("https://en.wikipedia.org/w/index.php?title=Reptile").unwrap();
+        assert_eq!(article_title(&url, "/wiki/"), None);
+    }
+
+    #[test]
+    fn article_paths_can_be_configured() {
+        let page_url = Url::parse("https://wiki.example.com/index.php/Main_Page").unwrap();
+        assert_eq!(
+            article_title(&page_url, "/index.php/").as_deref(),
+            Some("Main Page")
+        );
+        assert_eq!(
+            article_url(&page_url, "Nile crocodile", "/index.php/"),
+            "https://wiki.example.com/index.php/Nile_crocodile"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// Id of a page in the graph. Ids are given in the order the pages are found, starting from 0
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct NodeId(u64);
+
+/// Number of bits of the ids of a graph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdWidth {
+    U32,
+    #[default]
+    U64,
+}
+
+impl IdWidth {
+    /// Largest id that fits in the width
+    pub fn max_id(self) -> u64 {
+        match self {
+            IdWidth::U32 => u32::MAX as u64,
+            IdWidth::U64 => u64::MAX,
+        }
+    }
+}
+
+impl NodeId {
+    pub const fn new(id: u64) -> NodeId {
+        NodeId(id)
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Id of the page found after `count` other pages, or `None` if it does not fit in `width`
+    pub fn allocate(count: usize, width: IdWidth) -> Option<NodeId> {
+        let id = u64::try_from(count).ok()?;
+        (id <= width.max_id()).then_some(NodeId(id))
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for NodeId {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        id.parse().map(NodeId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocation_is_checked_against_the_width() {
+        assert_eq!(NodeId::allocate(0, IdWidth::U32), Some(NodeId::new(0)));
+        let max = u32::MAX as usize;
+        assert_eq!(
+            NodeId::allocate(max, IdWidth::U32),
+            Some(NodeId::new(u32::MAX as u64))
+        );
+        assert_eq!(NodeId::allocate(max + 1, IdWidth::U32), None);
+        assert_eq!(
+            NodeId::allocate(max + 1, IdWidth::U64),
+            Some(NodeId::new(max as u64 + 1))
+        );
+    }
+}
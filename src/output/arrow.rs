@@ -0,0 +1,285 @@
+use std::{io, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray, UInt32Array,
+    UInt64Array,
+};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{ArrowError, DataType, Field, SchemaRef};
+
+use super::{Attribute, AttributeKind, AttributeValue, Edge, Finish, Node, OutputWriter, Schema};
+use crate::node_id::{IdWidth, NodeId};
+
+/// Number of rows buffered before they are written as a record batch
+const BATCH_SIZE: usize = 64 * 1024;
+
+/// Writes the edges and the nodes to two separate Arrow IPC files (also known as Feather v2),
+/// which can be memory-mapped without parsing them.
+///
+/// The columns are the same as in the csv format: `source,target` for the edges and `node_id,url`
+/// for the nodes, followed by a column for each attribute.
+pub struct ArrowWriter<E: Finish, N: Finish> {
+    edges: Table<E>,
+    nodes: Table<N>,
+}
+
+impl<E: Finish, N: Finish> ArrowWriter<E, N> {
+    pub fn new(edges: E, nodes: N) -> ArrowWriter<E, N> {
+        ArrowWriter {
+            edges: Table::Pending(edges),
+            nodes: Table::Pending(nodes),
+        }
+    }
+}
+
+/// One of the output files, whose arrow writer can only be created once the schema is known
+enum Table<W: Finish> {
+    Pending(W),
+    Writing(Box<TableWriter<W>>),
+    Finished,
+}
+
+struct TableWriter<W: Finish> {
+    writer: FileWriter<W>,
+    schema: SchemaRef,
+    id_width: IdWidth,
+    ids: Vec<NodeId>,
+    second: Column,
+    attributes: Vec<Column>,
+}
+
+/// Value of the second column of a row: the target of an edge or the url of a node
+enum Second<'a> {
+    Id(NodeId),
+    Url(&'a str),
+}
+
+/// Values of a column of the current batch
+enum Column {
+    Id(Vec<NodeId>, IdWidth),
+    String(Vec<String>),
+    Integer(Vec<i64>),
+    Float(Vec<f64>),
+    Boolean(Vec<bool>),
+}
+
+impl Column {
+    fn new(kind: AttributeKind) -> Column {
+        match kind {
+            AttributeKind::String => Column::String(Vec::new()),
+            AttributeKind::Integer => Column::Integer(Vec::new()),
+            AttributeKind::Float => Column::Float(Vec::new()),
+            AttributeKind::Boolean => Column::Boolean(Vec::new()),
+        }
+    }
+
+    fn data_type(kind: AttributeKind) -> DataType {
+        match kind {
+            AttributeKind::String => DataType::Utf8,
+            AttributeKind::Integer => DataType::Int64,
+            AttributeKind::Float => DataType::Float64,
+            AttributeKind::Boolean => DataType::Boolean,
+        }
+    }
+
+    fn push(&mut self, value: &AttributeValue) -> io::Result<()> {
+        match (self, value) {
+            (Column::String(values), AttributeValue::String(value)) => values.push(value.clone()),
+            (Column::Integer(values), AttributeValue::Integer(value)) => values.push(*value),
+            (Column::Float(values), AttributeValue::Float(value)) => values.push(*value),
+            (Column::Boolean(values), AttributeValue::Boolean(value)) => values.push(*value),
+            _ => {
+                return Err(io::Error::other(
+                    "The type of an attribute does not match the schema",
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the values of the column as an arrow array, leaving the column empty
+    fn take(&mut self) -> ArrayRef {
+        match self {
+            Column::Id(values, width) => id_array(std::mem::take(values), *width),
+            Column::String(values) => Arc::new(StringArray::from(std::mem::take(values))),
+            Column::Integer(values) => Arc::new(Int64Array::from(std::mem::take(values))),
+            Column::Float(values) => Arc::new(Float64Array::from(std::mem::take(values))),
+            Column::Boolean(values) => Arc::new(BooleanArray::from(std::mem::take(values))),
+        }
+    }
+}
+
+fn id_data_type(width: IdWidth) -> DataType {
+    match width {
+        IdWidth::U32 => DataType::UInt32,
+        IdWidth::U64 => DataType::UInt64,
+    }
+}
+
+fn id_array(ids: Vec<NodeId>, width: IdWidth) -> ArrayRef {
+    match width {
+        // The ids have been checked to fit in 32 bits when they were allocated
+        IdWidth::U32 => Arc::new(UInt32Array::from_iter_values(
+            ids.into_iter().map(|id| id.get() as u32),
+        )),
+        IdWidth::U64 => Arc::new(UInt64Array::from_iter_values(
+            ids.into_iter().map(NodeId::get),
+        )),
+    }
+}
+
+fn arrow_error(err: ArrowError) -> io::Error {
+    match err {
+        ArrowError::IoError(_, err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+fn finished_error() -> io::Error {
+    io::Error::other("The writer has already been finished")
+}
+
+impl<W: Finish> Table<W> {
+    /// Starts the file, with the id column `first` and the column `second`, which holds ids if it has no type,
+    /// followed by the attributes
+    fn begin(
+        &mut self,
+        first: &str,
+        second: (&str, Option<DataType>),
+        attributes: &[Attribute],
+        id_width: IdWidth,
+    ) -> io::Result<()> {
+        let Table::Pending(output) = std::mem::replace(self, Table::Finished) else {
+            return Err(io::Error::other("The writer has already been started"));
+        };
+
+        let fields =
+            [
+                Field::new(first, id_data_type(id_width), false),
+                Field::new(
+                    second.0,
+                    second.1.clone().unwrap_or(id_data_type(id_width)),
+                    false,
+                ),
+            ]
+            .into_iter()
+            .chain(attributes.iter().map(|attribute| {
+                Field::new(attribute.name, Column::data_type(attribute.kind), false)
+            }))
+            .collect::<Vec<_>>();
+        let schema = Arc::new(arrow_schema::Schema::new(fields));
+
+        let second = match second.1 {
+            None => Column::Id(Vec::new(), id_width),
+            Some(_) => Column::String(Vec::new()),
+        };
+        *self = Table::Writing(Box::new(TableWriter {
+            writer: FileWriter::try_new(output, &schema).map_err(arrow_error)?,
+            schema,
+            id_width,
+            ids: Vec::new(),
+            second,
+            attributes: attributes
+                .iter()
+                .map(|attribute| Column::new(attribute.kind))
+                .collect(),
+        }));
+        Ok(())
+    }
+
+    fn writer(&mut self) -> io::Result<&mut TableWriter<W>> {
+        match self {
+            Table::Writing(writer) => Ok(writer),
+            _ => Err(finished_error()),
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match std::mem::replace(self, Table::Finished) {
+            Table::Writing(mut writer) => {
+                writer.write_batch()?;
+                writer.writer.finish().map_err(arrow_error)?;
+                writer.writer.into_inner().map_err(arrow_error)?.finish()
+            }
+            Table::Pending(_) | Table::Finished => Ok(()),
+        }
+    }
+}
+
+impl<W: Finish> TableWriter<W> {
+    fn push(
+        &mut self,
+        id: NodeId,
+        second: Second,
+        attributes: &[AttributeValue],
+    ) -> io::Result<()> {
+        if attributes.len() != self.attributes.len() {
+            return Err(io::Error::other(
+                "The number of attributes does not match the schema",
+            ));
+        }
+        self.ids.push(id);
+        match (&mut self.second, second) {
+            (Column::Id(values, _), Second::Id(id)) => values.push(id),
+            (Column::String(values), Second::Url(url)) => values.push(url.to_owned()),
+            _ => unreachable!("The second column is chosen by the writer"),
+        }
+        for (column, value) in self.attributes.iter_mut().zip(attributes) {
+            column.push(value)?;
+        }
+
+        if self.ids.len() >= BATCH_SIZE {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> io::Result<()> {
+        if self.ids.is_empty() {
+            return Ok(());
+        }
+        let columns = [
+            id_array(std::mem::take(&mut self.ids), self.id_width),
+            self.second.take(),
+        ]
+        .into_iter()
+        .chain(self.attributes.iter_mut().map(Column::take))
+        .collect();
+        let batch = RecordBatch::try_new(self.schema.clone(), columns).map_err(arrow_error)?;
+        self.writer.write(&batch).map_err(arrow_error)
+    }
+}
+
+impl<E: Finish, N: Finish> OutputWriter for ArrowWriter<E, N> {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()> {
+        self.edges.begin(
+            "source",
+            ("target", None),
+            &schema.edge_attributes,
+            schema.id_width,
+        )?;
+        self.nodes.begin(
+            "node_id",
+            ("url", Some(DataType::Utf8)),
+            &schema.node_attributes,
+            schema.id_width,
+        )
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        self.edges
+            .writer()?
+            .push(edge.source, Second::Id(edge.target), &edge.attributes)
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        self.nodes
+            .writer()?
+            .push(node.id, Second::Url(&node.url), &node.attributes)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.edges.finish()?;
+        self.nodes.finish()
+    }
+}
@@ -0,0 +1,141 @@
+use std::io;
+
+use super::{AttributeValue, Edge, Finish, Node, OutputWriter, Schema};
+
+/// Writes the whole graph to a single sectioned csv file, with a metadata header:
+///
+/// ```text
+/// # graphgen 0.1.0
+/// # directed: true
+/// # depth: 5
+/// ## edges
+/// source,target
+/// 0,1
+/// ## nodes
+/// node_id,url,node_type
+/// 0,https://en.wikipedia.org/wiki/Rust,article
+/// ```
+///
+/// Lines starting with `#` are metadata, and `## edges` / `## nodes` start the csv section of the edges and of the nodes.
+pub struct CombinedWriter<W: Finish> {
+    /// The output before the header has been written
+    pending: Option<W>,
+    out: Option<csv::Writer<W>>,
+    delimiter: u8,
+    node_header: Vec<String>,
+    in_nodes: bool,
+}
+
+impl<W: Finish> CombinedWriter<W> {
+    pub fn new(out: W, delimiter: u8) -> CombinedWriter<W> {
+        CombinedWriter {
+            pending: Some(out),
+            out: None,
+            delimiter,
+            node_header: Vec::new(),
+            in_nodes: false,
+        }
+    }
+
+    fn out(&mut self) -> io::Result<&mut csv::Writer<W>> {
+        self.out
+            .as_mut()
+            .ok_or_else(|| io::Error::other("The writer has already been finished"))
+    }
+
+    /// Starts the nodes section, if it has not been started yet
+    fn start_nodes(&mut self) -> io::Result<()> {
+        if !self.in_nodes {
+            self.in_nodes = true;
+            // Written as a record with a single field, which is never quoted
+            self.out()?.write_record(["## nodes"])?;
+            let header = std::mem::take(&mut self.node_header);
+            self.out()?.write_record(&header)?;
+        }
+        Ok(())
+    }
+}
+
+fn record<'a>(
+    first: String,
+    second: String,
+    attributes: &'a [AttributeValue],
+) -> impl Iterator<Item = String> + 'a {
+    [first, second]
+        .into_iter()
+        .chain(attributes.iter().map(|value| value.to_string()))
+}
+
+impl<W: Finish> OutputWriter for CombinedWriter<W> {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()> {
+        let mut out = self
+            .pending
+            .take()
+            .ok_or_else(|| io::Error::other("The writer has already been started"))?;
+        writeln!(out, "# graphgen {}", env!("CARGO_PKG_VERSION"))?;
+        writeln!(out, "# directed: {}", schema.directed)?;
+        for (name, value) in &schema.metadata {
+            // Keep every value on its own line
+            let value = value.replace(['\r', '\n'], " ");
+            writeln!(out, "# {name}: {value}")?;
+        }
+
+        let mut builder = csv::WriterBuilder::new();
+        builder.delimiter(self.delimiter).flexible(true);
+        self.out = Some(builder.from_writer(out));
+        self.out()?.write_record(["## edges"])?;
+        let edge_header = ["source", "target"].into_iter().chain(
+            schema
+                .edge_attributes
+                .iter()
+                .map(|attribute| attribute.name),
+        );
+        self.out()?.write_record(edge_header)?;
+
+        self.node_header = ["node_id", "url"]
+            .into_iter()
+            .chain(
+                schema
+                    .node_attributes
+                    .iter()
+                    .map(|attribute| attribute.name),
+            )
+            .map(str::to_owned)
+            .collect();
+        Ok(())
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        self.out()?.write_record(record(
+            edge.source.to_string(),
+            edge.target.to_string(),
+            &edge.attributes,
+        ))?;
+        Ok(())
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        self.start_nodes()?;
+        self.out()?.write_record(record(
+            node.id.to_string(),
+            node.url.clone(),
+            &node.attributes,
+        ))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if let Some(mut out) = self.pending.take() {
+            return out.finish();
+        }
+        if self.out.is_none() {
+            return Ok(());
+        }
+        // Write the nodes section even if the graph has no nodes
+        self.start_nodes()?;
+        match self.out.take() {
+            Some(out) => out.into_inner().map_err(|err| err.into_error())?.finish(),
+            None => Ok(()),
+        }
+    }
+}
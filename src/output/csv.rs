@@ -0,0 +1,136 @@
+use std::io;
+
+use super::{AttributeValue, Edge, Finish, Node, OutputWriter, Schema};
+
+/// Writes the edges and the nodes to two separate csv files.
+///
+/// The edges have the columns `source,target` and the nodes `node_id,url`, followed by a column for each attribute.
+pub struct CsvWriter<E: Finish, N: Finish> {
+    edges: Option<csv::Writer<E>>,
+    nodes: Option<csv::Writer<N>>,
+}
+
+impl<E: Finish, N: Finish> CsvWriter<E, N> {
+    pub fn new(edges: E, nodes: N, delimiter: u8) -> CsvWriter<E, N> {
+        let mut builder = csv::WriterBuilder::new();
+        builder.delimiter(delimiter);
+        CsvWriter {
+            edges: Some(builder.from_writer(edges)),
+            nodes: Some(builder.from_writer(nodes)),
+        }
+    }
+}
+
+fn record<'a>(
+    first: String,
+    second: String,
+    attributes: &'a [AttributeValue],
+) -> impl Iterator<Item = String> + 'a {
+    [first, second]
+        .into_iter()
+        .chain(attributes.iter().map(|value| value.to_string()))
+}
+
+fn finish<W: Finish>(writer: Option<csv::Writer<W>>) -> io::Result<()> {
+    match writer {
+        Some(writer) => writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .finish(),
+        None => Ok(()),
+    }
+}
+
+fn finished_error() -> io::Error {
+    io::Error::other("The writer has already been finished")
+}
+
+impl<E: Finish, N: Finish> OutputWriter for CsvWriter<E, N> {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()> {
+        let edges = self.edges.as_mut().ok_or_else(finished_error)?;
+        edges.write_record(
+            ["source", "target"].into_iter().chain(
+                schema
+                    .edge_attributes
+                    .iter()
+                    .map(|attribute| attribute.name),
+            ),
+        )?;
+
+        let nodes = self.nodes.as_mut().ok_or_else(finished_error)?;
+        nodes.write_record(
+            ["node_id", "url"].into_iter().chain(
+                schema
+                    .node_attributes
+                    .iter()
+                    .map(|attribute| attribute.name),
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        let edges = self.edges.as_mut().ok_or_else(finished_error)?;
+        edges.write_record(record(
+            edge.source.to_string(),
+            edge.target.to_string(),
+            &edge.attributes,
+        ))?;
+        Ok(())
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        let nodes = self.nodes.as_mut().ok_or_else(finished_error)?;
+        nodes.write_record(record(
+            node.id.to_string(),
+            node.url.clone(),
+            &node.attributes,
+        ))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        finish(self.edges.take())?;
+        finish(self.nodes.take())
+    }
+}
+
+/// Writes the links to the external pages, kept apart from the graph, to a csv file with the columns `source,url`.
+/// Each link is given as a node, whose id is the one of the page it is found in and whose url is the one of the external page
+pub struct ExternalEdgesWriter<W: Finish> {
+    out: Option<csv::Writer<W>>,
+}
+
+impl<W: Finish> ExternalEdgesWriter<W> {
+    pub fn new(out: W, delimiter: u8) -> ExternalEdgesWriter<W> {
+        ExternalEdgesWriter {
+            out: Some(
+                csv::WriterBuilder::new()
+                    .delimiter(delimiter)
+                    .from_writer(out),
+            ),
+        }
+    }
+}
+
+impl<W: Finish> OutputWriter for ExternalEdgesWriter<W> {
+    fn begin(&mut self, _: &Schema) -> io::Result<()> {
+        let out = self.out.as_mut().ok_or_else(finished_error)?;
+        out.write_record(["source", "url"])?;
+        Ok(())
+    }
+
+    fn write_edge(&mut self, _: &Edge) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        let out = self.out.as_mut().ok_or_else(finished_error)?;
+        out.write_record([node.id.to_string().as_str(), &node.url])?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        finish(self.out.take())
+    }
+}
@@ -0,0 +1,131 @@
+use std::io;
+
+use super::{Attribute, AttributeKind, AttributeValue, Edge, Finish, Node, OutputWriter, Schema};
+
+/// Writes the graph to a single GraphML file.
+///
+/// The url of the nodes is saved in the `url` key, and every attribute in a key with the same name.
+/// Edges with attributes are written before the nodes, which is allowed by the GraphML schema.
+pub struct GraphmlWriter<W: Finish> {
+    out: W,
+    schema: Schema,
+}
+
+impl<W: Finish> GraphmlWriter<W> {
+    pub fn new(out: W) -> GraphmlWriter<W> {
+        GraphmlWriter {
+            out,
+            schema: Schema::default(),
+        }
+    }
+
+    fn write_key(&mut self, domain: &str, attribute: &Attribute) -> io::Result<()> {
+        let kind = match attribute.kind {
+            AttributeKind::String => "string",
+            AttributeKind::Integer => "long",
+            AttributeKind::Float => "double",
+            AttributeKind::Boolean => "boolean",
+        };
+        writeln!(
+            self.out,
+            r#"  <key id="{domain}_{name}" for="{domain}" attr.name="{name}" attr.type="{kind}"/>"#,
+            name = escape(attribute.name),
+        )
+    }
+}
+
+/// Escapes the characters that are not allowed in xml text and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_data(
+    out: &mut impl io::Write,
+    domain: &str,
+    attributes: &[Attribute],
+    values: &[AttributeValue],
+) -> io::Result<()> {
+    for (attribute, value) in attributes.iter().zip(values) {
+        writeln!(
+            out,
+            r#"      <data key="{domain}_{}">{}</data>"#,
+            escape(attribute.name),
+            escape(&value.to_string())
+        )?;
+    }
+    Ok(())
+}
+
+impl<W: Finish> OutputWriter for GraphmlWriter<W> {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()> {
+        self.schema = schema.clone();
+
+        writeln!(self.out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            self.out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        self.write_key("node", &Attribute::new("url", AttributeKind::String))?;
+        for attribute in &schema.node_attributes {
+            self.write_key("node", attribute)?;
+        }
+        for attribute in &schema.edge_attributes {
+            self.write_key("edge", attribute)?;
+        }
+
+        let edgedefault = if schema.directed {
+            "directed"
+        } else {
+            "undirected"
+        };
+        writeln!(self.out, r#"  <graph id="G" edgedefault="{edgedefault}">"#)
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        writeln!(
+            self.out,
+            r#"    <edge source="n{}" target="n{}">"#,
+            edge.source, edge.target
+        )?;
+        write_data(
+            &mut self.out,
+            "edge",
+            &self.schema.edge_attributes,
+            &edge.attributes,
+        )?;
+        writeln!(self.out, "    </edge>")
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        writeln!(self.out, r#"    <node id="n{}">"#, node.id)?;
+        writeln!(
+            self.out,
+            r#"      <data key="node_url">{}</data>"#,
+            escape(&node.url)
+        )?;
+        write_data(
+            &mut self.out,
+            "node",
+            &self.schema.node_attributes,
+            &node.attributes,
+        )?;
+        writeln!(self.out, "    </node>")
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        writeln!(self.out, "  </graph>")?;
+        writeln!(self.out, "</graphml>")?;
+        self.out.finish()
+    }
+}
@@ -0,0 +1,120 @@
+use std::io::{self, Write};
+
+use super::{Attribute, AttributeValue, Edge, Finish, Node, OutputWriter, Schema};
+
+/// Writes the graph to a single json file with the following structure,
+/// where every node and edge object also has a key for each of its attributes:
+///
+/// ```json
+/// {"directed": true, "edges": [{"source": 0, "target": 1}], "nodes": [{"id": 0, "url": "...", "node_type": "article"}]}
+/// ```
+pub struct JsonWriter<W: Finish> {
+    out: W,
+    schema: Schema,
+    section: Section,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Start,
+    Edges { empty: bool },
+    Nodes { empty: bool },
+}
+
+impl<W: Finish> JsonWriter<W> {
+    pub fn new(out: W) -> JsonWriter<W> {
+        JsonWriter {
+            out,
+            schema: Schema::default(),
+            section: Section::Start,
+        }
+    }
+
+    /// Closes the edges list if needed and opens the nodes one
+    fn start_nodes(&mut self) -> io::Result<()> {
+        if let Section::Edges { .. } = self.section {
+            self.out.write_all(b"\n],\"nodes\":[")?;
+            self.section = Section::Nodes { empty: true };
+        }
+        Ok(())
+    }
+}
+
+/// Writes a node or edge object to `out`, preceded by a comma if it is not the first one of its list
+fn write_object(
+    out: &mut impl Write,
+    section: &mut Section,
+    fields: [(&str, serde_json::Value); 2],
+    attributes: &[Attribute],
+    values: &[AttributeValue],
+) -> io::Result<()> {
+    let mut object = serde_json::Map::new();
+    for (name, value) in fields {
+        object.insert(name.to_owned(), value);
+    }
+    for (attribute, value) in attributes.iter().zip(values) {
+        object.insert(attribute.name.to_owned(), to_json(value));
+    }
+
+    let empty = match section {
+        Section::Edges { empty } | Section::Nodes { empty } => std::mem::replace(empty, false),
+        Section::Start => unreachable!("begin should be called before writing anything"),
+    };
+    if !empty {
+        out.write_all(b",")?;
+    }
+    out.write_all(b"\n")?;
+    serde_json::to_writer(&mut *out, &object)?;
+    Ok(())
+}
+
+pub(super) fn to_json(value: &AttributeValue) -> serde_json::Value {
+    match value {
+        AttributeValue::String(value) => value.clone().into(),
+        AttributeValue::Integer(value) => (*value).into(),
+        AttributeValue::Float(value) => (*value).into(),
+        AttributeValue::Boolean(value) => (*value).into(),
+    }
+}
+
+impl<W: Finish> OutputWriter for JsonWriter<W> {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()> {
+        self.schema = schema.clone();
+        write!(self.out, "{{\"directed\":{},\"edges\":[", schema.directed)?;
+        self.section = Section::Edges { empty: true };
+        Ok(())
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        write_object(
+            &mut self.out,
+            &mut self.section,
+            [
+                ("source", edge.source.get().into()),
+                ("target", edge.target.get().into()),
+            ],
+            &self.schema.edge_attributes,
+            &edge.attributes,
+        )
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        self.start_nodes()?;
+        write_object(
+            &mut self.out,
+            &mut self.section,
+            [
+                ("id", node.id.get().into()),
+                ("url", node.url.clone().into()),
+            ],
+            &self.schema.node_attributes,
+            &node.attributes,
+        )
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.start_nodes()?;
+        self.out.write_all(b"\n]}\n")?;
+        self.out.finish()
+    }
+}
@@ -0,0 +1,348 @@
+#[cfg(feature = "arrow")]
+mod arrow;
+mod combined;
+mod csv;
+mod graphml;
+mod json;
+mod pages_jsonl;
+mod thread;
+
+#[cfg(feature = "arrow")]
+pub use self::arrow::ArrowWriter;
+pub use self::combined::CombinedWriter;
+pub use self::csv::{CsvWriter, ExternalEdgesWriter};
+pub use self::graphml::GraphmlWriter;
+pub use self::json::JsonWriter;
+pub use self::pages_jsonl::PagesJsonlWriter;
+pub use self::thread::{Priority, ThreadedWriter, WriterThread};
+
+use std::io::{self, Write};
+
+use crate::compression::{Compression, OutputFile};
+use crate::node_id::{IdWidth, NodeId};
+
+/// Options controlling how the output files are written
+#[derive(Debug, Clone, Copy)]
+pub struct OutputOptions {
+    /// Field delimiter of the output files. Only used by the csv and combined formats
+    pub delimiter: u8,
+
+    /// Compression applied to the output files
+    pub compression: Compression,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        OutputOptions {
+            delimiter: b',',
+            compression: Compression::None,
+        }
+    }
+}
+
+/// Format of the output files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Two files, <output-file>_edges.csv and <output-file>_nodes.csv (.tsv if the delimiter is a tab)
+    #[default]
+    Csv,
+    /// A single file, <output-file>.json, with the lists of nodes and edges
+    Json,
+    /// A single file, <output-file>.graphml
+    Graphml,
+    /// Two Arrow IPC (Feather) files, <output-file>_edges.arrow and <output-file>_nodes.arrow.
+    /// Requires the `arrow` feature
+    Arrow,
+    /// A single sectioned csv file, <output-file>.csv (.tsv if the delimiter is a tab),
+    /// with a header describing the crawl followed by the edges and the nodes
+    Combined,
+    /// A single file, <output-file>.jsonl, with a json object per page containing its details and the ids of the pages it links to
+    PagesJsonl,
+}
+
+impl OutputFormat {
+    /// Paths of the files written for the output prefix `prefix`
+    pub fn file_paths(&self, prefix: impl AsRef<str>, options: &OutputOptions) -> Vec<String> {
+        let prefix = prefix.as_ref();
+        let compression = options.compression.extension();
+        let extension = if options.delimiter == b'\t' {
+            "tsv"
+        } else {
+            "csv"
+        };
+        match self {
+            OutputFormat::Csv => {
+                vec![
+                    format!("{prefix}_edges.{extension}{compression}"),
+                    format!("{prefix}_nodes.{extension}{compression}"),
+                ]
+            }
+            OutputFormat::Json => vec![format!("{prefix}.json{compression}")],
+            OutputFormat::Graphml => vec![format!("{prefix}.graphml{compression}")],
+            OutputFormat::Arrow => vec![
+                format!("{prefix}_edges.arrow{compression}"),
+                format!("{prefix}_nodes.arrow{compression}"),
+            ],
+            OutputFormat::Combined => vec![format!("{prefix}.{extension}{compression}")],
+            OutputFormat::PagesJsonl => vec![format!("{prefix}.jsonl{compression}")],
+        }
+    }
+
+    /// Infers the format of a single output file from its extension, e.g. `graph.graphml` or `graph.json.gz`.
+    /// Returns the prefix to pass to [`OutputFormat::file_paths`], the format and the compression,
+    /// or `None` if the extension is not the one of a single file format.
+    pub fn infer(path: &str) -> Option<(&str, OutputFormat, Compression)> {
+        let (path, compression) = [Compression::Gzip, Compression::Zstd]
+            .into_iter()
+            .find_map(|compression| {
+                path.strip_suffix(compression.extension())
+                    .map(|path| (path, compression))
+            })
+            .unwrap_or((path, Compression::None));
+
+        [
+            (".json", OutputFormat::Json),
+            (".jsonl", OutputFormat::PagesJsonl),
+            (".graphml", OutputFormat::Graphml),
+        ]
+        .into_iter()
+        .find_map(|(extension, format)| {
+            path.strip_suffix(extension)
+                .filter(|prefix| !prefix.is_empty())
+                .map(|prefix| (prefix, format, compression))
+        })
+    }
+
+    /// Fails if writing this format with the compression in `options` requires a feature that has been left out of the build
+    pub fn check_supported(&self, options: &OutputOptions) -> io::Result<()> {
+        if cfg!(not(feature = "arrow")) && *self == OutputFormat::Arrow {
+            return Err(crate::errors::missing_feature("arrow"));
+        }
+        if cfg!(not(feature = "zstd")) && options.compression == Compression::Zstd {
+            return Err(crate::errors::missing_feature("zstd"));
+        }
+        Ok(())
+    }
+
+    /// Creates the output files for the prefix `prefix` and returns a writer for them
+    pub fn create_writer(
+        &self,
+        prefix: impl AsRef<str>,
+        options: &OutputOptions,
+    ) -> io::Result<Box<dyn OutputWriter>> {
+        let paths = self.file_paths(prefix, options);
+        Ok(match self {
+            OutputFormat::Csv => Box::new(CsvWriter::new(
+                options.compression.create(&paths[0])?,
+                options.compression.create(&paths[1])?,
+                options.delimiter,
+            )),
+            OutputFormat::Json => Box::new(JsonWriter::new(options.compression.create(&paths[0])?)),
+            OutputFormat::Graphml => {
+                Box::new(GraphmlWriter::new(options.compression.create(&paths[0])?))
+            }
+            #[cfg(feature = "arrow")]
+            OutputFormat::Arrow => Box::new(ArrowWriter::new(
+                options.compression.create(&paths[0])?,
+                options.compression.create(&paths[1])?,
+            )),
+            #[cfg(not(feature = "arrow"))]
+            OutputFormat::Arrow => return Err(crate::errors::missing_feature("arrow")),
+            OutputFormat::Combined => Box::new(CombinedWriter::new(
+                options.compression.create(&paths[0])?,
+                options.delimiter,
+            )),
+            OutputFormat::PagesJsonl => Box::new(PagesJsonlWriter::new(
+                options.compression.create(&paths[0])?,
+            )),
+        })
+    }
+}
+
+/// Type of the values of an attribute, which every format writes in its own way:
+///
+/// | Kind    | csv, combined  | json, pages-jsonl | graphml `attr.type` | arrow     |
+/// |---------|----------------|-------------------|---------------------|-----------|
+/// | String  | text           | string            | `string`            | `Utf8`    |
+/// | Integer | text           | number            | `long`              | `Int64`   |
+/// | Float   | text           | number            | `double`            | `Float64` |
+/// | Boolean | `true`/`false` | boolean           | `boolean`           | `Boolean` |
+///
+/// The attributes keep their name in every format, so a graph read back from any of them has the same attributes,
+/// e.g. the `node_type` of the nodes (see [`crate::graph::NodeType`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeKind {
+    String,
+    Integer,
+    Float,
+    Boolean,
+}
+
+/// Value of an attribute of a node or of an edge
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl std::fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttributeValue::String(value) => write!(f, "{value}"),
+            AttributeValue::Integer(value) => write!(f, "{value}"),
+            AttributeValue::Float(value) => write!(f, "{value}"),
+            AttributeValue::Boolean(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Name and type of an attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribute {
+    pub name: &'static str,
+    pub kind: AttributeKind,
+}
+
+impl Attribute {
+    pub const fn new(name: &'static str, kind: AttributeKind) -> Attribute {
+        Attribute { name, kind }
+    }
+}
+
+/// Describes the graph being written: whether it is directed and which attributes its nodes and edges have.
+/// The attributes of every [`Node`] and [`Edge`] are in the same order as in the schema.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    pub directed: bool,
+    /// Width of the ids, for the formats with typed columns
+    pub id_width: IdWidth,
+    pub node_attributes: Vec<Attribute>,
+    pub edge_attributes: Vec<Attribute>,
+    /// Parameters of the crawl that built the graph, as pairs of name and value.
+    /// Only written by the formats that can describe themselves
+    pub metadata: Vec<(&'static str, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: NodeId,
+    pub url: String,
+    pub attributes: Vec<AttributeValue>,
+    /// Only given to the writers that need it, see [`OutputWriter::needs_page_details`]
+    pub details: Option<PageDetails>,
+}
+
+/// Details of a page that are only written by the formats describing each page on its own
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageDetails {
+    /// Title of the article, for the articles of a wiki
+    pub title: Option<String>,
+    /// Number of links from the nearest seed to the page when it was scraped or found, if known
+    pub depth: Option<u64>,
+    /// Keywords contained in the page, if they have been recorded
+    pub keywords: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub source: NodeId,
+    pub target: NodeId,
+    pub attributes: Vec<AttributeValue>,
+}
+
+/// Writes a graph in some output format.
+///
+/// `begin` is called once before anything else, then every edge is written, followed by every node.
+/// `finish` is called once everything has been written.
+pub trait OutputWriter {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()>;
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()>;
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()>;
+
+    /// Whether the nodes have to be given their [`PageDetails`], which take some more work to compute
+    fn needs_page_details(&self) -> bool {
+        false
+    }
+
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// A writer that has to be finished once everything has been written to it, e.g. to write the trailer of a compressed file
+pub trait Finish: Write {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Finish for OutputFile {
+    fn finish(&mut self) -> io::Result<()> {
+        OutputFile::finish(self)
+    }
+}
+
+impl Finish for io::Stdout {}
+impl Finish for io::Stderr {}
+impl Finish for io::Sink {}
+
+/// Where the nodes are written when the graph is written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum StdoutNodes {
+    /// Do not write the nodes
+    #[default]
+    None,
+    /// Write the nodes to stdout after the edges, separated from them by an empty line
+    Separator,
+    /// Write the nodes to stderr
+    Stderr,
+}
+
+/// Returns a csv writer that writes the edges to stdout, and the nodes as requested by `nodes`
+pub fn stdout_writer(delimiter: u8, nodes: StdoutNodes) -> Box<dyn OutputWriter> {
+    match nodes {
+        StdoutNodes::None => Box::new(CsvWriter::new(io::stdout(), io::sink(), delimiter)),
+        StdoutNodes::Stderr => Box::new(CsvWriter::new(io::stdout(), io::stderr(), delimiter)),
+        StdoutNodes::Separator => Box::new(CsvWriter::new(
+            io::stdout(),
+            AfterSeparator::default(),
+            delimiter,
+        )),
+    }
+}
+
+/// Buffers everything written to it, and writes it to stdout after an empty line once finished
+#[derive(Default)]
+struct AfterSeparator(Vec<u8>);
+
+impl Write for AfterSeparator {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Finish for AfterSeparator {
+    fn finish(&mut self) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(b"\n")?;
+        stdout.write_all(&std::mem::take(&mut self.0))?;
+        stdout.flush()
+    }
+}
+
+/// Parses a delimiter given on the command line. Accepts a single ascii character, `\t` or `tab`
+pub fn parse_delimiter(delimiter: &str) -> Result<u8, String> {
+    match delimiter {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if delimiter.len() == 1 && delimiter.is_ascii() => Ok(delimiter.as_bytes()[0]),
+        _ => Err(format!(
+            "The delimiter must be a single ascii character or `tab`, found `{delimiter}`"
+        )),
+    }
+}
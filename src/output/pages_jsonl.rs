@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use super::{json::to_json, Edge, Finish, Node, OutputWriter, Schema};
+use crate::node_id::NodeId;
+
+/// Writes one json object per line for each page, with its links, e.g.
+///
+/// ```json
+/// {"id": 0, "url": "...", "title": "Rust", "depth": 0, "keywords": ["borrow"], "outlinks": [1, 2]}
+/// ```
+///
+/// `title` is `null` for the pages that are not articles, `depth` for the pages whose depth is not known
+/// and `keywords` unless they are recorded. Every page also has a key for each of its attributes.
+/// The pages are only written once every edge has been read, so the out links of every page are kept in memory.
+pub struct PagesJsonlWriter<W: Finish> {
+    out: W,
+    schema: Schema,
+    out_links: HashMap<NodeId, Vec<NodeId>>,
+}
+
+impl<W: Finish> PagesJsonlWriter<W> {
+    pub fn new(out: W) -> PagesJsonlWriter<W> {
+        PagesJsonlWriter {
+            out,
+            schema: Schema::default(),
+            out_links: HashMap::new(),
+        }
+    }
+}
+
+impl<W: Finish> OutputWriter for PagesJsonlWriter<W> {
+    fn begin(&mut self, schema: &Schema) -> std::io::Result<()> {
+        self.schema = schema.clone();
+        Ok(())
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> std::io::Result<()> {
+        self.out_links
+            .entry(edge.source)
+            .or_default()
+            .push(edge.target);
+        // An undirected edge is a link of both of its pages
+        if !self.schema.directed && edge.source != edge.target {
+            self.out_links
+                .entry(edge.target)
+                .or_default()
+                .push(edge.source);
+        }
+        Ok(())
+    }
+
+    fn write_node(&mut self, node: &Node) -> std::io::Result<()> {
+        let details = node.details.clone().unwrap_or_default();
+        let out_links = self.out_links.remove(&node.id).unwrap_or_default();
+
+        let mut object = serde_json::Map::new();
+        object.insert("id".to_owned(), node.id.get().into());
+        object.insert("url".to_owned(), node.url.clone().into());
+        object.insert("title".to_owned(), details.title.into());
+        object.insert("depth".to_owned(), details.depth.into());
+        object.insert("keywords".to_owned(), details.keywords.into());
+        object.insert(
+            "outlinks".to_owned(),
+            out_links.iter().map(|id| id.get()).collect(),
+        );
+        // Attributes that are also details of the page, such as the title, are only written once
+        for (attribute, value) in self.schema.node_attributes.iter().zip(&node.attributes) {
+            object
+                .entry(attribute.name.to_owned())
+                .or_insert_with(|| to_json(value));
+        }
+
+        serde_json::to_writer(&mut self.out, &object)?;
+        self.out.write_all(b"\n")
+    }
+
+    fn needs_page_details(&self) -> bool {
+        true
+    }
+
+    fn finish(&mut self) -> std::io::Result<()> {
+        self.out.finish()
+    }
+}
@@ -0,0 +1,366 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use crossbeam_channel::{Receiver, Select, Sender, TryRecvError};
+
+use super::{Edge, Node, OutputFormat, OutputOptions, OutputWriter, Schema};
+
+/// Number of nodes and edges of a graph waiting to be written, above which its export waits for the writer thread
+const CAPACITY: usize = 4096;
+
+/// Priority of a graph being written. The writer thread writes the nodes and edges of the graphs with the highest
+/// priority first, so that e.g. the graph saved at the end of the crawl is not slowed down by a window of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// Written while the crawl goes on, such as the windows of a continuous crawl
+    Background,
+    /// Waited for by the user, such as the graph saved at the end of the crawl or when asked to flush it
+    Foreground,
+}
+
+/// Creates the writers of a graph, on the writer thread
+type CreateWriters = Box<dyn FnOnce() -> io::Result<Vec<Box<dyn OutputWriter>>> + Send>;
+
+enum Message {
+    Begin(Schema),
+    Edge(Edge),
+    Node(Node),
+    Finish,
+}
+
+/// Graph to write, sent to the writer thread
+struct Job {
+    priority: Priority,
+    create: CreateWriters,
+    messages: Receiver<Message>,
+    /// Whether the writers need the details of the pages, once they have been created
+    created: Sender<io::Result<bool>>,
+    result: Sender<io::Result<()>>,
+}
+
+/// Graph being written by the writer thread
+struct ActiveJob {
+    priority: Priority,
+    writers: Vec<Box<dyn OutputWriter>>,
+    messages: Receiver<Message>,
+    result: Sender<io::Result<()>>,
+}
+
+/// Thread owning every output file. The graphs are exported on the threads that save them, which send their nodes
+/// and edges to the writer thread through a bounded channel: the serialization and the writing to disk
+/// happen on the writer thread, and an export waits when the writer thread falls behind,
+/// instead of buffering the whole graph in memory.
+///
+/// Each graph is exported once and written in every format by the writer thread. The thread is started
+/// the first time a graph is written, and cloning the handle shares the same thread
+#[derive(Clone, Default)]
+pub struct WriterThread {
+    jobs: Arc<Mutex<Option<Sender<Job>>>>,
+}
+
+impl WriterThread {
+    /// Writer of a graph with `priority` to the files for the prefix `prefix`, in each of `formats`
+    pub fn files(
+        &self,
+        priority: Priority,
+        prefix: impl Into<String>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+    ) -> io::Result<ThreadedWriter> {
+        let (prefix, formats, options) = (prefix.into(), formats.to_vec(), *options);
+        self.writer(priority, move || {
+            formats
+                .iter()
+                .map(|format| format.create_writer(&prefix, &options))
+                .collect()
+        })
+    }
+
+    /// Writer of a graph with `priority` to the writers returned by `create`, which is called on the writer thread.
+    /// Fails if they cannot be created
+    pub fn writer(
+        &self,
+        priority: Priority,
+        create: impl FnOnce() -> io::Result<Vec<Box<dyn OutputWriter>>> + Send + 'static,
+    ) -> io::Result<ThreadedWriter> {
+        let (messages_tx, messages) = crossbeam_channel::bounded(CAPACITY);
+        let (created, created_rx) = crossbeam_channel::bounded(1);
+        let (result, result_rx) = crossbeam_channel::bounded(1);
+        let job = Job {
+            priority,
+            create: Box::new(create),
+            messages,
+            created,
+            result,
+        };
+        self.jobs
+            .lock()
+            .unwrap()
+            .get_or_insert_with(spawn)
+            .send(job)
+            .map_err(|_| io::Error::other("The writer thread has stopped"))?;
+        let needs_page_details = created_rx
+            .recv()
+            .map_err(|_| io::Error::other("The writer thread has stopped"))??;
+        Ok(ThreadedWriter {
+            messages: messages_tx,
+            result: result_rx,
+            needs_page_details,
+        })
+    }
+}
+
+/// Starts the writer thread, which stops once every handle to it has been dropped and every graph has been written
+fn spawn() -> Sender<Job> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    thread::spawn(move || write_jobs(rx));
+    tx
+}
+
+fn write_jobs(jobs: Receiver<Job>) {
+    // Sorted by decreasing priority, so that the first job with a message is the one to write
+    let mut active: Vec<ActiveJob> = Vec::new();
+    let mut accepting = true;
+    loop {
+        while accepting {
+            match jobs.try_recv() {
+                Ok(job) => start(job, &mut active),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => accepting = false,
+            }
+        }
+
+        let next = active
+            .iter()
+            .enumerate()
+            .find_map(|(idx, job)| match job.messages.try_recv() {
+                Ok(message) => Some((idx, Some(message))),
+                Err(TryRecvError::Empty) => None,
+                // The export has been abandoned, e.g. because it failed
+                Err(TryRecvError::Disconnected) => Some((idx, None)),
+            });
+        match next {
+            Some((idx, Some(message))) => {
+                if handle(&mut active[idx], message) {
+                    active.remove(idx);
+                }
+                continue;
+            }
+            Some((idx, None)) => {
+                active.remove(idx);
+                continue;
+            }
+            None => {}
+        }
+
+        if !accepting && active.is_empty() {
+            return;
+        }
+        // Wait until a new job or a message of any job arrives
+        let mut select = Select::new();
+        if accepting {
+            select.recv(&jobs);
+        }
+        for job in &active {
+            select.recv(&job.messages);
+        }
+        select.ready();
+    }
+}
+
+/// Creates the writers of a new job, replying whether they need the details of the pages
+fn start(job: Job, active: &mut Vec<ActiveJob>) {
+    let writers = match (job.create)() {
+        Ok(writers) => writers,
+        Err(err) => {
+            let _ = job.created.send(Err(err));
+            return;
+        }
+    };
+    let _ = job
+        .created
+        .send(Ok(writers.iter().any(|writer| writer.needs_page_details())));
+    let idx = active.partition_point(|other| other.priority >= job.priority);
+    active.insert(
+        idx,
+        ActiveJob {
+            priority: job.priority,
+            writers,
+            messages: job.messages,
+            result: job.result,
+        },
+    );
+}
+
+/// Writes a message of a job to each of its writers. Returns whether the job is over, because it has been finished
+/// or because a writer failed, in which case the error is sent back and the export stops at its next message
+fn handle(job: &mut ActiveJob, message: Message) -> bool {
+    let mut result = Ok(());
+    for writer in &mut job.writers {
+        result = match &message {
+            Message::Begin(schema) => writer.begin(schema),
+            Message::Edge(edge) => writer.write_edge(edge),
+            Message::Node(node) => writer.write_node(node),
+            Message::Finish => writer.finish(),
+        };
+        if result.is_err() {
+            break;
+        }
+    }
+    let over = result.is_err() || matches!(message, Message::Finish);
+    if over {
+        let _ = job.result.send(result);
+    }
+    over
+}
+
+/// Sends the graph being exported to the writer thread, see [`WriterThread`]
+pub struct ThreadedWriter {
+    messages: Sender<Message>,
+    result: Receiver<io::Result<()>>,
+    needs_page_details: bool,
+}
+
+impl ThreadedWriter {
+    /// Sends a message to the writer thread, waiting if it is behind. If the writer thread has stopped writing
+    /// the graph, fails with the error it has stopped on
+    fn send(&self, message: Message) -> io::Result<()> {
+        self.messages.send(message).map_err(|_| self.error())
+    }
+
+    fn error(&self) -> io::Error {
+        match self.result.recv() {
+            Ok(Err(err)) => err,
+            _ => io::Error::other("The writer thread has stopped"),
+        }
+    }
+}
+
+impl OutputWriter for ThreadedWriter {
+    fn begin(&mut self, schema: &Schema) -> io::Result<()> {
+        self.send(Message::Begin(schema.clone()))
+    }
+
+    fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        self.send(Message::Edge(edge.clone()))
+    }
+
+    fn write_node(&mut self, node: &Node) -> io::Result<()> {
+        self.send(Message::Node(node.clone()))
+    }
+
+    fn needs_page_details(&self) -> bool {
+        self.needs_page_details
+    }
+
+    /// Waits until the writer thread has written the whole graph
+    fn finish(&mut self) -> io::Result<()> {
+        self.send(Message::Finish)?;
+        self.result
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("The writer thread has stopped")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_id::NodeId;
+
+    /// Records what is written to it, failing on the edges if asked to
+    struct Recorder {
+        written: Arc<Mutex<Vec<String>>>,
+        fail_on_edges: bool,
+    }
+
+    impl OutputWriter for Recorder {
+        fn begin(&mut self, _: &Schema) -> io::Result<()> {
+            self.written.lock().unwrap().push("begin".to_owned());
+            Ok(())
+        }
+
+        fn write_edge(&mut self, edge: &Edge) -> io::Result<()> {
+            if self.fail_on_edges {
+                return Err(io::Error::other("disk full"));
+            }
+            let line = format!("{}-{}", edge.source, edge.target);
+            self.written.lock().unwrap().push(line);
+            Ok(())
+        }
+
+        fn write_node(&mut self, node: &Node) -> io::Result<()> {
+            self.written.lock().unwrap().push(node.url.clone());
+            Ok(())
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            self.written.lock().unwrap().push("finish".to_owned());
+            Ok(())
+        }
+    }
+
+    fn recorder(fail_on_edges: bool) -> (Arc<Mutex<Vec<String>>>, Recorder) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            written: written.clone(),
+            fail_on_edges,
+        };
+        (written, recorder)
+    }
+
+    fn write_graph(writer: &mut ThreadedWriter) -> io::Result<()> {
+        writer.begin(&Schema::default())?;
+        for target in 1..=100 {
+            writer.write_edge(&Edge {
+                source: NodeId::new(0),
+                target: NodeId::new(target),
+                attributes: Vec::new(),
+            })?;
+        }
+        writer.write_node(&Node {
+            id: NodeId::new(0),
+            url: "a".to_owned(),
+            attributes: Vec::new(),
+            details: None,
+        })?;
+        writer.finish()
+    }
+
+    #[test]
+    fn graphs_are_written_to_every_writer_on_the_thread() {
+        let thread = WriterThread::default();
+        let (first, first_writer) = recorder(false);
+        let (second, second_writer) = recorder(false);
+        let mut writer = thread
+            .writer(Priority::Foreground, move || {
+                Ok(vec![Box::new(first_writer), Box::new(second_writer)])
+            })
+            .unwrap();
+        write_graph(&mut writer).unwrap();
+
+        let written = first.lock().unwrap().clone();
+        assert_eq!(written.len(), 103);
+        assert_eq!(written[1], "0-1");
+        assert_eq!(written[101..], ["a", "finish"]);
+        assert_eq!(*second.lock().unwrap(), written);
+
+        // The thread goes on writing the other graphs once one of them has failed
+        let (_, failing) = recorder(true);
+        let mut writer = thread
+            .writer(Priority::Background, move || Ok(vec![Box::new(failing)]))
+            .unwrap();
+        let err = write_graph(&mut writer).unwrap_err();
+        assert_eq!(err.to_string(), "disk full");
+        assert!(thread
+            .writer(Priority::Foreground, || Err(io::Error::other("no space")))
+            .is_err());
+        let (_, working) = recorder(false);
+        let mut writer = thread
+            .writer(Priority::Foreground, move || Ok(vec![Box::new(working)]))
+            .unwrap();
+        write_graph(&mut writer).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, io};
+
+use crate::edge_store::EdgeStore;
+use crate::node_id::NodeId;
+use crate::output::AttributeValue;
+
+/// Probability of following a link instead of jumping to a random page
+const DAMPING: f64 = 0.85;
+
+/// The iterations stop once the ranks change by less than this in total, or after `MAX_ITERATIONS`
+const TOLERANCE: f64 = 1e-10;
+const MAX_ITERATIONS: usize = 100;
+
+/// PageRank of the pages of a graph, along with their position once sorted by decreasing rank
+#[derive(Debug)]
+pub struct Ranking {
+    /// Rank and position of each page
+    ranks: HashMap<NodeId, (f64, usize)>,
+    /// Number of pages at the top of the ranking that are kept
+    top: usize,
+}
+
+impl Ranking {
+    /// Computes the PageRank of `nodes`, considering only the links between them.
+    /// Each distinct link counts once, regardless of its weight, and the ranks sum to 1.
+    /// Only the `top_fraction` of the pages with the highest rank are kept, or every page if it is `None`
+    pub fn compute(
+        links: &mut EdgeStore,
+        nodes: &[NodeId],
+        top_fraction: Option<f64>,
+    ) -> io::Result<Ranking> {
+        let ranks = pagerank(links, nodes)?;
+
+        let mut order: Vec<_> = nodes.iter().zip(ranks).collect();
+        order.sort_by(|(id, rank), (other_id, other_rank)| {
+            other_rank.total_cmp(rank).then(id.cmp(other_id))
+        });
+        let top = top_fraction.map_or(order.len(), |fraction| {
+            (order.len() as f64 * fraction).ceil() as usize
+        });
+
+        Ok(Ranking {
+            ranks: order
+                .into_iter()
+                .enumerate()
+                .map(|(position, (id, rank))| (*id, (rank, position)))
+                .collect(),
+            top,
+        })
+    }
+
+    /// Whether the page is one of the top pages that are kept
+    pub fn is_kept(&self, id: &NodeId) -> bool {
+        self.position(id)
+            .is_some_and(|position| position < self.top)
+    }
+
+    /// PageRank of the page, if it has been ranked
+    pub fn rank(&self, id: &NodeId) -> Option<f64> {
+        self.ranks.get(id).map(|(rank, _)| *rank)
+    }
+
+    /// Position of the page once sorted by decreasing rank, starting from 0
+    pub fn position(&self, id: &NodeId) -> Option<usize> {
+        self.ranks.get(id).map(|(_, position)| *position)
+    }
+
+    /// Values of the `pagerank` and `rank_percentile` attributes of a page
+    pub fn attributes(&self, id: &NodeId) -> [AttributeValue; 2] {
+        let (rank, position) = self.ranks.get(id).copied().unwrap_or_default();
+        [
+            AttributeValue::Float(rank),
+            AttributeValue::Integer(rank_percentile(position, self.ranks.len())),
+        ]
+    }
+}
+
+/// Percentile bucket of the page at `position` out of `count` pages sorted by decreasing rank,
+/// from 100 for the top 1% of the pages down to 1 for the bottom 1%
+pub fn rank_percentile(position: usize, count: usize) -> i64 {
+    100 - (position * 100 / count.max(1)) as i64
+}
+
+/// Computes the PageRank of `nodes` by power iteration, in the same order.
+/// The edges are read from `links` at every iteration, so that spilled edges are not loaded in memory
+fn pagerank(links: &mut EdgeStore, nodes: &[NodeId]) -> io::Result<Vec<f64>> {
+    let count = nodes.len();
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    let index: HashMap<NodeId, usize> = nodes.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    let mut out_degree = vec![0usize; count];
+    for edge in links.sorted_edges()? {
+        let ((source, dest), _) = edge?;
+        if let (Some(&source), Some(_)) = (index.get(&source), index.get(&dest)) {
+            out_degree[source] += 1;
+        }
+    }
+
+    let mut ranks = vec![1.0 / count as f64; count];
+    for _ in 0..MAX_ITERATIONS {
+        // The rank of the pages without links is spread over every page
+        let dangling: f64 = ranks
+            .iter()
+            .zip(&out_degree)
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(rank, _)| rank)
+            .sum();
+        let base = (1.0 - DAMPING + DAMPING * dangling) / count as f64;
+        let mut next = vec![base; count];
+        for edge in links.sorted_edges()? {
+            let ((source, dest), _) = edge?;
+            if let (Some(&source), Some(&dest)) = (index.get(&source), index.get(&dest)) {
+                next[dest] += DAMPING * ranks[source] / out_degree[source] as f64;
+            }
+        }
+
+        let change: f64 = next.iter().zip(&ranks).map(|(a, b)| (a - b).abs()).sum();
+        ranks = next;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+    Ok(ranks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(id: u64) -> NodeId {
+        NodeId::new(id)
+    }
+
+    #[test]
+    fn linked_pages_rank_higher() {
+        // 0 and 1 link to each other, 2 and 3 only link to 0
+        let mut links = EdgeStore::default();
+        for edge in [(0, 1), (1, 0), (2, 0), (3, 0)] {
+            links.insert((id(edge.0), id(edge.1))).unwrap();
+        }
+        let nodes = [id(0), id(1), id(2), id(3)];
+        let ranking = Ranking::compute(&mut links, &nodes, Some(0.5)).unwrap();
+
+        assert_eq!(ranking.position(&id(0)), Some(0));
+        assert_eq!(ranking.position(&id(1)), Some(1));
+        // Ties are broken by id
+        assert_eq!(ranking.position(&id(2)), Some(2));
+        assert_eq!(ranking.position(&id(3)), Some(3));
+        assert!(ranking.is_kept(&id(1)));
+        assert!(!ranking.is_kept(&id(2)));
+
+        let total: f64 = nodes
+            .iter()
+            .map(|node| match ranking.attributes(node)[0] {
+                AttributeValue::Float(rank) => rank,
+                _ => unreachable!(),
+            })
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn percentiles_go_from_100_to_1() {
+        assert_eq!(rank_percentile(0, 4), 100);
+        assert_eq!(rank_percentile(3, 4), 25);
+        assert_eq!(rank_percentile(0, 1000), 100);
+        assert_eq!(rank_percentile(999, 1000), 1);
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::checkpoint::{Checkpoint, CheckpointState};
+use crate::config::{RetryConfig, ScraperConfig};
+use crate::errors::ScraperError;
+use crate::graph::Graph;
+use crate::http::HttpClient;
+use crate::mediawiki;
+use crate::node_id::NodeId;
+use crate::pagerank::Ranking;
+use crate::worker;
+
+/// Directory with the state of each stage of a staged crawl: `crawl → enrich → analyze → export`.
+///
+/// The crawl stage writes its checkpoint to the directory, and every later stage reads the state of the ones before it
+/// and writes its own, so a stage can be run again without redoing the previous ones, e.g. after a failure of the pageviews API
+#[derive(Debug, Clone)]
+pub struct StageDir {
+    path: PathBuf,
+}
+
+/// Results of the enrich and analyze stages, by page. Only filled when exporting a staged crawl
+#[derive(Debug, Default)]
+pub struct StageResults {
+    /// Views of each article in the period of the enrich stage. `None` if the stage has not been run
+    pub pageviews: Option<HashMap<NodeId, u64>>,
+    /// Measures of each page computed by the analyze stage. `None` if the stage has not been run
+    pub analysis: Option<HashMap<NodeId, PageAnalysis>>,
+}
+
+/// Line of the state of the enrich stage
+#[derive(Debug, Serialize, Deserialize)]
+struct PageViews {
+    url: String,
+    pageviews: u64,
+}
+
+/// Measures of a page computed by the analyze stage
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PageAnalysis {
+    pub pagerank: f64,
+    pub in_degree: u64,
+    pub out_degree: u64,
+}
+
+/// Line of the state of the analyze stage
+#[derive(Debug, Serialize, Deserialize)]
+struct AnalysisLine {
+    url: String,
+    #[serde(flatten)]
+    analysis: PageAnalysis,
+}
+
+/// Settings of the enrich stage
+#[derive(Debug, Clone)]
+pub struct EnrichConfig {
+    /// Base url of the Wikimedia REST API the pageviews are fetched from
+    pub pageviews_api: String,
+    /// Number of days, up to yesterday, whose views are summed
+    pub days: u64,
+    /// Path of the articles of the wiki, used to find their titles
+    pub article_path: String,
+    pub retry: RetryConfig,
+}
+
+impl Default for EnrichConfig {
+    fn default() -> Self {
+        EnrichConfig {
+            pageviews_api: "https://wikimedia.org/api/rest_v1".to_owned(),
+            days: 30,
+            article_path: "/wiki/".to_owned(),
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PageviewsResponse {
+    #[serde(default)]
+    items: Vec<PageviewsItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PageviewsItem {
+    views: u64,
+}
+
+impl StageDir {
+    pub fn new(path: impl Into<PathBuf>) -> StageDir {
+        StageDir { path: path.into() }
+    }
+
+    /// Creates the directory, if it does not exist yet
+    pub fn create(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.path)
+    }
+
+    /// Checkpoint written by the crawl stage, from which it can also be resumed
+    pub fn checkpoint(&self) -> PathBuf {
+        self.path.join("crawl.checkpoint")
+    }
+
+    fn enrichment(&self) -> PathBuf {
+        self.path.join("enrich.jsonl")
+    }
+
+    fn analysis(&self) -> PathBuf {
+        self.path.join("analyze.jsonl")
+    }
+
+    /// Writes `graph` as the state of the crawl stage, so that the following stages run on it as on a crawled graph.
+    /// Fails if the directory already has the state of a crawl stage
+    pub fn write_graph(&self, graph: &Graph) -> io::Result<()> {
+        let path = self.checkpoint();
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "The crawl stage {} already exists. Delete it or use another directory",
+                    path.display()
+                ),
+            ));
+        }
+        let own_pages = graph.pages.lock().unwrap();
+        let mut own_links = graph.links.lock().unwrap();
+        let mut state = CheckpointState {
+            seeds: &[],
+            hosts: &HashSet::new(),
+            queue: &[],
+            pages: &own_pages,
+            links: &mut own_links,
+            graph,
+        };
+        Checkpoint::write(path, &mut state)
+    }
+
+    /// Loads the graph built by the crawl stage
+    pub fn load_graph(&self, config: Arc<ScraperConfig>) -> io::Result<Graph> {
+        let path = self.checkpoint();
+        let mut checkpoint = Checkpoint::load(&path).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!(
+                    "Could not load the state of the crawl stage {}: {err}",
+                    path.display()
+                ),
+            )
+        })?;
+        if !checkpoint.queue.is_empty() {
+            eprintln!(
+                "[WARN] {} pages are still in the queue of the crawl stage, which has been interrupted or has given up on them. Continue it with --resume {}",
+                checkpoint.queue.len(),
+                path.display()
+            );
+        }
+        let graph = Graph::new(config);
+        checkpoint.restore_graph(&graph)?;
+        Ok(graph)
+    }
+
+    /// Adds the results of the enrich and analyze stages that have been run to `graph`, so that they are exported with it
+    pub fn load_results(&self, graph: &Graph) -> io::Result<()> {
+        let own_pages = graph.pages.lock().unwrap();
+        let by_id = |url: &str| own_pages.get(url).copied();
+
+        let pageviews = read_lines::<PageViews>(&self.enrichment())?.map(|lines| {
+            lines
+                .into_iter()
+                .filter_map(|line| Some((by_id(&line.url)?, line.pageviews)))
+                .collect()
+        });
+        let analysis = read_lines::<AnalysisLine>(&self.analysis())?.map(|lines| {
+            lines
+                .into_iter()
+                .filter_map(|line| Some((by_id(&line.url)?, line.analysis)))
+                .collect()
+        });
+        *graph.stages.lock().unwrap() = StageResults {
+            pageviews,
+            analysis,
+        };
+        Ok(())
+    }
+
+    /// Runs the enrich stage, fetching the pageviews of the articles of `graph` that the previous runs have not enriched yet.
+    /// Every result is written as soon as it is fetched, and the articles whose pageviews cannot be fetched are left for the next run.
+    /// Returns the number of articles enriched by this run and the number of failures
+    pub fn enrich(
+        &self,
+        graph: &Graph,
+        client: &HttpClient,
+        config: &EnrichConfig,
+    ) -> Result<(usize, usize), ScraperError> {
+        let path = self.enrichment();
+        let done: HashSet<String> = read_lines::<PageViews>(&path)?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|line| line.url)
+            .collect();
+        let mut urls: Vec<String> = graph
+            .pages
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|url| !done.contains(*url))
+            .cloned()
+            .collect();
+        urls.sort();
+        eprintln!(
+            "[INFO] {} pages have been enriched by the previous runs, {} are left",
+            done.len(),
+            urls.len()
+        );
+
+        let mut out = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)?;
+        // A line cut short by an interrupted run is ended, so that it does not swallow the next one
+        if !ends_with_newline(&mut out)? {
+            writeln!(out)?;
+        }
+        let (start, end) = pageviews_period(config.days, SystemTime::now());
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(ScraperError::RuntimeFailed)?;
+
+        let (mut enriched, mut failed) = (0, 0);
+        for url in urls {
+            let Some(request_url) = pageviews_url(&url, config, &start, &end) else {
+                continue;
+            };
+            let pageviews = runtime.block_on(async {
+                let body = worker::get_text_with_retry(client, &request_url, &config.retry).await?;
+                let response: PageviewsResponse = serde_json::from_str(&body).map_err(|err| {
+                    ScraperError::InvalidApiResponse {
+                        url: request_url.clone(),
+                        reason: err.to_string(),
+                    }
+                })?;
+                Ok::<_, ScraperError>(response.items.iter().map(|item| item.views).sum())
+            });
+            match pageviews {
+                Ok(pageviews) => {
+                    let line = serde_json::to_string(&PageViews { url, pageviews })
+                        .map_err(io::Error::other)?;
+                    writeln!(out, "{line}")?;
+                    enriched += 1;
+                }
+                Err(err) => {
+                    eprintln!("[WARN] Could not fetch the pageviews of {url}: {err}");
+                    failed += 1;
+                }
+            }
+        }
+        out.sync_all()?;
+        Ok((enriched, failed))
+    }
+
+    /// Runs the analyze stage, computing the PageRank and the degree of every page of `graph`.
+    /// The state of the stage is replaced only once it has been written completely
+    pub fn analyze(&self, graph: &Graph) -> io::Result<usize> {
+        let mut own_links = graph.links.lock().unwrap();
+        let own_pages = graph.pages.lock().unwrap();
+        // Pages merged into another one share its id, and are analyzed once
+        let mut pages: Vec<_> = own_pages.iter().collect();
+        pages.sort();
+        let mut seen = HashSet::new();
+        pages.retain(|(_, id)| seen.insert(**id));
+        let nodes: Vec<NodeId> = pages.iter().map(|(_, id)| **id).collect();
+
+        let ranking = Ranking::compute(&mut own_links, &nodes, None)?;
+        let degrees = own_links.degrees()?;
+
+        let path = self.analysis();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        for (url, id) in &pages {
+            let (in_degree, out_degree) = degrees.get(id).copied().unwrap_or_default();
+            let line = AnalysisLine {
+                url: (*url).clone(),
+                analysis: PageAnalysis {
+                    pagerank: ranking.rank(id).unwrap_or_default(),
+                    in_degree,
+                    out_degree,
+                },
+            };
+            let line = serde_json::to_string(&line).map_err(io::Error::other)?;
+            writeln!(out, "{line}")?;
+        }
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(tmp_path, path)?;
+        Ok(pages.len())
+    }
+}
+
+/// Reads a file of json lines, or returns `None` if it does not exist because its stage has not been run.
+/// A last line cut short by an interrupted run is skipped
+fn read_lines<T: DeserializeOwned>(path: &Path) -> io::Result<Option<Vec<T>>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut values = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(value) => values.push(value),
+            Err(err) => eprintln!("[WARN] Skipping a line of {}: {err}", path.display()),
+        }
+    }
+    Ok(Some(values))
+}
+
+/// Whether a file is empty or ends with a newline
+fn ends_with_newline(file: &mut File) -> io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(true);
+    }
+    file.seek(SeekFrom::End(-1))?;
+    let mut last = [0];
+    file.read_exact(&mut last)?;
+    Ok(last[0] == b'\n')
+}
+
+/// Url of the daily views of the article at `url` from `start` to `end` in the REST API, or `None` if it is not an article
+fn pageviews_url(url: &str, config: &EnrichConfig, start: &str, end: &str) -> Option<String> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    let project = parsed.host_str()?;
+    let title = mediawiki::article_title(&parsed, &config.article_path)?.replace(' ', "_");
+    Some(format!(
+        "{}/metrics/pageviews/per-article/{project}/all-access/user/{}/daily/{start}/{end}",
+        config.pageviews_api.trim_end_matches('/'),
+        utf8_percent_encode(&title, NON_ALPHANUMERIC)
+    ))
+}
+
+/// First and last day, as `YYYYMMDD`, of the `days` days up to the one before `now`
+fn pageviews_period(days: u64, now: SystemTime) -> (String, String) {
+    let today = now
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+        / 86_400;
+    let end = today.saturating_sub(1);
+    let start = end.saturating_sub(days.max(1) - 1);
+    (format_day(start), format_day(end))
+}
+
+/// Formats a number of days since the unix epoch as `YYYYMMDD`, in the proleptic Gregorian calendar
+fn format_day(days: u64) -> String {
+    // Algorithm from http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}{month:02}{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn days_are_formatted_as_dates() {
+        assert_eq!(format_day(0), "19700101");
+        assert_eq!(format_day(11_016), "20000229");
+        assert_eq!(format_day(19_783), "20240301");
+    }
+
+    #[test]
+    fn period_ends_the_day_before() {
+        // 2024-03-01 at noon
+        let now = UNIX_EPOCH + Duration::from_secs(19_783 * 86_400 + 43_200);
+        assert_eq!(
+            pageviews_period(30, now),
+            ("20240131".to_owned(), "20240229".to_owned())
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::StatusCode;
+
+/// Settings of the adaptive rate limiter
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveRateConfig {
+    /// Requests per second at the start of the crawl
+    pub initial_rate: f64,
+
+    /// The rate never goes below this value, in requests per second
+    pub min_rate: f64,
+
+    /// The rate never goes above this value, in requests per second
+    pub max_rate: f64,
+
+    /// Responses slower than this are treated as a sign of an overloaded server
+    #[serde(deserialize_with = "crate::config::deserialize_secs")]
+    pub target_latency: Duration,
+
+    /// Requests per second added after each fast response
+    pub increase: f64,
+
+    /// Factor the rate is multiplied by after a slow or throttled response
+    pub decrease_factor: f64,
+}
+
+impl Default for AdaptiveRateConfig {
+    fn default() -> Self {
+        AdaptiveRateConfig {
+            initial_rate: 5.0,
+            min_rate: 0.2,
+            max_rate: 50.0,
+            target_latency: Duration::from_millis(1000),
+            increase: 0.5,
+            decrease_factor: 0.5,
+        }
+    }
+}
+
+/// AIMD (additive increase, multiplicative decrease) rate limiter shared by all of the workers.
+///
+/// The rate is increased by a constant after every response that is fast and not throttled,
+/// and multiplied by a factor smaller than 1 after every response that is slow or has status 429 or 503.
+#[derive(Debug)]
+pub struct AdaptiveRateLimiter {
+    config: AdaptiveRateConfig,
+    state: Mutex<LimiterState>,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    rate: f64,
+    next_slot: Instant,
+}
+
+impl AdaptiveRateLimiter {
+    pub fn new(config: AdaptiveRateConfig) -> AdaptiveRateLimiter {
+        let rate = config.initial_rate.clamp(config.min_rate, config.max_rate);
+        AdaptiveRateLimiter {
+            config,
+            state: Mutex::new(LimiterState {
+                rate,
+                next_slot: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until the caller is allowed to send a request
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut state = self.state.lock().unwrap();
+            let slot = state.next_slot.max(Instant::now());
+            state.next_slot = slot + Duration::from_secs_f64(1.0 / state.rate);
+            slot
+        };
+
+        let now = Instant::now();
+        if slot > now {
+            tokio::time::sleep(slot - now).await;
+        }
+    }
+
+    /// Updates the rate given the latency and status of a response
+    pub fn record(&self, latency: Duration, status: Option<StatusCode>) {
+        let throttled = matches!(
+            status,
+            Some(StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+        );
+        let mut state = self.state.lock().unwrap();
+
+        if throttled || status.is_none() || latency > self.config.target_latency {
+            let rate = (state.rate * self.config.decrease_factor).max(self.config.min_rate);
+            if rate < state.rate {
+                eprintln!(
+                    "[INFO] Slowing down to {rate:.2} requests/s (latency: {}ms, status: {})",
+                    latency.as_millis(),
+                    status.map_or("none".to_owned(), |status| status.to_string())
+                );
+            }
+            state.rate = rate;
+        } else {
+            state.rate = (state.rate + self.config.increase).min(self.config.max_rate);
+        }
+    }
+}
+
+/// Token bucket limiting the rate of the requests of all of the workers to a fixed value.
+///
+/// The bucket holds up to `burst` tokens and is refilled with `rate` tokens per second.
+/// Every request takes a token, waiting for one to be available if the bucket is empty.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket allowing `rate` requests per second, with bursts of up to `burst` requests
+    pub fn new(rate: f64, burst: u32) -> TokenBucket {
+        let burst = burst.max(1) as f64;
+        TokenBucket {
+            rate,
+            burst,
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a token is available, and takes it
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let refilled = (now - state.last_refill).as_secs_f64() * self.rate;
+                state.tokens = (state.tokens + refilled).min(self.burst);
+                state.last_refill = now;
+
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - state.tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Limits the rate of the requests to each host separately, with a token bucket per host created as the hosts are found,
+/// so that a slow host does not hold up the requests to the others
+#[derive(Debug)]
+pub struct HostTokenBuckets {
+    rate: f64,
+    burst: u32,
+    buckets: Mutex<HashMap<String, Arc<TokenBucket>>>,
+}
+
+impl HostTokenBuckets {
+    /// Allows `rate` requests per second to each host, with bursts of up to `burst` requests
+    pub fn new(rate: f64, burst: u32) -> HostTokenBuckets {
+        HostTokenBuckets {
+            rate,
+            burst,
+            buckets: Mutex::default(),
+        }
+    }
+
+    /// Waits until a request can be sent to `host`
+    pub async fn acquire(&self, host: &str) {
+        let bucket = self
+            .buckets
+            .lock()
+            .unwrap()
+            .entry(host.to_owned())
+            .or_insert_with(|| Arc::new(TokenBucket::new(self.rate, self.burst)))
+            .clone();
+        bucket.acquire().await;
+    }
+}
+
+/// Limits the bandwidth used by all of the workers to a fixed number of bytes per second.
+///
+/// The size of a response is only known as it is downloaded, so every chunk of a response delays
+/// the following chunks and requests by the time it would have taken to download it at the allowed rate
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    bytes_per_second: f64,
+    next_request: Mutex<Instant>,
+}
+
+impl BandwidthLimiter {
+    pub fn new(bytes_per_second: u64) -> BandwidthLimiter {
+        BandwidthLimiter {
+            bytes_per_second: bytes_per_second.max(1) as f64,
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until the responses downloaded so far fit in the allowed bandwidth
+    pub async fn acquire(&self) {
+        let next_request = *self.next_request.lock().unwrap();
+        let now = Instant::now();
+        if next_request > now {
+            tokio::time::sleep(next_request - now).await;
+        }
+    }
+
+    /// Records a chunk of a response of `bytes` bytes
+    pub fn record(&self, bytes: usize) {
+        let mut next_request = self.next_request.lock().unwrap();
+        *next_request = (*next_request).max(Instant::now())
+            + Duration::from_secs_f64(bytes as f64 / self.bytes_per_second);
+    }
+}
+
+/// Parses a bandwidth given on the command line as bytes per second, with an optional `k`, `M` or `G` suffix
+/// for multiples of 1024, e.g. `512k`. The unit can be spelled out, as in `2MB/s`
+pub fn parse_bandwidth(bandwidth: &str) -> Result<u64, String> {
+    let value = bandwidth.strip_suffix("/s").unwrap_or(bandwidth);
+    let value = value.strip_suffix(['B', 'b']).unwrap_or(value);
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 1 << 10),
+        Some((i, 'm' | 'M')) => (&value[..i], 1 << 20),
+        Some((i, 'g' | 'G')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    match number.trim().parse::<u64>() {
+        Ok(number) if number > 0 => number
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("The bandwidth `{bandwidth}` is too large")),
+        _ => Err(format!(
+            "Expected a positive number of bytes per second, e.g. 512k or 2M, found `{bandwidth}`"
+        )),
+    }
+}
+
+/// Parses a rate given on the command line, which has to be a positive number of requests per second
+pub fn parse_rate(rate: &str) -> Result<f64, String> {
+    match rate.parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!(
+            "Expected a positive number of requests per second, found `{rate}`"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bandwidths_are_parsed_with_their_unit() {
+        assert_eq!(parse_bandwidth("2048"), Ok(2048));
+        assert_eq!(parse_bandwidth("512k"), Ok(512 * 1024));
+        assert_eq!(parse_bandwidth("2M"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_bandwidth("2MB/s"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_bandwidth("100kB"), Ok(100 * 1024));
+        assert_eq!(parse_bandwidth("300B/s"), Ok(300));
+        assert!(parse_bandwidth("0M").is_err());
+        assert!(parse_bandwidth("fast").is_err());
+        assert!(parse_bandwidth("/s").is_err());
+    }
+}
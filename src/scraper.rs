@@ -1,160 +1,886 @@
-use crossbeam_channel::Receiver;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver},
+    task::JoinSet,
+};
 
+use crate::alerts::{AbandonedPages, BudgetAlerts, EmptyPageGuard};
+use crate::cache::CachedResponse;
+use crate::checkpoint::{Checkpoint, CheckpointState};
+use crate::config::{CrawlStrategy, ScraperConfig, SeedPreset, SiteMode};
+use crate::control::{CrawlControl, CrawlHandle, Output};
+use crate::edge_store::EdgeStore;
 use crate::errors::ScraperError;
-use crate::worker::Worker;
+use crate::events::{Decision, EventLog};
+use crate::gephi::GephiStream;
+use crate::graph::Graph;
+use crate::http::{self, HttpClient};
+use crate::keywords::KeywordFilter;
+use crate::links;
+use crate::mediawiki;
+use crate::output::{OutputFormat, OutputOptions};
+use crate::simulate::ExportedGraph;
+use crate::worker::{self, InLinks, QueueItem, QueueSender, SharedState, Worker};
 
 use std::{
-    collections::{HashMap, HashSet},
-    io::Write,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
-pub type ID = u64;
+/// Fraction of failed requests above which a host is reported as one to leave out of the next crawls
+const MAX_HOST_ERROR_RATE: f64 = 0.5;
+
+/// Number of pages with the highest betweenness reported once the crawl is over
+const BRIDGE_PAGES: usize = 10;
+
+/// Pages the crawl is limited to, shared by all of the workers
+#[derive(Clone)]
+struct CrawlScope {
+    /// Hosts whose articles are followed
+    hosts: Arc<HashSet<String>>,
+    /// Pages whose links are kept, when the crawl builds the graph of a category
+    corpus: Option<Arc<HashSet<String>>>,
+}
 
 pub struct WikipediaScraper<'a> {
-    url: &'a str,
-    depth: u64,
-    links: Arc<Mutex<HashSet<(ID, ID)>>>,
-    pages: Arc<Mutex<HashMap<String, ID>>>,
-    keywords: Option<Vec<String>>,
-    num_threads: usize,
-    undirected: bool,
-    keep_external_links: bool,
+    /// Urls to start the scrape from, all with the full depth
+    seeds: &'a [String],
+    graph: Graph,
+    config: Arc<ScraperConfig>,
+    control: Arc<CrawlControl>,
+    client: Arc<HttpClient>,
+    keywords: Arc<KeywordFilter>,
+    alerts: Arc<BudgetAlerts>,
+    empty_pages: Arc<EmptyPageGuard>,
+    abandoned: Arc<AbandonedPages>,
+    /// Log of the decisions taken about each page, opened when the scrape starts
+    events: Arc<EventLog>,
+    /// Checkpoint of the interrupted crawl to continue, if any
+    resumed: Option<Checkpoint>,
+    /// Graph exported by a previous crawl to start from, if any
+    imported: Option<ExportedGraph>,
 }
 
 impl<'a> WikipediaScraper<'a> {
+    /// Fails if some of the options cannot be used together or have an invalid value, see [`ScraperConfig::validate`]
     pub fn new(
-        url: &'a str,
-        depth: u64,
-        num_threads: usize,
-        keywords: Option<Vec<String>>,
-        undirected: bool,
-        keep_external_links: bool,
-    ) -> WikipediaScraper<'a> {
-        if depth == 0 {
+        seeds: &'a [String],
+        mut config: ScraperConfig,
+    ) -> Result<WikipediaScraper<'a>, ScraperError> {
+        if config.depth == 0 {
             eprintln!("[WARN] Depth must be greater than 0. Setting it to 1.");
+            config.depth = 1;
         }
-        if num_threads == 0 {
+        if config.num_threads == 0 {
             eprintln!("[WARN] Number of threads must be greater than 0. Setting it to 1.");
+            config.num_threads = 1;
+        }
+        if config.concurrency == 0 {
+            eprintln!("[WARN] Concurrency must be greater than 0. Setting it to 1.");
+            config.concurrency = 1;
         }
+        let keywords = config.keyword_filter()?;
+
+        let config = Arc::new(config);
+        Ok(WikipediaScraper {
+            seeds,
+            graph: Graph::new(config.clone()),
+            client: Arc::new(HttpClient::new(&config)),
+            keywords: Arc::new(keywords),
+            alerts: Arc::new(BudgetAlerts::new(config.warn_nodes, config.warn_edges)),
+            empty_pages: Arc::new(EmptyPageGuard::new(config.max_empty_pages)),
+            abandoned: Default::default(),
+            events: Default::default(),
+            config,
+            control: Default::default(),
+            resumed: None,
+            imported: None,
+        })
+    }
+
+    /// Continues the crawl saved in `checkpoint` when scraping, instead of starting a new one from the seeds
+    pub fn resume(&mut self, checkpoint: Checkpoint) {
+        self.resumed = Some(checkpoint);
+    }
 
-        WikipediaScraper {
-            url,
-            depth: depth.max(1),
-            links: Default::default(),
-            pages: Default::default(),
-            keywords,
-            num_threads: num_threads.max(1),
-            undirected,
-            keep_external_links,
+    /// Adds the pages and links of `graph` to the graph when scraping, so that the crawl only fetches the pages
+    /// that the crawl which exported it has not expanded, and the graph written at the end is the union of both
+    pub fn import(&mut self, graph: ExportedGraph) {
+        self.imported = Some(graph);
+    }
+
+    /// Handle used to control the scrape while it is running.
+    /// `output` is where the graph is saved when the handle is asked to flush it.
+    pub fn handle(&self, output: Option<Output>) -> CrawlHandle {
+        CrawlHandle {
+            control: self.control.clone(),
+            graph: self.graph.clone(),
+            keywords: self.keywords.clone(),
+            bandwidth: self.client.bandwidth(),
+            output,
         }
     }
 
-    pub fn num_links(&self) -> usize {
-        self.links.lock().unwrap().len()
+    /// Keyword filter used by the workers, whose keywords can be changed while the scrape is running
+    pub fn keywords(&self) -> Arc<KeywordFilter> {
+        self.keywords.clone()
+    }
+
+    /// Handle to the graph being built, which can be read from other threads while the scrape is running
+    pub fn graph(&self) -> Graph {
+        self.graph.clone()
+    }
+
+    pub fn num_links(&self) -> Result<usize, std::io::Error> {
+        self.graph.num_links()
     }
 
     pub fn num_pages(&self) -> usize {
-        self.pages.lock().unwrap().len()
+        self.graph.num_pages()
+    }
+
+    pub fn save_to_file(
+        &self,
+        output_file: impl AsRef<str>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+    ) -> Result<(), std::io::Error> {
+        self.graph.save_to_file(output_file, formats, options)
     }
 
-    pub fn worker(
+    fn worker(
         &self,
-        thread_idx: usize,
-        stopped_threads: Arc<Mutex<Vec<bool>>>,
-        rx: Receiver<(String, u64)>,
-        tx: crossbeam_channel::Sender<(String, u64)>,
+        worker_id: usize,
+        scope: &CrawlScope,
+        gephi: Option<GephiStream>,
+        in_flight: Arc<Mutex<HashMap<usize, QueueItem>>>,
+        in_links: Option<Arc<Mutex<InLinks>>>,
+        queue: QueueSender,
     ) -> Worker {
-        let stopped_threads = stopped_threads.clone();
+        let state = SharedState {
+            graph: self.graph.clone(),
+            hosts: scope.hosts.clone(),
+            corpus: scope.corpus.clone(),
+            control: self.control.clone(),
+            client: self.client.clone(),
+            keywords: self.keywords.clone(),
+            gephi,
+            alerts: self.alerts.clone(),
+            empty_pages: self.empty_pages.clone(),
+            abandoned: self.abandoned.clone(),
+            events: self.events.clone(),
+            in_flight,
+            in_links,
+        };
+        Worker::new(worker_id, state, self.config.clone(), queue)
+    }
 
-        Worker::new(
-            thread_idx,
-            self.links.clone(),
-            self.pages.clone(),
-            self.keywords.clone(),
-            rx,
-            tx,
-            stopped_threads,
-            self.keep_external_links,
-        )
+    /// Returns the urls to start the scrape from: the seed urls and, if requested,
+    /// the same articles in the other languages they are available in
+    async fn seed_urls(&self) -> Result<Vec<String>, ScraperError> {
+        let article_path = (self.config.site_mode == SiteMode::Wiki)
+            .then_some(self.config.wiki.article_path.as_str());
+        let mut members = Vec::new();
+        if let Some(category) = &self.config.category {
+            members = mediawiki::category_members(
+                &self.client,
+                &category.url,
+                category.depth,
+                &self.config.wiki,
+                &self.config.retry,
+            )
+            .await?;
+            eprintln!(
+                "[INFO] Found {} pages in the category {}",
+                members.len(),
+                category.url
+            );
+        }
+        if let Some(random) = &self.config.random_seeds {
+            let articles = mediawiki::random_articles(
+                &self.client,
+                &random.url,
+                random.count,
+                &self.config.wiki,
+                &self.config.retry,
+            )
+            .await?;
+            eprintln!("[INFO] Picked {} random articles", articles.len());
+            members.extend(articles);
+        }
+        if let Some(preset) = &self.config.preset {
+            // The vital articles are the links of a page, and the featured ones the members of a category
+            let articles = match preset.preset {
+                SeedPreset::VitalLevel3 => {
+                    mediawiki::get_page_links(
+                        &self.client,
+                        &preset.url,
+                        &self.config.wiki,
+                        &self.config.retry,
+                    )
+                    .await?
+                    .anchors
+                }
+                SeedPreset::Featured => {
+                    mediawiki::category_members(
+                        &self.client,
+                        &preset.url,
+                        0,
+                        &self.config.wiki,
+                        &self.config.retry,
+                    )
+                    .await?
+                }
+            };
+            eprintln!("[INFO] Found {} articles in {}", articles.len(), preset.url);
+            members.extend(articles);
+        }
+
+        let mut seeds = Vec::new();
+        for seed in self.seeds.iter().chain(&members) {
+            let seed = links::normalize(seed, article_path);
+            if !seeds.contains(&seed) {
+                seeds.push(seed);
+            }
+        }
+        if !self.config.all_languages_of_seed {
+            return Ok(seeds);
+        }
+
+        for seed in self.seeds {
+            let Some(content) =
+                Worker::get_page_content(&self.client, seed, None, &self.config.retry).await?
+            else {
+                continue;
+            };
+
+            for (lang, url) in worker::get_interlanguage_links(&content) {
+                let url = links::normalize(&url, article_path);
+                let selected = self
+                    .config
+                    .languages
+                    .as_ref()
+                    .is_none_or(|languages| languages.contains(&lang));
+                if selected && !seeds.contains(&url) {
+                    eprintln!("[INFO] Adding seed {url} for language {lang}");
+                    seeds.push(url);
+                }
+            }
+        }
+        Ok(seeds)
     }
 
     pub fn scrape(&mut self) -> Result<(), ScraperError> {
-        let stopped_threads = Arc::new(Mutex::new(vec![false; self.num_threads]));
-        let (tx, rx) = crossbeam_channel::unbounded::<(String, u64)>();
+        if let Some(max_in_memory) = self.config.max_edges_in_memory {
+            let spill_dir = self
+                .config
+                .spill_dir
+                .clone()
+                .unwrap_or_else(std::env::temp_dir);
+            *self.graph.links.lock().unwrap() = EdgeStore::with_spill(spill_dir, max_in_memory)
+                .map_err(ScraperError::EdgeSpillFailed)?;
+        }
+        if let Some(imported) = self.imported.take() {
+            imported
+                .preload(&self.graph, self.config.id_width)
+                .map_err(ScraperError::ImportFailed)?;
+            eprintln!("[INFO] Imported {} pages", imported.num_pages());
+        }
+        if let Some(checkpoint) = &mut self.resumed {
+            checkpoint
+                .restore_graph(&self.graph)
+                .map_err(ScraperError::EdgeSpillFailed)?;
+        }
 
-        tx.send((self.url.to_owned(), self.depth))?;
+        self.events = Arc::new(
+            EventLog::create(
+                self.config.event_log.as_deref(),
+                self.config.explain.clone(),
+            )
+            .map_err(ScraperError::EventLogFailed)?,
+        );
 
-        let handles = (0..self.num_threads)
-            .map(|thread_idx| {
-                let stopped_threads = stopped_threads.clone();
-                let worker = self.worker(thread_idx, stopped_threads, rx.clone(), tx.clone());
-                std::thread::spawn(move || worker.scrape())
-            })
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(self.config.num_threads)
+            .enable_all()
+            .build()
+            .map_err(ScraperError::RuntimeFailed)?;
+
+        let (gephi, gephi_thread) = match &self.config.gephi_stream {
+            Some(url) => {
+                let (stream, thread) = GephiStream::start(url, !self.config.undirected);
+                (Some(stream), Some(thread))
+            }
+            None => (None, None),
+        };
+
+        let result = runtime.block_on(self.crawl(gephi));
+        // Pages abandoned by the workers may still be parsed by the blocking threads, which are not waited for
+        runtime.shutdown_background();
+
+        let link_kinds = self
+            .graph
+            .link_kinds
+            .counts()
+            .into_iter()
+            .map(|(kind, count)| format!("{} {count}", kind.as_str()))
             .collect::<Vec<_>>();
+        eprintln!("[INFO] Links found by kind: {}", link_kinds.join(", "));
+
+        if self.config.degrees {
+            match self.graph.degree_stats() {
+                Ok((in_degree, out_degree)) => eprintln!(
+                    "[INFO] In-degree: min {}, max {}, mean {:.2}. Out-degree: min {}, max {}, mean {:.2}",
+                    in_degree.min,
+                    in_degree.max,
+                    in_degree.mean,
+                    out_degree.min,
+                    out_degree.max,
+                    out_degree.mean
+                ),
+                Err(err) => eprintln!("[WARN] Could not compute the degrees of the pages: {err}"),
+            }
+        }
+        if self.config.components {
+            match self.graph.components() {
+                Ok(components) => {
+                    let distribution = components
+                        .size_distribution()
+                        .into_iter()
+                        .rev()
+                        .map(|(size, count)| format!("{size} pages ×{count}"))
+                        .collect::<Vec<_>>();
+                    eprintln!(
+                        "[INFO] {} connected components, the largest one with {} of the {} pages. By size: {}",
+                        components.sizes().len(),
+                        components.sizes().first().copied().unwrap_or_default(),
+                        components.sizes().iter().sum::<usize>(),
+                        distribution.join(", ")
+                    );
+                }
+                Err(err) => {
+                    eprintln!("[WARN] Could not compute the components of the graph: {err}")
+                }
+            }
+        }
+
+        if self.config.centrality {
+            match self.graph.bridge_pages(BRIDGE_PAGES) {
+                Ok((pages, sampled)) => {
+                    let pages = pages
+                        .into_iter()
+                        .map(|(url, betweenness)| format!("{url} {betweenness:.4}"))
+                        .collect::<Vec<_>>();
+                    eprintln!(
+                        "[INFO] Pages with the highest betweenness{}: {}",
+                        if sampled { ", estimated" } else { "" },
+                        pages.join(", ")
+                    );
+                }
+                Err(err) => {
+                    eprintln!("[WARN] Could not compute the centrality of the pages: {err}")
+                }
+            }
+        }
+        if self.config.clustering {
+            match self.graph.clustering() {
+                Ok(clustering) => eprintln!(
+                    "[INFO] Average clustering coefficient: {:.4}",
+                    clustering.average()
+                ),
+                Err(err) => eprintln!(
+                    "[WARN] Could not compute the clustering coefficient of the pages: {err}"
+                ),
+            }
+        }
 
-        handles
+        let bandwidth = self.client.bandwidth();
+        let by_host = bandwidth
+            .by_host()
             .into_iter()
-            .for_each(|handle| handle.join().unwrap().unwrap());
+            .map(|(host, bytes)| format!("{host} {}", http::format_bytes(bytes)))
+            .collect::<Vec<_>>();
+        eprintln!(
+            "[INFO] Downloaded {} in total: {}",
+            http::format_bytes(bandwidth.total()),
+            by_host.join(", ")
+        );
 
-        Ok(())
-    }
+        if self.config.checks_external() {
+            self.report_hosts();
+        }
 
-    pub fn save_to_file(&self, output_file: impl AsRef<str>) -> Result<(), std::io::Error> {
-        let edges_file_path = format!("{}_edges.csv", output_file.as_ref());
-        let nodes_file_path = format!("{}_nodes.csv", output_file.as_ref());
+        for (host, block) in self.client.blocked().by_host() {
+            eprintln!(
+                "[WARN] {host} blocked the crawler: {} block pages, the last one being a {}",
+                block.count, block.reason
+            );
+        }
 
-        let mut edges_file = std::fs::File::create(edges_file_path)?;
-        let mut nodes_file = std::fs::File::create(nodes_file_path)?;
+        let abandoned = self.abandoned.urls();
+        if !abandoned.is_empty() {
+            eprintln!(
+                "[WARN] {} pages have been abandoned after the page timeout: {}",
+                abandoned.len(),
+                abandoned.join(", ")
+            );
+        }
 
-        edges_file.write_all("source,target\n".as_bytes())?;
-        nodes_file.write_all("node_id,url\n".as_bytes())?;
+        let (failed, given_up) = {
+            let own_failed = self.graph.failed.lock().unwrap();
+            let given_up = own_failed
+                .values()
+                .filter(|failed| failed.attempts >= self.config.max_page_attempts)
+                .count();
+            (own_failed.len(), given_up)
+        };
+        if failed > 0 {
+            eprintln!(
+                "[WARN] {failed} pages could not be fetched, {given_up} of which will not be retried when resuming the crawl"
+            );
+        }
 
-        let own_links = self.links.lock().unwrap();
-        let own_pages = self.pages.lock().unwrap();
+        // The workers have dropped their streams, so the thread stops once the last events have been sent
+        if let Some(gephi_thread) = gephi_thread {
+            gephi_thread.finish();
+        }
 
-        if !self.undirected {
-            for (url, id) in own_pages.iter() {
-                nodes_file.write_all(format!("{},\"{}\"\n", id, url).as_bytes())?;
-            }
+        result
+    }
 
-            for (source, dest) in own_links.iter() {
-                edges_file.write_all(format!("{},{}\n", source, dest).as_bytes())?;
+    /// Reports the requests sent to each host, the bytes downloaded from it, the fraction of the requests that failed
+    /// and the time waited on average before each request, then lists the hosts that failed most of the requests
+    fn report_hosts(&self) {
+        let blocked = self.client.blocked().by_host();
+        let bytes = self.client.bandwidth().by_host();
+        let mut failing = Vec::new();
+        eprintln!("[INFO] Requests by host:");
+        for (host, requests) in self.client.requests().by_host() {
+            eprintln!(
+                "[INFO]   {host}: {} requests, {}, {:.1}% errors, {} ms waited on average",
+                requests.requests,
+                http::format_bytes(bytes.get(&host).copied().unwrap_or_default()),
+                requests.error_rate() * 100.0,
+                requests.mean_delay().as_millis()
+            );
+            if requests.error_rate() > MAX_HOST_ERROR_RATE || blocked.contains_key(&host) {
+                failing.push(host);
             }
-        } else {
-            let mut visited_edges = HashSet::new();
-            let mut visited_pages_set = HashSet::new();
-            let mut visited_pages = HashMap::new();
+        }
+        if !failing.is_empty() {
+            eprintln!(
+                "[WARN] {} hosts failed most of the requests or blocked the crawler, and could be left out with --url-deny: {}",
+                failing.len(),
+                failing.join(", ")
+            );
+        }
+    }
 
-            for (source, dest) in own_links.iter() {
-                // If the edge (a,b) has already been inserted, then do not check for (b,a)
-                // since we do not want to add duplicate edges
-                if visited_edges.contains(&(source, dest))
-                    || visited_edges.contains(&(dest, source))
-                {
-                    continue;
+    /// Scrapes the pages in the queue, up to `concurrency` at a time, until the queue is empty
+    /// and no page is being scraped, until `max_pages` pages have been scraped, until the crawl has run for `max_duration`
+    /// or until the crawl is stopped
+    async fn crawl(&self, gephi: Option<GephiStream>) -> Result<(), ScraperError> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<QueueItem>();
+        let queue = QueueSender::new(tx);
+
+        if let (Some(bot_login), Some(seed)) = (&self.config.bot_login, self.seeds.first()) {
+            mediawiki::login(&self.client, seed, &self.config.wiki, bot_login).await?;
+            eprintln!("[INFO] Logged in as {}", bot_login.user);
+        }
+
+        let (seeds, hosts) = match &self.resumed {
+            Some(checkpoint) => {
+                for item in &checkpoint.queue {
+                    queue.send(item.clone());
                 }
-                if own_links.contains(&(*dest, *source)) {
-                    visited_edges.insert((source, dest));
-                    visited_pages_set.insert(source);
-                    visited_pages_set.insert(dest);
+                self.retry_failed(&checkpoint.queue, &queue);
+                (checkpoint.seeds.clone(), Arc::new(checkpoint.hosts.clone()))
+            }
+            None => {
+                let seeds = self.seed_urls().await?;
+                let hosts = seeds
+                    .iter()
+                    .filter_map(|seed| worker::get_host(seed))
+                    .collect::<HashSet<_>>();
+                for seed in &seeds {
+                    self.events.record(seed, || Decision::Queued {
+                        depth: self.config.depth,
+                        from: None,
+                    });
+                    queue.send((seed.clone(), self.config.depth));
                 }
+                *self.graph.seeds.lock().unwrap() = seeds.clone();
+                (seeds, Arc::new(hosts))
             }
+        };
+        // The members of the category are the seeds, which the checkpoint keeps as well
+        let scope = CrawlScope {
+            hosts,
+            corpus: self
+                .config
+                .category
+                .is_some()
+                .then(|| Arc::new(seeds.into_iter().collect())),
+        };
+
+        // Ids of the workers that are not scraping any page
+        let mut idle_workers = (0..self.config.concurrency).rev().collect::<Vec<_>>();
+        let mut workers = JoinSet::new();
+        let in_flight = Arc::new(Mutex::new(HashMap::new()));
+        let mut frontier = Frontier::new(self.config.strategy);
+        let mut checkpoint_timer = tokio::time::interval(self.config.checkpoint_interval);
+        // The first tick completes immediately, and there is nothing to save yet
+        checkpoint_timer.tick().await;
+        let started_at = Instant::now();
+        // Number of pages given to a worker, which is checked against the maximum number of pages
+        let mut started_pages = 0;
+        // Stale cached responses waiting to be refreshed, and the ones being refreshed
+        let mut stale = VecDeque::new();
+        let mut refreshes = JoinSet::new();
 
-            for (url, id) in own_pages.iter() {
-                if visited_pages_set.contains(id) {
-                    visited_pages.insert(id, url);
+        loop {
+            // Wait here rather than after taking a page from the frontier, so that a paused crawl keeps its whole queue.
+            // The pages being scraped are not interrupted.
+            if self.control.is_paused() {
+                eprintln!("[INFO] Crawl paused. Waiting to be resumed");
+                let control = self.control.clone();
+                tokio::task::spawn_blocking(move || control.wait_while_paused())
+                    .await
+                    .expect("Waiting for the crawl to be resumed should not panic");
+            }
+            if let Some(max_duration) = self.config.max_duration {
+                if started_at.elapsed() >= max_duration && !self.control.is_stopped() {
+                    eprintln!(
+                        "[INFO] The crawl has run for {}s, the maximum allowed. Stopping",
+                        max_duration.as_secs()
+                    );
+                    self.control.stop();
                 }
             }
+            if self.control.is_stopped() {
+                eprintln!("[INFO] Crawl stopped. Waiting for the pages being scraped");
+                break;
+            }
+            while let Ok(item) = rx.try_recv() {
+                frontier.push(item);
+            }
+            for item in queue.take_retries() {
+                frontier.push(item);
+            }
+            self.refresh_stale(&mut stale, &mut refreshes);
+            if workers.is_empty() && frontier.is_empty() {
+                eprintln!("[INFO] The queue is empty and no page is being scraped. Stopping");
+                break;
+            }
+            let budget_reached = self
+                .config
+                .max_pages
+                .is_some_and(|max_pages| started_pages >= max_pages);
+            if budget_reached && workers.is_empty() {
+                eprintln!("[INFO] Scraped {started_pages} pages, the maximum allowed. Stopping");
+                break;
+            }
 
-            for (id, url) in visited_pages.iter() {
-                nodes_file.write_all(format!("{},\"{}\"\n", id, url).as_bytes())?;
+            if !idle_workers.is_empty() && !budget_reached {
+                if let Some(((url, depth), queued_at)) = frontier.pop() {
+                    if self
+                        .config
+                        .frontier_ttl
+                        .is_some_and(|ttl| queued_at.elapsed() >= ttl)
+                        && !self.is_still_relevant(&url, queued_at)
+                    {
+                        eprintln!(
+                            "[INFO] Dropping {url}, queued before it was scraped or rejected"
+                        );
+                        self.events.record(&url, || Decision::Skipped {
+                            reason: "queued for longer than the frontier ttl, and scraped or rejected since".to_owned(),
+                        });
+                        continue;
+                    }
+                    started_pages += 1;
+                    let worker_id = idle_workers.pop().expect("There should be an idle worker");
+                    in_flight
+                        .lock()
+                        .unwrap()
+                        .insert(worker_id, (url.clone(), depth));
+                    let worker = self.worker(
+                        worker_id,
+                        &scope,
+                        gephi.clone(),
+                        in_flight.clone(),
+                        frontier.in_links(),
+                        queue.clone(),
+                    );
+                    workers.spawn(async move { (worker_id, worker.scrape(url, depth).await) });
+                    continue;
+                }
             }
 
-            for (source, dest) in visited_edges.iter() {
-                edges_file.write_all(format!("{},{}\n", source, dest).as_bytes())?;
+            tokio::select! {
+                Some(item) = rx.recv() => frontier.push(item),
+                Some(joined) = workers.join_next() => {
+                    let (worker_id, result) = joined.expect("A worker should not panic");
+                    result?;
+                    // The pages skipped by the worker, e.g. the ones that could not be fetched, are still there
+                    in_flight.lock().unwrap().remove(&worker_id);
+                    idle_workers.push(worker_id);
+                },
+                Some(joined) = refreshes.join_next() => {
+                    joined.expect("Refreshing a cached response should not panic");
+                },
+                _ = checkpoint_timer.tick(), if self.config.checkpoint.is_some() => {
+                    self.write_checkpoint(&scope.hosts, &in_flight, &mut frontier, &mut rx, &queue);
+                },
+                else => break,
             }
         }
+
+        while let Some(joined) = workers.join_next().await {
+            joined.expect("A worker should not panic").1?;
+        }
+        self.refresh_stale(&mut stale, &mut refreshes);
+        if !refreshes.is_empty() {
+            eprintln!(
+                "[INFO] Waiting for {} stale cached pages to be refreshed",
+                stale.len() + refreshes.len()
+            );
+        }
+        while let Some(joined) = refreshes.join_next().await {
+            joined.expect("Refreshing a cached response should not panic");
+            self.refresh_stale(&mut stale, &mut refreshes);
+        }
+        if self.config.checkpoint.is_some() {
+            self.write_checkpoint(&scope.hosts, &in_flight, &mut frontier, &mut rx, &queue);
+        }
         Ok(())
     }
+
+    /// Queues again the pages that the crawl saved in the checkpoint could not fetch, unless they are already in its `queue`
+    /// or the crawl has given up on them too many times
+    fn retry_failed(&self, queued: &[QueueItem], queue: &QueueSender) {
+        let queued: HashSet<_> = queued.iter().map(|(url, _)| url.as_str()).collect();
+        let own_failed = self.graph.failed.lock().unwrap();
+        let mut retried: Vec<_> = own_failed
+            .iter()
+            .filter(|(url, failed)| {
+                failed.attempts < self.config.max_page_attempts && !queued.contains(url.as_str())
+            })
+            .map(|(url, failed)| (url.clone(), failed.depth))
+            .collect();
+        if retried.is_empty() {
+            return;
+        }
+        retried.sort();
+        eprintln!(
+            "[INFO] Retrying {} pages that could not be fetched before",
+            retried.len()
+        );
+        for item in retried {
+            queue.send(item);
+        }
+    }
+
+    /// Whether a page taken from the frontier after waiting since `queued_at` still has to be scraped:
+    /// it has not been scraped nor rejected by the keywords since it was queued
+    fn is_still_relevant(&self, url: &str, queued_at: Instant) -> bool {
+        let own_pages = self.graph.pages.lock().unwrap();
+        let Some(id) = own_pages.get(url) else {
+            return true;
+        };
+        if self.graph.ghosts.lock().unwrap().contains(id) {
+            return false;
+        }
+        self.graph
+            .expanded
+            .lock()
+            .unwrap()
+            .get(id)
+            .is_none_or(|visit| visit.at < queued_at)
+    }
+
+    /// Refreshes the stale cached responses used by the workers in the background, up to `concurrency` at a time
+    fn refresh_stale(
+        &self,
+        stale: &mut VecDeque<(String, CachedResponse)>,
+        refreshes: &mut JoinSet<()>,
+    ) {
+        stale.extend(self.client.take_stale());
+        while refreshes.len() < self.config.concurrency {
+            let Some((url, cached)) = stale.pop_front() else {
+                break;
+            };
+            let client = self.client.clone();
+            refreshes.spawn(async move { client.refresh(&url, cached).await });
+        }
+    }
+
+    /// Writes the state of the crawl to the checkpoint file, with the pages in the frontier and the ones waiting in the channel,
+    /// which are moved to the frontier. Failures are only logged, so that the crawl can keep going.
+    fn write_checkpoint(
+        &self,
+        hosts: &HashSet<String>,
+        in_flight: &Mutex<HashMap<usize, QueueItem>>,
+        frontier: &mut Frontier,
+        rx: &mut UnboundedReceiver<QueueItem>,
+        queue: &QueueSender,
+    ) {
+        let Some(path) = &self.config.checkpoint else {
+            return;
+        };
+
+        tokio::task::block_in_place(|| {
+            // The workers add pages to the queue while holding these locks, so the queue cannot change while they are held
+            let own_pages = self.graph.pages.lock().unwrap();
+            let mut own_links = self.graph.links.lock().unwrap();
+            let own_in_flight = in_flight.lock().unwrap();
+
+            let mut waiting = Vec::new();
+            while let Ok(item) = rx.try_recv() {
+                waiting.push(item);
+            }
+            waiting.extend(queue.take_retries());
+            let queue = own_in_flight
+                .values()
+                .cloned()
+                .chain(frontier.items())
+                .chain(waiting.iter().cloned())
+                .collect::<Vec<_>>();
+
+            let own_seeds = self.graph.seeds.lock().unwrap();
+            let mut state = CheckpointState {
+                seeds: &own_seeds,
+                hosts,
+                queue: &queue,
+                pages: &own_pages,
+                links: &mut own_links,
+                graph: &self.graph,
+            };
+            match Checkpoint::write(path, &mut state) {
+                Ok(()) => eprintln!(
+                    "[INFO] Wrote checkpoint {} with {} pages and {} pages in the queue",
+                    path.display(),
+                    own_pages.len(),
+                    queue.len()
+                ),
+                Err(err) => eprintln!(
+                    "[WARN] Could not write checkpoint {}: {err}",
+                    path.display()
+                ),
+            }
+
+            for item in waiting {
+                frontier.push(item);
+            }
+        })
+    }
+}
+
+/// Pages waiting to be scraped, taken in the order given by the crawl strategy along with the time they have been queued at
+struct Frontier {
+    strategy: CrawlStrategy,
+    queue: VecDeque<(QueueItem, Instant)>,
+    /// Pages by number of links to them, then by the order they have been found
+    by_links: BinaryHeap<(usize, Reverse<usize>, QueueItem)>,
+    /// Pages waiting in `by_links`, with the order they have been found, their depth and the time they have been queued at.
+    /// A page queued again to be revisited has several entries
+    waiting: HashMap<String, Vec<(usize, u64, Instant)>>,
+    /// Number of links to each page, updated by the workers
+    in_links: Arc<Mutex<InLinks>>,
+    found: usize,
+}
+
+impl Frontier {
+    fn new(strategy: CrawlStrategy) -> Frontier {
+        Frontier {
+            strategy,
+            queue: VecDeque::new(),
+            by_links: BinaryHeap::new(),
+            waiting: HashMap::new(),
+            in_links: Default::default(),
+            found: 0,
+        }
+    }
+
+    /// Counts of the links to the pages the workers have to update, if the strategy needs them
+    fn in_links(&self) -> Option<Arc<Mutex<InLinks>>> {
+        (self.strategy == CrawlStrategy::Priority).then(|| self.in_links.clone())
+    }
+
+    fn push(&mut self, (url, depth): QueueItem) {
+        let queued_at = Instant::now();
+        match self.strategy {
+            CrawlStrategy::Bfs | CrawlStrategy::Dfs => {
+                self.queue.push_back(((url, depth), queued_at))
+            }
+            CrawlStrategy::Priority => {
+                let links = self.in_links.lock().unwrap().count(&url);
+                self.waiting
+                    .entry(url.clone())
+                    .or_default()
+                    .push((self.found, depth, queued_at));
+                self.by_links
+                    .push((links, Reverse(self.found), (url, depth)));
+            }
+        }
+        self.found += 1;
+    }
+
+    fn pop(&mut self) -> Option<(QueueItem, Instant)> {
+        match self.strategy {
+            CrawlStrategy::Bfs => self.queue.pop_front(),
+            CrawlStrategy::Dfs => self.queue.pop_back(),
+            CrawlStrategy::Priority => {
+                let mut in_links = self.in_links.lock().unwrap();
+                // The old entries of the pages that have gained links become stale, and are skipped when popped
+                for url in in_links.take_changed() {
+                    let links = in_links.count(&url);
+                    for &(order, depth, _) in self.waiting.get(&url).into_iter().flatten() {
+                        self.by_links
+                            .push((links, Reverse(order), (url.clone(), depth)));
+                    }
+                }
+                while let Some((links, Reverse(order), (url, depth))) = self.by_links.pop() {
+                    let Some(entries) = self.waiting.get_mut(&url) else {
+                        continue;
+                    };
+                    let Some(idx) = entries.iter().position(|entry| entry.0 == order) else {
+                        continue;
+                    };
+                    if links != in_links.count(&url) {
+                        continue;
+                    }
+                    let (_, _, queued_at) = entries.swap_remove(idx);
+                    if entries.is_empty() {
+                        self.waiting.remove(&url);
+                    }
+                    return Some(((url, depth), queued_at));
+                }
+                None
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty() && self.waiting.is_empty()
+    }
+
+    /// Pages in the frontier, in the order they have been added
+    fn items(&self) -> Vec<QueueItem> {
+        let mut waiting = self
+            .waiting
+            .iter()
+            .flat_map(|(url, entries)| {
+                entries
+                    .iter()
+                    .map(move |(order, depth, _)| (*order, (url.clone(), *depth)))
+            })
+            .collect::<Vec<_>>();
+        waiting.sort_by_key(|(order, _)| *order);
+        self.queue
+            .iter()
+            .map(|(item, _)| item.clone())
+            .chain(waiting.into_iter().map(|(_, item)| item))
+            .collect()
+    }
 }
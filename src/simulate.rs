@@ -0,0 +1,395 @@
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet, VecDeque},
+    io,
+    path::Path,
+    sync::Arc,
+};
+
+use crate::compression::Compression;
+use crate::config::ScraperConfig;
+use crate::graph::Graph;
+use crate::node_id::{IdWidth, NodeId};
+use crate::output::{OutputFormat, OutputOptions};
+use crate::snapshot;
+use crate::worker;
+
+/// Order in which the simulated crawl expands the pages it has found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SimulationStrategy {
+    /// Breadth first, in the order the pages are found. This is what the scraper does
+    #[default]
+    Bfs,
+    /// Depth first, expanding the page found last
+    Dfs,
+    /// Expand the page with the most links from the pages expanded so far
+    Degree,
+}
+
+/// Parameters of a simulated crawl
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Url of the page to start from. Defaults to the seed of the exported crawl
+    pub seed: Option<String>,
+    pub depth: u64,
+    pub strategy: SimulationStrategy,
+    /// Maximum number of pages to expand
+    pub max_pages: Option<usize>,
+    /// Configuration of the simulated graph, used when it is exported
+    pub graph: ScraperConfig,
+}
+
+/// A graph exported by a previous crawl, loaded in memory
+#[derive(Debug, Default)]
+pub struct ExportedGraph {
+    urls: HashMap<NodeId, String>,
+    out_links: HashMap<NodeId, Vec<NodeId>>,
+    /// Canonical url of the pages merged into another one, by their url
+    aliases: HashMap<String, String>,
+}
+
+/// Outcome of a simulated crawl
+pub struct Simulation {
+    /// Number of pages that have been expanded
+    pub expanded: usize,
+    pub graph: Graph,
+}
+
+impl ExportedGraph {
+    /// Loads the snapshot at `path`, if it is one, or else the csv files written for the prefix `path`
+    pub fn open(path: impl AsRef<str>, delimiter: u8) -> io::Result<ExportedGraph> {
+        let path = path.as_ref();
+        if snapshot::is_snapshot(path) {
+            let graph = snapshot::load(path, Arc::new(ScraperConfig::default()))?;
+            ExportedGraph::from_graph(&graph)
+        } else {
+            ExportedGraph::load(path, delimiter)
+        }
+    }
+
+    /// Loads the csv files written for the prefix `prefix`, with any compression
+    pub fn load(prefix: impl AsRef<str>, delimiter: u8) -> io::Result<ExportedGraph> {
+        let [edges_path, nodes_path] = find_csv_files(prefix.as_ref(), delimiter)?;
+        let mut graph = ExportedGraph::default();
+
+        for record in csv_reader(&nodes_path, delimiter)?.into_records() {
+            let record = record?;
+            graph
+                .urls
+                .insert(parse_id(&record, 0)?, record[1].to_owned());
+        }
+        for record in csv_reader(&edges_path, delimiter)?.into_records() {
+            let record = record?;
+            let (source, target) = (parse_id(&record, 0)?, parse_id(&record, 1)?);
+            graph.out_links.entry(source).or_default().push(target);
+        }
+        Ok(graph)
+    }
+
+    /// Pages and links of `graph`. The pages merged into another one are found by their url, but are not pages of their own
+    pub fn from_graph(graph: &Graph) -> io::Result<ExportedGraph> {
+        let aliases = graph.aliases.lock().unwrap();
+        let mut exported = ExportedGraph {
+            aliases: aliases.clone(),
+            urls: graph
+                .pages
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(url, _)| !aliases.contains_key(*url))
+                .map(|(url, id)| (*id, url.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        for edge in graph.links.lock().unwrap().sorted_edges()? {
+            let ((source, target), _) = edge?;
+            exported.out_links.entry(source).or_default().push(target);
+        }
+        Ok(exported)
+    }
+
+    pub fn num_pages(&self) -> usize {
+        self.urls.len()
+    }
+
+    /// Id of the page with the given url
+    fn find(&self, url: &str) -> Option<NodeId> {
+        let url = self.aliases.get(url).map_or(url, String::as_str);
+        self.urls
+            .iter()
+            .find(|(_, page_url)| *page_url == url)
+            .map(|(id, _)| *id)
+    }
+
+    /// Urls of the pages along one of the shortest paths of links from the page at `from` to the one at `to`, both included.
+    /// Returns `None` if there is no such path, and fails if `from` is not in the graph
+    pub fn shortest_path(&self, from: &str, to: &str) -> io::Result<Option<Vec<String>>> {
+        let from = self
+            .find(from)
+            .ok_or_else(|| io::Error::other(format!("The page {from} is not in the graph")))?;
+        let Some(to) = self.find(to) else {
+            return Ok(None);
+        };
+
+        // Breadth first, recording the page each page has been found from
+        let mut previous = HashMap::from([(from, from)]);
+        let mut frontier = VecDeque::from([from]);
+        while let Some(page) = frontier.pop_front() {
+            if page == to {
+                break;
+            }
+            for &target in self.out_links.get(&page).into_iter().flatten() {
+                // Links to pages left out of the nodes file lead nowhere
+                if !self.urls.contains_key(&target) {
+                    continue;
+                }
+                if let Entry::Vacant(entry) = previous.entry(target) {
+                    entry.insert(page);
+                    frontier.push_back(target);
+                }
+            }
+        }
+        if !previous.contains_key(&to) {
+            return Ok(None);
+        }
+
+        let mut path = vec![self.urls[&to].clone()];
+        let mut page = to;
+        while page != from {
+            page = previous[&page];
+            path.push(self.urls[&page].clone());
+        }
+        path.reverse();
+        Ok(Some(path))
+    }
+
+    /// Adds the pages and links to `graph` with new ids, allocated in the order of the exported ones.
+    /// The links of the pages expanded by the exported crawl are also recorded as imported,
+    /// so that a new crawl expands these pages from the graph instead of fetching them again
+    pub fn preload(&self, graph: &Graph, id_width: IdWidth) -> io::Result<()> {
+        let mut own_pages = graph.pages.lock().unwrap();
+        let mut own_links = graph.links.lock().unwrap();
+        let mut own_imported = graph.imported.lock().unwrap();
+
+        let mut exported_ids = self.urls.keys().copied().collect::<Vec<_>>();
+        exported_ids.sort_unstable();
+        let mut ids = HashMap::with_capacity(exported_ids.len());
+        for exported_id in exported_ids {
+            let url = &self.urls[&exported_id];
+            let id = match own_pages.get(url) {
+                Some(id) => *id,
+                None => {
+                    let id = NodeId::allocate(own_pages.len(), id_width).ok_or_else(|| {
+                        io::Error::other("The imported graph has more pages than the ids can count")
+                    })?;
+                    own_pages.insert(url.clone(), id);
+                    id
+                }
+            };
+            ids.insert(exported_id, id);
+        }
+
+        for (source, targets) in &self.out_links {
+            let Some(&source_id) = ids.get(source) else {
+                continue;
+            };
+            let links = own_imported.links.entry(source_id).or_default();
+            // Edges to pages left out of the nodes file, such as the excluded ghosts, are skipped
+            for target in targets {
+                if let Some(&target_id) = ids.get(target) {
+                    own_links.insert((source_id, target_id))?;
+                    links.push(self.urls[target].clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replays a crawl over the graph, without any request: expanding a page finds the pages it links to in the graph.
+    /// Pages that had not been expanded by the exported crawl have no links.
+    pub fn simulate(&self, config: SimulationConfig) -> io::Result<Simulation> {
+        let seed = match &config.seed {
+            Some(url) => self.find(url).ok_or_else(|| {
+                io::Error::other(format!("The page {url} is not in the exported graph"))
+            })?,
+            // The seed of a crawl always gets the first id
+            None => NodeId::new(0),
+        };
+        let seed_url = self
+            .urls
+            .get(&seed)
+            .ok_or_else(|| io::Error::other("The exported graph has no pages"))?;
+        let hosts = worker::get_host(seed_url)
+            .into_iter()
+            .collect::<HashSet<_>>();
+
+        let graph = Graph::new(Arc::new(ScraperConfig {
+            depth: config.depth,
+            ..config.graph.clone()
+        }));
+        let mut frontier = Frontier::new(config.strategy);
+        // Ids of the pages in the simulated graph, assigned in the order the pages are found as the scraper does
+        let mut ids = HashMap::from([(seed, NodeId::new(0))]);
+        frontier.push(seed, config.depth);
+        let mut expanded = 0;
+
+        let mut own_links = graph.links.lock().unwrap();
+        while let Some((page, depth)) = frontier.pop() {
+            if config
+                .max_pages
+                .is_some_and(|max_pages| expanded >= max_pages)
+            {
+                break;
+            }
+            expanded += 1;
+
+            for &target in self.out_links.get(&page).into_iter().flatten() {
+                let found = !ids.contains_key(&target);
+                if found {
+                    let new_id =
+                        NodeId::allocate(ids.len(), config.graph.id_width).ok_or_else(|| {
+                            io::Error::other(
+                                "The simulated graph has more pages than the ids can count",
+                            )
+                        })?;
+                    ids.insert(target, new_id);
+                }
+                let target_id = ids[&target];
+                own_links.insert((ids[&page], target_id))?;
+
+                frontier.link(target);
+                let followable = self.urls.get(&target).is_some_and(|url| {
+                    worker::is_followable_article(url, &hosts, &config.graph.wiki.article_path)
+                });
+                if found && followable && depth > 1 {
+                    frontier.push(target, depth - 1);
+                }
+            }
+        }
+        drop(own_links);
+
+        graph.pages.lock().unwrap().extend(
+            ids.into_iter()
+                .map(|(page, id)| (self.urls.get(&page).cloned().unwrap_or_default(), id)),
+        );
+        Ok(Simulation { expanded, graph })
+    }
+}
+
+impl Simulation {
+    pub fn save_to_file(
+        &self,
+        output_file: impl AsRef<str>,
+        formats: &[OutputFormat],
+        options: &OutputOptions,
+    ) -> io::Result<()> {
+        self.graph.save_to_file(output_file, formats, options)
+    }
+}
+
+/// Pages waiting to be expanded, along with their remaining depth
+struct Frontier {
+    strategy: SimulationStrategy,
+    queue: VecDeque<(NodeId, u64)>,
+    /// Pages by number of links to them, then by the order they have been found
+    by_degree: BinaryHeap<(usize, Reverse<usize>, NodeId, u64)>,
+    in_degrees: HashMap<NodeId, usize>,
+    /// Pages waiting in `by_degree`, with the order they have been found and their depth
+    waiting: HashMap<NodeId, (usize, u64)>,
+    found: usize,
+}
+
+impl Frontier {
+    fn new(strategy: SimulationStrategy) -> Frontier {
+        Frontier {
+            strategy,
+            queue: VecDeque::new(),
+            by_degree: BinaryHeap::new(),
+            in_degrees: HashMap::new(),
+            waiting: HashMap::new(),
+            found: 0,
+        }
+    }
+
+    fn push(&mut self, page: NodeId, depth: u64) {
+        match self.strategy {
+            SimulationStrategy::Bfs | SimulationStrategy::Dfs => {
+                self.queue.push_back((page, depth))
+            }
+            SimulationStrategy::Degree => {
+                let degree = self.in_degrees.get(&page).copied().unwrap_or_default();
+                self.by_degree
+                    .push((degree, Reverse(self.found), page, depth));
+                self.waiting.insert(page, (self.found, depth));
+            }
+        }
+        self.found += 1;
+    }
+
+    /// Records a link to `page` from an expanded page
+    fn link(&mut self, page: NodeId) {
+        if self.strategy != SimulationStrategy::Degree {
+            return;
+        }
+        let degree = self.in_degrees.entry(page).or_default();
+        *degree += 1;
+        // The old entry becomes stale, and is skipped when popped
+        if let Some(&(order, depth)) = self.waiting.get(&page) {
+            self.by_degree.push((*degree, Reverse(order), page, depth));
+        }
+    }
+
+    fn pop(&mut self) -> Option<(NodeId, u64)> {
+        match self.strategy {
+            SimulationStrategy::Bfs => self.queue.pop_front(),
+            SimulationStrategy::Dfs => self.queue.pop_back(),
+            SimulationStrategy::Degree => {
+                while let Some((degree, _, page, depth)) = self.by_degree.pop() {
+                    if self.waiting.contains_key(&page)
+                        && self.in_degrees.get(&page).copied().unwrap_or_default() == degree
+                    {
+                        self.waiting.remove(&page);
+                        return Some((page, depth));
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Finds the edges and nodes files for the prefix, trying every compression
+fn find_csv_files(prefix: &str, delimiter: u8) -> io::Result<[(String, Compression); 2]> {
+    for compression in [Compression::None, Compression::Gzip, Compression::Zstd] {
+        let options = OutputOptions {
+            delimiter,
+            compression,
+        };
+        let paths = OutputFormat::Csv.file_paths(prefix, &options);
+        if paths.iter().all(|path| Path::new(path).exists()) {
+            let [edges, nodes] =
+                <[String; 2]>::try_from(paths).expect("The csv format should have two files");
+            return Ok([(edges, compression), (nodes, compression)]);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("Could not find the csv files of the graph {prefix}"),
+    ))
+}
+
+fn csv_reader(
+    (path, compression): &(String, Compression),
+    delimiter: u8,
+) -> io::Result<csv::Reader<Box<dyn io::Read>>> {
+    Ok(csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_reader(compression.open(path)?))
+}
+
+fn parse_id(record: &csv::StringRecord, column: usize) -> io::Result<NodeId> {
+    record
+        .get(column)
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| io::Error::other(format!("Invalid id in the record {record:?}")))
+}
@@ -0,0 +1,70 @@
+use std::{collections::HashSet, io, path::Path, sync::Arc};
+
+use crate::checkpoint::{Checkpoint, CheckpointState};
+use crate::compression::Compression;
+use crate::config::ScraperConfig;
+use crate::graph::Graph;
+
+/// Extension of the snapshots, before the one of their compression
+pub const EXTENSION: &str = ".snapshot";
+
+/// Whether the file at `path` is a snapshot, as told by its extension, e.g. `graph.snapshot` or `graph.snapshot.zst`
+pub fn is_snapshot(path: &str) -> bool {
+    let compression = Compression::from_path(path);
+    path.strip_suffix(compression.extension())
+        .is_some_and(|path| path.ends_with(EXTENSION))
+}
+
+/// Writes the whole state of `graph` to `path`, in the binary format of the checkpoints with an empty queue,
+/// compressed as told by the extension of `path`. A snapshot loads much faster than the csv files of the graph,
+/// which remain the format to share it with other tools
+pub fn write(path: &str, graph: &Graph) -> io::Result<()> {
+    if Path::new(path).exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("The snapshot {path} already exists"),
+        ));
+    }
+    let seeds = graph.seeds.lock().unwrap().clone();
+    let own_pages = graph.pages.lock().unwrap();
+    let mut own_links = graph.links.lock().unwrap();
+    let mut state = CheckpointState {
+        seeds: &seeds,
+        hosts: &HashSet::new(),
+        queue: &[],
+        pages: &own_pages,
+        links: &mut own_links,
+        graph,
+    };
+    let mut out = Compression::from_path(path).create(path)?;
+    Checkpoint::write_to(&mut out, &mut state)?;
+    out.finish()
+}
+
+/// Loads the graph of the snapshot at `path`, to be exported with `config`
+pub fn load(path: &str, config: Arc<ScraperConfig>) -> io::Result<Graph> {
+    let mut checkpoint = Checkpoint::load(path).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("Could not load the snapshot {path}: {err}"),
+        )
+    })?;
+    let graph = Graph::new(config);
+    checkpoint.restore_graph(&graph)?;
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_are_told_by_their_extension() {
+        assert!(is_snapshot("graph.snapshot"));
+        assert!(is_snapshot("out/graph.snapshot.zst"));
+        assert!(is_snapshot("graph.snapshot.gz"));
+        assert!(!is_snapshot("graph"));
+        assert!(!is_snapshot("graph.zst"));
+        assert!(!is_snapshot("graph.snapshot.csv"));
+    }
+}
@@ -0,0 +1,53 @@
+use std::{
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::control::Output;
+use crate::graph::{self, Graph};
+
+/// Thread writing the windows of a crawl, see [`emit_periodically`]
+pub struct WindowEmitter {
+    /// Dropped to stop the thread
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl WindowEmitter {
+    /// Stops writing windows, waiting for the one being written if any, so that the windows are not written
+    /// while the final graph is being saved
+    pub fn stop(self) {
+        drop(self.stop);
+        let _ = self.thread.join();
+    }
+}
+
+/// Writes the subgraph of the `nodes` pages found last every `interval`, while the crawl is running,
+/// to the output files for the prefixes `<output-file>_window_1`, `<output-file>_window_2` and so on,
+/// until the returned emitter is stopped.
+/// The windows follow how the neighborhood of the seeds evolves, which the final graph does not show
+pub fn emit_periodically(
+    graph: Graph,
+    output: Output,
+    nodes: usize,
+    interval: Duration,
+) -> WindowEmitter {
+    let (output_file, formats, options) = output;
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = thread::spawn(move || {
+        for index in 1.. {
+            if stopped.recv_timeout(interval) != Err(RecvTimeoutError::Timeout) {
+                return;
+            }
+            let prefix = graph::window_prefix(&output_file, index);
+            match graph.save_window(&prefix, &formats, &options, nodes) {
+                Ok(written) => {
+                    eprintln!("[INFO] Saved the {written} pages found last to {prefix}")
+                }
+                Err(err) => eprintln!("[WARN] Could not save the window {prefix}: {err}"),
+            }
+        }
+    });
+    WindowEmitter { stop, thread }
+}
@@ -1,100 +1,183 @@
 use std::{
-    collections::{HashMap, HashSet},
-    io::Read,
+    collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet},
+    hash::{BuildHasher, Hasher},
     sync::{Arc, Mutex},
-    thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
-use crossbeam_channel::{select, Receiver, Sender};
-use reqwest::blocking::get;
+use tokio::{
+    sync::mpsc::{error::SendError, UnboundedSender},
+    task::JoinSet,
+};
+
+use crate::{
+    alerts::{AbandonedPages, BudgetAlerts, EmptyPageGuard},
+    config::{
+        ExternalLinks, GhostNodes, LinkSampling, LinkScope, RetryConfig, ScraperConfig, SiteMode,
+    },
+    control::CrawlControl,
+    edge_store::Weight,
+    errors::ScraperError,
+    events::{Decision, EventLog},
+    gephi::GephiStream,
+    graph::{self, EdgeType, FailedPage, Graph, Provenance, Visit},
+    http::HttpClient,
+    keywords::{KeywordFilter, Keywords},
+    links::{classify_link, namespace, normalize_url, LinkKind},
+    mediawiki,
+    node_id::NodeId,
+};
+
+/// A url waiting to be scraped, along with its remaining depth
+pub type QueueItem = (String, u64);
+
+/// State shared between the scraper and all of its workers
+#[derive(Clone)]
+pub struct SharedState {
+    pub graph: Graph,
+    /// Hosts whose articles are followed when found in a page
+    pub hosts: Arc<HashSet<String>>,
+    /// Pages that the graph is limited to, e.g. the members of a category. Links to other pages are dropped
+    pub corpus: Option<Arc<HashSet<String>>>,
+    pub control: Arc<CrawlControl>,
+    pub client: Arc<HttpClient>,
+    pub keywords: Arc<KeywordFilter>,
+    pub gephi: Option<GephiStream>,
+    pub alerts: Arc<BudgetAlerts>,
+    pub empty_pages: Arc<EmptyPageGuard>,
+    pub abandoned: Arc<AbandonedPages>,
+    pub events: Arc<EventLog>,
+    /// Page being scraped by each worker, until its links have been added to the graph
+    pub in_flight: Arc<Mutex<HashMap<usize, QueueItem>>>,
+    /// Number of links found to each page, counted only for the priority strategy
+    pub in_links: Option<Arc<Mutex<InLinks>>>,
+}
+
+/// Number of links found to each page, used to scrape first the pages with the most links
+#[derive(Debug, Default)]
+pub struct InLinks {
+    counts: HashMap<String, usize>,
+    /// Pages whose count has changed since they were last taken
+    changed: HashSet<String>,
+}
+
+impl InLinks {
+    fn add(&mut self, url: &str) {
+        *self.counts.entry(url.to_owned()).or_default() += 1;
+        self.changed.insert(url.to_owned());
+    }
+
+    pub fn count(&self, url: &str) -> usize {
+        self.counts.get(url).copied().unwrap_or_default()
+    }
+
+    /// Returns the pages whose count has changed since the last call
+    pub fn take_changed(&mut self) -> HashSet<String> {
+        std::mem::take(&mut self.changed)
+    }
+}
 
-use crate::{errors::ScraperError, scraper::ID};
+/// Sends the pages to scrape to the crawl loop. The pages that cannot be sent, because the crawl loop
+/// is not receiving them anymore, are kept in a retry buffer, which the crawl loop moves to its queue
+#[derive(Debug, Clone)]
+pub struct QueueSender {
+    tx: UnboundedSender<QueueItem>,
+    retry: Arc<Mutex<Vec<QueueItem>>>,
+}
 
+impl QueueSender {
+    pub fn new(tx: UnboundedSender<QueueItem>) -> QueueSender {
+        QueueSender {
+            tx,
+            retry: Default::default(),
+        }
+    }
+
+    /// Sends the page to the queue. Returns `false` if the queue is closed and the page has been kept in the retry buffer
+    pub fn send(&self, item: QueueItem) -> bool {
+        match self.tx.send(item) {
+            Ok(()) => true,
+            Err(SendError(item)) => {
+                self.retry.lock().unwrap().push(item);
+                false
+            }
+        }
+    }
+
+    /// Returns the pages in the retry buffer, emptying it
+    pub fn take_retries(&self) -> Vec<QueueItem> {
+        std::mem::take(&mut self.retry.lock().unwrap())
+    }
+}
+
+/// Links found in a page
+pub struct PageLinks {
+    /// Url in the `<link rel="canonical">` element of the page, if it has one
+    pub canonical_url: Option<String>,
+    /// Complete urls of the anchors that are part of the graph, in the order they appear in the page
+    pub anchors: Vec<String>,
+    /// Whether the page is a disambiguation page
+    pub disambiguation: bool,
+    /// Type of the first anchor pointing to each url. Only filled if the types of the edges are recorded
+    pub edge_types: HashMap<String, EdgeType>,
+    /// Language code and url of the same article in the other languages. Only filled if the interlanguage links are recorded
+    pub interlanguage: Vec<(String, String)>,
+}
+
+/// Page that has been fetched, waiting for its links to be added to the graph
+struct ScrapedPage {
+    links: PageLinks,
+    /// Keywords contained in the page, if they are recorded
+    keyword_matches: Option<Vec<String>>,
+    fetched_at: SystemTime,
+}
+
+/// Scrapes a single page at a time. The scraper runs up to `concurrency` workers at once,
+/// each one with its own id, which is given to a new worker once the page has been scraped.
+#[derive(Clone)]
 pub struct Worker {
     id: usize,
-    links: Arc<Mutex<HashSet<(ID, ID)>>>,
-    pages: Arc<Mutex<HashMap<String, ID>>>,
-    keywords: Option<Vec<String>>,
-    rx: Receiver<(String, u64)>,
-    tx: Sender<(String, u64)>,
-    stopped_threads: Arc<Mutex<Vec<bool>>>,
-    keep_external_links: bool,
+    state: SharedState,
+    config: Arc<ScraperConfig>,
+    queue: QueueSender,
 }
 
 impl Worker {
     pub fn new(
         id: usize,
-        links: Arc<Mutex<HashSet<(ID, ID)>>>,
-        pages: Arc<Mutex<HashMap<String, ID>>>,
-        keywords: Option<Vec<String>>,
-        rx: Receiver<(String, u64)>,
-        tx: Sender<(String, u64)>,
-        stopped_threads: Arc<Mutex<Vec<bool>>>,
-        keep_external_links: bool,
+        state: SharedState,
+        config: Arc<ScraperConfig>,
+        queue: QueueSender,
     ) -> Worker {
         Worker {
             id,
-            links,
-            pages,
-            keywords,
-            rx,
-            tx,
-            stopped_threads,
-            keep_external_links,
+            state,
+            config,
+            queue,
         }
     }
 
-    pub fn scrape(&self) -> Result<(), ScraperError> {
-        loop {
-            select! {
-                recv(self.rx) -> msg => {
-                    self.stopped_threads.lock().unwrap().iter_mut().for_each(|x| *x = false);
-
-                    if let Ok((url, depth)) = msg {
-                        eprintln!("[Thread {}] Scraping {} with depth: {}", self.id, url, depth);
-                        self.scrape_with_depth(url, depth)?;
-                    }
-                },
-                default => {
-                    let mut locked_stopped_threads = self.stopped_threads.lock().unwrap();
-
-                    locked_stopped_threads[self.id] = true;
-
-                    let stopped_threads_count = locked_stopped_threads.iter().filter(|x| **x).count();
-                    let nt = locked_stopped_threads.len();
-
-                    eprintln!("[Thread {}] {} threads stuck with nothing to do", self.id, stopped_threads_count);
-
-                    if stopped_threads_count == nt {
-                        debug_assert!(self.rx.len() == 0, "Expected rx to be empty, found {} links", self.rx.len());
-                        eprintln!("[Thread {}] All threads have nothing to do. Stopping the current one", self.id);
-                        break;
-                    } else {
-                        eprintln!("[Thread {}] Going to sleep for 500ms", self.id);
-                        drop(locked_stopped_threads);
-                        thread::sleep(Duration::from_millis(500));
-                    }
-                }
-            }
-        }
-        Ok(())
+    /// Scrapes the page at `url`, sending the pages it links to that have to be scraped next to the queue
+    pub async fn scrape(&self, url: String, depth: u64) -> Result<(), ScraperError> {
+        eprintln!(
+            "[Worker {}] Scraping {} with depth: {}",
+            self.id, url, depth
+        );
+        self.scrape_with_depth(url, depth).await
     }
 
-    pub fn get_page_content(
+    /// Fetches the page at `url`, retrying transient failures as configured by `retry`.
+    /// Returns `None` if the page is not accepted by the keywords.
+    pub async fn get_page_content(
+        client: &HttpClient,
         url: impl AsRef<str>,
-        keywords: Option<&Vec<String>>,
+        keywords: Option<&Keywords>,
+        retry: &RetryConfig,
     ) -> Result<Option<String>, ScraperError> {
-        let mut resp = get(url.as_ref())?;
-        let mut content = String::new();
-        resp.read_to_string(&mut content)?;
+        let content = get_text_with_retry(client, url.as_ref(), retry).await?;
 
         if let Some(keywords) = keywords {
-            let lower_content = content.to_lowercase();
-            if keywords
-                .iter()
-                .any(|keyword| lower_content.contains(keyword.to_lowercase().as_str()))
-            {
+            if keywords.matches(&content) {
                 return Ok(Some(content));
             } else {
                 return Ok(None);
@@ -103,75 +186,660 @@ impl Worker {
         Ok(Some(content))
     }
 
-    pub fn get_anchor_list(&self, page_content: &str) -> Result<Vec<String>, ScraperError> {
+    pub fn get_anchor_list(
+        &self,
+        page_url: &str,
+        page_content: &str,
+    ) -> Result<PageLinks, ScraperError> {
         let document = scraper::Html::parse_document(page_content);
 
-        let content_selector =
-            scraper::Selector::parse("#bodyContent").expect("Static selector should be valid");
-        let content = document.select(&content_selector).next().map_or_else(
-            || Err(ScraperError::NoContentFound("".into())),
-            |content| Ok(content),
-        )?;
-        let anchor_selector =
-            scraper::Selector::parse("a").expect("Static selector should be valid");
-
-        let anchors = content.select(&anchor_selector);
+        let content_selector = scraper::Selector::parse(&self.config.wiki.content_selector)
+            .expect("The content selector should have been validated");
+        let content = document
+            .select(&content_selector)
+            .next()
+            .ok_or_else(|| ScraperError::NoContentFound(page_url.to_owned()))?;
+        let anchors = scoped_anchors(content, self.config.link_scope);
+        let page_url = reqwest::Url::parse(page_url)
+            .map_err(|_| ScraperError::InvalidUrl(page_url.to_owned()))?;
+        let base_url = get_document_base(&document, &page_url);
 
+        // Every page of a generic site is part of the graph, and so are the other sites it links to if they are followed
+        let (article_path, keep_external_links) = match self.config.site_mode {
+            SiteMode::Wiki => (
+                Some(self.config.wiki.article_path.as_str()),
+                self.config.keep_external_links.is_some(),
+            ),
+            SiteMode::Generic => (
+                None,
+                self.config.keep_external_links.is_some() || !self.config.same_domain_only,
+            ),
+        };
+        let anchor_types = self
+            .config
+            .edge_types
+            .then(|| get_edge_types(content, &anchors));
         let mut anchor_list = Vec::new();
-        for anchor in anchors {
+        let mut edge_types = HashMap::new();
+        let mut link_counts = BTreeMap::new();
+        for (idx, anchor) in anchors.iter().enumerate() {
             if let Some(href) = anchor.value().attr("href") {
-                if let Some(url) = get_complete_url(href, self.keep_external_links) {
+                let kind = classify_link(href, &self.config.wiki.article_path);
+                *link_counts.entry(kind).or_default() += 1;
+                if let Some(url) = get_complete_url(
+                    href,
+                    kind,
+                    &page_url,
+                    &base_url,
+                    article_path,
+                    &self.config.wiki.namespaces,
+                    keep_external_links,
+                ) {
+                    if let Some(anchor_types) = &anchor_types {
+                        edge_types.entry(url.clone()).or_insert(anchor_types[idx]);
+                    }
                     anchor_list.push(url);
                 }
             }
         }
-        Ok(anchor_list)
+        self.state.graph.link_kinds.add(&link_counts);
+        if let Some(max_links) = self.config.max_links_per_page {
+            anchor_list = cap_links(anchor_list, max_links, self.config.link_sampling);
+        }
+        let interlanguage = if self.config.interlanguage_links {
+            interlanguage_links(&document)
+                .into_iter()
+                .filter_map(|(lang, href)| {
+                    let mut url = page_url.join(&href).ok()?;
+                    normalize_url(&mut url, article_path);
+                    Some((lang, url.to_string()))
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Ok(PageLinks {
+            canonical_url: get_canonical_url(&document, &page_url, article_path),
+            anchors: anchor_list,
+            disambiguation: is_disambiguation(&document),
+            edge_types,
+            interlanguage,
+        })
+    }
+
+    /// Records that the page at `url` has been rejected by the keywords, adding it to the pages if it is a seed
+    fn add_ghost(&self, url: &str) {
+        let mut own_pages = self.state.graph.pages.lock().unwrap();
+        let id = match own_pages.get(url) {
+            Some(id) => *id,
+            None => {
+                let Some(new_id) = self.allocate_id(own_pages.len()) else {
+                    return;
+                };
+                own_pages.insert(url.to_owned(), new_id);
+                new_id
+            }
+        };
+        self.state.graph.ghosts.lock().unwrap().insert(id);
+    }
+
+    /// Records whether links have been found in the page at `url`, failing if too many pages in a row have none
+    fn record_links_found(&self, url: &str, found: bool) -> Result<(), ScraperError> {
+        match self.state.empty_pages.record(found) {
+            Some(count) => Err(ScraperError::NoLinksFound {
+                count,
+                selector: self.config.wiki.content_selector.clone(),
+                url: url.to_owned(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the id of a new page, given the number of pages in the graph.
+    /// If there are no ids left, the crawl is stopped and `None` is returned
+    fn allocate_id(&self, num_pages: usize) -> Option<NodeId> {
+        let id = NodeId::allocate(num_pages, self.config.id_width);
+        if id.is_none() && !self.state.control.is_stopped() {
+            eprintln!(
+                "[WARN] The graph has used every id up to {}. Stopping the crawl",
+                self.config.id_width.max_id()
+            );
+            self.state.control.stop();
+        }
+        id
+    }
+
+    /// Fetches the page at `url` and finds its links in the HTML.
+    /// Returns `None` if the page has to be skipped
+    async fn fetch_html_page(
+        &self,
+        url: &str,
+        depth: u64,
+    ) -> Result<Option<ScrapedPage>, ScraperError> {
+        let keywords = self.state.keywords.keywords();
+        let filter = self.state.keywords.keywords_at(self.hop(depth));
+        let page_content = match Worker::get_page_content(
+            &self.state.client,
+            url,
+            filter.as_deref(),
+            &self.config.retry,
+        )
+        .await
+        {
+            Ok(page_content) => {
+                self.record_fetch(url, depth, true);
+                page_content
+            }
+            // A page that cannot be fetched is skipped rather than stopping the whole crawl
+            Err(err) if err.is_transient() => {
+                eprintln!("[Worker {}] Giving up on {url}: {err}", self.id);
+                self.record_fetch(url, depth, false);
+                self.state.events.record(url, || Decision::Failed {
+                    error: err.to_string(),
+                });
+                return Ok(None);
+            }
+            Err(err) => return Err(err),
+        };
+        if filter.is_some() {
+            self.state.keywords.record(depth, page_content.is_some());
+        }
+
+        let Some(page_content) = page_content else {
+            eprintln!("[Worker {}] Skipping {url}", self.id);
+            self.skipped(url, "does not match the keywords");
+            if self.config.ghost_nodes != GhostNodes::Keep {
+                self.add_ghost(url);
+            }
+            return Ok(None);
+        };
+        let fetched_at = SystemTime::now();
+
+        let Some(parsed) = self.parse_page(url, page_content, keywords).await else {
+            return Ok(None);
+        };
+        let (page_links, keyword_matches) = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("[Worker {}] Skipping {url}", self.id);
+                self.skipped(url, &err.to_string());
+                self.record_links_found(url, false)?;
+                return Ok(None);
+            }
+        };
+        Ok(Some(ScrapedPage {
+            links: page_links,
+            keyword_matches,
+            fetched_at,
+        }))
+    }
+
+    /// Records that the page at `url`, to be scraped with `depth`, could not be fetched even after retrying it,
+    /// so that it is queued again when the crawl is resumed, or forgets its previous failures once it has been fetched
+    fn record_fetch(&self, url: &str, depth: u64, fetched: bool) {
+        let mut own_failed = self.state.graph.failed.lock().unwrap();
+        if fetched {
+            own_failed.remove(url);
+            return;
+        }
+        let failed = own_failed
+            .entry(url.to_owned())
+            .or_insert(FailedPage { depth, attempts: 0 });
+        failed.depth = depth;
+        failed.attempts += 1;
+    }
+
+    /// Finds the links of a fetched page, and the keywords it contains if they are recorded.
+    /// With a page timeout, the page is parsed on a blocking thread and abandoned, returning `None`,
+    /// if it takes longer. The thread cannot be interrupted, but the worker moves on to the next page
+    async fn parse_page(
+        &self,
+        url: &str,
+        page_content: String,
+        keywords: Option<Arc<Keywords>>,
+    ) -> Option<Result<(PageLinks, Option<Vec<String>>), ScraperError>> {
+        let worker = self.clone();
+        let page_url = url.to_owned();
+        let parse = move || {
+            let page_links = worker.get_anchor_list(&page_url, &page_content)?;
+            // Computed before taking the locks, since it goes through the whole content again
+            let keyword_matches = keywords
+                .filter(|_| worker.config.record_keyword_matches)
+                .map(|keywords| keywords.matching(&page_content));
+            Ok((page_links, keyword_matches))
+        };
+
+        let Some(page_timeout) = self.config.page_timeout else {
+            return Some(parse());
+        };
+        match tokio::time::timeout(page_timeout, tokio::task::spawn_blocking(parse)).await {
+            Ok(Ok(parsed)) => Some(parsed),
+            Ok(Err(err)) => std::panic::resume_unwind(err.into_panic()),
+            Err(_) => {
+                eprintln!(
+                    "[WARN] [Worker {}] Abandoning {url}: still parsing it after {:.1}s",
+                    self.id,
+                    page_timeout.as_secs_f64()
+                );
+                self.state.abandoned.record(url);
+                self.skipped(url, "abandoned after the page timeout");
+                None
+            }
+        }
+    }
+
+    /// Fetches the links of the page at `url` from the Action API of its wiki.
+    /// Returns `None` if the page has to be skipped
+    async fn fetch_api_page(
+        &self,
+        url: &str,
+        depth: u64,
+    ) -> Result<Option<ScrapedPage>, ScraperError> {
+        match mediawiki::get_page_links(
+            &self.state.client,
+            url,
+            &self.config.wiki,
+            &self.config.retry,
+        )
+        .await
+        {
+            Ok(mut links) => {
+                self.record_fetch(url, depth, true);
+                if let Some(max_links) = self.config.max_links_per_page {
+                    links.anchors = cap_links(links.anchors, max_links, self.config.link_sampling);
+                }
+                Ok(Some(ScrapedPage {
+                    links,
+                    keyword_matches: None,
+                    fetched_at: SystemTime::now(),
+                }))
+            }
+            Err(err) if err.is_transient() => {
+                eprintln!("[Worker {}] Giving up on {url}: {err}", self.id);
+                self.record_fetch(url, depth, false);
+                self.state.events.record(url, || Decision::Failed {
+                    error: err.to_string(),
+                });
+                Ok(None)
+            }
+            Err(err @ (ScraperError::InvalidUrl(_) | ScraperError::InvalidApiResponse { .. })) => {
+                eprintln!("[Worker {}] Skipping {url}: {err}", self.id);
+                self.skipped(url, &err.to_string());
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
     }
 
-    fn scrape_with_depth(
+    async fn scrape_with_depth(
         &self,
         start_url: impl AsRef<str>,
         depth: u64,
     ) -> Result<(), ScraperError> {
-        let Some(page_content)= Worker::get_page_content(start_url.as_ref(), self.keywords.as_ref())? else {
-            eprintln!("[Thread {}] Skipping {}", self.id, start_url.as_ref());
+        if self.expand_imported(start_url.as_ref(), depth) {
             return Ok(());
+        }
+        let page = if self.config.use_api {
+            self.fetch_api_page(start_url.as_ref(), depth).await?
+        } else {
+            self.fetch_html_page(start_url.as_ref(), depth).await?
         };
-
-        let Ok(anchor_list) = self.get_anchor_list(&page_content) else {
-            eprintln!("[Thread {}] Skipping {}", self.id, start_url.as_ref());
+        let Some(ScrapedPage {
+            links:
+                PageLinks {
+                    canonical_url,
+                    anchors: anchor_list,
+                    disambiguation,
+                    edge_types,
+                    interlanguage,
+                },
+            keyword_matches,
+            fetched_at,
+        }) = page
+        else {
             return Ok(());
         };
+        if disambiguation && self.config.skip_disambiguation {
+            eprintln!(
+                "[Worker {}] Skipping disambiguation page {}",
+                self.id,
+                start_url.as_ref()
+            );
+            self.skipped(start_url.as_ref(), "disambiguation page");
+            let own_pages = self.state.graph.pages.lock().unwrap();
+            if let Some(id) = own_pages.get(start_url.as_ref()) {
+                self.state.graph.disambiguation.lock().unwrap().insert(*id);
+            }
+            return Ok(());
+        }
+        // The page is identified by its canonical url, if it is different from the one it has been fetched from
+        let canonical_url =
+            canonical_url.filter(|url| !self.config.ignore_canonical && url != start_url.as_ref());
 
+        self.record_links_found(start_url.as_ref(), !anchor_list.is_empty())?;
         if anchor_list.is_empty() {
             eprintln!(
-                "[Thread {}] No links found in page {}",
+                "[Worker {}] No links found in page {}",
                 self.id,
                 start_url.as_ref()
             );
+            self.skipped(start_url.as_ref(), "no links found in the page");
             return Ok(());
         }
 
-        let mut own_pages = self.pages.lock().unwrap();
-        let mut own_links = self.links.lock().unwrap();
+        let anchor_list = match &self.state.corpus {
+            Some(corpus) => anchor_list
+                .into_iter()
+                .filter(|url| {
+                    self.keep_link(
+                        start_url.as_ref(),
+                        url,
+                        corpus.contains(url),
+                        "not a member of the category",
+                    )
+                })
+                .collect(),
+            None => anchor_list,
+        };
+        let anchor_list = if self.config.url_filter.is_empty() {
+            anchor_list
+        } else {
+            anchor_list
+                .into_iter()
+                .filter(|url| {
+                    self.keep_link(
+                        start_url.as_ref(),
+                        url,
+                        self.config.url_filter.allows(url),
+                        "excluded by the url filters",
+                    )
+                })
+                .collect()
+        };
+        // The links to the external pages are recorded apart, once the page has its id
+        let (separate_links, anchor_list): (Vec<_>, Vec<_>) =
+            if self.config.keep_external_links == Some(ExternalLinks::Separate) {
+                anchor_list
+                    .into_iter()
+                    .partition(|url| self.is_external(url))
+            } else {
+                (Vec::new(), anchor_list)
+            };
+        // The other languages of the article are linked after its own links
+        let interlanguage: Vec<_> = interlanguage
+            .into_iter()
+            .filter(|(_, url)| {
+                self.config.url_filter.is_empty()
+                    || self.keep_link(
+                        start_url.as_ref(),
+                        url,
+                        self.config.url_filter.allows(url),
+                        "excluded by the url filters",
+                    )
+            })
+            .collect();
+        let mut anchor_list = anchor_list;
+        for (_, url) in &interlanguage {
+            if !anchor_list.contains(url) {
+                anchor_list.push(url.clone());
+            }
+        }
+        let languages: HashMap<String, String> = interlanguage
+            .into_iter()
+            .map(|(lang, url)| (url, lang))
+            .collect();
+        let mut links = aggregate_links(anchor_list);
+        for link in &mut links {
+            link.edge_type = edge_types.get(&link.url).copied();
+            link.interlanguage = languages.get(&link.url).cloned();
+        }
+        let page_url = canonical_url
+            .clone()
+            .unwrap_or_else(|| start_url.as_ref().to_owned());
+        let external_links = self.add_page(
+            start_url.as_ref(),
+            canonical_url,
+            links,
+            depth,
+            keyword_matches,
+            fetched_at,
+        )?;
+        if !separate_links.is_empty() {
+            let own_pages = self.state.graph.pages.lock().unwrap();
+            if let Some(id) = own_pages.get(&page_url) {
+                let mut own_external_links = self.state.graph.external_links.lock().unwrap();
+                own_external_links.extend(separate_links.into_iter().map(|url| (*id, url)));
+            }
+        }
+        self.check_external_links(external_links).await;
+        Ok(())
+    }
+
+    /// Expands the page at `url` with the links it has in the imported graph, instead of fetching it again,
+    /// if the imported crawl has expanded it. Returns whether it has.
+    fn expand_imported(&self, url: &str, depth: u64) -> bool {
+        let own_pages = self.state.graph.pages.lock().unwrap();
+        let Some(&id) = own_pages.get(url) else {
+            return false;
+        };
+        let mut own_imported = self.state.graph.imported.lock().unwrap();
+        let Some(links) = own_imported.links.remove(&id) else {
+            return false;
+        };
+
+        eprintln!(
+            "[Worker {}] Page {url} has been imported. Expanding it from the imported graph",
+            self.id
+        );
+        own_imported.queued.insert(url.to_owned());
+        self.state
+            .events
+            .record(url, || Decision::Imported { depth });
+        if depth > 1 {
+            for link in links {
+                if self.is_followable(&link) && own_imported.queued.insert(link.clone()) {
+                    self.enqueue(url, (link, depth - 1));
+                }
+            }
+        }
+        drop(own_imported);
+
+        let visit = Visit {
+            at: Instant::now(),
+            hop: self.hop(depth),
+        };
+        self.state.graph.expanded.lock().unwrap().insert(id, visit);
+        self.state.in_flight.lock().unwrap().remove(&self.id);
+        true
+    }
+
+    /// Adds a scraped page and its links to the graph, queueing the new articles it links to.
+    /// Returns the new external pages that have to be checked, if checking them is enabled.
+    fn add_page(
+        &self,
+        start_url: &str,
+        canonical_url: Option<String>,
+        links: Vec<PageLink>,
+        depth: u64,
+        keyword_matches: Option<Vec<String>>,
+        fetched_at: SystemTime,
+    ) -> Result<Vec<(NodeId, String)>, ScraperError> {
+        let mut own_pages = self.state.graph.pages.lock().unwrap();
+        let mut own_links = self.state.graph.links.lock().unwrap();
+
+        let page_url = canonical_url.as_deref().unwrap_or(start_url);
+        if let Some(canonical_url) = &canonical_url {
+            eprintln!(
+                "[Worker {}] Page {} has the canonical url {canonical_url}",
+                self.id, start_url
+            );
+            self.state
+                .graph
+                .aliases
+                .lock()
+                .unwrap()
+                .insert(start_url.to_owned(), canonical_url.clone());
+            self.state
+                .events
+                .record(start_url, || Decision::Redirected {
+                    to: canonical_url.clone(),
+                });
+            // The node of the page found with the other url takes the canonical one, unless the canonical page is already in the graph.
+            // In that case the other node is merged into the canonical one, with the links to the page found before its canonical url was known,
+            // and the other url is kept in the pages, pointing to the canonical node. Either way the number of pages stays the same,
+            // which keeps the ids allocated from it from colliding with the ones already given
+            match (
+                own_pages.get(start_url).copied(),
+                own_pages.get(canonical_url).copied(),
+            ) {
+                (Some(id), None) => {
+                    own_pages.remove(start_url);
+                    own_pages.insert(canonical_url.clone(), id);
+                }
+                (Some(id), Some(canonical_id)) if id != canonical_id => {
+                    eprintln!(
+                        "[Worker {}] Merging page {} into {canonical_url}",
+                        self.id, start_url
+                    );
+                    own_pages.insert(start_url.to_owned(), canonical_id);
+                    self.state
+                        .graph
+                        .merge_edges(&mut own_links, id, canonical_id);
+                }
+                _ => {}
+            }
+        }
 
         // If the page has already been visited, just add the links to the links set by recovering its id
         // else generate a new id and add it to the pages before proceeding to process the links
-        let start_url_id = if let Some(start_url_id) = own_pages.get(start_url.as_ref()) {
+        let start_url_id = if let Some(start_url_id) = own_pages.get(page_url) {
             *start_url_id
         } else {
-            let new_id = own_pages.len() as ID;
-            own_pages.insert(start_url.as_ref().to_string(), new_id);
+            let Some(new_id) = self.allocate_id(own_pages.len()) else {
+                return Ok(Vec::new());
+            };
+            own_pages.insert(page_url.to_owned(), new_id);
+            if let Some(gephi) = &self.state.gephi {
+                gephi.add_node(new_id, page_url);
+            }
+            self.state.alerts.check_nodes(own_pages.len());
             new_id
         };
 
-        for anchor in anchor_list {
+        // The same page may be fetched with several urls that have the same canonical one,
+        // or be queued again by several pages once it can be revisited
+        let revisiting = {
+            let mut own_expanded = self.state.graph.expanded.lock().unwrap();
+            let hop = self.hop(depth);
+            if own_expanded
+                .get(&start_url_id)
+                .is_some_and(|visit| !self.can_revisit(visit, hop))
+            {
+                eprintln!(
+                    "[Worker {}] Page {page_url} has already been scraped. Skipping {}",
+                    self.id, start_url
+                );
+                self.skipped(start_url, "already scraped");
+                return Ok(Vec::new());
+            }
+            let visit = Visit {
+                at: Instant::now(),
+                hop,
+            };
+            let revisiting = own_expanded.insert(start_url_id, visit).is_some();
+            if revisiting {
+                eprintln!("[Worker {}] Revisiting {page_url}", self.id);
+            }
+            revisiting
+        };
+        self.state.events.record(page_url, || Decision::Scraped {
+            depth,
+            links: links.len(),
+        });
+
+        if let Some(keyword_matches) = keyword_matches {
+            self.state
+                .graph
+                .keyword_matches
+                .lock()
+                .unwrap()
+                .insert(start_url_id, keyword_matches);
+        }
+
+        if self.config.provenance {
+            let mut own_provenance = self.state.graph.provenance.lock().unwrap();
+            let provenance = Provenance {
+                sequence: own_provenance.len() as u64,
+                worker_id: self.id,
+                fetched_at,
+            };
+            own_provenance.insert(start_url_id, provenance);
+        }
+
+        let own_aliases = self.state.graph.aliases.lock().unwrap();
+        // New external pages, checked once the locks have been released
+        let mut external_links = Vec::new();
+        let num_links = links.len();
+        // Pages linked to by the page, whose number is its out-degree
+        let mut linked = HashSet::new();
+        let mut links = links.into_iter().enumerate();
+        while let Some((idx, link)) = links.next() {
+            if let Some(max_degree) = self.config.stop_expanding_at_degree {
+                if linked.len() >= max_degree {
+                    eprintln!(
+                        "[Worker {}] Page {} reached {max_degree} links. Skipping its other {} links",
+                        self.id,
+                        page_url,
+                        num_links - idx
+                    );
+                    for link in std::iter::once(link).chain(links.map(|(_, link)| link)) {
+                        self.state.events.record(&link.url, || Decision::Rejected {
+                            from: page_url.to_owned(),
+                            reason: format!("the page already has {max_degree} links"),
+                        });
+                    }
+                    break;
+                }
+            }
+
+            let anchor = graph::resolve_alias(&own_aliases, &link.url).to_owned();
+            let next_depth = self.next_depth(depth, link.interlanguage.is_some());
+            self.state.events.record(&anchor, || Decision::Linked {
+                from: page_url.to_owned(),
+            });
+
             // If the link has already been visited, just add the current link to the links set
             if let Some(anchor_id) = own_pages.get(&anchor) {
-                own_links.insert((start_url_id, *anchor_id));
+                let new_in_run = own_links
+                    .insert_weighted((start_url_id, *anchor_id), link.count)
+                    .map_err(ScraperError::EdgeSpillFailed)?;
+                self.record_position((start_url_id, *anchor_id), link.position);
+                self.record_edge_type((start_url_id, *anchor_id), link.edge_type);
+                self.record_interlanguage((start_url_id, *anchor_id), link.interlanguage);
+                // The links of a page are only found again when it is revisited, and the store cannot tell
+                // whether they are new once some edges have been spilled to disk, so they are not counted then
+                let new_link = linked.insert(*anchor_id)
+                    && new_in_run
+                    && !(revisiting && own_links.has_spilled());
+                if new_link {
+                    self.count_in_link(&anchor);
+                    if let Some(gephi) = &self.state.gephi {
+                        gephi.add_edge(start_url_id, *anchor_id);
+                    }
+                    self.state.alerts.add_edge();
+                }
+                if next_depth > 0 && self.is_due_for_revisit(*anchor_id, next_depth) {
+                    eprintln!(
+                        "[Worker {}] Adding {} to the queue again with depth: {}",
+                        self.id, anchor, next_depth
+                    );
+                    self.enqueue(page_url, (anchor, next_depth));
+                }
             } else {
                 // Else generate the anchor id and add it to the pages
-                let anchor_id = own_pages.len() as ID;
+                let Some(anchor_id) = self.allocate_id(own_pages.len()) else {
+                    break;
+                };
 
                 let anchor_insert_res = own_pages.insert(anchor.clone(), anchor_id);
                 debug_assert!(
@@ -180,53 +848,882 @@ impl Worker {
                 );
 
                 // Add the link
-                let link_insert_res = own_links.insert((start_url_id, anchor_id));
+                let link_insert_res = own_links
+                    .insert_weighted((start_url_id, anchor_id), link.count)
+                    .map_err(ScraperError::EdgeSpillFailed)?;
                 debug_assert!(
                     link_insert_res,
                     "Should not be adding a link that already exists"
                 );
+                self.record_position((start_url_id, anchor_id), link.position);
+                self.record_edge_type((start_url_id, anchor_id), link.edge_type);
+                self.record_interlanguage((start_url_id, anchor_id), link.interlanguage);
+                linked.insert(anchor_id);
+                self.count_in_link(&anchor);
+                if self.config.stop_at.as_ref() == Some(&anchor) && !self.state.control.is_stopped()
+                {
+                    eprintln!("[INFO] Found {anchor}. Stopping the crawl");
+                    self.state.control.stop();
+                }
 
-                if anchor.starts_with("https://en.wikipedia.org/wiki/") {
+                if let Some(gephi) = &self.state.gephi {
+                    gephi.add_node(anchor_id, anchor.as_str());
+                    gephi.add_edge(start_url_id, anchor_id);
+                }
+                self.state.alerts.check_nodes(own_pages.len());
+                self.state.alerts.add_edge();
+
+                if self.is_followable(&anchor) {
                     // And then scrape that page recursively
                     // if it was not already in the map
-                    if depth > 1 {
-                        println!(
-                            "[Thread {}] Adding {} to the queue with depth: {}",
-                            self.id,
-                            anchor,
-                            depth - 1
+                    if next_depth > 0 {
+                        eprintln!(
+                            "[Worker {}] Adding {} to the queue with depth: {}",
+                            self.id, anchor, next_depth
                         );
-                        self.tx.send((anchor, depth - 1))?;
+                        self.enqueue(page_url, (anchor, next_depth));
+                    } else {
+                        self.skipped(&anchor, "not queued, since the depth has been reached");
+                    }
+                } else {
+                    self.skipped(&anchor, "not queued, since it is not followed by the crawl");
+                    if self.config.checks_external() && self.is_external_host(&anchor) {
+                        external_links.push((anchor_id, anchor));
                     }
                 }
             }
         }
 
-        Ok(())
+        // Removed while holding the locks, so that a checkpoint sees either the page in the queue or its links in the graph
+        self.state.in_flight.lock().unwrap().remove(&self.id);
+        Ok(external_links)
+    }
+
+    /// Adds the page to the queue. The queue is closed when the crawl is being torn down, in which case the page is expected
+    /// to be dropped; otherwise it is kept in the retry buffer and the failure is logged
+    fn enqueue(&self, from: &str, item: QueueItem) {
+        self.state.events.record(&item.0, || Decision::Queued {
+            depth: item.1,
+            from: Some(from.to_owned()),
+        });
+        if !self.queue.send(item.clone()) && !self.state.control.is_stopped() {
+            eprintln!(
+                "[WARN] [Worker {}] Could not add {} to the queue. It will be retried",
+                self.id, item.0
+            );
+        }
+    }
+
+    /// Records the position of a link among the links of its source page, if the positions are recorded.
+    /// The position found first is kept when the page is scraped again
+    fn record_position(&self, edge: (NodeId, NodeId), position: usize) {
+        if self.config.link_positions {
+            let mut own_positions = self.state.graph.link_positions.lock().unwrap();
+            own_positions.entry(edge).or_insert(position as u64);
+        }
+    }
+
+    /// Records the type of a link, if it is known. The type found first is kept when the page is scraped again
+    fn record_edge_type(&self, edge: (NodeId, NodeId), edge_type: Option<EdgeType>) {
+        if let Some(edge_type) = edge_type {
+            let mut own_edge_types = self.state.graph.edge_types.lock().unwrap();
+            own_edge_types.entry(edge).or_insert(edge_type);
+        }
     }
-}
 
-fn get_complete_url(url: &str, keep_external_links: bool) -> Option<String> {
-    // All of the internal links start with a slash
-    if !url.starts_with('/') {
-        return if keep_external_links {
-            Some(url.to_owned())
+    /// Records the language of the target of an interlanguage link. The language found first is kept
+    fn record_interlanguage(&self, edge: (NodeId, NodeId), lang: Option<String>) {
+        if let Some(lang) = lang {
+            let mut own_interlanguage = self.state.graph.interlanguage.lock().unwrap();
+            own_interlanguage.entry(edge).or_insert(lang);
+        }
+    }
+
+    /// Remaining depth of the pages linked by a page found with `depth` remaining.
+    /// The other languages of an article are only followed with their own depth, if any
+    fn next_depth(&self, depth: u64, interlanguage: bool) -> u64 {
+        let next_depth = depth.saturating_sub(1);
+        if interlanguage {
+            self.config
+                .interlanguage_depth
+                .map_or(0, |budget| next_depth.min(budget))
         } else {
-            None
-        };
+            next_depth
+        }
+    }
+
+    /// Records a new link to the page at `url`, if the links are counted
+    fn count_in_link(&self, url: &str) {
+        if let Some(in_links) = &self.state.in_links {
+            in_links.lock().unwrap().add(url);
+        }
+    }
+
+    /// Records that the page at `url` has been skipped
+    fn skipped(&self, url: &str, reason: &str) {
+        self.state.events.record(url, || Decision::Skipped {
+            reason: reason.to_owned(),
+        });
+    }
+
+    /// Whether the link to `url` found in the page at `from` is kept, as told by `kept`,
+    /// recording that it has been left out otherwise
+    fn keep_link(&self, from: &str, url: &str, kept: bool, reason: &str) -> bool {
+        if !kept {
+            self.state.events.record(url, || Decision::Rejected {
+                from: from.to_owned(),
+                reason: reason.to_owned(),
+            });
+        }
+        kept
+    }
+
+    /// Whether the page at `url`, found in a scraped page, has to be scraped too
+    fn is_followable(&self, url: &str) -> bool {
+        match self.config.site_mode {
+            SiteMode::Wiki => {
+                is_followable_article(url, &self.state.hosts, &self.config.wiki.article_path)
+                    || (self.config.interlanguage_depth.is_some()
+                        && is_other_language_article(
+                            url,
+                            &self.state.hosts,
+                            &self.config.wiki.article_path,
+                        ))
+            }
+            SiteMode::Generic => {
+                is_followable_page(url, &self.state.hosts, self.config.same_domain_only)
+            }
+        }
+    }
+
+    /// Whether the page at `url` is neither followed nor on one of the hosts of the crawl
+    fn is_external(&self, url: &str) -> bool {
+        !self.is_followable(url) && self.is_external_host(url)
+    }
+
+    fn is_external_host(&self, url: &str) -> bool {
+        get_host(url).is_none_or(|host| !self.state.hosts.contains(&host))
+    }
+
+    /// Number of hops from the seed of a page found with `depth` remaining
+    fn hop(&self, depth: u64) -> u64 {
+        self.config.depth.saturating_sub(depth)
+    }
+
+    /// Whether a page last scraped with `visit` can be scraped again when found at `hop`
+    fn can_revisit(&self, visit: &Visit, hop: u64) -> bool {
+        let revisit = &self.config.revisit;
+        revisit
+            .after
+            .is_some_and(|after| visit.at.elapsed() >= after)
+            || revisit
+                .after_hops
+                .is_some_and(|hops| hop >= visit.hop.saturating_add(hops))
     }
 
-    if url.contains(':') {
+    /// Whether a page already in the graph, found again with `depth` remaining, has to be queued again.
+    /// Pages that have not been scraped yet are already in the queue, or have been rejected
+    fn is_due_for_revisit(&self, id: NodeId, depth: u64) -> bool {
+        if self.config.revisit.is_disabled() {
+            return false;
+        }
+        let hop = self.hop(depth);
+        self.state
+            .graph
+            .expanded
+            .lock()
+            .unwrap()
+            .get(&id)
+            .is_some_and(|visit| self.can_revisit(visit, hop))
+    }
+
+    /// Checks the external links found for the first time in a page, all at once, fetching them if requested
+    async fn check_external_links(&self, links: Vec<(NodeId, String)>) {
+        let fetch = self.config.keep_external_links == Some(ExternalLinks::Fetch);
+        let mut checks = JoinSet::new();
+        for (id, url) in links {
+            let client = self.state.client.clone();
+            checks.spawn(async move {
+                let check = if fetch {
+                    client.fetch_link(&url).await
+                } else {
+                    client.check_link(&url).await
+                };
+                (id, check)
+            });
+        }
+        while let Some(joined) = checks.join_next().await {
+            let (id, check) = joined.expect("Checking a link should not panic");
+            self.state
+                .graph
+                .link_checks
+                .lock()
+                .unwrap()
+                .insert(id, check);
+        }
+    }
+}
+
+/// Fetches `url`, retrying transient failures as configured by `retry`
+pub async fn get_text_with_retry(
+    client: &HttpClient,
+    url: &str,
+    retry: &RetryConfig,
+) -> Result<String, ScraperError> {
+    let mut attempt = 1;
+    loop {
+        match client.get_text(url).await {
+            Ok(content) => return Ok(content),
+            Err(err) if err.is_transient() && attempt < retry.max_attempts => {
+                let delay = err
+                    .retry_after()
+                    .unwrap_or_else(|| backoff_delay(retry, attempt));
+                eprintln!(
+                    "[WARN] {err}. Retrying in {}ms (attempt {attempt} of {})",
+                    delay.as_millis(),
+                    retry.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Exponential backoff with jitter: the delay before the retry following the attempt number `attempt` (starting from 1)
+/// is a random value between half and all of `base_delay * 2^(attempt - 1)`, capped at `max_delay`
+pub(crate) fn backoff_delay(retry: &RetryConfig, attempt: u32) -> Duration {
+    let exponential = retry
+        .base_delay
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(retry.max_delay);
+    // Every RandomState is seeded randomly, which is enough for jitter without a dependency on rand
+    let random = RandomState::new().build_hasher().finish();
+    let jitter = (random as f64 / u64::MAX as f64) * 0.5;
+    exponential.mul_f64(0.5 + jitter)
+}
+
+/// Returns the host of `url`, if it has one
+pub fn get_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|parsed| parsed.host_str().map(|host| host.to_owned()))
+}
+
+/// Returns the language code of a wikipedia url, e.g. `it` for `https://it.wikipedia.org/wiki/Coccodrillo`
+pub fn get_wikipedia_language(url: &str) -> Option<String> {
+    let host = get_host(url)?;
+    let lang = host.strip_suffix(".wikipedia.org")?;
+    // Skip the mobile subdomain, e.g. it.m.wikipedia.org
+    Some(lang.trim_end_matches(".m").to_owned())
+}
+
+/// Returns the url of the seed of a crawl in the wikipedia of `lang`. The seed is either the url of an article,
+/// e.g. `https://en.wikipedia.org/wiki/Crocodile`, or its title, e.g. `Crocodile`, which is looked up in the English wikipedia by default.
+/// Returns `None` if the seed is not valid, or if `lang` is given for an url outside of wikipedia
+pub fn wikipedia_seed_url(seed: &str, lang: Option<&str>) -> Option<String> {
+    if let Ok(mut url) = reqwest::Url::parse(seed) {
+        if matches!(url.scheme(), "http" | "https") {
+            if let Some(lang) = lang {
+                get_wikipedia_language(url.as_str())?;
+                url.set_host(Some(&format!("{lang}.wikipedia.org"))).ok()?;
+            }
+            return Some(url.to_string());
+        }
+    }
+
+    let lang = lang.unwrap_or("en");
+    let wiki = reqwest::Url::parse(&format!("https://{lang}.wikipedia.org/")).ok()?;
+    Some(mediawiki::article_url(&wiki, seed, "/wiki/"))
+}
+
+/// Returns the url of the seed of a crawl of the wiki at `base_url`. The seed is either the url of an article,
+/// which is used as it is, or its title, e.g. `Main Page`
+pub fn wiki_seed_url(seed: &str, base_url: &reqwest::Url, article_path: &str) -> String {
+    match reqwest::Url::parse(seed) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => url.to_string(),
+        _ => mediawiki::article_url(base_url, seed, article_path),
+    }
+}
+
+/// Whether `url` is an article of one of the wikis being scraped, whose articles have paths starting with `article_path`
+pub fn is_followable_article(url: &str, hosts: &HashSet<String>, article_path: &str) -> bool {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    parsed.path().starts_with(article_path)
+        && parsed.host_str().is_some_and(|host| hosts.contains(host))
+}
+
+/// Whether `url` is an article of another language of one of the wikis in `hosts`, e.g. `de.wikipedia.org` for `en.wikipedia.org`
+pub fn is_other_language_article(url: &str, hosts: &HashSet<String>, article_path: &str) -> bool {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let Some((_, family)) = parsed.host_str().and_then(|host| host.split_once('.')) else {
+        return false;
+    };
+    parsed.path().starts_with(article_path)
+        && hosts.iter().any(|host| {
+            host.split_once('.')
+                .is_some_and(|(_, other)| other == family)
+        })
+}
+
+/// Whether `url` is a web page that can be followed when scraping a generic site,
+/// either on any host or only on one of the hosts being scraped if `same_domain_only` is set
+pub fn is_followable_page(url: &str, hosts: &HashSet<String>, same_domain_only: bool) -> bool {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    matches!(parsed.scheme(), "http" | "https")
+        && (!same_domain_only || parsed.host_str().is_some_and(|host| hosts.contains(host)))
+}
+
+/// Link from a page to another one, aggregating every anchor of the page pointing to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageLink {
+    pub url: String,
+    /// Number of anchors of the page pointing to the url
+    pub count: Weight,
+    /// Position of the first anchor pointing to the url among the distinct links of the page, starting from 0
+    pub position: usize,
+    /// Type of the first anchor pointing to the url, if the types of the edges are recorded
+    pub edge_type: Option<EdgeType>,
+    /// Language of the url, if it is the same article in another language
+    pub interlanguage: Option<String>,
+}
+
+/// Aggregates the anchors of a page by url, in the order in which each url is first found
+pub fn aggregate_links(anchors: Vec<String>) -> Vec<PageLink> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut links: Vec<PageLink> = Vec::new();
+    for url in anchors {
+        match positions.get(&url) {
+            Some(&position) => links[position].count += 1,
+            None => {
+                positions.insert(url.clone(), links.len());
+                links.push(PageLink {
+                    url,
+                    count: 1,
+                    position: links.len(),
+                    edge_type: None,
+                    interlanguage: None,
+                });
+            }
+        }
+    }
+    links
+}
+
+/// Keeps `max_links` of the distinct urls in `anchors`, picked with `sampling`, along with their repetitions.
+/// The links that are kept stay in the order they are found
+pub fn cap_links(anchors: Vec<String>, max_links: usize, sampling: LinkSampling) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut distinct: Vec<&String> = anchors
+        .iter()
+        .filter(|url| seen.insert(url.as_str()))
+        .collect();
+    if distinct.len() <= max_links {
+        return anchors;
+    }
+    if sampling == LinkSampling::Random {
+        // Sorting by the hash of a randomly seeded RandomState shuffles the links without a dependency on rand
+        let state = RandomState::new();
+        distinct.sort_by_cached_key(|url| state.hash_one(url));
+    }
+    let kept: HashSet<String> = distinct.into_iter().take(max_links).cloned().collect();
+    anchors
+        .into_iter()
+        .filter(|url| kept.contains(url))
+        .collect()
+}
+
+/// Returns the text of the `<title>` element of a page, if it has a non-empty one
+pub fn get_page_title(page_content: &str) -> Option<String> {
+    let document = scraper::Html::parse_document(page_content);
+    let selector = scraper::Selector::parse("title").expect("Static selector should be valid");
+    let title = document
+        .select(&selector)
+        .next()?
+        .text()
+        .collect::<String>();
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!title.is_empty()).then_some(title)
+}
+
+/// Returns the urls of the same article in other languages, along with their language code
+pub fn get_interlanguage_links(page_content: &str) -> Vec<(String, String)> {
+    interlanguage_links(&scraper::Html::parse_document(page_content))
+}
+
+fn interlanguage_links(document: &scraper::Html) -> Vec<(String, String)> {
+    let selector = scraper::Selector::parse("a.interlanguage-link-target")
+        .expect("Static selector should be valid");
+
+    document
+        .select(&selector)
+        .filter_map(|anchor| {
+            let href = anchor.value().attr("href")?;
+            let href = if href.starts_with("//") {
+                format!("https:{href}")
+            } else {
+                href.to_owned()
+            };
+            let lang = anchor
+                .value()
+                .attr("lang")
+                .map(|lang| lang.to_owned())
+                .or_else(|| get_wikipedia_language(&href))?;
+            Some((lang, href))
+        })
+        .collect()
+}
+
+/// Returns the anchors in the part of `content` given by `scope`, in the order they appear in the page
+fn scoped_anchors(content: scraper::ElementRef, scope: LinkScope) -> Vec<scraper::ElementRef> {
+    let anchor_selector = scraper::Selector::parse("a").expect("Static selector should be valid");
+    let paragraphs = match scope {
+        LinkScope::All => return content.select(&anchor_selector).collect(),
+        LinkScope::Prose => {
+            let selector = scraper::Selector::parse("p").expect("Static selector should be valid");
+            content.select(&selector).collect::<Vec<_>>()
+        }
+        // The lead ends at the first heading of a section. The title of the page is an h1 outside of the content
+        LinkScope::Lead => {
+            let selector =
+                scraper::Selector::parse("p, h2").expect("Static selector should be valid");
+            content
+                .select(&selector)
+                .take_while(|element| element.value().name() == "p")
+                .collect()
+        }
+    };
+    paragraphs
+        .into_iter()
+        .flat_map(|paragraph| paragraph.select(&anchor_selector))
+        .collect()
+}
+
+/// Returns the type of each anchor of `anchors`, found in `content`, from the part of the page it is in
+fn get_edge_types(content: scraper::ElementRef, anchors: &[scraper::ElementRef]) -> Vec<EdgeType> {
+    // The "See also" section goes from its heading to the next section heading
+    let selector = scraper::Selector::parse("h2, a").expect("Static selector should be valid");
+    let see_also_selector =
+        scraper::Selector::parse("#See_also").expect("Static selector should be valid");
+    let mut in_see_also = false;
+    let mut see_also = HashSet::new();
+    for element in content.select(&selector) {
+        if element.value().name() == "h2" {
+            in_see_also = element.value().id() == Some("See_also")
+                || element.select(&see_also_selector).next().is_some();
+        } else if in_see_also {
+            see_also.insert(element.id());
+        }
+    }
+
+    anchors
+        .iter()
+        .map(|anchor| {
+            let boxes = anchor
+                .ancestors()
+                .take_while(|node| node.id() != content.id())
+                .filter_map(scraper::ElementRef::wrap);
+            for element in boxes {
+                let classes = element.value().classes().collect::<Vec<_>>();
+                if classes.iter().any(|class| class.starts_with("infobox")) {
+                    return EdgeType::Infobox;
+                }
+                if element.value().attr("role") == Some("navigation")
+                    || classes
+                        .iter()
+                        .any(|class| matches!(*class, "navbox" | "vertical-navbox" | "sidebar"))
+                {
+                    return EdgeType::Navbox;
+                }
+            }
+            if see_also.contains(&anchor.id()) {
+                EdgeType::SeeAlso
+            } else {
+                EdgeType::Body
+            }
+        })
+        .collect()
+}
+
+/// Returns the url that the relative links of a page are resolved against:
+/// the one in its `<base href>` element if it has one, else the url of the page itself
+fn get_document_base(document: &scraper::Html, page_url: &reqwest::Url) -> reqwest::Url {
+    let selector = scraper::Selector::parse("base[href]").expect("Static selector should be valid");
+    document
+        .select(&selector)
+        .next()
+        .and_then(|base| base.value().attr("href"))
+        .and_then(|href| page_url.join(href.trim()).ok())
+        .unwrap_or_else(|| page_url.clone())
+}
+
+/// Returns the url in the `<link rel="canonical">` element of a page, normalized, if it has one
+fn get_canonical_url(
+    document: &scraper::Html,
+    page_url: &reqwest::Url,
+    article_path: Option<&str>,
+) -> Option<String> {
+    let selector = scraper::Selector::parse(r#"link[rel~="canonical"][href]"#)
+        .expect("Static selector should be valid");
+    let href = document.select(&selector).next()?.value().attr("href")?;
+    let mut url = page_url.join(href.trim()).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
         return None;
     }
+    normalize_url(&mut url, article_path);
+    Some(url.to_string())
+}
 
-    if url.starts_with("/w") && !url.starts_with("/wiki") {
+/// Whether the page is a disambiguation page, with the box at the top of the ones of Wikipedia
+/// or in the category of the disambiguation pages
+fn is_disambiguation(document: &scraper::Html) -> bool {
+    let selector = scraper::Selector::parse(
+        r#"#disambigbox, .dmbox-disambig, a[href$="Category:Disambiguation_pages"], a[href$="Category:All_disambiguation_pages"]"#,
+    )
+    .expect("Static selector should be valid");
+    document.select(&selector).next().is_some()
+}
+
+/// Returns the complete url of a link of kind `kind` found in the page at `page_url`, resolving it against
+/// `base_url` as described in RFC 3986 and normalizing it, or `None` if the link should not be part of the graph.
+/// Links to the origin of the page are kept only if they point to an article, whose path starts with `article_path`,
+/// or to a page of one of the `namespaces`, or to any page if it is `None`.
+fn get_complete_url(
+    url: &str,
+    kind: LinkKind,
+    page_url: &reqwest::Url,
+    base_url: &reqwest::Url,
+    article_path: Option<&str>,
+    namespaces: &[String],
+    keep_external_links: bool,
+) -> Option<String> {
+    if matches!(
+        kind,
+        LinkKind::Fragment | LinkKind::Mailto | LinkKind::Tel | LinkKind::Javascript
+    ) {
         return None;
     }
 
-    if let Some((url, _tag)) = url.split_once("#") {
-        return Some("https://en.wikipedia.org".to_owned() + url);
+    let mut absolute_url = base_url.join(url.trim()).ok()?;
+    normalize_url(&mut absolute_url, article_path);
+
+    // Links to the same wiki are internal links, however they are written
+    if absolute_url.origin() == page_url.origin() {
+        let is_article = article_path.is_none_or(|article_path| {
+            match classify_link(absolute_url.path(), article_path) {
+                LinkKind::Article => true,
+                LinkKind::Namespaced => {
+                    let title = &absolute_url.path()[article_path.len()..];
+                    namespace(title)
+                        .is_some_and(|namespace| namespaces.iter().any(|n| n == namespace))
+                }
+                _ => false,
+            }
+        });
+        return is_article.then(|| absolute_url.to_string());
+    }
+    let is_web_page = matches!(absolute_url.scheme(), "http" | "https");
+    (keep_external_links && is_web_page).then(|| absolute_url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE_URL: &str = "https://en.wikipedia.org/wiki/Reptile";
+
+    fn complete_from(base_url: &str, url: &str, keep_external_links: bool) -> Option<String> {
+        let page_url = reqwest::Url::parse(PAGE_URL).unwrap();
+        let base_url = reqwest::Url::parse(base_url).unwrap();
+        get_complete_url(
+            url,
+            classify_link(url, "/wiki/"),
+            &page_url,
+            &base_url,
+            Some("/wiki/"),
+            &[],
+            keep_external_links,
+        )
     }
 
-    return Some("https://en.wikipedia.org".to_owned() + url);
+    fn complete(url: &str, keep_external_links: bool) -> Option<String> {
+        complete_from(PAGE_URL, url, keep_external_links)
+    }
+
+    fn urls(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn anchors_are_aggregated_by_url() {
+        let link = |url: &str, count, position| PageLink {
+            url: url.to_owned(),
+            count,
+            position,
+            edge_type: None,
+            interlanguage: None,
+        };
+        assert_eq!(
+            aggregate_links(urls(&["/a", "/b", "/a", "/c", "/a", "/b"])),
+            [link("/a", 3, 0), link("/b", 2, 1), link("/c", 1, 2)]
+        );
+    }
+
+    #[test]
+    fn links_are_capped_to_the_first_distinct_ones() {
+        let anchors = urls(&["/a", "/b", "/a", "/c", "/d", "/b"]);
+        assert_eq!(
+            cap_links(anchors.clone(), 2, LinkSampling::First),
+            urls(&["/a", "/b", "/a", "/b"])
+        );
+        assert_eq!(cap_links(anchors.clone(), 4, LinkSampling::First), anchors);
+    }
+
+    #[test]
+    fn links_are_capped_to_a_random_sample() {
+        let anchors = urls(&["/a", "/b", "/c", "/d", "/e", "/f", "/a"]);
+        let capped = cap_links(anchors.clone(), 3, LinkSampling::Random);
+        let distinct: HashSet<_> = capped.iter().collect();
+        assert_eq!(distinct.len(), 3);
+        // The sampled links keep their repetitions and their order in the page
+        let expected: Vec<_> = anchors
+            .into_iter()
+            .filter(|url| distinct.contains(url))
+            .collect();
+        assert_eq!(capped, expected);
+    }
+
+    #[test]
+    fn articles_are_resolved_without_fragment() {
+        assert_eq!(
+            complete("/wiki/Crocodile#History", false).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Crocodile")
+        );
+    }
+
+    #[test]
+    fn protocol_relative_links_to_the_same_wiki_are_internal() {
+        assert_eq!(
+            complete("//en.wikipedia.org/wiki/Crocodile", false).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Crocodile")
+        );
+        assert_eq!(
+            complete("//en.wikipedia.org/wiki/Special:Random", true),
+            None
+        );
+    }
+
+    #[test]
+    fn protocol_relative_links_to_other_hosts_are_external() {
+        let url = "//upload.wikimedia.org/wikipedia/commons/crocodile.jpg";
+        assert_eq!(complete(url, false), None);
+        assert_eq!(
+            complete(url, true).as_deref(),
+            Some("https://upload.wikimedia.org/wikipedia/commons/crocodile.jpg")
+        );
+    }
+
+    #[test]
+    fn pseudo_links_are_never_kept() {
+        for url in [
+            "mailto:info@example.com",
+            "tel:+39000000",
+            "javascript:void(0)",
+            "#History",
+        ] {
+            assert_eq!(complete(url, true), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn seeds_are_resolved_in_the_given_language() {
+        assert_eq!(
+            wikipedia_seed_url("https://en.wikipedia.org/wiki/Crocodile", None).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Crocodile")
+        );
+        assert_eq!(
+            wikipedia_seed_url("https://en.wikipedia.org/wiki/Coccodrillo", Some("it")).as_deref(),
+            Some("https://it.wikipedia.org/wiki/Coccodrillo")
+        );
+        assert_eq!(
+            wikipedia_seed_url("Nile crocodile", None).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Nile_crocodile")
+        );
+        assert_eq!(
+            wikipedia_seed_url("Krokodile", Some("de")).as_deref(),
+            Some("https://de.wikipedia.org/wiki/Krokodile")
+        );
+        assert_eq!(
+            wikipedia_seed_url("https://example.com/wiki/Crocodile", Some("it")),
+            None
+        );
+    }
+
+    #[test]
+    fn other_languages_of_the_wikis_are_recognized() {
+        let hosts = HashSet::from(["en.wikipedia.org".to_owned()]);
+        assert!(is_other_language_article(
+            "https://de.wikipedia.org/wiki/Krokodile",
+            &hosts,
+            "/wiki/"
+        ));
+        assert!(!is_other_language_article(
+            "https://de.wiktionary.org/wiki/Krokodil",
+            &hosts,
+            "/wiki/"
+        ));
+        assert!(!is_other_language_article(
+            "https://de.wikipedia.org/w/index.php",
+            &hosts,
+            "/wiki/"
+        ));
+    }
+
+    #[test]
+    fn external_links_are_kept_only_if_requested() {
+        assert_eq!(complete("https://example.com/", false), None);
+        assert_eq!(
+            complete("https://example.com/", true).as_deref(),
+            Some("https://example.com/")
+        );
+    }
+
+    #[test]
+    fn relative_paths_are_resolved_against_the_page() {
+        assert_eq!(
+            complete("Crocodile", false).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Crocodile")
+        );
+        assert_eq!(
+            complete("./Crocodile#History", false).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Crocodile")
+        );
+        assert_eq!(
+            complete("../wiki/Alligator", false).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Alligator")
+        );
+        assert_eq!(complete("../w/index.php?title=Crocodile", true), None);
+    }
+
+    #[test]
+    fn relative_paths_are_resolved_against_the_base() {
+        assert_eq!(
+            complete_from("https://en.wikipedia.org/wiki/", "Crocodile", false).as_deref(),
+            Some("https://en.wikipedia.org/wiki/Crocodile")
+        );
+        assert_eq!(
+            complete_from("https://example.com/docs/", "../page.html", true).as_deref(),
+            Some("https://example.com/page.html")
+        );
+    }
+
+    #[test]
+    fn anchors_are_taken_from_the_scope() {
+        let page = scraper::Html::parse_document(
+            "<div id=\"content\"><table><tr><td><a href=\"/wiki/Infobox\">I</a></td></tr></table>\
+             <p><a href=\"/wiki/Lead\">L</a></p><h2>History</h2><p><a href=\"/wiki/Body\">B</a></p>\
+             <ul><li><a href=\"/wiki/Reference\">R</a></li></ul></div>",
+        );
+        let selector = scraper::Selector::parse("#content").unwrap();
+        let content = page.select(&selector).next().unwrap();
+        let hrefs = |scope| {
+            scoped_anchors(content, scope)
+                .into_iter()
+                .filter_map(|anchor| anchor.value().attr("href"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hrefs(LinkScope::All),
+            [
+                "/wiki/Infobox",
+                "/wiki/Lead",
+                "/wiki/Body",
+                "/wiki/Reference"
+            ]
+        );
+        assert_eq!(hrefs(LinkScope::Prose), ["/wiki/Lead", "/wiki/Body"]);
+        assert_eq!(hrefs(LinkScope::Lead), ["/wiki/Lead"]);
+    }
+
+    #[test]
+    fn anchors_are_typed_by_the_part_of_the_page() {
+        let page = scraper::Html::parse_document(
+            "<div id=\"content\"><table class=\"infobox vcard\"><tr><td><a href=\"/wiki/I\">I</a></td></tr></table>\
+             <p><a href=\"/wiki/B\">B</a></p>\
+             <div class=\"mw-heading mw-heading2\"><h2 id=\"See_also\">See also</h2></div><ul><li><a href=\"/wiki/S\">S</a></li></ul>\
+             <h2><span class=\"mw-headline\" id=\"References\">References</span></h2><p><a href=\"/wiki/R\">R</a></p>\
+             <div role=\"navigation\" class=\"navbox\"><table><tr><td><a href=\"/wiki/N\">N</a></td></tr></table></div></div>",
+        );
+        let selector = scraper::Selector::parse("#content").unwrap();
+        let content = page.select(&selector).next().unwrap();
+        let anchors = scoped_anchors(content, LinkScope::All);
+        assert_eq!(
+            get_edge_types(content, &anchors),
+            [
+                EdgeType::Infobox,
+                EdgeType::Body,
+                EdgeType::SeeAlso,
+                EdgeType::Body,
+                EdgeType::Navbox
+            ]
+        );
+    }
+
+    #[test]
+    fn document_base() {
+        let page_url = reqwest::Url::parse("https://example.com/a/b/page.html").unwrap();
+        let document = scraper::Html::parse_document(
+            r#"<html><head><base href="../docs/"></head><body></body></html>"#,
+        );
+        assert_eq!(
+            get_document_base(&document, &page_url).as_str(),
+            "https://example.com/a/docs/"
+        );
+
+        let document = scraper::Html::parse_document("<html><body></body></html>");
+        assert_eq!(get_document_base(&document, &page_url), page_url);
+    }
+
+    #[test]
+    fn disambiguation_pages() {
+        let document = scraper::Html::parse_document(
+            r#"<html><body><table id="disambigbox" class="metadata plainlinks dmbox dmbox-disambig"></table></body></html>"#,
+        );
+        assert!(is_disambiguation(&document));
+        let document = scraper::Html::parse_document(
+            r#"<html><body><div id="catlinks"><a href="/wiki/Category:All_disambiguation_pages">All disambiguation pages</a></div></body></html>"#,
+        );
+        assert!(is_disambiguation(&document));
+        let document = scraper::Html::parse_document(
+            r#"<html><body><a href="/wiki/Category:Crocodiles">Crocodiles</a></body></html>"#,
+        );
+        assert!(!is_disambiguation(&document));
+    }
+
+    #[test]
+    fn canonical_url() {
+        let page_url = reqwest::Url::parse("https://en.m.wikipedia.org/wiki/UK").unwrap();
+        let document = scraper::Html::parse_document(
+            r#"<html><head><link rel="canonical" href="https://en.wikipedia.org/wiki/United_Kingdom#History"></head></html>"#,
+        );
+        assert_eq!(
+            get_canonical_url(&document, &page_url, Some("/wiki/")).as_deref(),
+            Some("https://en.wikipedia.org/wiki/United_Kingdom")
+        );
+
+        let document = scraper::Html::parse_document(
+            r#"<html><head><link rel="alternate" href="/wiki/Other"></head></html>"#,
+        );
+        assert_eq!(
+            get_canonical_url(&document, &page_url, Some("/wiki/")),
+            None
+        );
+    }
 }
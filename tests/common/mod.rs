@@ -0,0 +1,231 @@
+//! Local HTTP server serving canned wiki pages, so that the whole scraper can be run against it in the tests
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use graphgen::graph::Graph;
+use graphgen::output::{OutputFormat, OutputOptions};
+
+/// Serves the pages it has been started with, by path, on a free port of localhost,
+/// until the test process exits. Every other path is answered with a 404.
+/// A path with a query is served the page of the path without it, if there is no page for the whole of it.
+/// The pages are gzipped if the request accepts it, and the ones whose body is [`UNAVAILABLE`] are answered with a 503
+pub struct FixtureServer {
+    addr: SocketAddr,
+    pages: Arc<Mutex<HashMap<String, String>>>,
+    /// Paths requested so far, in the order the requests have been received
+    requests: Arc<Mutex<Vec<String>>>,
+    /// Number of pages served gzipped
+    gzipped: Arc<Mutex<usize>>,
+}
+
+impl FixtureServer {
+    pub fn start(pages: &[(&str, String)]) -> FixtureServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Should bind to a free port");
+        let addr = listener.local_addr().unwrap();
+        let pages: Arc<Mutex<HashMap<String, String>>> = Arc::new(Mutex::new(
+            pages
+                .iter()
+                .map(|(path, body)| (path.to_string(), body.clone()))
+                .collect(),
+        ));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let gzipped = Arc::new(Mutex::new(0));
+
+        let server_pages = pages.clone();
+        let server_requests = requests.clone();
+        let server_gzipped = gzipped.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let pages = server_pages.clone();
+                let requests = server_requests.clone();
+                let gzipped = server_gzipped.clone();
+                thread::spawn(move || serve(stream, &pages, &requests, &gzipped));
+            }
+        });
+
+        FixtureServer {
+            addr,
+            pages,
+            requests,
+            gzipped,
+        }
+    }
+
+    /// Absolute url of the page at `path`
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr)
+    }
+
+    /// Serves `body` at `path` from now on, in place of the page served there so far
+    pub fn set_page(&self, path: &str, body: String) {
+        self.pages.lock().unwrap().insert(path.to_owned(), body);
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn gzipped(&self) -> usize {
+        *self.gzipped.lock().unwrap()
+    }
+}
+
+fn serve(
+    stream: TcpStream,
+    pages: &Mutex<HashMap<String, String>>,
+    requests: &Mutex<Vec<String>>,
+    gzipped: &Mutex<usize>,
+) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // The headers have to be read before answering, even though only the accepted encodings are used
+    let mut accepts_gzip = false;
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+        let header = line.to_ascii_lowercase();
+        if let Some(encodings) = header.strip_prefix("accept-encoding:") {
+            accepts_gzip |= encodings
+                .split(',')
+                .any(|encoding| encoding.trim() == "gzip");
+        }
+        line.clear();
+    }
+
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_owned();
+    requests.lock().unwrap().push(path.clone());
+
+    let pages = pages.lock().unwrap();
+    let page = pages
+        .get(&path)
+        .or_else(|| pages.get(path.split('?').next().unwrap_or_default()));
+    let response = match page {
+        Some(body) if body == UNAVAILABLE => {
+            b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec()
+        }
+        Some(body) if accepts_gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body.as_bytes()).unwrap();
+            let body = encoder.finish().unwrap();
+            *gzipped.lock().unwrap() += 1;
+            let mut response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .into_bytes();
+            response.extend(body);
+            response
+        }
+        Some(body) => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .into_bytes(),
+        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+    };
+    drop(pages);
+    let _ = (&stream).write_all(&response);
+}
+
+/// Body of the pages answered with a 503, as if the server were overloaded
+pub const UNAVAILABLE: &str = "<unavailable>";
+
+/// Html of a wiki article linking to the articles with the given titles
+pub fn article(links: &[&str]) -> String {
+    article_with_head("", links)
+}
+
+/// Html of a wiki article with the given content in its head, linking to the articles with the given titles
+pub fn article_with_head(head: &str, links: &[&str]) -> String {
+    let anchors = links
+        .iter()
+        .map(|title| format!("<a href=\"/wiki/{title}\">{title}</a>"))
+        .collect::<String>();
+    format!("<html><head>{head}</head><body><div id=\"bodyContent\">{anchors}</div></body></html>")
+}
+
+/// Edges of the graph, as pairs of urls
+pub fn edges(graph: &Graph) -> BTreeSet<(String, String)> {
+    // The pages merged into another one share its id, and are left out
+    let aliases = graph.aliases.lock().unwrap();
+    let urls: HashMap<_, _> = graph
+        .pages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(url, _)| !aliases.contains_key(*url))
+        .map(|(url, id)| (*id, url.clone()))
+        .collect();
+    graph
+        .links
+        .lock()
+        .unwrap()
+        .sorted_edges()
+        .unwrap()
+        .map(|edge| {
+            let ((source, dest), _) = edge.unwrap();
+            (urls[&source].clone(), urls[&dest].clone())
+        })
+        .collect()
+}
+
+/// Directory under the system temporary one, named after the test using it, removed with everything in it
+/// once dropped, so that it is cleaned up even if the test fails
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("graphgen-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Path of `name` in the directory, as a string, e.g. to use it as the prefix of the output files
+    pub fn file(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Nodes and edges files of the graph exported in the csv format
+pub fn export_csv(graph: &Graph) -> (String, String) {
+    // The tests run in parallel, so each export gets its own directory
+    static EXPORTS: AtomicUsize = AtomicUsize::new(0);
+    let dir = TempDir::new(&format!(
+        "export-{}",
+        EXPORTS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let prefix = dir.file("graph");
+    graph
+        .save_to_file(&prefix, &[OutputFormat::Csv], &OutputOptions::default())
+        .unwrap();
+    let nodes = std::fs::read_to_string(format!("{prefix}_nodes.csv")).unwrap();
+    let edges = std::fs::read_to_string(format!("{prefix}_edges.csv")).unwrap();
+    (nodes, edges)
+}
@@ -0,0 +1,1800 @@
+//! Runs the whole scraper against canned wiki pages served locally
+
+mod common;
+
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use common::{article, article_with_head, edges, export_csv, FixtureServer, TempDir, UNAVAILABLE};
+use graphgen::checkpoint::Checkpoint;
+use graphgen::config::{
+    CategoryConfig, CrawlStrategy, ExternalLinks, GhostNodes, KeywordMode, PresetSeeds,
+    RandomSeeds, RateLimitConfig, RetryConfig, RevisitConfig, ScraperConfig, SeedPreset, SiteMode,
+    UrlFilter, WikiConfig, UNLIMITED_DEPTH,
+};
+use graphgen::errors::{InvalidConfig, ScraperError};
+use graphgen::events::{self, Decision};
+use graphgen::http::HttpClient;
+use graphgen::node_id::NodeId;
+use graphgen::output::{OutputFormat, OutputOptions, Priority};
+use graphgen::scraper::WikipediaScraper;
+use graphgen::simulate::ExportedGraph;
+use graphgen::snapshot;
+use graphgen::window;
+use regex::Regex;
+
+/// S links to A and B, A links to C and B links to D, which links back to S
+fn tree() -> FixtureServer {
+    FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "B"])),
+        ("/wiki/A", article(&["C"])),
+        ("/wiki/B", article(&["D"])),
+        ("/wiki/C", article(&[])),
+        ("/wiki/D", article(&["S"])),
+    ])
+}
+
+/// Scrapes from the page at `path` with a single worker, so that the order of the requests is deterministic
+fn crawl(server: &FixtureServer, path: &str, config: ScraperConfig) -> graphgen::graph::Graph {
+    let seeds = vec![server.url(path)];
+    let mut scraper = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            concurrency: 1,
+            ..config
+        },
+    )
+    .expect("The config should be valid");
+    scraper.scrape().expect("The crawl should succeed");
+    scraper.graph()
+}
+
+fn edge_set(server: &FixtureServer, edges: &[(&str, &str)]) -> BTreeSet<(String, String)> {
+    edges
+        .iter()
+        .map(|(source, dest)| (server.url(source), server.url(dest)))
+        .collect()
+}
+
+#[test]
+fn links_are_followed_up_to_the_depth() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        edges(&graph),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/A"),
+                ("/wiki/S", "/wiki/B"),
+                ("/wiki/A", "/wiki/C"),
+                ("/wiki/B", "/wiki/D"),
+            ]
+        )
+    );
+    // The pages at the last depth are added to the graph, but not scraped
+    assert_eq!(graph.pages.lock().unwrap().len(), 5);
+    assert!(!server.requests().contains(&"/wiki/C".to_owned()));
+}
+
+#[test]
+fn links_are_filtered_by_their_url() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            url_filter: UrlFilter {
+                allow: vec![Regex::new("/wiki/[A-C]$").unwrap()],
+                deny: vec![Regex::new("/wiki/B$").unwrap()],
+            },
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        edges(&graph),
+        edge_set(&server, &[("/wiki/S", "/wiki/A"), ("/wiki/A", "/wiki/C")])
+    );
+    assert!(!server.requests().contains(&"/wiki/B".to_owned()));
+}
+
+#[test]
+fn keywords_are_only_required_from_the_given_hop() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            keywords: Some(vec!["crocodile".to_owned()]),
+            keywords_from_hop: 2,
+            ..Default::default()
+        },
+    );
+
+    // C and D, two links away from S, are rejected so D is not expanded
+    assert_eq!(
+        edges(&graph),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/A"),
+                ("/wiki/S", "/wiki/B"),
+                ("/wiki/A", "/wiki/C"),
+                ("/wiki/B", "/wiki/D"),
+            ]
+        )
+    );
+    assert!(server.requests().contains(&"/wiki/D".to_owned()));
+}
+
+#[test]
+fn crawl_stops_at_max_pages() {
+    let server = tree();
+    crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 5,
+            max_pages: Some(2),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(server.requests(), ["/wiki/S", "/wiki/A"]);
+}
+
+#[test]
+fn strategy_sets_the_order_of_the_pages() {
+    let server = tree();
+    crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 5,
+            strategy: CrawlStrategy::Bfs,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        server.requests(),
+        ["/wiki/S", "/wiki/A", "/wiki/B", "/wiki/C", "/wiki/D"]
+    );
+
+    let server = tree();
+    crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 5,
+            strategy: CrawlStrategy::Dfs,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        server.requests(),
+        ["/wiki/S", "/wiki/B", "/wiki/D", "/wiki/A", "/wiki/C"]
+    );
+}
+
+#[test]
+fn redirects_are_merged_into_their_canonical_page() {
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["United_States", "Other"])),
+        ("/wiki/United_States", article(&["Other"])),
+        ("/wiki/Other", article(&["USA"])),
+        (
+            "/wiki/USA",
+            article_with_head(
+                "<link rel=\"canonical\" href=\"/wiki/United_States\">",
+                &["Other"],
+            ),
+        ),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        edges(&graph),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/United_States"),
+                ("/wiki/S", "/wiki/Other"),
+                ("/wiki/United_States", "/wiki/Other"),
+                ("/wiki/Other", "/wiki/United_States"),
+            ]
+        )
+    );
+}
+
+#[test]
+fn spilled_edges_are_the_same_as_the_ones_kept_in_memory() {
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["United_States", "Other"])),
+        ("/wiki/United_States", article(&["Other"])),
+        ("/wiki/Other", article(&["USA", "S"])),
+        (
+            "/wiki/USA",
+            article_with_head(
+                "<link rel=\"canonical\" href=\"/wiki/United_States\">",
+                &["Other", "S"],
+            ),
+        ),
+    ]);
+    let config = ScraperConfig {
+        depth: 3,
+        ..Default::default()
+    };
+    let in_memory = crawl(&server, "/wiki/S", config.clone());
+    let dir = TempDir::new("spill");
+    let spilled = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            max_edges_in_memory: Some(1),
+            spill_dir: Some(dir.path().to_owned()),
+            ..config
+        },
+    );
+
+    assert!(spilled.links.lock().unwrap().has_spilled());
+    assert_eq!(edges(&spilled), edges(&in_memory));
+    assert_eq!(spilled.num_links().unwrap(), 5);
+    // The nodes are written in no particular order, but the edges are sorted
+    assert_eq!(export_csv(&spilled).1, export_csv(&in_memory).1);
+}
+
+#[test]
+fn urls_of_the_same_page_are_normalized() {
+    let server = FixtureServer::start(&[
+        (
+            "/wiki/S",
+            article(&["A", "A/", "A?action=view", "A#History", "%41"]),
+        ),
+        ("/wiki/A", article(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(edges(&graph), edge_set(&server, &[("/wiki/S", "/wiki/A")]));
+    assert_eq!(server.requests(), ["/wiki/S", "/wiki/A"]);
+}
+
+#[test]
+fn pages_of_generic_sites_are_told_apart_by_their_query() {
+    let page = |links: &[&str]| {
+        let anchors: String = links
+            .iter()
+            .map(|href| format!("<a href=\"{href}\">{href}</a>"))
+            .collect();
+        format!("<html><body><div id=\"bodyContent\">{anchors}</div></body></html>")
+    };
+    let server = FixtureServer::start(&[
+        ("/", page(&["/?p=1", "/?p=2", "/?p=1&utm_source=feed"])),
+        ("/?p=1", page(&[])),
+        ("/?p=2", page(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/",
+        ScraperConfig {
+            depth: 2,
+            site_mode: SiteMode::Generic,
+            same_domain_only: true,
+            ..Default::default()
+        },
+    );
+
+    // The tracking parameters are dropped, but the query telling the posts apart is kept
+    assert_eq!(
+        edges(&graph),
+        edge_set(&server, &[("/", "/?p=1"), ("/", "/?p=2")])
+    );
+    let mut requests = server.requests();
+    requests.sort();
+    assert_eq!(requests, ["/", "/?p=1", "/?p=2"]);
+}
+
+#[test]
+fn articles_are_identified_by_their_title() {
+    let server = FixtureServer::start(&[
+        (
+            "/wiki/S",
+            article(&["Nile_crocodile", "Nile crocodile", "Nile%20crocodile"]),
+        ),
+        ("/wiki/Nile_crocodile", article(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        edges(&graph),
+        edge_set(&server, &[("/wiki/S", "/wiki/Nile_crocodile")])
+    );
+}
+
+#[test]
+fn only_the_included_namespaces_are_followed() {
+    let server = FixtureServer::start(&[
+        (
+            "/wiki/S",
+            article(&["Star_Wars:_Andor", "Category:Crocodiles", "Talk:S"]),
+        ),
+        ("/wiki/Star_Wars:_Andor", article(&[])),
+        ("/wiki/Category:Crocodiles", article(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            wiki: WikiConfig {
+                namespaces: vec!["Category".to_owned()],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        edges(&graph),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/Star_Wars:_Andor"),
+                ("/wiki/S", "/wiki/Category:Crocodiles"),
+            ]
+        )
+    );
+}
+
+#[test]
+fn nodes_are_exported_with_their_type() {
+    let server = FixtureServer::start(&[(
+        "/wiki/S",
+        article(&[
+            "A",
+            "Category:Crocodiles",
+            "File:Crocodile.jpg",
+            "Help:Contents",
+        ])
+        .replace(
+            "</div>",
+            "<a href=\"https://example.com/crocodiles\">Crocodiles</a></div>",
+        ),
+    )]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 1,
+            keep_external_links: Some(ExternalLinks::Nodes),
+            wiki: WikiConfig {
+                namespaces: vec!["Category".to_owned(), "File".to_owned(), "Help".to_owned()],
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+
+    let dir = TempDir::new("types");
+    let prefix = dir.file("graph");
+    graph
+        .save_to_file(&prefix, &[OutputFormat::Json], &OutputOptions::default())
+        .unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(format!("{prefix}.json")).unwrap()).unwrap();
+
+    let types = json["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|node| {
+            (
+                node["url"].as_str().unwrap().to_owned(),
+                node["node_type"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect::<BTreeSet<_>>();
+    let expected = [
+        (server.url("/wiki/S"), "article"),
+        (server.url("/wiki/A"), "article"),
+        (server.url("/wiki/Category:Crocodiles"), "category"),
+        (server.url("/wiki/File:Crocodile.jpg"), "image"),
+        (server.url("/wiki/Help:Contents"), "page"),
+        ("https://example.com/crocodiles".to_owned(), "external"),
+    ];
+    assert_eq!(
+        types,
+        expected
+            .into_iter()
+            .map(|(url, node_type)| (url, node_type.to_owned()))
+            .collect()
+    );
+}
+
+#[test]
+fn external_links_are_fetched_or_kept_apart() {
+    let elsewhere = FixtureServer::start(&[(
+        "/elsewhere",
+        "<html><head><title>Somewhere else</title></head><body><a href=\"/more\">More</a></body></html>"
+            .to_owned(),
+    )]);
+    // Under another host than the wiki, so that the page is external
+    let external = elsewhere
+        .url("/elsewhere")
+        .replace("127.0.0.1", "localhost");
+    let server = FixtureServer::start(&[
+        (
+            "/wiki/S",
+            article(&["A"]).replace(
+                "</div>",
+                &format!("<a href=\"{external}\">Elsewhere</a></div>"),
+            ),
+        ),
+        ("/wiki/A", article(&[])),
+    ]);
+    let dir = TempDir::new("external");
+    let prefix = dir.file("graph");
+
+    let fetched = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            keep_external_links: Some(ExternalLinks::Fetch),
+            ..Default::default()
+        },
+    );
+    fetched
+        .save_to_file(&prefix, &[OutputFormat::Json], &OutputOptions::default())
+        .unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(format!("{prefix}.json")).unwrap()).unwrap();
+    let node = json["nodes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|node| node["url"] == external.as_str())
+        .expect("The external page should be in the graph");
+    assert_eq!(node["title"], "Somewhere else");
+    assert_eq!(node["http_status"], 200);
+    // The external page is fetched once, and its links are not followed
+    assert_eq!(elsewhere.requests(), ["/elsewhere"]);
+
+    let separate = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            keep_external_links: Some(ExternalLinks::Separate),
+            output_file: Some(prefix.clone()),
+            ..Default::default()
+        },
+    );
+    separate
+        .save_to_file(&prefix, &[OutputFormat::Csv], &OutputOptions::default())
+        .unwrap();
+    let external_edges = std::fs::read_to_string(format!("{prefix}_external_edges.csv")).unwrap();
+
+    assert_eq!(external_edges, format!("source,url\n0,{external}\n"));
+    assert_eq!(
+        edges(&separate),
+        edge_set(&server, &[("/wiki/S", "/wiki/A")])
+    );
+    assert_eq!(elsewhere.requests().len(), 1);
+}
+
+#[test]
+fn block_pages_are_not_scraped() {
+    let captcha =
+        "<html><head><title>Are you a robot?</title></head><body><div id=\"bodyContent\">\
+        <div class=\"g-recaptcha\"></div><a href=\"/wiki/Help\">Help</a></div></body></html>";
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "B"])),
+        ("/wiki/A", article(&[])),
+        ("/wiki/B", captcha.to_owned()),
+    ]);
+    crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            retry: RetryConfig {
+                max_attempts: 2,
+                base_delay: Duration::from_millis(10),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    );
+
+    // The block page is retried after backing off, and its links are not followed
+    assert_eq!(
+        server.requests(),
+        ["/wiki/S", "/wiki/A", "/wiki/B", "/wiki/B"]
+    );
+}
+
+#[test]
+fn category_members_are_the_graph() {
+    let members = r#"{"query": {"categorymembers": [
+        {"ns": 0, "title": "A"}, {"ns": 0, "title": "B"}, {"ns": 14, "title": "Category:Sub"}
+    ]}}"#;
+    let server = FixtureServer::start(&[
+        ("/w/api.php", members.to_owned()),
+        ("/wiki/A", article(&["B", "C"])),
+        ("/wiki/B", article(&["A"])),
+        ("/wiki/C", article(&["A"])),
+    ]);
+    let mut scraper = WikipediaScraper::new(
+        &[],
+        ScraperConfig {
+            depth: 3,
+            concurrency: 1,
+            category: Some(CategoryConfig {
+                url: server.url("/wiki/Category:Topic"),
+                depth: 0,
+            }),
+            ..Default::default()
+        },
+    )
+    .expect("The config should be valid");
+    scraper.scrape().expect("The crawl should succeed");
+
+    // Only the members are scraped, and the links to other pages are dropped
+    assert_eq!(
+        edges(&scraper.graph()),
+        edge_set(&server, &[("/wiki/A", "/wiki/B"), ("/wiki/B", "/wiki/A")])
+    );
+    assert!(!server.requests().contains(&"/wiki/C".to_owned()));
+    assert!(server.requests()[0].contains("cmtitle=Category%3ATopic"));
+}
+
+#[test]
+fn random_articles_are_the_seeds() {
+    let random = r#"{"query": {"random": [{"id": 1, "ns": 0, "title": "A"}, {"id": 2, "ns": 0, "title": "B"}]}}"#;
+    let server = FixtureServer::start(&[
+        ("/w/api.php", random.to_owned()),
+        ("/wiki/A", article(&[])),
+        ("/wiki/B", article(&[])),
+    ]);
+    let mut scraper = WikipediaScraper::new(
+        &[],
+        ScraperConfig {
+            depth: 1,
+            concurrency: 1,
+            random_seeds: Some(RandomSeeds {
+                url: server.url("/wiki/Special:Random"),
+                count: 3,
+            }),
+            ..Default::default()
+        },
+    )
+    .expect("The config should be valid");
+    scraper.scrape().expect("The crawl should succeed");
+
+    // The wiki only has two articles, so the second request finds no new one and they are used
+    let requests = server.requests();
+    assert!(requests[0].contains("list=random") && requests[0].contains("rnlimit=3"));
+    assert!(requests[1].contains("rnlimit=1"));
+    assert_eq!(requests[2..], ["/wiki/A", "/wiki/B"]);
+}
+
+#[test]
+fn vital_articles_are_the_seeds() {
+    let links = r#"{"query": {"pages": [{"title": "Wikipedia:Vital articles", "links": [{"ns": 0, "title": "A"}, {"ns": 0, "title": "B"}]}]}}"#;
+    let server = FixtureServer::start(&[
+        ("/w/api.php", links.to_owned()),
+        ("/wiki/A", article(&["B"])),
+        ("/wiki/B", article(&[])),
+    ]);
+    let mut scraper = WikipediaScraper::new(
+        &[],
+        ScraperConfig {
+            depth: 1,
+            concurrency: 1,
+            preset: Some(PresetSeeds {
+                preset: SeedPreset::VitalLevel3,
+                url: server.url("/wiki/Wikipedia:Vital_articles"),
+            }),
+            ..Default::default()
+        },
+    )
+    .expect("The config should be valid");
+    scraper.scrape().expect("The crawl should succeed");
+
+    let requests = server.requests();
+    assert!(requests[0].contains("prop=links") && requests[0].contains("Vital+articles"));
+    assert_eq!(requests[1..], ["/wiki/A", "/wiki/B"]);
+}
+
+#[test]
+fn imported_pages_are_not_fetched_again() {
+    let dir = TempDir::new("import");
+    let prefix = dir.file("first");
+
+    let server = tree();
+    let first = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+    first
+        .save_to_file(&prefix, &[OutputFormat::Csv], &OutputOptions::default())
+        .unwrap();
+    let fetched = server.requests().len();
+
+    let seeds = vec![server.url("/wiki/S")];
+    let mut scraper = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            depth: 3,
+            concurrency: 1,
+            ..Default::default()
+        },
+    )
+    .expect("The config should be valid");
+    scraper.import(ExportedGraph::load(&prefix, b',').unwrap());
+    scraper.scrape().expect("The crawl should succeed");
+
+    // Only the pages at the last depth of the first crawl are fetched
+    assert_eq!(server.requests()[fetched..], ["/wiki/C", "/wiki/D"]);
+    assert_eq!(
+        edges(&scraper.graph()),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/A"),
+                ("/wiki/S", "/wiki/B"),
+                ("/wiki/A", "/wiki/C"),
+                ("/wiki/B", "/wiki/D"),
+                ("/wiki/D", "/wiki/S"),
+            ]
+        )
+    );
+}
+
+#[test]
+fn disambiguation_pages_are_skipped() {
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["Mercury", "A"])),
+        (
+            "/wiki/Mercury",
+            article(&["Mercury_(planet)", "Category:Disambiguation_pages"]),
+        ),
+        ("/wiki/A", article(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            skip_disambiguation: true,
+            ..Default::default()
+        },
+    );
+
+    // The links of the disambiguation page are not followed, and it is left out of the written graph
+    assert_eq!(server.requests(), ["/wiki/S", "/wiki/Mercury", "/wiki/A"]);
+    let (nodes, _) = export_csv(&graph);
+    assert!(!nodes.contains("Mercury"), "{nodes}");
+}
+
+#[test]
+fn crawl_stops_after_too_many_pages_without_links() {
+    let titles = ["P1", "P2", "P3", "P4", "P5"];
+    let mut pages = vec![("/wiki/S".to_owned(), article(&titles))];
+    pages.extend(titles.iter().map(|title| {
+        (
+            format!("/wiki/{title}"),
+            "<html><body></body></html>".to_owned(),
+        )
+    }));
+    let pages = pages
+        .iter()
+        .map(|(path, body)| (path.as_str(), body.clone()))
+        .collect::<Vec<_>>();
+    let server = FixtureServer::start(&pages);
+
+    let seeds = vec![server.url("/wiki/S")];
+    let mut scraper = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            depth: 2,
+            concurrency: 1,
+            max_empty_pages: Some(3),
+            ..Default::default()
+        },
+    )
+    .expect("The config should be valid");
+    let err = scraper.scrape().expect_err("The crawl should be stopped");
+    assert!(
+        matches!(err, ScraperError::NoLinksFound { count: 4, .. }),
+        "{err}"
+    );
+    assert_eq!(server.requests().len(), 5);
+}
+
+#[test]
+fn pages_taking_too_long_to_parse_are_abandoned() {
+    let titles = (0..200_000).map(|i| format!("H{i}")).collect::<Vec<_>>();
+    let titles = titles.iter().map(String::as_str).collect::<Vec<_>>();
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "Huge"])),
+        ("/wiki/A", article(&["S"])),
+        ("/wiki/Huge", article(&titles)),
+    ]);
+
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            page_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        edges(&graph),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/A"),
+                ("/wiki/S", "/wiki/Huge"),
+                ("/wiki/A", "/wiki/S")
+            ]
+        )
+    );
+}
+
+#[test]
+fn stale_cached_pages_are_used_and_refreshed_in_the_background() {
+    let server = FixtureServer::start(&[("/wiki/S", article(&["A"])), ("/wiki/A", article(&[]))]);
+    let dir = TempDir::new("stale");
+    let cached = |stale_while_revalidate| ScraperConfig {
+        depth: 2,
+        cache_dir: Some(dir.path().to_owned()),
+        stale_while_revalidate,
+        ..Default::default()
+    };
+
+    crawl(&server, "/wiki/S", cached(None));
+    assert_eq!(server.requests(), ["/wiki/S", "/wiki/A"]);
+
+    // The pages have just been cached, so they are used without being refreshed
+    crawl(&server, "/wiki/S", cached(Some(Duration::from_secs(3600))));
+    assert_eq!(server.requests().len(), 2);
+
+    let graph = crawl(&server, "/wiki/S", cached(Some(Duration::ZERO)));
+
+    assert_eq!(edges(&graph), edge_set(&server, &[("/wiki/S", "/wiki/A")]));
+    let refreshed = server.requests()[2..]
+        .iter()
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    assert_eq!(
+        refreshed,
+        BTreeSet::from(["/wiki/A".to_owned(), "/wiki/S".to_owned()])
+    );
+}
+
+#[test]
+fn repeated_links_are_aggregated_with_their_first_position() {
+    let server = FixtureServer::start(&[("/wiki/S", article(&["A", "B", "A", "C", "B", "A"]))]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 1,
+            weighted: true,
+            link_positions: true,
+            ..Default::default()
+        },
+    );
+
+    let (_, edges_file) = export_csv(&graph);
+
+    // S is the page 0, and its links get the ids 1 to 3 in the order they are first found
+    assert_eq!(
+        edges_file,
+        "source,target,weight,position\n0,1,3,0\n0,2,2,1\n0,3,1,2\n"
+    );
+}
+
+#[test]
+fn edges_are_typed_by_the_part_of_the_page() {
+    let server = FixtureServer::start(&[(
+        "/wiki/S",
+        article(&["A", "B"]).replace(
+            "<a href=\"/wiki/B\">",
+            "<table class=\"infobox\"><tr><td><a href=\"/wiki/B\">",
+        ),
+    )]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 1,
+            edge_types: true,
+            ..Default::default()
+        },
+    );
+
+    let (_, edges_file) = export_csv(&graph);
+
+    assert_eq!(edges_file, "source,target,type\n0,1,body\n0,2,infobox\n");
+}
+
+#[test]
+fn interlanguage_links_are_recorded_without_being_followed() {
+    let server = FixtureServer::start(&[
+        (
+            "/wiki/S",
+            article(&["A"]).replace(
+                "</body>",
+                "<a class=\"interlanguage-link-target\" lang=\"de\" href=\"/wiki/X\">Deutsch</a></body>",
+            ),
+        ),
+        ("/wiki/A", article(&[])),
+        ("/wiki/X", article(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            interlanguage_links: true,
+            ..Default::default()
+        },
+    );
+
+    let (_, edges_file) = export_csv(&graph);
+
+    assert_eq!(server.requests(), ["/wiki/S", "/wiki/A"]);
+    assert_eq!(edges_file, "source,target,interlanguage\n0,1,\n0,2,de\n");
+}
+
+#[test]
+fn expired_pages_scraped_since_they_were_queued_are_dropped() {
+    // B is queued again by both A1 and A2 to be revisited, and the second entry is stale once the first one is scraped
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["B", "A1", "A2"])),
+        ("/wiki/A1", article(&["B"])),
+        ("/wiki/A2", article(&["B"])),
+        ("/wiki/B", article(&["C"])),
+        ("/wiki/C", article(&[])),
+    ]);
+    crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            revisit: RevisitConfig {
+                after_hops: Some(1),
+                ..Default::default()
+            },
+            frontier_ttl: Some(Duration::ZERO),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(
+        server.requests(),
+        ["/wiki/S", "/wiki/B", "/wiki/A1", "/wiki/A2", "/wiki/C", "/wiki/B"]
+    );
+}
+
+#[test]
+fn compressed_responses_are_decompressed() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(server.gzipped(), 3);
+    assert_eq!(
+        edges(&graph),
+        BTreeSet::from([
+            (server.url("/wiki/S"), server.url("/wiki/A")),
+            (server.url("/wiki/S"), server.url("/wiki/B")),
+            (server.url("/wiki/A"), server.url("/wiki/C")),
+            (server.url("/wiki/B"), server.url("/wiki/D")),
+        ])
+    );
+}
+
+#[test]
+fn bridge_graphs_merge_two_languages_by_item() {
+    let items = |pages: &[(&str, &str)]| {
+        let pages = pages
+            .iter()
+            .map(|(title, item)| {
+                format!(r#"{{"title": "{title}", "pageprops": {{"wikibase_item": "{item}"}}}}"#)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(r#"{{"query": {{"pages": [{pages}]}}}}"#)
+    };
+    let italian = FixtureServer::start(&[
+        ("/wiki/Coccodrillo", article(&["Rettile"])),
+        ("/wiki/Rettile", article(&[])),
+        (
+            "/w/api.php",
+            items(&[("Coccodrillo", "Q1"), ("Rettile", "Q2")]),
+        ),
+    ]);
+    let english = FixtureServer::start(&[
+        (
+            "/wiki/Crocodile",
+            article(&["Reptile", "Nile"]).replace(
+                "</body>",
+                &format!(
+                    "<a class=\"interlanguage-link-target\" lang=\"it\" href=\"{}\">Italiano</a></body>",
+                    italian.url("/wiki/Coccodrillo")
+                ),
+            ),
+        ),
+        ("/wiki/Reptile", article(&[])),
+        ("/wiki/Nile", article(&[])),
+        (
+            "/w/api.php",
+            items(&[("Crocodile", "Q1"), ("Reptile", "Q2"), ("Nile", "Q3")]),
+        ),
+    ]);
+    let graph = graphgen::bridge::crawl(
+        &english.url("/wiki/Crocodile"),
+        ["en".to_owned(), "it".to_owned()],
+        ScraperConfig {
+            depth: 2,
+            concurrency: 1,
+            ..Default::default()
+        },
+    )
+    .expect("The crawl should succeed");
+
+    assert_eq!(
+        graph.links.into_iter().collect::<Vec<_>>(),
+        [
+            (("Q1".to_owned(), "Q2".to_owned()), [true, true]),
+            (("Q1".to_owned(), "Q3".to_owned()), [true, false]),
+        ]
+    );
+    assert_eq!(
+        graph.items["Q2"],
+        [
+            Some(english.url("/wiki/Reptile")),
+            Some(italian.url("/wiki/Rettile"))
+        ]
+    );
+    assert_eq!(graph.items["Q3"], [Some(english.url("/wiki/Nile")), None]);
+}
+
+#[test]
+fn exported_nodes_and_edges_are_filtered_by_their_attributes() {
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "A", "B"])),
+        ("/wiki/A", article(&["C"])),
+        ("/wiki/B", article(&["D"])),
+        ("/wiki/C", article(&[])),
+        ("/wiki/D", article(&["S"])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            weighted: true,
+            node_filter: Some("degree >= 2 || depth == 2 && title != 'D'".parse().unwrap()),
+            edge_filter: Some("weight == 1".parse().unwrap()),
+            ..Default::default()
+        },
+    );
+
+    let (nodes_file, edges_file) = export_csv(&graph);
+
+    // D is rejected by the node filter, and the link from S to A, found twice, by the edge filter
+    let nodes: BTreeSet<_> = nodes_file.lines().skip(1).map(str::to_owned).collect();
+    let expected: BTreeSet<_> = [(0, "S"), (1, "A"), (2, "B"), (3, "C")]
+        .into_iter()
+        .map(|(id, title)| {
+            format!(
+                "{id},{},article,{title}",
+                server.url(&format!("/wiki/{title}"))
+            )
+        })
+        .collect();
+    assert_eq!(nodes, expected);
+    assert_eq!(edges_file, "source,target,weight\n0,2,1\n1,3,1\n");
+}
+
+#[test]
+fn pages_that_could_not_be_fetched_are_retried_when_resuming() {
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "B"])),
+        ("/wiki/A", UNAVAILABLE.to_owned()),
+        ("/wiki/B", article(&[])),
+    ]);
+    let dir = TempDir::new("failed");
+    let config = ScraperConfig {
+        depth: 2,
+        checkpoint: Some(dir.path().join("crawl.checkpoint")),
+        retry: RetryConfig {
+            max_attempts: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let resume = |config: &ScraperConfig| {
+        let checkpoint = Checkpoint::load(dir.path().join("crawl.checkpoint")).unwrap();
+        let seeds = checkpoint.seeds.clone();
+        let mut scraper =
+            WikipediaScraper::new(&seeds, config.clone()).expect("The config should be valid");
+        scraper.resume(checkpoint);
+        scraper.scrape().expect("The crawl should succeed");
+        scraper.graph()
+    };
+
+    let graph = crawl(&server, "/wiki/S", config.clone());
+    assert_eq!(
+        graph.failed.lock().unwrap()[&server.url("/wiki/A")].attempts,
+        1
+    );
+
+    // A has failed as many times as allowed, and is left out
+    server.set_page("/wiki/A", article(&["C"]));
+    let graph = resume(&ScraperConfig {
+        max_page_attempts: 1,
+        ..config.clone()
+    });
+    assert_eq!(graph.failed.lock().unwrap().len(), 1);
+    assert_eq!(
+        server.requests(),
+        ["/wiki/S", "/wiki/A", "/wiki/B"].map(str::to_owned)
+    );
+
+    let graph = resume(&config);
+    assert!(graph.failed.lock().unwrap().is_empty());
+    assert!(edges(&graph).contains(&(server.url("/wiki/A"), server.url("/wiki/C"))));
+}
+
+#[test]
+fn nodes_are_exported_with_their_degrees() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            degrees: true,
+            ..Default::default()
+        },
+    );
+
+    let (nodes_file, _) = export_csv(&graph);
+
+    let nodes: BTreeSet<_> = nodes_file.lines().map(str::to_owned).collect();
+    let expected: BTreeSet<_> = [
+        ("S", 0, 2),
+        ("A", 1, 1),
+        ("B", 1, 1),
+        ("C", 1, 0),
+        ("D", 1, 0),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(id, (title, in_degree, out_degree))| {
+        let url = server.url(&format!("/wiki/{title}"));
+        format!("{id},{url},article,{title},{in_degree},{out_degree}")
+    })
+    .chain(["node_id,url,node_type,title,in_degree,out_degree".to_owned()])
+    .collect();
+    assert_eq!(nodes, expected);
+
+    let (in_degree, out_degree) = graph.degree_stats().unwrap();
+    assert_eq!((in_degree.min, in_degree.max), (0, 1));
+    assert_eq!((out_degree.min, out_degree.max), (0, 2));
+    assert_eq!(in_degree.mean, 0.8);
+    assert_eq!(out_degree.mean, 0.8);
+}
+
+#[test]
+fn nodes_are_labeled_with_their_component() {
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "B"])),
+        ("/wiki/A", article(&["S"])),
+        ("/wiki/T", article(&["C"])),
+    ]);
+    let seeds = vec![server.url("/wiki/S"), server.url("/wiki/T")];
+    let mut scraper = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            depth: 2,
+            concurrency: 1,
+            components: true,
+            ..Default::default()
+        },
+    )
+    .expect("The config should be valid");
+    scraper.scrape().expect("The crawl should succeed");
+    let graph = scraper.graph();
+
+    let (nodes_file, _) = export_csv(&graph);
+
+    let components: BTreeSet<_> = nodes_file
+        .lines()
+        .skip(1)
+        .map(|line| {
+            let columns: Vec<_> = line.split(',').collect();
+            (columns[3].to_owned(), columns[4].to_owned())
+        })
+        .collect();
+    let expected = [("S", "0"), ("A", "0"), ("B", "0"), ("T", "1"), ("C", "1")];
+    assert_eq!(
+        components,
+        expected
+            .into_iter()
+            .map(|(title, component)| (title.to_owned(), component.to_owned()))
+            .collect()
+    );
+    assert_eq!(graph.components().unwrap().sizes(), [3, 2]);
+}
+
+#[test]
+fn snapshots_load_the_whole_graph() {
+    let dir = TempDir::new("snapshot");
+    let path = dir.file("graph.snapshot.gz");
+
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+    snapshot::write(&path, &graph).unwrap();
+    assert!(snapshot::write(&path, &graph).is_err());
+    let loaded = snapshot::load(&path, Default::default()).unwrap();
+    let exported = ExportedGraph::open(&path, b',').unwrap();
+
+    assert_eq!(edges(&loaded), edges(&graph));
+    assert_eq!(*loaded.seeds.lock().unwrap(), [server.url("/wiki/S")]);
+    assert_eq!(exported.num_pages(), graph.num_pages());
+}
+
+#[test]
+fn requests_are_counted_by_host() {
+    let server = FixtureServer::start(&[
+        ("/wiki/A", article(&[])),
+        ("/wiki/B", UNAVAILABLE.to_owned()),
+    ]);
+    let client = HttpClient::new(&ScraperConfig::default());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        client.get_text(&server.url("/wiki/A")).await.unwrap();
+        assert!(client.get_text(&server.url("/wiki/B")).await.is_err());
+        client.check_link(&server.url("/wiki/C")).await;
+    });
+
+    let by_host = client.requests().by_host();
+    assert_eq!(by_host.len(), 1);
+    let requests = by_host.values().next().unwrap();
+    assert_eq!((requests.requests, requests.errors), (3, 2));
+    assert!((requests.error_rate() - 2.0 / 3.0).abs() < 1e-9);
+    assert!(client.bandwidth().total() > 0);
+}
+
+#[test]
+fn external_hosts_have_their_own_rate_limit() {
+    let server = FixtureServer::start(&[("/wiki/A", article(&[]))]);
+    let client = HttpClient::new(&ScraperConfig {
+        external_rate_limit: Some(RateLimitConfig {
+            requests_per_second: 10.0,
+            burst: 1,
+        }),
+        ..Default::default()
+    });
+    // Under another host than the wiki, so that the links are external
+    let external = server.url("/wiki/A").replace("127.0.0.1", "localhost");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let started = Instant::now();
+    runtime.block_on(async {
+        for _ in 0..3 {
+            client.get_text(&server.url("/wiki/A")).await.unwrap();
+        }
+        // The requests to the wiki are not limited, so they have been sent right away
+        assert!(started.elapsed() < Duration::from_millis(100));
+        for _ in 0..3 {
+            client.check_link(&external).await;
+        }
+        client.fetch_link(&external).await;
+    });
+
+    // The first request to the external host is sent right away, and the following ones 100ms apart
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    let by_host = client.requests().by_host();
+    assert!(by_host["127.0.0.1"].delay < Duration::from_millis(50));
+    assert_eq!(by_host["localhost"].requests, 4);
+    assert!(by_host["localhost"].delay >= Duration::from_millis(250));
+}
+
+#[test]
+fn crawl_stops_once_the_end_of_the_path_is_found() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            stop_at: Some(server.url("/wiki/D")),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(server.requests(), ["/wiki/S", "/wiki/A", "/wiki/B"]);
+    let exported = ExportedGraph::from_graph(&graph).unwrap();
+    assert_eq!(
+        exported
+            .shortest_path(&server.url("/wiki/S"), &server.url("/wiki/D"))
+            .unwrap(),
+        Some(vec![
+            server.url("/wiki/S"),
+            server.url("/wiki/B"),
+            server.url("/wiki/D")
+        ])
+    );
+    assert_eq!(
+        exported
+            .shortest_path(&server.url("/wiki/C"), &server.url("/wiki/S"))
+            .unwrap(),
+        None
+    );
+    assert!(exported
+        .shortest_path(&server.url("/wiki/E"), &server.url("/wiki/S"))
+        .is_err());
+}
+
+#[test]
+fn links_to_chains_of_redirects_are_merged() {
+    let canonical = |title: &str| format!("<link rel=\"canonical\" href=\"/wiki/{title}\">");
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["X", "Y", "Q"])),
+        ("/wiki/X", article_with_head(&canonical("Y"), &["S"])),
+        ("/wiki/Y", article_with_head(&canonical("Z"), &["S"])),
+        ("/wiki/Q", article(&["X"])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            ..Default::default()
+        },
+    );
+
+    // Q links to X once X is known to redirect to Y, and Y to Z
+    assert_eq!(
+        edges(&graph),
+        edge_set(
+            &server,
+            &[
+                ("/wiki/S", "/wiki/Z"),
+                ("/wiki/S", "/wiki/Q"),
+                ("/wiki/Z", "/wiki/S"),
+                ("/wiki/Q", "/wiki/Z")
+            ]
+        )
+    );
+    assert_eq!(graph.merge_aliases(), 0);
+
+    // A page left apart from the one it redirects to is merged before the export, summing the weights of its links
+    let (q, z) = {
+        let own_pages = graph.pages.lock().unwrap();
+        (
+            own_pages[&server.url("/wiki/Q")],
+            own_pages[&server.url("/wiki/Z")],
+        )
+    };
+    let stray = NodeId::new(100);
+    graph
+        .pages
+        .lock()
+        .unwrap()
+        .insert(server.url("/wiki/Y"), stray);
+    graph.links.lock().unwrap().insert((q, stray)).unwrap();
+    assert_eq!(graph.merge_aliases(), 1);
+    let weights: Vec<_> = graph
+        .links
+        .lock()
+        .unwrap()
+        .sorted_edges()
+        .unwrap()
+        .map(Result::unwrap)
+        .filter(|((source, _), _)| *source == q)
+        .collect();
+    assert_eq!(weights, [((q, z), 2)]);
+}
+
+#[test]
+fn nodes_are_exported_with_their_centrality() {
+    let server = tree();
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 3,
+            centrality: true,
+            ..Default::default()
+        },
+    );
+
+    // S is on the paths from D to A, B and C and from B to A and C, out of the 12 pairs of other pages,
+    // and A on the ones from S, B and D to C
+    let (pages, sampled) = graph.bridge_pages(2).unwrap();
+    assert!(!sampled);
+    assert_eq!(
+        pages,
+        [
+            (server.url("/wiki/S"), 5.0 / 12.0),
+            (server.url("/wiki/A"), 3.0 / 12.0)
+        ]
+    );
+
+    let (nodes_file, _) = export_csv(&graph);
+
+    assert_eq!(
+        nodes_file.lines().next().unwrap(),
+        "node_id,url,node_type,title,betweenness,closeness"
+    );
+    // C is 1 link away from A, 2 from S, 3 from D and 4 from B
+    let c_columns: Vec<_> = nodes_file
+        .lines()
+        .find(|line| line.contains("/wiki/C,"))
+        .unwrap()
+        .split(',')
+        .collect();
+    assert_eq!(c_columns[4], "0");
+    let closeness: f64 = c_columns[5].parse().unwrap();
+    assert!((closeness - (1.0 + 1.0 / 2.0 + 1.0 / 3.0 + 1.0 / 4.0) / 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn decisions_about_each_page_are_logged() {
+    let dir = TempDir::new("events");
+    let path = dir.path().join("events.jsonl");
+
+    let server = tree();
+    crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            url_filter: UrlFilter {
+                allow: Vec::new(),
+                deny: vec![Regex::new("/wiki/B$").unwrap()],
+            },
+            event_log: Some(path.clone()),
+            ..Default::default()
+        },
+    );
+    let trail = |page: &str| {
+        events::trail(&path, &server.url(page))
+            .unwrap()
+            .into_iter()
+            .map(|event| event.decision)
+            .collect::<Vec<_>>()
+    };
+    let (seed, a, b, c) = (
+        trail("/wiki/S"),
+        trail("/wiki/A"),
+        trail("/wiki/B"),
+        trail("/wiki/C"),
+    );
+
+    assert_eq!(
+        seed,
+        [
+            Decision::Queued {
+                depth: 2,
+                from: None
+            },
+            Decision::Scraped { depth: 2, links: 1 }
+        ]
+    );
+    let from_s = server.url("/wiki/S");
+    assert_eq!(
+        a,
+        [
+            Decision::Linked {
+                from: from_s.clone()
+            },
+            Decision::Queued {
+                depth: 1,
+                from: Some(from_s.clone())
+            },
+            Decision::Scraped { depth: 1, links: 1 }
+        ]
+    );
+    assert_eq!(
+        b,
+        [Decision::Rejected {
+            from: from_s,
+            reason: "excluded by the url filters".to_owned()
+        }]
+    );
+    assert_eq!(
+        c,
+        [
+            Decision::Linked {
+                from: server.url("/wiki/A")
+            },
+            Decision::Skipped {
+                reason: "not queued, since the depth has been reached".to_owned()
+            }
+        ]
+    );
+}
+
+#[test]
+fn continuous_crawls_go_on_until_their_budget_runs_out() {
+    // P0 links to P1, which links to P2, and so on. P7 is never scraped
+    let pages: Vec<_> = (0..8)
+        .map(|i| (format!("/wiki/P{i}"), article(&[&format!("P{}", i + 1)])))
+        .collect();
+    let pages: Vec<_> = pages
+        .iter()
+        .map(|(path, page)| (path.as_str(), page.clone()))
+        .collect();
+    let server = FixtureServer::start(&pages);
+    let graph = crawl(
+        &server,
+        "/wiki/P0",
+        ScraperConfig {
+            depth: UNLIMITED_DEPTH,
+            max_pages: Some(7),
+            ..Default::default()
+        },
+    );
+    assert_eq!(graph.num_pages(), 8);
+    assert_eq!(server.requests().len(), 7);
+
+    let dir = TempDir::new("window");
+    let prefix = graphgen::graph::window_prefix(&dir.file("graph"), 1);
+    let written = graph
+        .save_window(&prefix, &[OutputFormat::Csv], &OutputOptions::default(), 3)
+        .unwrap();
+    let nodes_file = std::fs::read_to_string(format!("{prefix}_nodes.csv")).unwrap();
+    let edges_file = std::fs::read_to_string(format!("{prefix}_edges.csv")).unwrap();
+
+    assert_eq!(written, 3);
+    let urls: BTreeSet<_> = nodes_file
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').nth(1).unwrap().to_owned())
+        .collect();
+    assert_eq!(
+        urls,
+        ["/wiki/P5", "/wiki/P6", "/wiki/P7"]
+            .iter()
+            .map(|path| server.url(path))
+            .collect()
+    );
+    // The links between the pages of the window, from P5 to P6 and from P6 to P7
+    assert_eq!(edges_file.lines().count(), 3);
+
+    // No window is written once the emitter has been stopped
+    let periodic = TempDir::new("periodic-windows");
+    let output = (
+        periodic.file("graph"),
+        vec![OutputFormat::Csv],
+        OutputOptions::default(),
+    );
+    let emitter = window::emit_periodically(graph, output, 3, Duration::from_millis(20));
+    std::thread::sleep(Duration::from_millis(100));
+    emitter.stop();
+    let windows = std::fs::read_dir(periodic.path()).unwrap().count();
+    assert!(windows > 0);
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(std::fs::read_dir(periodic.path()).unwrap().count(), windows);
+}
+
+#[test]
+fn nodes_are_exported_with_their_clustering_coefficient() {
+    // S, A and B link to each other, and S to C as well
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "B", "C"])),
+        ("/wiki/A", article(&["B"])),
+        ("/wiki/B", article(&["S"])),
+        ("/wiki/C", article(&[])),
+    ]);
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 2,
+            clustering: true,
+            ..Default::default()
+        },
+    );
+    assert!((graph.clustering().unwrap().average() - (1.0 / 3.0 + 2.0) / 4.0).abs() < 1e-9);
+
+    let (nodes_file, _) = export_csv(&graph);
+
+    assert_eq!(
+        nodes_file.lines().next().unwrap(),
+        "node_id,url,node_type,title,clustering"
+    );
+    let clustering = |page: &str| -> f64 {
+        nodes_file
+            .lines()
+            .find(|line| line.contains(&format!("/wiki/{page},")))
+            .unwrap()
+            .rsplit(',')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+    assert!((clustering("S") - 1.0 / 3.0).abs() < 1e-9);
+    assert_eq!(clustering("A"), 1.0);
+    assert_eq!(clustering("C"), 0.0);
+}
+
+#[test]
+fn only_the_giant_component_is_exported_if_asked_to() {
+    // S, A and B link to each other, and so do C and D, which only get a link from S:
+    // in the undirected graph, they are a component of their own
+    let server = FixtureServer::start(&[
+        ("/wiki/S", article(&["A", "B", "C", "D"])),
+        ("/wiki/A", article(&["S", "B"])),
+        ("/wiki/B", article(&["S", "A"])),
+        ("/wiki/C", article(&["D"])),
+        ("/wiki/D", article(&["C"])),
+    ]);
+    let export = |giant_component_only| {
+        let graph = crawl(
+            &server,
+            "/wiki/S",
+            ScraperConfig {
+                depth: 2,
+                undirected: true,
+                giant_component_only,
+                ..Default::default()
+            },
+        );
+        let (nodes_file, edges_file) = export_csv(&graph);
+        let mut pages: Vec<_> = nodes_file
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().to_owned())
+            .collect();
+        pages.sort();
+        (nodes_file, pages, edges_file.lines().count() - 1)
+    };
+
+    let (_, pages, edges) = export(false);
+    assert_eq!(
+        pages,
+        ["/wiki/A", "/wiki/B", "/wiki/C", "/wiki/D", "/wiki/S"].map(|path| server.url(path))
+    );
+    assert_eq!(edges, 4);
+
+    let (nodes_file, pages, edges) = export(true);
+    // The component is only used to pick the pages, so it is not exported without --components
+    assert_eq!(
+        nodes_file.lines().next().unwrap(),
+        "node_id,url,node_type,title"
+    );
+    assert_eq!(
+        pages,
+        ["/wiki/A", "/wiki/B", "/wiki/S"].map(|path| server.url(path))
+    );
+    // The links between S, A and B
+    assert_eq!(edges, 3);
+}
+
+#[test]
+fn options_that_cannot_be_used_together_are_all_reported() {
+    let server = tree();
+    let seeds = vec![server.url("/wiki/S")];
+    let Err(err) = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            depth: UNLIMITED_DEPTH,
+            check_external: true,
+            top_fraction: Some(1.5),
+            ..Default::default()
+        },
+    ) else {
+        panic!("The config should be rejected");
+    };
+    assert_eq!(
+        err.to_string(),
+        "Invalid configuration: an unlimited depth requires max_pages or max_duration; \
+        top_fraction requires pagerank; check_external requires keep_external_links; \
+        top_fraction has to be between 0 and 1, found 1.5"
+    );
+    // The crawl does not start
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn invalid_values_are_reported_before_the_crawl() {
+    let server = tree();
+    let seeds = vec![server.url("/wiki/S")];
+    let Err(err) = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            use_api: true,
+            keywords: Some(vec!["(unclosed".to_owned()]),
+            keyword_mode: KeywordMode::Regex,
+            headers: vec![("Bad Name".to_owned(), "value".to_owned())],
+            wiki: WikiConfig {
+                content_selector: "div[".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    ) else {
+        panic!("The config should be rejected");
+    };
+    let ScraperError::InvalidConfig(InvalidConfig(problems)) = err else {
+        panic!("Expected an invalid configuration, found {err}");
+    };
+    assert_eq!(problems.len(), 4, "{problems:?}");
+    assert_eq!(problems[0], "use_api cannot be used with keywords");
+    assert!(problems[1].starts_with("Invalid header name `Bad Name`"));
+    assert!(problems[2].starts_with("Invalid CSS selector `div[`"));
+    assert!(problems[3].contains("(unclosed"));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn generic_sites_cannot_use_the_options_of_wikis() {
+    let server = tree();
+    let seeds = vec![server.url("/wiki/S")];
+    let Err(err) = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            site_mode: SiteMode::Generic,
+            all_languages_of_seed: true,
+            ghost_nodes: GhostNodes::Exclude,
+            ..Default::default()
+        },
+    ) else {
+        panic!("The config should be rejected");
+    };
+    assert_eq!(
+        err.to_string(),
+        "Invalid configuration: ghost_nodes requires keywords or exclude_keywords; \
+        the generic site mode cannot be used with all_languages_of_seed"
+    );
+}
+
+#[test]
+fn configs_are_read_from_json_files() {
+    let server = tree();
+    let dir = TempDir::new("config-file");
+    let path = dir.path().join("config.json");
+    std::fs::write(
+        &path,
+        r#"{
+            "depth": 1,
+            "strategy": "dfs",
+            "page_timeout": 1.5,
+            "url_filter": {"deny": ["/wiki/B"]},
+            "wiki": {"namespaces": ["Category"]},
+            "keep_external_links": "nodes"
+        }"#,
+    )
+    .unwrap();
+    let config = ScraperConfig::from_file(&path).expect("The config should be valid");
+    assert_eq!(config.strategy, CrawlStrategy::Dfs);
+    assert_eq!(config.page_timeout, Some(Duration::from_millis(1500)));
+    assert_eq!(config.wiki.namespaces, ["Category"]);
+    assert_eq!(config.wiki.article_path, "/wiki/");
+    assert_eq!(config.keep_external_links, Some(ExternalLinks::Nodes));
+
+    let graph = crawl(&server, "/wiki/S", config);
+    assert_eq!(edges(&graph), edge_set(&server, &[("/wiki/S", "/wiki/A")]));
+
+    std::fs::write(&path, r#"{"depht": 2}"#).unwrap();
+    let Err(ScraperError::InvalidConfig(InvalidConfig(problems))) = ScraperConfig::from_file(&path)
+    else {
+        panic!("The misspelled option should be rejected");
+    };
+    assert!(
+        problems[0].starts_with("unknown field `depht`"),
+        "{problems:?}"
+    );
+
+    std::fs::write(&path, r#"{"check_external": true, "top_fraction": 1.5}"#).unwrap();
+    let err = ScraperConfig::from_file(&path).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid configuration: top_fraction requires pagerank; check_external requires keep_external_links; \
+        top_fraction has to be between 0 and 1, found 1.5"
+    );
+}
+
+#[test]
+fn external_links_kept_separate_are_only_exported_to_files() {
+    let server = tree();
+    let seeds = vec![server.url("/wiki/S")];
+    let Err(err) = WikipediaScraper::new(
+        &seeds,
+        ScraperConfig {
+            keep_external_links: Some(ExternalLinks::Separate),
+            ..Default::default()
+        },
+    ) else {
+        panic!("The config should be rejected");
+    };
+    assert_eq!(
+        err.to_string(),
+        "Invalid configuration: keep_external_links = separate requires output_file"
+    );
+
+    let graph = crawl(
+        &server,
+        "/wiki/S",
+        ScraperConfig {
+            depth: 1,
+            keep_external_links: Some(ExternalLinks::Separate),
+            output_file: Some("graph".to_owned()),
+            ..Default::default()
+        },
+    );
+    let mut writer = graph
+        .writer
+        .writer(Priority::Foreground, || Ok(Vec::new()))
+        .unwrap();
+    let err = graph.export(&mut writer).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}